@@ -0,0 +1,91 @@
+//! How fast games can be made, forked and stepped, for search tools. Run with
+//! `cargo bench -p rustic-mountain-core`
+use std::{hint::black_box, time::Instant};
+
+use rustic_mountain_core::{memory::CartData, practice::Practice, Celeste};
+
+mod consts {
+    include!("../../src/consts.rs");
+}
+
+/// runs `f` `n` times and prints how many runs a second that is
+fn bench(name: &str, n: u32, mut f: impl FnMut(u32)) {
+    let start = Instant::now();
+    for i in 0..n {
+        f(i);
+    }
+    let secs = start.elapsed().as_secs_f64();
+    println!("{:<32} {:>12.0} /s", name, n as f64 / secs);
+}
+
+/// the same cheap pseudo random buttons every run
+fn buttons(celeste: &mut Celeste, i: u32) {
+    let mask = (i.wrapping_mul(2654435761) >> 26) as u8;
+    for (b, button) in celeste.mem.buttons.iter_mut().enumerate() {
+        *button = mask & (1 << b) != 0;
+    }
+}
+
+fn playing(cart: &CartData, level: u8) -> Celeste {
+    let mut celeste = Celeste::from_cart(cart);
+    celeste.seed(level as u64);
+    celeste.begin_practice(Practice {
+        level,
+        ..Default::default()
+    });
+    celeste
+}
+
+fn main() {
+    bench("Celeste::new", 2_000, |_| {
+        black_box(Celeste::new(
+            consts::MAPDATA.into(),
+            consts::SPRITES.into(),
+            consts::FLAGS.into(),
+            consts::FONTATLAS.into(),
+        ));
+    });
+    let cart = CartData::new(
+        consts::MAPDATA,
+        consts::SPRITES,
+        consts::FLAGS,
+        consts::FONTATLAS,
+    );
+    bench("Celeste::from_cart", 20_000, |_| {
+        black_box(Celeste::from_cart(&cart));
+    });
+
+    let mut celeste = playing(&cart, 3);
+    bench("clone", 200_000, |_| {
+        black_box(celeste.clone());
+    });
+    bench("save_state", 200_000, |_| {
+        black_box(celeste.save_state());
+    });
+
+    bench("next_tick", 1_000_000, |i| {
+        if i % 3000 == 0 {
+            celeste = playing(&cart, (i / 3000 % 30) as u8);
+        }
+        buttons(&mut celeste, i);
+        celeste.next_tick();
+    });
+    bench("next_tick + draw", 100_000, |i| {
+        buttons(&mut celeste, i);
+        celeste.next_tick();
+        celeste.draw();
+    });
+
+    // what a breadth first search does: fork the game at every tick and try every input
+    let mut frontier = vec![playing(&cart, 0)];
+    bench("fork + next_tick", 500_000, |i| {
+        let mut fork = frontier[i as usize % frontier.len()].clone();
+        buttons(&mut fork, i);
+        fork.next_tick();
+        if frontier.len() < 1000 {
+            frontier.push(fork);
+        } else {
+            frontier[i as usize * 7 % 1000] = fork;
+        }
+    });
+}
@@ -0,0 +1,123 @@
+/// Assist mode options, see `Celeste::assist`. Any of these being on marks the run as assisted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assist {
+    /// percentage of ticks that actually run, 50-100. the rest are skipped
+    pub game_speed: u8,
+    /// dashes refill every tick
+    pub infinite_dashes: bool,
+    /// spikes don't kill, and falling out of the room bounces you back up
+    pub invincible: bool,
+    /// dashes to use instead of `Celeste::max_djump`
+    pub air_dashes: Option<u8>,
+}
+
+impl Assist {
+    pub fn is_active(&self) -> bool {
+        *self != Assist::default()
+    }
+}
+
+impl Default for Assist {
+    fn default() -> Self {
+        Assist {
+            game_speed: 100,
+            infinite_dashes: false,
+            invincible: false,
+            air_dashes: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testcart, Celeste};
+    use alloc::{vec, vec::Vec};
+
+    /// runs right, jumping and dashing now and then
+    fn press(celeste: &mut Celeste, tick: u32) {
+        celeste.mem.buttons = vec![
+            false,
+            tick % 60 < 40,
+            tick.is_multiple_of(17),
+            false,
+            tick % 23 < 4,
+            tick.is_multiple_of(41),
+        ];
+    }
+
+    fn at_speed(game_speed: u8) -> Celeste {
+        let mut celeste = testcart::celeste();
+        celeste.seed(3);
+        celeste.begin_game();
+        celeste.assist.game_speed = game_speed;
+        celeste
+    }
+
+    #[test]
+    fn slowed_down_games_play_out_the_same_every_time() {
+        let mut a = at_speed(70);
+        let mut b = at_speed(70);
+        let mut states = Vec::new();
+        let mut fork = None;
+        for tick in 0..300 {
+            press(&mut a, tick);
+            press(&mut b, tick);
+            a.next_tick();
+            b.next_tick();
+            assert!(a.save_state().data == b.save_state().data, "tick {}", tick);
+            states.push(a.save_state().data);
+            if tick == 100 {
+                assert_ne!(a.speed_clock, 0);
+                fork = Some((a.clone(), a.save_state()));
+            }
+        }
+
+        // part way through the clock, a fork or a loaded state carries on the same way
+        let (mut fork, saved) = fork.unwrap();
+        let mut loaded = at_speed(70);
+        assert!(loaded.load_state(&saved));
+        for tick in 101..300 {
+            press(&mut fork, tick);
+            press(&mut loaded, tick);
+            fork.next_tick();
+            loaded.next_tick();
+            let state = &states[tick as usize];
+            assert!(fork.save_state().data == *state, "tick {}", tick);
+            assert!(loaded.save_state().data == *state, "tick {}", tick);
+        }
+    }
+
+    #[test]
+    fn game_speed_skips_ticks_evenly() {
+        let mut celeste = at_speed(70);
+        for _ in 0..100 {
+            celeste.next_tick();
+        }
+        assert_eq!(celeste.run_frames, 70);
+
+        // below 50 is 50
+        let mut celeste = at_speed(10);
+        for _ in 0..100 {
+            celeste.next_tick();
+        }
+        assert_eq!(celeste.run_frames, 50);
+    }
+
+    #[test]
+    fn presses_during_skipped_ticks_count_on_the_next_one() {
+        let mut celeste = at_speed(50);
+        // the first tick is skipped, the second runs
+        celeste.mem.buttons[4] = true;
+        celeste.next_tick();
+        assert_eq!(celeste.run_frames, 0);
+        celeste.mem.buttons[4] = false;
+        celeste.next_tick();
+        assert_eq!(celeste.run_frames, 1);
+        assert!(celeste.mem.buttons[4]);
+        // and only on that one
+        celeste.mem.buttons[4] = false;
+        celeste.next_tick();
+        celeste.next_tick();
+        assert!(!celeste.mem.buttons[4]);
+    }
+}
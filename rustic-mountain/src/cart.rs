@@ -0,0 +1,148 @@
+//! Reading and writing the `__gfx__`, `__gff__` and `__map__` sections of pico-8's `.p8` cart
+//! files, so a map edited here can be opened in pico-8 and the other way around. The code,
+//! sound and label are passed through untouched, they don't mean anything to this crate
+use alloc::{rc::Rc, string::String, vec, vec::Vec};
+
+use crate::memory::CartData;
+
+const HEADER: &str = "pico-8 cartridge // http://www.pico-8.com\nversion 41\n";
+
+/// the sections `CartData` has, in the order pico-8 writes them
+const DATA_SECTIONS: [&str; 3] = ["__gfx__", "__gff__", "__map__"];
+
+fn is_section(line: &str) -> bool {
+    line.len() > 4 && line.starts_with("__") && line.ends_with("__")
+}
+
+impl CartData {
+    /// This cart with the data sections of the `.p8` file `text` in place of its own. A section
+    /// the file doesn't have is kept from this cart, and the font always is since carts don't
+    /// have one. Returns `None` if `text` isn't a `.p8` file or a section doesn't parse
+    pub fn with_p8(&self, text: &str) -> Option<CartData> {
+        if !text.starts_with("pico-8 cartridge") {
+            return None;
+        }
+        let mut cart = self.clone();
+        let mut section = "";
+        let mut row = 0;
+        for line in text.lines().map(str::trim_end) {
+            if is_section(line) {
+                section = line;
+                row = 0;
+                match section {
+                    "__gfx__" => cart.sprites = Rc::new(vec![0; 128 * 128]),
+                    "__gff__" => cart.flags = Rc::new(vec![0; 256]),
+                    "__map__" => {
+                        cart.map = Rc::new(vec![0; 128 * 32]);
+                        cart.map_width = 128;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match section {
+                // one hex digit per pixel
+                "__gfx__" if row < 128 => {
+                    let sprites = Rc::make_mut(&mut cart.sprites);
+                    for (i, c) in line.chars().take(128).enumerate() {
+                        sprites[row * 128 + i] = c.to_digit(16)? as u8;
+                    }
+                }
+                // two hex digits per sprite or tile
+                "__gff__" if row < 2 => {
+                    read_bytes(line, &mut Rc::make_mut(&mut cart.flags)[row * 128..])?
+                }
+                "__map__" if row < 32 => {
+                    read_bytes(line, &mut Rc::make_mut(&mut cart.map)[row * 128..])?
+                }
+                _ => continue,
+            }
+            row += 1;
+        }
+        Some(cart)
+    }
+
+    /// `base`, the text of a `.p8` file, with its data sections replaced by this cart's. Every
+    /// other section is kept, so writing into the original cart keeps its code. `base` can be
+    /// empty, which gives a cart with nothing but the data. Only the top left 128x32 tiles of a
+    /// map of any other size fit in the file
+    pub fn to_p8(&self, base: &str) -> String {
+        let mut text = String::new();
+        if !base.starts_with("pico-8 cartridge") {
+            text += HEADER;
+        }
+        let mut written = false;
+        let mut skipping = false;
+        for line in base.lines() {
+            if is_section(line.trim_end()) {
+                skipping = DATA_SECTIONS.contains(&line.trim_end());
+                // all three go where the first of them was
+                if skipping && !written {
+                    self.write_sections(&mut text);
+                    written = true;
+                }
+            }
+            if !skipping {
+                text += line;
+                text.push('\n');
+            }
+        }
+        if !written {
+            self.write_sections(&mut text);
+        }
+        text
+    }
+
+    fn write_sections(&self, text: &mut String) {
+        *text += "__gfx__\n";
+        for row in self.sprites.chunks(128) {
+            text.extend(
+                row.iter()
+                    .map(|&c| char::from_digit(c as u32 & 0xf, 16).unwrap()),
+            );
+            text.push('\n');
+        }
+        *text += "__gff__\n";
+        for row in self.flags.chunks(128) {
+            *text += &hex::encode(row);
+            text.push('\n');
+        }
+        *text += "__map__\n";
+        let w = self.map_width.max(1);
+        for y in 0..32 {
+            let row: Vec<u8> = (0..128)
+                .map(|x| {
+                    if x < w {
+                        self.map.get(x + y * w).copied().unwrap_or(0)
+                    } else {
+                        0
+                    }
+                })
+                .collect();
+            *text += &hex::encode(row);
+            text.push('\n');
+        }
+    }
+}
+
+/// The `__lua__` section of the `.p8` file `text`, the code `lua::LuaCart` runs. Its first line
+/// is line 1 in the interpreter's errors
+pub fn code(text: &str) -> Option<String> {
+    let mut lines = text.lines().skip_while(|line| line.trim_end() != "__lua__");
+    lines.next()?;
+    let mut code = String::new();
+    for line in lines.take_while(|line| !is_section(line.trim_end())) {
+        code += line;
+        code.push('\n');
+    }
+    Some(code)
+}
+
+/// Decodes a line of two digit hex numbers into `out`, stopping at whichever runs out first
+fn read_bytes(line: &str, out: &mut [u8]) -> Option<()> {
+    for (byte, pair) in out.iter_mut().zip(line.as_bytes().chunks(2)) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(())
+}
@@ -0,0 +1,156 @@
+//! A debug layer that shows what the physics sees: object hitboxes, solid and spike tiles, and
+//! the player's movement state. Set `Celeste::debug` to have `draw` put it on the game screen,
+//! or use `shapes` and `panel` to draw it yourself at a higher resolution
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+
+use crate::{
+    fixed::Fix16,
+    structures::{ObjectKind, ObjectType},
+    Celeste,
+};
+
+/// outline of a solid tile
+pub const SOLID_COL: u8 = 13;
+/// the part of a spike tile that kills, see `Celeste::spikes_at`
+pub const SPIKE_COL: u8 = 8;
+pub const HITBOX_COL: u8 = 11;
+pub const PLAYER_COL: u8 = 10;
+/// objects that `check` can't find
+pub const NON_COLLIDABLE_COL: u8 = 5;
+
+/// An outline on the game screen. Both corners are inclusive, like `Memory::rect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugRect {
+    pub x0: i32,
+    pub y0: i32,
+    pub x1: i32,
+    pub y1: i32,
+    pub col: u8,
+}
+
+/// Everything the overlay outlines, tiles first and then objects in update order. Only the
+/// tiles on the screen are there
+pub fn shapes(celeste: &Celeste) -> Vec<DebugRect> {
+    let mut shapes = Vec::new();
+    // rooms bigger than the screen scroll, the rest never do
+    let (dx, dy) = (celeste.scroll.x.to_int(), celeste.scroll.y.to_int());
+    let (tx, ty) = (dx / 8, dy / 8);
+    for j in ty..(ty + 17).min(celeste.room.h) {
+        for i in tx..(tx + 17).min(celeste.room.w) {
+            let tile = celeste.tile_at(Fix16::from_int(i), Fix16::from_int(j));
+            let (x, y) = (i * 8, j * 8);
+            if celeste.mem.fget(tile, 0) {
+                shapes.push(DebugRect {
+                    x0: x,
+                    y0: y,
+                    x1: x + 7,
+                    y1: y + 7,
+                    col: SOLID_COL,
+                });
+            }
+            let spike = match tile {
+                17 => Some((x, y + 6, x + 7, y + 7)),
+                27 => Some((x, y, x + 7, y + 2)),
+                43 => Some((x, y, x + 2, y + 7)),
+                59 => Some((x + 6, y, x + 7, y + 7)),
+                _ => None,
+            };
+            if let Some((x0, y0, x1, y1)) = spike {
+                shapes.push(DebugRect {
+                    x0,
+                    y0,
+                    x1,
+                    y1,
+                    col: SPIKE_COL,
+                });
+            }
+        }
+    }
+    for (_, obj) in celeste.objects.iter() {
+        let obj = obj.borrow();
+        shapes.push(DebugRect {
+            x0: obj.left().to_int(),
+            y0: obj.top().to_int(),
+            x1: obj.right().to_int(),
+            y1: obj.bottom().to_int(),
+            col: if obj.kind() == ObjectKind::Player {
+                PLAYER_COL
+            } else if obj.collidable {
+                HITBOX_COL
+            } else {
+                NON_COLLIDABLE_COL
+            },
+        });
+    }
+    for r in &mut shapes {
+        (r.x0, r.y0, r.x1, r.y1) = (r.x0 - dx, r.y0 - dy, r.x1 - dx, r.y1 - dy);
+    }
+    shapes
+}
+
+/// The text panel: the player's movement state, if there's a player, then how many of each
+/// object there are
+pub fn panel(celeste: &Celeste) -> Vec<String> {
+    let mut lines = Vec::new();
+    let player = celeste
+        .objects
+        .of_kind(ObjectKind::Player)
+        .map(|(_, obj)| obj.borrow())
+        .next();
+    if let Some(obj) = player {
+        lines.push(format!(
+            "pos {:.2} {:.2}",
+            obj.pos.x.to_f32(),
+            obj.pos.y.to_f32()
+        ));
+        lines.push(format!(
+            "spd {:.2} {:.2}",
+            obj.spd.x.to_f32(),
+            obj.spd.y.to_f32()
+        ));
+        lines.push(format!(
+            "rem {:.2} {:.2}",
+            obj.rem.x.to_f32(),
+            obj.rem.y.to_f32()
+        ));
+        if let ObjectType::Player(p) = &obj.obj_type {
+            let p = p.borrow();
+            lines.push(format!("grace {} jbuffer {}", p.grace, p.jbuffer));
+            lines.push(format!("djump {} dash_time {}", p.djump, p.dash_time));
+        }
+    } else {
+        lines.push("no player".into());
+    }
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for (id, _) in celeste.objects.iter() {
+        let name = match celeste.objects.kind(id) {
+            Some(ObjectKind::Custom(_)) => "custom".into(),
+            Some(kind) => format!("{:?}", kind).to_lowercase(),
+            None => continue,
+        };
+        *counts.entry(name).or_default() += 1;
+    }
+    lines.push(format!("objects {}", celeste.objects.len()));
+    for (name, count) in counts {
+        lines.push(format!(" {} {}", name, count));
+    }
+    lines
+}
+
+/// Draws the overlay onto the game screen, on top of everything else
+pub fn draw(celeste: &mut Celeste) {
+    for r in shapes(celeste) {
+        celeste.mem.rect(r.x0, r.y0, r.x1, r.y1, r.col);
+    }
+    let lines = panel(celeste);
+    let width = lines
+        .iter()
+        .map(|line| celeste.mem.measure(line).0)
+        .max()
+        .unwrap_or(0);
+    celeste.mem.rectfill(0, 0, width, lines.len() as i32 * 6, 0);
+    for (i, line) in lines.iter().enumerate() {
+        celeste.mem.print(line, 1, 1 + i as i32 * 6, 7);
+    }
+}
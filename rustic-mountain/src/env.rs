@@ -0,0 +1,361 @@
+//! A reinforcement learning environment in the style of OpenAI Gym. `Env` owns a `Celeste`,
+//! `reset` starts an episode and `step` plays one tick with the buttons an agent picked.
+//!
+//! Everything here is deterministic: the same seed and the same actions always give the same
+//! observations and rewards
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    events::GameEvent, fixed::Fix16, objects::player::Player, practice::Practice,
+    snapshot::SaveState, structures::ObjectKind, Celeste,
+};
+
+/// What `Env::step` and `Env::reset` return the game as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObservationKind {
+    /// `Observation::Frame`. Makes the game draw every tick, whatever `EnvConfig::render` says
+    Frame,
+    /// `Observation::Tiles`
+    Tiles,
+    /// `Observation::Features`
+    Features,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Observation {
+    /// the 128x128 screen, one palette index per pixel, row by row
+    Frame(Vec<u8>),
+    /// the 16x16 tiles on the screen as one of the `TILE_` classes each, row by row. Objects are
+    /// put over the tile their middle is in
+    Tiles(Vec<u8>),
+    /// `FEATURE_COUNT` numbers about the player and what's around them, see `FEATURE_` for
+    /// where each one is
+    Features(Vec<f32>),
+}
+
+pub const TILE_EMPTY: u8 = 0;
+/// a tile with flag 0 set, or past the sides of the room the player can't leave through
+pub const TILE_SOLID: u8 = 1;
+/// spikes, or below the bottom of a room without an exit there
+pub const TILE_SPIKE: u8 = 2;
+/// a collidable object other than the player
+pub const TILE_OBJECT: u8 = 3;
+pub const TILE_PLAYER: u8 = 4;
+
+/// 1 if there's a player, 0 while it's dead or still spawning. Everything about the player is
+/// 0 when there isn't one
+pub const FEATURE_ALIVE: usize = 0;
+/// position divided by the room's size in pixels, so 0-1 inside the room
+pub const FEATURE_X: usize = 1;
+pub const FEATURE_Y: usize = 2;
+pub const FEATURE_SPD_X: usize = 3;
+pub const FEATURE_SPD_Y: usize = 4;
+/// dashes left
+pub const FEATURE_DJUMP: usize = 5;
+/// 1 if the player was on the ground last tick
+pub const FEATURE_ON_GROUND: usize = 6;
+/// ticks left in the current dash
+pub const FEATURE_DASH_TIME: usize = 7;
+/// `TILE_EMPTY`, `TILE_SOLID` or `TILE_SPIKE` for the `HAZARD_SIZE` by `HAZARD_SIZE` tiles
+/// centered on the player, row by row
+pub const FEATURE_HAZARDS: usize = 8;
+pub const HAZARD_SIZE: usize = 5;
+pub const FEATURE_COUNT: usize = FEATURE_HAZARDS + HAZARD_SIZE * HAZARD_SIZE;
+
+/// What each step is worth. Every field can be 0 to turn it off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rewards {
+    /// for every pixel the player gets above the highest point they've reached in the room
+    pub height: f32,
+    /// for reaching the next room
+    pub room_clear: f32,
+    /// for dying, so it should be negative
+    pub death: f32,
+    /// for every step, negative to hurry the agent along
+    pub step: f32,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            height: 0.1,
+            room_clear: 10.0,
+            death: -5.0,
+            step: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvConfig {
+    pub observation: ObservationKind,
+    pub rewards: Rewards,
+    /// Runs `Celeste::draw` after every tick. Leave it off when nothing looks at the screen, the
+    /// game plays the same and is much faster without it
+    pub render: bool,
+    /// the room each episode starts in, and what the player has there
+    pub start: Practice,
+    /// ends the episode when the player dies
+    pub end_on_death: bool,
+    /// ends the episode when the player reaches the next room
+    pub end_on_room_clear: bool,
+    /// ends the episode after this many steps
+    pub max_steps: Option<u32>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        EnvConfig {
+            observation: ObservationKind::Features,
+            rewards: Rewards::default(),
+            render: false,
+            start: Practice::default(),
+            end_on_death: false,
+            end_on_room_clear: false,
+            max_steps: Some(30 * 60),
+        }
+    }
+}
+
+/// What happened during a step, besides the reward
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StepInfo {
+    /// the room the game is in after the step
+    pub level: u8,
+    /// steps since `reset`, counting this one
+    pub steps: u32,
+    pub died: bool,
+    pub room_cleared: bool,
+    /// the episode ended because of `EnvConfig::max_steps` rather than something the agent did
+    pub truncated: bool,
+}
+
+pub struct Env {
+    /// The game being played. Its events are drained by `step`, so don't rely on them
+    pub celeste: Celeste,
+    pub config: EnvConfig,
+    /// the game as it was given to `new`, every episode starts from it
+    initial: SaveState,
+    steps: u32,
+    /// the highest y the player has reached in the current room, `None` until there's a player
+    best_y: Option<i32>,
+}
+
+impl Env {
+    /// Wraps `celeste`, which should be fresh from `Celeste::new` with any objects registered and
+    /// `assist` set up the way the agent should play
+    pub fn new(celeste: Celeste, config: EnvConfig) -> Env {
+        Env {
+            initial: celeste.save_state(),
+            celeste,
+            config,
+            steps: 0,
+            best_y: None,
+        }
+    }
+
+    /// Starts a new episode in `config.start`, with the random number generator seeded by `seed`
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.celeste.load_state(&self.initial);
+        self.celeste.seed(seed);
+        self.celeste.begin_practice(self.config.start);
+        self.celeste.drain_events();
+        self.steps = 0;
+        self.best_y = None;
+        self.track_height();
+        if self.drawing() {
+            self.celeste.draw();
+        }
+        self.observe()
+    }
+
+    /// Holds the buttons in `action`, a `btn()` bitmask like `Replay` uses, for one tick.
+    /// Returns the observation after it, the reward for it, whether the episode is over, and
+    /// what happened
+    pub fn step(&mut self, action: u8) -> (Observation, f32, bool, StepInfo) {
+        for (i, button) in self.celeste.mem.buttons.iter_mut().enumerate() {
+            *button = action & (1 << i) != 0;
+        }
+        self.celeste.next_tick();
+        if self.drawing() {
+            self.celeste.draw();
+        }
+        self.steps += 1;
+
+        let rewards = self.config.rewards;
+        let mut reward = rewards.step;
+        let mut info = StepInfo {
+            steps: self.steps,
+            ..StepInfo::default()
+        };
+        let mut summit = false;
+        for event in self.celeste.drain_events() {
+            match event {
+                GameEvent::PlayerDied { .. } => info.died = true,
+                GameEvent::RoomEntered { .. } => info.room_cleared = true,
+                GameEvent::SummitReached => summit = true,
+                _ => {}
+            }
+        }
+        if info.died {
+            reward += rewards.death;
+        }
+        if info.room_cleared {
+            reward += rewards.room_clear;
+            self.best_y = None;
+        }
+        reward += rewards.height * self.track_height() as f32;
+        info.level = self.celeste.level;
+
+        let mut done = summit
+            || (info.died && self.config.end_on_death)
+            || (info.room_cleared && self.config.end_on_room_clear);
+        if !done && self.config.max_steps.is_some_and(|max| self.steps >= max) {
+            done = true;
+            info.truncated = true;
+        }
+        (self.observe(), reward, done, info)
+    }
+
+    /// The game as `config.observation` describes, without stepping it
+    pub fn observe(&self) -> Observation {
+        match self.config.observation {
+            ObservationKind::Frame => Observation::Frame(self.celeste.mem.graphics.clone()),
+            ObservationKind::Tiles => Observation::Tiles(self.tiles()),
+            ObservationKind::Features => Observation::Features(self.features()),
+        }
+    }
+
+    fn drawing(&self) -> bool {
+        self.config.render || self.config.observation == ObservationKind::Frame
+    }
+
+    /// how many pixels the player has gone above `best_y`, which is moved up to match
+    fn track_height(&mut self) -> i32 {
+        let Some((_, obj)) = self.celeste.objects.of_kind(ObjectKind::Player).next() else {
+            return 0;
+        };
+        let y = obj.borrow().pos.y.to_int();
+        let best = self.best_y.get_or_insert(y);
+        let gained = (*best - y).max(0);
+        *best = (*best).min(y);
+        gained
+    }
+
+    /// The class of the tile at `x`, `y` in the room, which can be outside of it. Past the
+    /// room's sides is solid and below it is spikes, unless the room exits that way
+    fn tile_class(&self, x: i32, y: i32) -> u8 {
+        let room = &self.celeste.room;
+        if y >= room.h {
+            return if room.exits.bottom {
+                TILE_EMPTY
+            } else {
+                TILE_SPIKE
+            };
+        }
+        if (x < 0 && !room.exits.left) || (x >= room.w && !room.exits.right) {
+            return TILE_SOLID;
+        }
+        if y < 0 || !(0..room.w).contains(&x) {
+            return TILE_EMPTY;
+        }
+        let tile = self.celeste.tile_at(Fix16::from_int(x), Fix16::from_int(y));
+        if matches!(tile, 17 | 27 | 43 | 59) {
+            TILE_SPIKE
+        } else if self.celeste.mem.fget(tile, 0) {
+            TILE_SOLID
+        } else {
+            TILE_EMPTY
+        }
+    }
+
+    fn tiles(&self) -> Vec<u8> {
+        // the 16x16 tiles on the screen, which is all of the room unless it scrolls
+        let left = self.celeste.scroll.x.to_int() / 8;
+        let top = self.celeste.scroll.y.to_int() / 8;
+        let mut tiles: Vec<u8> = (0..16 * 16)
+            .map(|i| self.tile_class(left + i % 16, top + i / 16))
+            .collect();
+        for (_, obj) in self.celeste.objects.iter() {
+            let obj = obj.borrow();
+            let class = match obj.kind() {
+                ObjectKind::Player => TILE_PLAYER,
+                _ if obj.collidable => TILE_OBJECT,
+                _ => continue,
+            };
+            let x = ((obj.left() + obj.right()) / Fix16::from_int(16)).to_int() - left;
+            let y = ((obj.top() + obj.bottom()) / Fix16::from_int(16)).to_int() - top;
+            if (0..16).contains(&x) && (0..16).contains(&y) {
+                tiles[(y * 16 + x) as usize] = class;
+            }
+        }
+        tiles
+    }
+
+    fn features(&self) -> Vec<f32> {
+        let mut features = vec![0.0; FEATURE_COUNT];
+        let Some((id, obj)) = self.celeste.objects.of_kind(ObjectKind::Player).next() else {
+            return features;
+        };
+        let obj = obj.borrow();
+        features[FEATURE_ALIVE] = 1.0;
+        features[FEATURE_X] = obj.pos.x.to_f32() / self.celeste.room.width() as f32;
+        features[FEATURE_Y] = obj.pos.y.to_f32() / self.celeste.room.height() as f32;
+        features[FEATURE_SPD_X] = obj.spd.x.to_f32();
+        features[FEATURE_SPD_Y] = obj.spd.y.to_f32();
+        if let Some(player) = self.celeste.get::<Player>(id) {
+            let player = player.borrow();
+            features[FEATURE_DJUMP] = player.djump as f32;
+            features[FEATURE_ON_GROUND] = player.was_on_ground as u8 as f32;
+            features[FEATURE_DASH_TIME] = player.dash_time as f32;
+        }
+        let x = ((obj.left() + obj.right()) / Fix16::from_int(16)).to_int();
+        let y = ((obj.top() + obj.bottom()) / Fix16::from_int(16)).to_int();
+        let half = HAZARD_SIZE as i32 / 2;
+        for j in 0..HAZARD_SIZE as i32 {
+            for i in 0..HAZARD_SIZE as i32 {
+                features[FEATURE_HAZARDS + (j * HAZARD_SIZE as i32 + i) as usize] =
+                    self.tile_class(x + i - half, y + j - half) as f32;
+            }
+        }
+        features
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testcart;
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_episode() {
+        for observation in [
+            ObservationKind::Frame,
+            ObservationKind::Tiles,
+            ObservationKind::Features,
+        ] {
+            let config = EnvConfig {
+                observation,
+                ..EnvConfig::default()
+            };
+            let mut a = Env::new(testcart::celeste(), config);
+            let mut b = Env::new(testcart::celeste(), config);
+            assert_eq!(a.reset(7), b.reset(7));
+            // run right, jump now and then and dash up right every so often
+            for tick in 0..300u32 {
+                let mut action = 1 << 1;
+                if tick % 25 < 4 {
+                    action |= 1 << 4;
+                }
+                if tick % 60 == 10 {
+                    action |= 1 << 2 | 1 << 5;
+                }
+                let (obs_a, reward_a, done_a, info_a) = a.step(action);
+                let (obs_b, reward_b, done_b, info_b) = b.step(action);
+                assert!(obs_a == obs_b, "{:?} tick {}", observation, tick);
+                assert_eq!(reward_a, reward_b, "{:?} tick {}", observation, tick);
+                assert_eq!((done_a, info_a), (done_b, info_b));
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::pause::PauseItem;
+
+/// Something that happened during a tick, for frontends that want to react to the game (audio,
+/// stats, splits, ...) without digging through objects. Collected in `Celeste::events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEvent {
+    /// the player touched spikes or fell out of the room. `x` and `y` are where they died
+    PlayerDied {
+        level: u8,
+        x: i32,
+        y: i32,
+    },
+    /// a new room was loaded, either by starting the game or by leaving the previous one
+    RoomEntered {
+        level: u8,
+    },
+    /// a strawberry was collected. fly fruits and chest fruits count too
+    BerryCollected {
+        level: u8,
+    },
+    DashUsed {
+        level: u8,
+    },
+    KeyCollected {
+        level: u8,
+    },
+    /// `big` is the chest at the summit that holds the orb
+    ChestOpened {
+        level: u8,
+        big: bool,
+    },
+    OrbCollected,
+    /// the player reached the flag
+    SummitReached,
+    /// a run just finished faster than `Celeste::personal_best`, which now holds it
+    PersonalBest,
+    /// `level` was finished faster than its ghost, `Celeste::ghosts` now holds the new one
+    GhostRecorded {
+        level: u8,
+    },
+    /// an item of the pause menu that the frontend has to handle was picked. the menu is closed
+    /// by the time this is emitted
+    PauseMenu(PauseItem),
+    /// the cart would've called `sfx()` with this number here
+    SfxRequested(u8),
+}
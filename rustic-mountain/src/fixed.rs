@@ -0,0 +1,282 @@
+use core::{
+    f64::consts::PI,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign},
+};
+
+/// A signed 16.16 fixed point number, the only numeric type pico-8 has.
+///
+/// All arithmetic wraps or saturates exactly like pico-8 does, so anything simulated with it
+/// (positions, subpixel remainders, speeds) comes out bit-identical to the original cart.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub struct Fix16(i32);
+
+/// Shorthand for `Fix16::from_f32`, for writing pico-8 literals
+pub const fn fix(v: f32) -> Fix16 {
+    Fix16::from_f32(v)
+}
+
+impl Fix16 {
+    pub const ZERO: Fix16 = Fix16(0);
+    pub const ONE: Fix16 = Fix16(0x1_0000);
+    pub const MAX: Fix16 = Fix16(i32::MAX);
+    pub const MIN: Fix16 = Fix16(i32::MIN);
+
+    pub const fn from_bits(bits: i32) -> Fix16 {
+        Fix16(bits)
+    }
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+    pub const fn from_int(v: i32) -> Fix16 {
+        Fix16(v << 16)
+    }
+    /// Converts the same way pico-8 parses a number literal: rounded to the nearest 1/65536
+    pub const fn from_f32(v: f32) -> Fix16 {
+        let scaled = v as f64 * 65536.0;
+        Fix16(if scaled < 0.0 {
+            (scaled - 0.5) as i64 as i32
+        } else {
+            (scaled + 0.5) as i64 as i32
+        })
+    }
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / 65536.0
+    }
+    /// `flr()`, returned as an integer
+    pub const fn to_int(self) -> i32 {
+        self.0 >> 16
+    }
+
+    /// `flr()`
+    pub const fn floor(self) -> Fix16 {
+        Fix16(self.0 & !0xffff)
+    }
+    /// `ceil()`
+    pub const fn ceil(self) -> Fix16 {
+        Fix16(self.0.wrapping_neg() & !0xffff).neg_const()
+    }
+    /// `abs()`. -32768 has no positive counterpart, so it saturates to `MAX` like pico-8's does
+    pub const fn abs(self) -> Fix16 {
+        Fix16(self.0.saturating_abs())
+    }
+    /// `sgn()`. note that unlike the cart's own `sign()`, this returns 1 for 0
+    pub const fn sgn(self) -> Fix16 {
+        if self.0 < 0 {
+            Fix16(-0x1_0000)
+        } else {
+            Fix16::ONE
+        }
+    }
+    pub fn min(self, other: Fix16) -> Fix16 {
+        Ord::min(self, other)
+    }
+    pub fn max(self, other: Fix16) -> Fix16 {
+        Ord::max(self, other)
+    }
+
+    /// pico-8's `sin()`: takes turns instead of radians and is inverted, so `sin(0.25) == -1`
+    pub fn sin(self) -> Fix16 {
+        Fix16::from_unit(-libm::sin(self.turns() * 2.0 * PI))
+    }
+    /// pico-8's `cos()`: takes turns instead of radians
+    pub fn cos(self) -> Fix16 {
+        Fix16::from_unit(libm::cos(self.turns() * 2.0 * PI))
+    }
+
+    /// only the fractional part matters for trig, and dropping the integer part first keeps
+    /// large angles as precise as pico-8's
+    fn turns(self) -> f64 {
+        (self.0 & 0xffff) as f64 / 65536.0
+    }
+    fn from_unit(v: f64) -> Fix16 {
+        Fix16(libm::round(v * 65536.0) as i32)
+    }
+    const fn neg_const(self) -> Fix16 {
+        Fix16(self.0.wrapping_neg())
+    }
+}
+
+impl From<i32> for Fix16 {
+    fn from(v: i32) -> Self {
+        Fix16::from_int(v)
+    }
+}
+impl From<f32> for Fix16 {
+    fn from(v: f32) -> Self {
+        Fix16::from_f32(v)
+    }
+}
+
+impl Add for Fix16 {
+    type Output = Fix16;
+    fn add(self, rhs: Fix16) -> Fix16 {
+        Fix16(self.0.wrapping_add(rhs.0))
+    }
+}
+impl Sub for Fix16 {
+    type Output = Fix16;
+    fn sub(self, rhs: Fix16) -> Fix16 {
+        Fix16(self.0.wrapping_sub(rhs.0))
+    }
+}
+impl Mul for Fix16 {
+    type Output = Fix16;
+    fn mul(self, rhs: Fix16) -> Fix16 {
+        Fix16(((self.0 as i64 * rhs.0 as i64) >> 16) as i32)
+    }
+}
+impl Div for Fix16 {
+    type Output = Fix16;
+    /// division by zero and overflow saturate to ±0x7fff.ffff instead of panicking
+    fn div(self, rhs: Fix16) -> Fix16 {
+        let saturated = if (self.0 < 0) != (rhs.0 < 0) {
+            Fix16(-i32::MAX)
+        } else {
+            Fix16(i32::MAX)
+        };
+        if rhs.0 == 0 {
+            return saturated;
+        }
+        let res = ((self.0 as i64) << 16) / rhs.0 as i64;
+        if res > i32::MAX as i64 || res < -(i32::MAX as i64) {
+            saturated
+        } else {
+            Fix16(res as i32)
+        }
+    }
+}
+impl Rem for Fix16 {
+    type Output = Fix16;
+    /// pico-8's `%` uses the absolute value of the divisor, so the result is never negative
+    fn rem(self, rhs: Fix16) -> Fix16 {
+        let m = rhs.0.wrapping_abs();
+        if m == 0 {
+            return Fix16::ZERO;
+        }
+        Fix16(self.0.rem_euclid(m))
+    }
+}
+impl Neg for Fix16 {
+    type Output = Fix16;
+    fn neg(self) -> Fix16 {
+        self.neg_const()
+    }
+}
+
+impl AddAssign for Fix16 {
+    fn add_assign(&mut self, rhs: Fix16) {
+        *self = *self + rhs;
+    }
+}
+impl SubAssign for Fix16 {
+    fn sub_assign(&mut self, rhs: Fix16) {
+        *self = *self - rhs;
+    }
+}
+impl MulAssign for Fix16 {
+    fn mul_assign(&mut self, rhs: Fix16) {
+        *self = *self * rhs;
+    }
+}
+impl DivAssign for Fix16 {
+    fn div_assign(&mut self, rhs: Fix16) {
+        *self = *self / rhs;
+    }
+}
+impl RemAssign for Fix16 {
+    fn rem_assign(&mut self, rhs: Fix16) {
+        *self = *self % rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals_round_to_nearest() {
+        assert_eq!(fix(0.1).to_bits(), 0x199a);
+        assert_eq!(fix(-0.1).to_bits(), -0x199a);
+        assert_eq!(fix(0.707_106_77).to_bits(), 46341);
+        assert_eq!(fix(-1.5).to_bits(), -0x1_8000);
+        assert_eq!(fix(32767.0), Fix16::from_int(32767));
+    }
+
+    #[test]
+    fn mul_truncates_and_wraps() {
+        // the cart's diagonal dash acceleration, which a literal can't give
+        assert_eq!((fix(1.5) * fix(0.707_106_77)).to_bits(), 69511);
+        assert_eq!(fix(1.5) * fix(-0.5), fix(-0.75));
+        assert_eq!(Fix16::from_bits(1) * fix(0.5), Fix16::ZERO);
+        assert_eq!(Fix16::from_bits(-1) * fix(0.5), Fix16::from_bits(-1));
+        assert_eq!(fix(256.0) * fix(256.0), Fix16::ZERO);
+    }
+
+    #[test]
+    fn div_saturates() {
+        assert_eq!(fix(7.0) / fix(2.0), fix(3.5));
+        assert_eq!(fix(-7.0) / fix(2.0), fix(-3.5));
+        assert_eq!(Fix16::ONE / Fix16::ZERO, Fix16::MAX);
+        assert_eq!(fix(-1.0) / Fix16::ZERO, -Fix16::MAX);
+        assert_eq!(fix(30000.0) / fix(0.5), Fix16::MAX);
+        assert_eq!(fix(30000.0) / fix(-0.5), -Fix16::MAX);
+        assert_eq!(Fix16::MIN / fix(-1.0), Fix16::MAX);
+    }
+
+    #[test]
+    fn rem_is_never_negative() {
+        assert_eq!(fix(5.0) % fix(3.0), fix(2.0));
+        assert_eq!(fix(-1.0) % fix(3.0), fix(2.0));
+        assert_eq!(fix(5.0) % fix(-3.0), fix(2.0));
+        assert_eq!(fix(-5.5) % fix(2.0), fix(0.5));
+        assert_eq!(fix(-5.5) % fix(-2.0), fix(0.5));
+        assert_eq!(fix(3.0) % Fix16::ZERO, Fix16::ZERO);
+    }
+
+    #[test]
+    fn trig_at_quarter_turns() {
+        assert_eq!(Fix16::ZERO.sin(), Fix16::ZERO);
+        assert_eq!(fix(0.25).sin(), fix(-1.0));
+        assert_eq!(fix(0.5).sin(), Fix16::ZERO);
+        assert_eq!(fix(0.75).sin(), Fix16::ONE);
+        assert_eq!(Fix16::ZERO.cos(), Fix16::ONE);
+        assert_eq!(fix(0.25).cos(), Fix16::ZERO);
+        assert_eq!(fix(0.5).cos(), fix(-1.0));
+        assert_eq!(fix(0.75).cos(), Fix16::ZERO);
+        // only the fractional part counts
+        assert_eq!(fix(-2.75).sin(), fix(-1.0));
+        assert_eq!(fix(3.5).cos(), fix(-1.0));
+    }
+
+    #[test]
+    fn trig_between_quarter_turns() {
+        // pico-8 prints these as -0.5878, 0.809, -0.9511, -0.309, 0.9921 and 0.1254. the angles
+        // are the literals' 16.16 values, and each result is the nearest 16.16 value to the exact
+        // one at that angle
+        assert_eq!(fix(0.1).sin().to_bits(), -38523);
+        assert_eq!(fix(0.1).cos().to_bits(), 53018);
+        assert_eq!(fix(0.3).sin().to_bits(), -62328);
+        assert_eq!(fix(0.3).cos().to_bits(), -20253);
+        assert_eq!(fix(0.77).sin().to_bits(), 65019);
+        assert_eq!(fix(0.77).cos().to_bits(), 8216);
+        // odd and even
+        assert_eq!(fix(-0.1).sin(), -fix(0.1).sin());
+        assert_eq!(fix(-0.1).cos(), fix(0.1).cos());
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(fix(-1.5).floor(), fix(-2.0));
+        assert_eq!(fix(-1.5).ceil(), fix(-1.0));
+        assert_eq!(fix(1.25).ceil(), fix(2.0));
+        assert_eq!(fix(-0.5).to_int(), -1);
+        assert_eq!(Fix16::ZERO.sgn(), Fix16::ONE);
+    }
+
+    #[test]
+    fn abs_saturates() {
+        assert_eq!(fix(-1.5).abs(), fix(1.5));
+        assert_eq!(Fix16::from_bits(-1).abs(), Fix16::from_bits(1));
+        assert_eq!(Fix16::MIN.abs(), Fix16::MAX);
+    }
+}
@@ -0,0 +1,236 @@
+//! Ghosts of the fastest run through each room. `Celeste` records the player every tick, keeps
+//! the recording whenever a room is finished faster than its ghost, and draws the ghost of the
+//! current room behind the objects when `Celeste::show_ghost` is set
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+
+use crate::{structures::FlipState, Celeste};
+
+/// How the player looked on one tick, see `Celeste::player_frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GhostFrame {
+    pub x: i32,
+    pub y: i32,
+    pub spr: u8,
+    pub flip_x: bool,
+}
+
+/// A room's recording, one frame per tick counted by `Celeste::il_frames`. `None` while there's
+/// no player, e.g. after dying
+pub type GhostRun = Vec<Option<GhostFrame>>;
+
+/// The best recording of each room, by level. Frontends load this from wherever they keep it,
+/// and should save it again on `GameEvent::GhostRecorded`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Ghosts {
+    pub rooms: BTreeMap<u8, GhostRun>,
+}
+
+/// what the ghost's colors are swapped for, so it can't be mistaken for the player
+const GHOST_PAL: [u8; 16] = [0, 1, 1, 1, 2, 1, 13, 6, 2, 4, 9, 3, 13, 5, 2, 13];
+
+impl Ghosts {
+    /// Keeps `run` as the ghost of `level` if it's faster than the one there. Returns whether
+    /// it was
+    pub fn offer(&mut self, level: u8, run: GhostRun) -> bool {
+        if self
+            .rooms
+            .get(&level)
+            .is_some_and(|best| best.len() <= run.len())
+        {
+            return false;
+        }
+        self.rooms.insert(level, run);
+        true
+    }
+
+    /// A `room <level>` line for each room, followed by a line for each frame: `x y spr flip`,
+    /// or `-` when there was no player. This is what frontends should save
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (level, run) in &self.rooms {
+            text += &format!("room {}\n", level);
+            for frame in run {
+                match frame {
+                    Some(f) => text += &format!("{} {} {} {}\n", f.x, f.y, f.spr, f.flip_x as u8),
+                    None => text += "-\n",
+                }
+            }
+        }
+        text
+    }
+    /// Parses what `to_text` wrote. Returns `None` if any line doesn't make sense, or a room
+    /// comes up twice
+    pub fn from_text(text: &str) -> Option<Ghosts> {
+        let mut ghosts = Ghosts::default();
+        let mut run = None;
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(level) = line.strip_prefix("room ") {
+                let level = level.parse().ok()?;
+                if ghosts.rooms.contains_key(&level) {
+                    return None;
+                }
+                run = Some(ghosts.rooms.entry(level).or_default());
+                continue;
+            }
+            let frame = if line == "-" {
+                None
+            } else {
+                let mut parts = line.split(' ');
+                let mut next = || parts.next()?.parse::<i32>().ok();
+                let frame = GhostFrame {
+                    x: next()?,
+                    y: next()?,
+                    spr: next()?.try_into().ok()?,
+                    flip_x: next()? != 0,
+                };
+                if parts.next().is_some() {
+                    return None;
+                }
+                Some(frame)
+            };
+            run.as_mut()?.push(frame);
+        }
+        Some(ghosts)
+    }
+}
+
+/// Draws the ghost of the current room where it was at this point in its run
+pub fn draw(celeste: &mut Celeste) {
+    let Some(frame) = celeste
+        .ghosts
+        .rooms
+        .get(&celeste.level)
+        .and_then(|run| run.get(celeste.il_frames as usize))
+        .copied()
+        .flatten()
+    else {
+        return;
+    };
+    for (i, &col) in GHOST_PAL.iter().enumerate() {
+        celeste.mem.pal(i, col);
+    }
+    celeste.mem.spr(
+        frame.spr,
+        frame.x,
+        frame.y,
+        Some(FlipState {
+            x: frame.flip_x,
+            y: false,
+        }),
+    );
+    for i in 0..16 {
+        celeste.mem.pal(i, i as u8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::GameEvent, testcart};
+    use alloc::vec;
+
+    fn frame(x: i32, y: i32) -> Option<GhostFrame> {
+        Some(GhostFrame {
+            x,
+            y,
+            spr: 1,
+            flip_x: x < 0,
+        })
+    }
+
+    /// a run `ticks` long
+    fn run(ticks: i32) -> GhostRun {
+        (0..ticks).map(|x| frame(x, 96)).collect()
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let mut ghosts = Ghosts::default();
+        ghosts
+            .rooms
+            .insert(0, vec![frame(-3, 100), None, frame(4, -8)]);
+        ghosts.rooms.insert(12, run(5));
+        ghosts.rooms.insert(30, vec![]);
+        let text = ghosts.to_text();
+        assert!(text.starts_with("room 0\n-3 100 1 1\n-\n4 -8 1 0\nroom 12\n"));
+        assert_eq!(Ghosts::from_text(&text), Some(ghosts));
+        assert_eq!(Ghosts::from_text(""), Some(Ghosts::default()));
+        assert_eq!(
+            Ghosts::from_text("\r\nroom 2\r\n 1 2 3 0 \r\n\n-\r\n")
+                .unwrap()
+                .rooms[&2],
+            [
+                Some(GhostFrame {
+                    x: 1,
+                    y: 2,
+                    spr: 3,
+                    flip_x: false
+                }),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn from_text_rejects_malformed_lines() {
+        for text in [
+            "1 2 3 0\n",
+            "room\n",
+            "room x\n",
+            "room 300\n",
+            "room 1\n1 2 3\n",
+            "room 1\n1 2 3 0 5\n",
+            "room 1\n1 2 256 0\n",
+            "room 1\na b c d\n",
+            "room 1\n-\nroom 1\n-\n",
+        ] {
+            assert_eq!(Ghosts::from_text(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn offer_keeps_the_fastest_run() {
+        let mut ghosts = Ghosts::default();
+        assert!(ghosts.offer(3, run(50)));
+        assert!(!ghosts.offer(3, run(60)));
+        // a tie keeps the one that got there first
+        assert!(!ghosts.offer(3, vec![None; 50]));
+        assert_eq!(ghosts.rooms[&3], run(50));
+        assert!(ghosts.offer(3, run(40)));
+        assert_eq!(ghosts.rooms[&3], run(40));
+        // rooms are separate
+        assert!(ghosts.offer(4, run(90)));
+        assert_eq!(ghosts.rooms.len(), 2);
+    }
+
+    #[test]
+    fn finishing_a_room_records_its_ghost() {
+        let mut celeste = testcart::celeste();
+        celeste.begin_game();
+        for _ in 0..50 {
+            celeste.next_tick();
+        }
+        celeste.next_room();
+        assert_eq!(celeste.ghosts.rooms[&0].len(), 50);
+        assert!(celeste
+            .drain_events()
+            .any(|e| e == GameEvent::GhostRecorded { level: 0 }));
+
+        // a slower run, or an assisted one, doesn't replace it
+        celeste.begin_game();
+        for _ in 0..60 {
+            celeste.next_tick();
+        }
+        celeste.next_room();
+        celeste.begin_game();
+        celeste.assist.infinite_dashes = true;
+        for _ in 0..20 {
+            celeste.next_tick();
+        }
+        celeste.next_room();
+        assert_eq!(celeste.ghosts.rooms[&0].len(), 50);
+        assert!(!celeste
+            .drain_events()
+            .any(|e| matches!(e, GameEvent::GhostRecorded { .. })));
+    }
+}
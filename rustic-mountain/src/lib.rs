@@ -1,35 +1,74 @@
 #![no_std]
+pub mod assist;
+pub mod cart;
+pub mod debug;
+pub mod env;
+pub mod events;
+pub mod fixed;
+pub mod ghost;
+#[cfg(feature = "lua")]
+pub mod lua;
 pub mod memory;
 pub mod objects;
+pub mod p8scii;
+pub mod pause;
+pub mod practice;
+pub mod replay;
+pub mod rewind;
+pub mod rng;
+pub mod rooms;
+pub mod snapshot;
+pub mod speedrun;
+pub mod stats;
 pub mod structures;
+#[cfg(test)]
+mod testcart;
 pub mod utils;
 extern crate alloc;
 
-use core::cell::RefCell;
-use alloc::{format, rc::Rc, string::String, vec, vec::Vec};
-
-use memory::Memory;
+use core::cell::{RefCell, RefMut};
+use alloc::{collections::BTreeMap, format, rc::Rc, string::String, vec, vec::Vec};
+
+use assist::Assist;
+use events::GameEvent;
+use ghost::{GhostFrame, GhostRun, Ghosts};
+use memory::{CartData, Memory};
+use pause::{PauseItem, PauseMenu};
+use practice::Practice;
+use rng::GameRng;
+use rooms::{Layout, Room};
+use speedrun::Splits;
+use stats::RoomStats;
 use objects::{
-    balloon::Balloon, bigchest::BigChest, chest::Chest, fakewall::FakeWall, fallfloor::FallFloor,
-    flag::Flag, flyfruit::FlyFruit, fruit::Fruit, key::Key, message::Message,
-    platform::Platform, playerspawn::PlayerSpawn, roomtitle::RoomTitle,
-    spring::Spring,
+    balloon::Balloon, bigchest::BigChest, chest::Chest, custom, custom::GameObject,
+    fakewall::FakeWall, fallfloor::FallFloor, flag::Flag, flyfruit::FlyFruit, fruit::Fruit, key::Key, message::Message, platform::Platform,
+    playerspawn::PlayerSpawn, roomtitle::RoomTitle, spring::Spring,
 };
+use fixed::{fix, Fix16};
 use structures::*;
 
 use rand::prelude::*;
-use utils::{max, sin};
+use utils::sin;
+// the test harness links std, whose float methods take precedence
+#[cfg_attr(test, allow(unused_imports))]
 use utils::LibmExt;
 
+/// Clones share the cart's data and copy everything else, which is cheap enough for a search
+/// to fork the game at every tick. Clone between ticks, not from inside an object
+#[derive(Clone)]
 pub struct Celeste {
     /// Represents the pico-8 display buffers and memory. Go through this for any drawing
     pub mem: Memory,
-    pub objects: Vec<Rc<RefCell<Object>>>,
+    pub objects: Objects,
     pub got_fruit: Vec<bool>,
     pub max_djump: u8,
     pub deaths: u64,
     pub frames: u8,
-    pub room: Vector,
+    /// the part of the map being played, from `layout`
+    pub room: Room,
+    /// Where the screen's top left corner is in the room, in pixels. Follows the player in
+    /// rooms bigger than the screen, and is always 0, 0 in the rest
+    pub scroll: Vector,
     pub level: u8,
     pub has_dashed: bool,
     pub has_key: bool,
@@ -47,6 +86,51 @@ pub struct Celeste {
     pub flash_bg: bool,
     pub new_bg: bool,
     pub pause_player: bool,
+    /// Which part of the map each level is played in. Shared between clones, change it with
+    /// `Rc::make_mut` or by replacing it before the game starts
+    pub layout: Rc<Layout>,
+    /// What `load_room` spawns for each map tile. Starts out with the built in objects, see
+    /// `register_object` to add your own
+    pub object_constructors: BTreeMap<u8, ObjectConstructor>,
+    /// Everything that happened since the frontend last called `drain_events`. Nothing else
+    /// clears this, so drain it every frame
+    pub events: Vec<GameEvent>,
+    /// `psfx` stays quiet while this is counting down, so a jump doesn't cut off a pickup sound
+    pub sfx_timer: u8,
+    /// Frames the in-game timer has been running for this run
+    pub run_frames: u64,
+    /// Per-room stats for the current run, indexed by `level`. Reset by `begin_game`
+    pub room_stats: Vec<RoomStats>,
+    /// Splits of the current run, one is added every time `next_room` is called
+    pub splits: Splits,
+    /// The fastest finished run so far. Frontends load this from wherever they keep it, and
+    /// should save it again on `GameEvent::PersonalBest`
+    pub personal_best: Option<Splits>,
+    /// Set by `begin_practice`, `None` for a normal run
+    pub practice: Option<Practice>,
+    /// Frames spent in the current room, reset when it's entered or retried
+    pub il_frames: u64,
+    /// `il_frames` of the last room that was completed
+    pub last_il: Option<u64>,
+    /// Can be changed at any time. Runs that have any of it on are marked as assisted in their
+    /// splits and room stats
+    pub assist: Assist,
+    /// `assist.game_speed` added up over the ticks, a tick only runs once it reaches 100
+    speed_clock: u8,
+    /// buttons pressed during ticks that got skipped, so they still count on the next one
+    skipped_buttons: Vec<bool>,
+    /// Draws the `debug` overlay over the game
+    pub debug: bool,
+    /// The open pause menu, see `pause`
+    pub pause_menu: Option<PauseMenu>,
+    /// The fastest run through each room so far. Frontends load this like `personal_best`
+    pub ghosts: Ghosts,
+    /// Draws the ghost of the current room behind the player
+    pub show_ghost: bool,
+    /// the player in each tick of the current room so far
+    ghost_run: GhostRun,
+    /// the level `ghost_run` is of
+    ghost_level: u8,
 }
 impl Celeste {
     /// Returns a new celeste object
@@ -59,40 +143,38 @@ impl Celeste {
     ///
     /// # Examples
     /// ```
-    /// // see https://github.com/CoolElectronics/rustic-mountain/blob/main/standalone/src/consts.rs for example values of these constants
-    /// let celeste = Celeste::new(MAPDATA,SPRITES,FLAGS,FONTATLAS);
+    /// use rustic_mountain_core::Celeste;
+    ///
+    /// // a blank cart. see src/consts.rs in the UEFI frontend for the real game's sections
+    /// let map = "00".repeat(128 * 32);
+    /// let sprites = "0".repeat(128 * 128);
+    /// let flags = "00".repeat(256);
+    /// let celeste = Celeste::new(map, sprites, flags, String::new());
+    /// assert!(celeste.is_title());
     /// ```
     pub fn new(map: String, sprites: String, flags: String, fontatlas: String) -> Celeste {
+        Celeste::from_cart(&CartData::new(&map, &sprites, &flags, &fontatlas))
+    }
+    /// A new game on the title screen, sharing `cart` with every other game made from it. Much
+    /// faster than `new` when making lots of games
+    pub fn from_cart(cart: &CartData) -> Celeste {
         // let v: Box<dyn Fn(&mut Celeste) -> Box<dyn Object>> =
         //     ;
 
-        let mut mem = Memory::new(map, sprites, flags, fontatlas);
-        let mut clouds = vec![];
-        for _ in 0..16 {
-            clouds.push(Cloud {
-                x: mem.rng.gen_range(0..128),
-                y: mem.rng.gen_range(0..128),
-                spd: mem.rng.gen_range(1..4),
-                w: mem.rng.gen_range(32..64),
-            })
-        }
-        let mut particles = vec![];
-        for _i in 0..24 {
-            let size: f32 = mem.rng.gen_range(0.0..1.25);
-            particles.push(Particle {
-                x: mem.rng.gen_range(0.0..128.0),
-                y: mem.rng.gen_range(0.0..128.0),
-                s: size.floor(),
-                spd: mem.rng.gen_range(0.25..5.25),
-                off: mem.rng.gen_range(0.0..1.0),
-                c: mem.rng.gen_range(6..8),
-            })
-        }
+        let mut mem = Memory::from_cart(cart);
+        let (clouds, particles) = scatter_background(&mut mem.rng);
+        let layout = Layout::classic();
+        let room_stats = vec![RoomStats::default(); layout.rooms.len()];
 
         let mut cel = Celeste {
-            room: Vector { x: 0f32, y: 0f32 },
+            room: Room::screen(0, 0),
+            scroll: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            layout: Rc::new(layout),
             mem,
-            objects: vec![],
+            objects: Objects::new(),
             got_fruit: vec![],
             max_djump: 1,
             deaths: 0,
@@ -114,25 +196,90 @@ impl Celeste {
             flash_bg: false,
             pause_player: false,
             new_bg: false,
+            object_constructors: BTreeMap::new(),
+            events: vec![],
+            sfx_timer: 0,
+            run_frames: 0,
+            room_stats,
+            splits: Splits::default(),
+            personal_best: None,
+            practice: None,
+            il_frames: 0,
+            last_il: None,
+            assist: Assist::default(),
+            speed_clock: 0,
+            skipped_buttons: vec![false; 6],
+            debug: false,
+            pause_menu: None,
+            ghosts: Ghosts::default(),
+            show_ghost: false,
+            ghost_run: vec![],
+            ghost_level: 0,
         };
+        for tile in [1, 11, 12, 18, 22, 23, 26, 64, 28, 8, 20, 86, 96, 118] {
+            cel.object_constructors.insert(tile, init_builtin);
+        }
         cel.title_screen();
         // cel.load_room(0, 0);
         cel
     }
 
     /// Advances a game tick. Does not draw the screen buffer. Analagous to calling `_update()` in
-    /// the original pico-8 cart. Should be called 30 times a second for real-time gameplay
+    /// the original pico-8 cart, plus the parts of its `_draw()` that change the game, so this
+    /// alone fully simulates it. Should be called 30 times a second for real-time gameplay
     pub fn next_tick(&mut self) {
+        if let Some(menu) = &mut self.pause_menu {
+            match menu.update(&self.mem.buttons) {
+                Some(PauseItem::Continue) => self.pause_menu = None,
+                Some(PauseItem::RestartRoom) => {
+                    self.pause_menu = None;
+                    self.retry();
+                }
+                Some(item) => {
+                    self.pause_menu = None;
+                    self.emit(GameEvent::PauseMenu(item));
+                }
+                None => {}
+            }
+            return;
+        }
+        if !self.is_title() {
+            self.speed_clock += self.assist.game_speed.clamp(50, 100);
+            if self.speed_clock < 100 {
+                for (skipped, &pressed) in self.skipped_buttons.iter_mut().zip(&self.mem.buttons) {
+                    *skipped |= pressed;
+                }
+                return;
+            }
+            self.speed_clock -= 100;
+            for (button, skipped) in self.mem.buttons.iter_mut().zip(&mut self.skipped_buttons) {
+                *button |= core::mem::take(skipped);
+            }
+        }
+
         // summit
         self.frames += 1;
 
-        if self.level < 30 {
+        if self.level < self.layout.summit() {
             self.seconds += self.frames / 30;
             self.minutes += (self.seconds / 60) as u64;
             self.seconds %= 60;
+            self.run_frames += 1;
+            self.il_frames += 1;
+            self.record_ghost();
+            let assisted = self.assist.is_active();
+            self.splits.assisted |= assisted;
+            if let Some(room) = self.room_stats.get_mut(self.level as usize) {
+                room.frames += 1;
+                room.assisted |= assisted;
+            }
         }
         self.frames %= 30;
 
+        if self.sfx_timer > 0 {
+            self.sfx_timer -= 1;
+        }
+
         if self.freeze > 0 {
             self.freeze -= 1;
             return;
@@ -140,11 +287,11 @@ impl Celeste {
 
         if self.shake > 0 {
             self.shake -= 1;
-            self.mem.camera(0.0, 0.0);
+            self.mem.camera(Fix16::ZERO, Fix16::ZERO);
             if self.shake != 0 {
                 self.mem.camera = Vector {
-                    x: self.mem.rng.gen_range(-2.0..3.0),
-                    y: self.mem.rng.gen_range(-2.0..3.0),
+                    x: fix(self.mem.rng.gen_range(-2.0..3.0)),
+                    y: fix(self.mem.rng.gen_range(-2.0..3.0)),
                 }
             }
         }
@@ -152,29 +299,21 @@ impl Celeste {
         if self.delay_restart > 0 {
             self.delay_restart -= 1;
             if self.delay_restart == 0 {
-                self.load_room(self.room.x as u8, self.room.y as u8);
+                self.load_room(self.room);
             }
         }
 
         let mut i = 0;
-        let mut lastlen = self.objects.len();
-        loop {
-            if i >= self.objects.len() {
-                break;
+        while let Some(id) = self.objects.at(i) {
+            if let Some(v) = self.objects.get_object(id) {
+                let spd = v.borrow().spd.clone();
+                v.borrow_mut().do_move(self, spd.x, spd.y, Fix16::ZERO);
+                Object::run(&v, self, Object::update);
             }
-            let v = self.objects[i].clone();
-
-            let obj = v.borrow_mut();
-            let spd = Vector {
-                x: obj.spd.x,
-                y: obj.spd.y,
-            };
-            drop(obj);
-
-            v.borrow_mut().do_move(self, spd.x, spd.y, 0f32);
-            v.borrow_mut().update(self);
-            i += 1;
+            i = self.objects.flush(i + 1);
         }
+        self.late_update();
+        self.follow_player();
         if self.is_title() {
             if self.start_game {
                 self.start_game_flash -= 1.0;
@@ -185,35 +324,130 @@ impl Celeste {
                 // music -1
                 self.start_game_flash = 50.0;
                 self.start_game = true;
-                // sfx 38
+                self.sfx(38);
             }
         }
+        self.update_background();
         // let graph = &mut rself.borrow_mut().mem.graphics;
         // for i in 0..128 * 128 {
         //     // graphics[(i % 15) as u8] = i as ;
         // }
     }
+    /// The parts of the cart's object draws that change the game, which see every object after
+    /// its update. Anything spawned here waits until the next tick to move, like in the cart
+    fn late_update(&mut self) {
+        let mut i = 0;
+        while let Some(id) = self.objects.at(i) {
+            if let Some(v) = self.objects.get_object(id) {
+                let late: Option<fn(&mut Object, &mut Celeste)> = match v.borrow().kind() {
+                    ObjectKind::Player => Some(objects::player::Player::late_update),
+                    ObjectKind::PlayerSpawn => Some(PlayerSpawn::late_update),
+                    ObjectKind::BigChest => Some(BigChest::late_update),
+                    _ => None,
+                };
+                if let Some(late) = late {
+                    Object::run(&v, self, late);
+                }
+            }
+            i = self.objects.flush(i + 1);
+        }
+    }
+    /// moves the clouds and particles. the cart does this in `_draw`, which runs after every
+    /// tick that isn't frozen, same as here
+    fn update_background(&mut self) {
+        if !self.is_title() {
+            for cloud in &mut self.clouds {
+                cloud.x += cloud.spd;
+                if cloud.x > 128 {
+                    cloud.x = -cloud.w;
+                    cloud.y = self.mem.rng.gen_range(0..120);
+                }
+            }
+        }
+        for particle in &mut self.particles {
+            particle.x += particle.spd;
+            particle.y += sin(fix(particle.off)).to_f32();
+            if particle.x > 132.0 {
+                particle.x = -4.0;
+                particle.y = self.mem.rng.gen_range(0.0..128.0);
+            }
+        }
+        for particle in &mut self.dead_particles {
+            particle.x += particle.dx;
+            particle.y += particle.dy;
+            particle.t -= 0.2;
+        }
+        self.dead_particles.retain(|f| f.t > 0.0);
+    }
     pub fn is_title(&self) -> bool {
-        self.level == 32
+        self.level == self.layout.title_level()
     }
     pub fn begin_game(&mut self) {
+        self.reset_run();
+        self.practice = None;
         self.max_djump = 1;
+        // music 007
+        self.level = 0;
+        self.load_room(self.layout.room(0));
+        self.emit(GameEvent::RoomEntered { level: 0 });
+    }
+    /// Starts a practice run in the room `practice` describes. Runs started this way never
+    /// count as a personal best
+    pub fn begin_practice(&mut self, practice: Practice) {
+        self.reset_run();
+        self.practice = Some(practice);
+        self.got_fruit.clear();
+        self.level = practice.level;
+        self.retry();
+        self.emit(GameEvent::RoomEntered { level: self.level });
+    }
+    /// Reloads the current room with a fresh IL timer, without counting a death. In practice
+    /// mode the dashes, key and berry go back to how the practice run started
+    pub fn retry(&mut self) {
+        if let Some(practice) = self.practice {
+            self.max_djump = practice.max_djump;
+            while self.got_fruit.len() <= self.level as usize {
+                self.got_fruit.push(false);
+            }
+            self.got_fruit[self.level as usize] = practice.berry;
+        }
+        self.delay_restart = 0;
+        self.freeze = 0;
+        self.shake = 0;
+        self.dead_particles.clear();
+        self.il_frames = 0;
+        self.load_room(self.layout.room(self.level));
+    }
+    /// Restarts the random number generator from `seed` and rolls the background again, so that
+    /// a new game seeded with the same number plays out the same way given the same inputs
+    pub fn seed(&mut self, seed: u64) {
+        self.mem.rng = GameRng::new(seed);
+        (self.clouds, self.particles) = scatter_background(&mut self.mem.rng);
+    }
+    fn reset_run(&mut self) {
         self.deaths = 0;
         self.frames = 0;
         self.seconds = 0;
         self.minutes = 0;
+        self.run_frames = 0;
+        self.il_frames = 0;
+        self.last_il = None;
+        self.room_stats = vec![RoomStats::default(); self.layout.rooms.len()];
+        self.splits = Splits::default();
         self.music_timer = 0;
-        // music 007
-        self.level = 0;
-        self.load_room(0, 0);
     }
+    /// Draws the game into `mem.graphics`. Nothing else is changed, so bots and tests that don't
+    /// look at the screen can skip it
     pub fn draw(&mut self) {
-        if self.freeze > 0 {
+        if let Some(menu) = &self.pause_menu {
+            // the game stays frozen underneath, so only the menu needs redrawing
+            menu.draw(&mut self.mem);
             return;
         }
-        for i in 0..128 * 128 {
-            self.mem.graphics[i] = 0; // (i % 15) as u8;
+        if self.freeze > 0 {
+            return;
         }
+        self.mem.cls(0);
 
         self.mem.pal_reset();
 
@@ -222,7 +456,7 @@ impl Celeste {
                 self.mem.pal(
                     i,
                     if self.start_game_flash <= 10.0 {
-                        libm::ceilf(max(self.start_game_flash, 0.0) / 5.0) as u8
+                        libm::ceilf(self.start_game_flash.max(0.0) / 5.0) as u8
                     } else {
                         if self.frames % 10 < 5 {
                             7
@@ -236,7 +470,7 @@ impl Celeste {
 
         //clearing screen
         let bg_col = if self.flash_bg {
-            self.frames as u8 / 5
+            self.frames / 5
         } else {
             if self.new_bg {
                 2
@@ -244,11 +478,16 @@ impl Celeste {
                 0
             }
         };
-        self.mem.rrectfill(-1, -1, 128, 128, bg_col);
+        self.mem.rectfill(0, 0, 128, 128, bg_col);
+        // the background stays put while the room scrolls, with only the shake moving it
+        let shake = self.mem.camera.clone();
+        let scrolled = Vector {
+            x: shake.x + self.scroll.x,
+            y: shake.y + self.scroll.y,
+        };
 
         if !self.is_title() {
-            for cloud in &mut self.clouds {
-                cloud.x += cloud.spd;
+            for cloud in &self.clouds {
                 self.mem.rectfill(
                     cloud.x,
                     cloud.y,
@@ -256,52 +495,23 @@ impl Celeste {
                     cloud.y + 16 - (cloud.w as f32 * 0.1875) as i32,
                     if self.new_bg { 14 } else { 1 },
                 );
-                if cloud.x > 128 {
-                    cloud.x = -cloud.w;
-                    cloud.y = self.mem.rng.gen_range(0..120);
-                }
             }
         }
 
-        self.mem.map(
-            self.room.x as u8 * 16,
-            self.room.y as u8 * 16,
-            0,
-            0,
-            16,
-            16,
-            4,
-        );
-
-        for v in self.objects.clone() {
-            let mut r = v.borrow_mut();
-            if let ObjectType::Platform(_) = r.obj_type {
-                r.draw(self);
-            }
-        }
-        self.mem.map(
-            self.room.x as u8 * 16,
-            self.room.y as u8 * 16,
-            0,
-            0,
-            16,
-            16,
-            2, //2
-        );
-        for v in self.objects.clone() {
-            // cloning is fine here, it's just a vector of pointers
-            let mut r = v.borrow_mut();
-            if let ObjectType::Platform(_) = r.obj_type {
-            } else {
-                r.draw(self);
-            }
+        self.mem.camera = scrolled.clone();
+        let room = self.room;
+        self.mem.map(room.x, room.y, 0, 0, room.w, room.h, 4);
+
+        self.draw_objects(|kind| kind == ObjectKind::Platform);
+        self.mem.map(room.x, room.y, 0, 0, room.w, room.h, 2);
+        if self.show_ghost && !self.is_title() {
+            ghost::draw(self);
         }
+        self.draw_objects(|kind| kind != ObjectKind::Platform);
 
         // do particles here
-        for particle in &mut self.particles {
-            particle.x += particle.spd;
-            particle.y += sin(particle.off);
-
+        self.mem.camera = shake.clone();
+        for particle in &self.particles {
             self.mem.rectfill(
                 particle.x as i32,
                 particle.y as i32,
@@ -309,28 +519,18 @@ impl Celeste {
                 (particle.y + particle.s) as i32,
                 particle.c,
             );
-            if particle.x > 132.0 {
-                particle.x = -4.0;
-                particle.y = self.mem.rng.gen_range(0.0..128.0);
-            }
         }
-        for particle in &mut self.dead_particles {
-            particle.x += particle.dx;
-            particle.y += particle.dy;
-
-            particle.t -= 0.2;
-
-            if particle.t > 0.0 {
-                self.mem.rectfill(
-                    (particle.x - particle.t) as i32,
-                    (particle.y - particle.t) as i32,
-                    (particle.x + particle.t) as i32,
-                    (particle.y + particle.t) as i32,
-                    14 + ((particle.t * 5.0) % 2.0) as u8,
-                );
-            }
+        self.mem.camera = scrolled;
+        for particle in &self.dead_particles {
+            self.mem.rectfill(
+                (particle.x - particle.t) as i32,
+                (particle.y - particle.t) as i32,
+                (particle.x + particle.t) as i32,
+                (particle.y + particle.t) as i32,
+                14 + ((particle.t * 5.0) % 2.0) as u8,
+            );
         }
-        self.dead_particles.retain(|f| f.t > 0.0);
+        self.mem.camera = shake;
 
         if self.is_title() {
             self.mem.print("z+x", 58, 80, 5);
@@ -339,110 +539,265 @@ impl Celeste {
             self.mem.print("noel berry", 46, 102, 5);
         }
 
+        if self.practice.is_some() && !self.is_title() {
+            practice::draw_il_time(self);
+        }
+        if self.debug {
+            debug::draw(self);
+        }
+
         // todo: summit blinds
     }
+    /// draws every object `filter` accepts, in update order
+    fn draw_objects(&mut self, filter: impl Fn(ObjectKind) -> bool) {
+        let mut i = 0;
+        while let Some(id) = self.objects.at(i) {
+            if let Some(v) = self.objects.get_object(id) {
+                if filter(v.borrow().kind()) {
+                    v.borrow_mut().draw(self);
+                }
+            }
+            i = self.objects.flush(i + 1);
+        }
+    }
+    /// The object `id` refers to, if it's still around
+    pub fn get_object(&self, id: ObjectId) -> Option<Rc<RefCell<Object>>> {
+        self.objects.get_object(id)
+    }
+    /// The state of the `T` that `id` refers to, e.g. `celeste.get::<Player>(id)`
+    pub fn get<T: ObjectState>(&self, id: ObjectId) -> Option<Rc<RefCell<T>>> {
+        self.objects.get(id)
+    }
+    /// Makes `load_room` spawn a `T` for every `tile` in the map, replacing whatever used to
+    /// spawn there
+    pub fn register_object<T: GameObject>(&mut self, tile: u8) {
+        self.object_constructors.insert(tile, custom::init::<T>);
+    }
+    /// The state of the `GameObject` that `id` refers to
+    pub fn get_custom<T: GameObject>(&self, id: ObjectId) -> Option<RefMut<'_, T>> {
+        self.objects.get_custom(id)
+    }
+    pub fn emit(&mut self, event: GameEvent) {
+        RoomStats::record(&mut self.room_stats, event, self.run_frames);
+        self.events.push(event);
+    }
+    /// Takes every event that's happened since the last call
+    pub fn drain_events(&mut self) -> alloc::vec::Drain<'_, GameEvent> {
+        self.events.drain(..)
+    }
+    /// requests a sound effect, see `GameEvent::SfxRequested`
+    pub fn sfx(&mut self, num: u8) {
+        self.emit(GameEvent::SfxRequested(num));
+    }
+    /// the cart's `psfx()`: a sound effect that gets skipped while `sfx_timer` is running
+    pub fn psfx(&mut self, num: u8) {
+        if self.sfx_timer == 0 {
+            self.sfx(num);
+        }
+    }
+    /// Opens the pause menu over the current frame. Does nothing on the title screen
+    pub fn pause(&mut self) {
+        if !self.is_title() && self.pause_menu.is_none() {
+            self.pause_menu = Some(PauseMenu::new(&self.mem.buttons));
+        }
+    }
+    /// How many dashes the player gets back when refilling, `max_djump` unless assist mode
+    /// overrides it
+    pub fn dash_count(&self) -> u8 {
+        self.assist.air_dashes.unwrap_or(self.max_djump)
+    }
+    /// How the player looks right now, `None` if there isn't one. A player that's still
+    /// spawning counts
+    pub fn player_frame(&self) -> Option<GhostFrame> {
+        let (_, obj) = self
+            .objects
+            .of_kind(ObjectKind::Player)
+            .chain(self.objects.of_kind(ObjectKind::PlayerSpawn))
+            .next()?;
+        let obj = obj.borrow();
+        Some(GhostFrame {
+            x: obj.pos.x.to_int(),
+            y: obj.pos.y.to_int(),
+            spr: obj.spr,
+            flip_x: obj.flip.x,
+        })
+    }
+    /// adds the player as they were before this tick to `ghost_run`. goes by `il_frames`, so
+    /// retries and loading an earlier state just cut the run back
+    fn record_ghost(&mut self) {
+        if self.ghost_level != self.level {
+            self.ghost_level = self.level;
+            self.ghost_run.clear();
+        }
+        let tick = self.il_frames as usize - 1;
+        self.ghost_run.truncate(tick);
+        if self.ghost_run.len() == tick {
+            let frame = self.player_frame();
+            self.ghost_run.push(frame);
+        }
+    }
     /// advances to the next room
     pub fn next_room(&mut self) {
         // do sound at some point
+        self.splits.times.push(self.run_frames);
+        self.last_il = Some(self.il_frames);
+        // a run that started partway through the room, e.g. before a state was loaded, has gaps
+        let complete = self.ghost_level == self.level
+            && self.ghost_run.len() as u64 == self.il_frames
+            && self.room_stats.get(self.level as usize).is_some_and(|r| !r.assisted);
+        let run = core::mem::take(&mut self.ghost_run);
+        if complete && self.ghosts.offer(self.level, run) {
+            self.emit(GameEvent::GhostRecorded { level: self.level });
+        }
+        self.il_frames = 0;
+        let pb = self.personal_best.clone().unwrap_or_default();
+        if self.practice.is_none() && self.splits.beats(&pb, self.layout.summit() as usize) {
+            self.personal_best = Some(self.splits.clone());
+            self.emit(GameEvent::PersonalBest);
+        }
         self.level += 1;
-        self.load_room(self.level % 8, self.level / 8);
+        self.load_room(self.layout.room(self.level));
+        self.emit(GameEvent::RoomEntered { level: self.level });
     }
     pub fn title_screen(&mut self) {
         self.frames = 0;
         self.start_game_flash = 0.0;
-        self.level = 32;
+        self.level = self.layout.title_level();
         // music
-        self.load_room(7, 3);
+        self.load_room(self.layout.title);
     }
-    pub fn load_room(&mut self, x: u8, y: u8) {
+    /// Starts `room` over, with the objects its tiles spawn. Usually one from `layout`, but it
+    /// can be any part of the map
+    pub fn load_room(&mut self, room: Room) {
         self.objects.clear();
 
-        self.room = Vector {
-            x: x as f32,
-            y: y as f32,
-        };
+        self.room = room;
 
         self.has_dashed = false;
-        self.has_key = false;
-
-        for i in 0..16 {
-            for j in 0..16 {
-                let tile = self.mem.mget(x * 16 + i, y * 16 + j);
-                let x = i as f32 * 8.0;
-                let y = j as f32 * 8.0;
-                match match tile {
-                    1 => Some(PlayerSpawn::init(self, x, y)),
-                    11 | 12 => Some(Platform::init(self, x, y, tile)),
-                    18 => Some(Spring::init(self, x, y)),
-                    22 => Some(Balloon::init(self, x, y)),
-                    23 => Some(FallFloor::init(self, x, y)),
-                    26 | 64 | 28 | 8 | 20 => {
-                        if self.got_fruit.len() > self.level as usize
-                            && self.got_fruit[self.level as usize]
-                        {
-                            None
-                        } else {
-                            Some(match tile {
-                                8 => Key::init(self, x, y),
-                                20 => Chest::init(self, x, y),
-                                26 => Fruit::init(self, x, y),
-                                64 => FakeWall::init(self, x, y),
-                                28 => FlyFruit::init(self, x, y),
-                                _ => unreachable!(),
-                            })
-                        }
-                    }
-
-                    86 => Some(Message::init(self, x, y)),
-                    96 => Some(BigChest::init(self, x, y)),
-                    118 => Some(Flag::init(self, x, y)),
-                    _ => None,
-                } {
-                    Some(o) => {
-                        self.objects.push(Rc::new(RefCell::new(o)));
+        self.has_key = self.practice.is_some_and(|p| p.has_key);
+
+        for i in 0..room.w {
+            for j in 0..room.h {
+                let tile = self.mem.mget(room.x + i, room.y + j);
+                let x = Fix16::from_int(i * 8);
+                let y = Fix16::from_int(j * 8);
+                if let Some(&constructor) = self.object_constructors.get(&tile) {
+                    if let Some(o) = constructor(self, x, y, tile) {
+                        self.objects.spawn(o);
                     }
-                    None => (),
                 }
-
-                //
             }
         }
         if !self.is_title() {
-            let obj = RoomTitle::init(self, 0.0, 0.0);
-            self.objects.push(Rc::new(RefCell::new(obj)));
+            let obj = RoomTitle::init(self, Fix16::ZERO, Fix16::ZERO);
+            self.objects.spawn(obj);
         }
+        self.objects.flush(0);
+        self.follow_player();
     }
-    pub fn tile_at(&self, x: f32, y: f32) -> u8 {
-        return self.mem.mget(
-            (self.room.x as f32 * 16.0 + x) as u8,
-            (self.room.y as f32 * 16.0 + y) as u8,
-        );
+    /// The screen's top left corner for it to be centered on `x`, `y` in the room, as far as
+    /// the room's edges let it
+    pub fn scroll_for(&self, x: Fix16, y: Fix16) -> Vector {
+        let center = |v: Fix16, size: i32| (v.to_int() + 4 - 64).min(size - 128).max(0);
+        Vector {
+            x: Fix16::from_int(center(x, self.room.width())),
+            y: Fix16::from_int(center(y, self.room.height())),
+        }
     }
-    pub fn spikes_at(&self, x1: f32, y1: f32, x2: f32, y2: f32, xspd: f32, yspd: f32) -> bool {
-        let mut i = 0f32.max((x1 / 8.0).floor()) as u8;
+    /// moves `scroll` to the player, or where they're spawning
+    fn follow_player(&mut self) {
+        let target = if let Some((_, obj)) = self.objects.of_kind(ObjectKind::Player).next() {
+            let obj = obj.borrow();
+            Some((obj.pos.x, obj.pos.y))
+        } else if let Some((id, obj)) = self.objects.of_kind(ObjectKind::PlayerSpawn).next() {
+            let spawn = self.get::<PlayerSpawn>(id);
+            spawn.map(|spawn| (obj.borrow().pos.x, spawn.borrow().target))
+        } else {
+            None
+        };
+        if let Some((x, y)) = target {
+            self.scroll = self.scroll_for(x, y);
+        }
+    }
+    /// Runs `draw` with `scroll` taken out of the camera, for things that stay put on the screen
+    /// while a big room scrolls, like the room title
+    pub fn draw_fixed(&mut self, draw: impl FnOnce(&mut Celeste)) {
+        let camera = self.mem.camera.clone();
+        self.mem.camera(camera.x - self.scroll.x, camera.y - self.scroll.y);
+        draw(self);
+        self.mem.camera = camera;
+    }
+    pub fn tile_at(&self, x: Fix16, y: Fix16) -> u8 {
+        self.mem.mget(
+            (Fix16::from_int(self.room.x) + x).to_int(),
+            (Fix16::from_int(self.room.y) + y).to_int(),
+        )
+    }
+    pub fn spikes_at(
+        &self,
+        x1: Fix16,
+        y1: Fix16,
+        x2: Fix16,
+        y2: Fix16,
+        xspd: Fix16,
+        yspd: Fix16,
+    ) -> bool {
+        let eight = Fix16::from_int(8);
+        let mut i = Fix16::ZERO.max(x1 / eight).to_int();
         loop {
-            let mut j = 0f32.max((y1 / 8.0).floor()) as u8;
+            let mut j = Fix16::ZERO.max(y1 / eight).to_int();
             loop {
-                if match self.tile_at(i as f32, j as f32) {
-                    17 => yspd >= 0.0 && y2.rem_euclid(8.0) >= 6.0,
-                    27 => yspd <= 0.0 && y1.rem_euclid(8.0) <= 2.0,
-                    43 => xspd <= 0.0 && x1.rem_euclid(8.0) <= 2.0,
-                    59 => xspd >= 0.0 && x2.rem_euclid(8.0) >= 6.0,
+                if match self.tile_at(Fix16::from_int(i), Fix16::from_int(j)) {
+                    17 => yspd >= Fix16::ZERO && y2 % eight >= fix(6.0),
+                    27 => yspd <= Fix16::ZERO && y1 % eight <= fix(2.0),
+                    43 => xspd <= Fix16::ZERO && x1 % eight <= fix(2.0),
+                    59 => xspd >= Fix16::ZERO && x2 % eight >= fix(6.0),
                     _ => false,
                 } {
                     return true;
                 }
-                if j >= 15f32.min((y2 / 8.0).floor()) as u8 {
+                if j >= Fix16::from_int(self.room.h - 1).min(y2 / eight).to_int() {
                     break;
                 }
                 j += 1;
             }
-            if i >= utils::min(15f32, (x2 / 8f32).floor()) as u8 {
+            if i >= utils::min(Fix16::from_int(self.room.w - 1), x2 / eight).to_int() {
                 break;
             }
             i += 1;
         }
-        return false;
+        false
+    }
+}
+/// The constructor for every object that comes with the game
+fn init_builtin(celeste: &mut Celeste, x: Fix16, y: Fix16, tile: u8) -> Option<Object> {
+    match tile {
+        1 => Some(PlayerSpawn::init(celeste, x, y)),
+        11 | 12 => Some(Platform::init(celeste, x, y, tile)),
+        18 => Some(Spring::init(celeste, x, y)),
+        22 => Some(Balloon::init(celeste, x, y)),
+        23 => Some(FallFloor::init(celeste, x, y)),
+        26 | 64 | 28 | 8 | 20 => {
+            if celeste.got_fruit.len() > celeste.level as usize
+                && celeste.got_fruit[celeste.level as usize]
+            {
+                None
+            } else {
+                Some(match tile {
+                    8 => Key::init(celeste, x, y),
+                    20 => Chest::init(celeste, x, y),
+                    26 => Fruit::init(celeste, x, y),
+                    64 => FakeWall::init(celeste, x, y),
+                    28 => FlyFruit::init(celeste, x, y),
+                    _ => unreachable!(),
+                })
+            }
+        }
+
+        86 => Some(Message::init(celeste, x, y)),
+        96 => Some(BigChest::init(celeste, x, y)),
+        118 => Some(Flag::init(celeste, x, y)),
+        _ => None,
     }
 }
 pub fn draw_time(celeste: &mut Celeste, x: i32, y: i32) {
@@ -463,13 +818,42 @@ fn two_digit_str(n: u64) -> String {
     }
 }
 
+/// the clouds and snow behind the room, in random places
+fn scatter_background(rng: &mut GameRng) -> (Vec<Cloud>, Vec<Particle>) {
+    let mut clouds = vec![];
+    for _ in 0..16 {
+        clouds.push(Cloud {
+            x: rng.gen_range(0..128),
+            y: rng.gen_range(0..128),
+            spd: rng.gen_range(1..4),
+            w: rng.gen_range(32..64),
+        })
+    }
+    let mut particles = vec![];
+    for _i in 0..24 {
+        let size: f32 = rng.gen_range(0.0..1.25);
+        particles.push(Particle {
+            x: rng.gen_range(0.0..128.0),
+            y: rng.gen_range(0.0..128.0),
+            s: size.floor(),
+            spd: rng.gen_range(0.25..5.25),
+            off: rng.gen_range(0.0..1.0),
+            c: rng.gen_range(6..8),
+        })
+    }
+    (clouds, particles)
+}
+
+#[derive(Clone)]
 pub struct Cloud {
     pub x: i32,
     pub y: i32,
     pub spd: i32,
     pub w: i32,
 }
+crate::snapshot::snapshot_fields!(Cloud { x, y, spd, w });
 
+#[derive(Clone)]
 pub struct Particle {
     pub x: f32,
     pub y: f32,
@@ -478,7 +862,9 @@ pub struct Particle {
     pub off: f32,
     pub c: u8,
 }
+crate::snapshot::snapshot_fields!(Particle { x, y, s, spd, off, c });
 
+#[derive(Clone)]
 pub struct DeadParticle {
     pub x: f32,
     pub y: f32,
@@ -486,3 +872,4 @@ pub struct DeadParticle {
     pub dx: f32,
     pub dy: f32,
 }
+crate::snapshot::snapshot_fields!(DeadParticle { x, y, t, dx, dy });
@@ -0,0 +1,923 @@
+//! pico-8's built in functions. Drawing goes straight to `Memory`, so it looks the same as the
+//! native port's. `all` and `foreach` are written in Lua, see `PRELUDE`
+use alloc::{format, rc::Rc, vec, vec::Vec};
+use rand::RngCore;
+
+use super::{
+    ast::BinOp,
+    interp::{address, arith, atan2},
+    parser::parse,
+    value::{
+        format_hex, from_f64, parse_num, to_f64, Key, Native, NativeFn, Table, TableRef, Value,
+    },
+    LuaCart, LuaError,
+};
+use crate::{
+    fixed::Fix16,
+    memory::{Memory, CART_DATA, FLAGS},
+    rng::GameRng,
+    structures::FlipState,
+};
+
+type Results = Result<Vec<Value>, LuaError>;
+
+const NATIVES: &[(&str, NativeFn)] = &[
+    ("cls", cls),
+    ("pset", pset),
+    ("pget", pget),
+    ("sget", sget),
+    ("sset", sset),
+    ("fget", fget),
+    ("fset", fset),
+    ("mget", mget),
+    ("mset", mset),
+    ("line", line),
+    ("rect", rect),
+    ("rectfill", rectfill),
+    ("circ", circ),
+    ("circfill", circfill),
+    ("oval", oval),
+    ("ovalfill", ovalfill),
+    ("spr", spr),
+    ("sspr", sspr),
+    ("map", map),
+    ("mapdraw", map),
+    ("print", print),
+    ("cursor", cursor),
+    ("color", color),
+    ("camera", camera),
+    ("clip", clip),
+    ("pal", pal),
+    ("palt", palt),
+    ("fillp", fillp),
+    ("btn", btn),
+    ("btnp", btnp),
+    ("flr", flr),
+    ("ceil", ceil),
+    ("abs", abs),
+    ("sgn", sgn),
+    ("min", min),
+    ("max", max),
+    ("mid", mid),
+    ("sqrt", sqrt),
+    ("sin", sin),
+    ("cos", cos),
+    ("atan2", atan2_),
+    ("rnd", rnd),
+    ("srand", srand),
+    ("band", band),
+    ("bor", bor),
+    ("bxor", bxor),
+    ("bnot", bnot),
+    ("shl", shl),
+    ("shr", shr),
+    ("lshr", lshr),
+    ("rotl", rotl),
+    ("rotr", rotr),
+    ("tostr", tostr),
+    ("tonum", tonum),
+    ("chr", chr),
+    ("ord", ord),
+    ("sub", sub),
+    ("split", split),
+    ("type", type_),
+    ("add", add),
+    ("del", del),
+    ("deli", deli),
+    ("count", count),
+    ("pairs", pairs),
+    ("ipairs", ipairs),
+    ("next", next),
+    ("unpack", unpack),
+    ("pack", pack),
+    ("select", select),
+    ("setmetatable", setmetatable),
+    ("getmetatable", getmetatable),
+    ("rawget", rawget),
+    ("rawset", rawset),
+    ("rawequal", rawequal),
+    ("rawlen", rawlen),
+    ("assert", assert),
+    ("stop", stop),
+    ("printh", printh),
+    ("peek", peek),
+    ("poke", poke),
+    ("peek2", peek2),
+    ("poke2", poke2),
+    ("peek4", peek4),
+    ("poke4", poke4),
+    ("memcpy", memcpy),
+    ("memset", memset),
+    ("reload", reload),
+    ("dget", dget),
+    ("dset", dset),
+    ("time", time),
+    ("t", time),
+    ("stat", stat),
+    // there's no sound, and the rest don't mean anything outside of pico-8
+    ("sfx", nothing),
+    ("music", nothing),
+    ("cartdata", nothing),
+    ("cstore", nothing),
+    ("menuitem", nothing),
+    ("extcmd", nothing),
+    ("flip", nothing),
+    ("cocreate", no_coroutines),
+    ("coresume", no_coroutines),
+    ("costatus", no_coroutines),
+    ("yield", no_coroutines),
+];
+
+/// pico-8's `all`, which keeps going when the loop `del`s the item it's on, and `foreach`
+const PRELUDE: &str = "
+function all(c)
+ if (c==nil or #c==0) return function() end
+ local i,prev=1,nil
+ return function()
+  -- del() moves everything after the item down, so only move on if it's still there
+  if (c[i]==prev) i+=1
+  while (c[i]==nil and i<=#c) i+=1
+  prev=c[i]
+  return prev
+ end
+end
+
+function foreach(c,f)
+ for v in all(c) do f(v) end
+end
+";
+
+/// Sets up the globals every cart starts with
+pub fn register(cart: &mut LuaCart) -> Result<(), LuaError> {
+    for &(name, f) in NATIVES {
+        cart.set_global(name, native(name, f));
+    }
+    let prelude = parse(PRELUDE, &mut cart.globals)?;
+    cart.run(prelude)
+}
+
+fn native(name: &'static str, f: NativeFn) -> Value {
+    Value::Native(Rc::new(Native { name, f }))
+}
+
+fn arg(args: &[Value], i: usize) -> Value {
+    args.get(i).cloned().unwrap_or_default()
+}
+/// missing arguments and ones that aren't numbers count as 0, like they do in pico-8
+fn num(args: &[Value], i: usize) -> Fix16 {
+    opt_num(args, i).unwrap_or(Fix16::ZERO)
+}
+fn opt_num(args: &[Value], i: usize) -> Option<Fix16> {
+    args.get(i).and_then(Value::to_num)
+}
+fn int(args: &[Value], i: usize) -> i32 {
+    num(args, i).to_int()
+}
+fn opt_int(args: &[Value], i: usize) -> Option<i32> {
+    opt_num(args, i).map(Fix16::to_int)
+}
+fn truthy(args: &[Value], i: usize) -> bool {
+    args.get(i).is_some_and(Value::truthy)
+}
+/// a color argument, which is the pen color when it's left out
+fn col(cart: &LuaCart, args: &[Value], i: usize) -> u8 {
+    opt_int(args, i).map_or(cart.mem.pen, |c| c as u8)
+}
+fn table(cart: &LuaCart, args: &[Value], i: usize, func: &str) -> Result<TableRef, LuaError> {
+    match args.get(i) {
+        Some(Value::Table(t)) => Ok(t.clone()),
+        other => Err(cart.error(format!(
+            "bad argument #{} to '{}' (table expected, got {})",
+            i + 1,
+            func,
+            other.map_or("no value", Value::type_name)
+        ))),
+    }
+}
+fn none() -> Results {
+    Ok(Vec::new())
+}
+fn one(value: impl Into<Value>) -> Results {
+    Ok(vec![value.into()])
+}
+
+fn nothing(_: &mut LuaCart, _: &[Value]) -> Results {
+    none()
+}
+fn no_coroutines(cart: &mut LuaCart, _: &[Value]) -> Results {
+    Err(cart.error("coroutines aren't supported"))
+}
+
+fn cls(cart: &mut LuaCart, args: &[Value]) -> Results {
+    cart.mem.cls(int(args, 0) as u8);
+    none()
+}
+fn pset(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 2);
+    cart.mem.pset(c, int(args, 0), int(args, 1));
+    none()
+}
+fn pget(cart: &mut LuaCart, args: &[Value]) -> Results {
+    one(Value::int(cart.mem.pget(int(args, 0), int(args, 1)) as i32))
+}
+fn sget(cart: &mut LuaCart, args: &[Value]) -> Results {
+    one(Value::int(cart.mem.sget(int(args, 0), int(args, 1)) as i32))
+}
+fn sset(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 2);
+    cart.mem.sset(int(args, 0), int(args, 1), c);
+    none()
+}
+fn fget(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let sprite = int(args, 0) as u8;
+    match opt_int(args, 1) {
+        Some(flag) => one(cart.mem.fget(sprite, (flag & 7) as u8)),
+        None => one(Value::int(cart.mem.fget_all(sprite) as i32)),
+    }
+}
+/// `fset(n, f, v)` sets one flag, `fset(n, v)` all of them
+fn fset(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let sprite = int(args, 0) as u8;
+    if args.len() >= 3 {
+        cart.mem
+            .fset(sprite, (int(args, 1) & 7) as u8, truthy(args, 2));
+    } else {
+        cart.mem.poke(FLAGS + sprite as usize, int(args, 1) as u8);
+    }
+    none()
+}
+fn mget(cart: &mut LuaCart, args: &[Value]) -> Results {
+    one(Value::int(cart.mem.mget(int(args, 0), int(args, 1)) as i32))
+}
+fn mset(cart: &mut LuaCart, args: &[Value]) -> Results {
+    cart.mem
+        .mset(int(args, 0), int(args, 1), int(args, 2) as u8);
+    none()
+}
+/// `line(x0, y0, x1, y1)`, or `line(x1, y1)` to carry on from where the last line ended
+fn line(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let (from, to, c) = match args.len() {
+        0 | 1 => {
+            cart.line_end = None;
+            return none();
+        }
+        2 | 3 => {
+            let to = (int(args, 0), int(args, 1));
+            (cart.line_end.unwrap_or(to), to, col(cart, args, 2))
+        }
+        _ => (
+            (int(args, 0), int(args, 1)),
+            (int(args, 2), int(args, 3)),
+            col(cart, args, 4),
+        ),
+    };
+    cart.mem.line(from.0, from.1, to.0, to.1, c);
+    cart.line_end = Some(to);
+    none()
+}
+fn rect(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 4);
+    let [x0, y0, x1, y1] = [0, 1, 2, 3].map(|i| int(args, i));
+    cart.mem.rect(x0, y0, x1, y1, c);
+    none()
+}
+fn rectfill(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 4);
+    let [x0, y0, x1, y1] = [0, 1, 2, 3].map(|i| int(args, i));
+    cart.mem.rectfill(x0, y0, x1, y1, c);
+    none()
+}
+fn circ(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 3);
+    let r = opt_int(args, 2).unwrap_or(4);
+    cart.mem.circ(int(args, 0), int(args, 1), r, c);
+    none()
+}
+fn circfill(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 3);
+    let r = opt_int(args, 2).unwrap_or(4);
+    cart.mem.circfill(int(args, 0), int(args, 1), r, c);
+    none()
+}
+fn oval(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 4);
+    let [x0, y0, x1, y1] = [0, 1, 2, 3].map(|i| int(args, i));
+    cart.mem.oval(x0, y0, x1, y1, c);
+    none()
+}
+fn ovalfill(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let c = col(cart, args, 4);
+    let [x0, y0, x1, y1] = [0, 1, 2, 3].map(|i| int(args, i));
+    cart.mem.ovalfill(x0, y0, x1, y1, c);
+    none()
+}
+/// `spr(n, x, y, [w], [h], [flip_x], [flip_y])`, where `w` and `h` are in sprites
+fn spr(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let n = int(args, 0) & 0xff;
+    let (x, y) = (int(args, 1), int(args, 2));
+    let w = opt_num(args, 3).unwrap_or(Fix16::ONE);
+    let h = opt_num(args, 4).unwrap_or(Fix16::ONE);
+    let flip = Some(FlipState {
+        x: truthy(args, 5),
+        y: truthy(args, 6),
+    });
+    if w == Fix16::ONE && h == Fix16::ONE {
+        cart.mem.spr(n as u8, x, y, flip);
+    } else {
+        let (w, h) = (
+            (w * Fix16::from_int(8)).to_int(),
+            (h * Fix16::from_int(8)).to_int(),
+        );
+        cart.mem
+            .sspr(n % 16 * 8, n / 16 * 8, w, h, x, y, w, h, flip);
+    }
+    none()
+}
+fn sspr(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let [sx, sy, sw, sh, dx, dy] = [0, 1, 2, 3, 4, 5].map(|i| int(args, i));
+    let dw = opt_int(args, 6).unwrap_or(sw);
+    let dh = opt_int(args, 7).unwrap_or(sh);
+    let flip = Some(FlipState {
+        x: truthy(args, 8),
+        y: truthy(args, 9),
+    });
+    cart.mem.sspr(sx, sy, sw, sh, dx, dy, dw, dh, flip);
+    none()
+}
+/// `map(celx, cely, sx, sy, celw, celh, layers)`. Unlike `Memory::map`, `sx` and `sy` are in
+/// pixels, and only sprites with every flag in `layers` are drawn
+fn map(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let (celx, cely, sx, sy) = (int(args, 0), int(args, 1), int(args, 2), int(args, 3));
+    let celw = opt_int(args, 4).unwrap_or(128);
+    let celh = opt_int(args, 5).unwrap_or(32);
+    let layers = int(args, 6) as u8;
+    for j in 0..celh {
+        for i in 0..celw {
+            let tile = cart.mem.mget(celx + i, cely + j);
+            if tile != 0 && cart.mem.fget_all(tile) & layers == layers {
+                cart.mem.spr(tile, sx + i * 8, sy + j * 8, None);
+            }
+        }
+    }
+    none()
+}
+/// `print(text, [x, y], [col])`. Without a position it prints at the cursor
+fn print(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let text: alloc::string::String = arg(args, 0).to_text().iter().map(|&b| b as char).collect();
+    let right = if args.len() >= 3 {
+        let c = col(cart, args, 3);
+        cart.mem.print(&text, int(args, 1), int(args, 2), c)
+    } else {
+        let c = col(cart, args, 1);
+        cart.mem.print_at_cursor(&text, c)
+    };
+    one(Value::int(right))
+}
+fn cursor(cart: &mut LuaCart, args: &[Value]) -> Results {
+    cart.mem.cursor(int(args, 0), int(args, 1));
+    if let Some(c) = opt_int(args, 2) {
+        cart.mem.color(c as u8);
+    }
+    none()
+}
+fn color(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let previous = cart.mem.color(opt_int(args, 0).unwrap_or(6) as u8);
+    one(Value::int(previous as i32))
+}
+fn camera(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let previous = cart.mem.camera.clone();
+    cart.mem.camera(num(args, 0).floor(), num(args, 1).floor());
+    Ok(vec![Value::Num(previous.x), Value::Num(previous.y)])
+}
+fn clip(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if args.is_empty() {
+        cart.mem.clip_reset();
+    } else {
+        let [x, y, w, h] = [0, 1, 2, 3].map(|i| int(args, i));
+        cart.mem.clip(x, y, w, h);
+    }
+    none()
+}
+/// `pal()` resets, `pal(c0, c1, [p])` swaps one color, and `pal(table, [p])` swaps many
+fn pal(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let mut swap = |from: i32, to: i32, p: i32| match p {
+        0 => cart.mem.pal((from & 15) as usize, (to & 15) as u8),
+        1 => cart.mem.pal_display((from & 15) as usize, to as u8),
+        _ => {}
+    };
+    match args.first() {
+        None | Some(Value::Nil) => cart.mem.pal_reset(),
+        Some(Value::Table(t)) => {
+            let p = int(args, 1);
+            let mut key = Value::Nil;
+            while let Some((k, v)) = t.borrow().next(&key) {
+                if let (Some(from), Some(to)) = (k.to_num(), v.to_num()) {
+                    swap(from.to_int(), to.to_int(), p);
+                }
+                key = k;
+            }
+        }
+        _ => swap(int(args, 0), int(args, 1), int(args, 2)),
+    }
+    none()
+}
+/// `palt()` resets, `palt(c, t)` sets one color, and `palt(bits)` sets them all, color 0 being
+/// the highest bit
+fn palt(cart: &mut LuaCart, args: &[Value]) -> Results {
+    match args.len() {
+        0 => (0..16).for_each(|i| cart.mem.palt(i, i == 0)),
+        1 => {
+            let bits = int(args, 0);
+            (0..16).for_each(|i| cart.mem.palt(i, bits >> (15 - i) & 1 != 0));
+        }
+        _ => cart.mem.palt((int(args, 0) & 15) as usize, truthy(args, 1)),
+    }
+    none()
+}
+/// the pattern is the whole part, and `0b0.1` makes the gaps transparent
+fn fillp(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let bits = num(args, 0).to_bits();
+    cart.mem.fillp((bits >> 16) as u16, bits & 0x8000 != 0);
+    none()
+}
+
+/// `btn(i)`, or every button as bits with no arguments. Only player 0 has any buttons
+fn btn(cart: &mut LuaCart, args: &[Value]) -> Results {
+    buttons(cart, args, |_, held| held)
+}
+/// like `btn`, but only on the tick a button goes down, and then every few ticks while it's
+/// held, after a delay
+fn btnp(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let scale = cart.fps() / 30;
+    buttons(cart, args, |ticks, held| {
+        let (delay, every) = (15 * scale, 4 * scale);
+        held && (ticks == 1 || (ticks > delay && (ticks - delay - 1) % every == 0))
+    })
+}
+fn buttons(cart: &LuaCart, args: &[Value], pressed: impl Fn(u32, bool) -> bool) -> Results {
+    let down = |i: usize| {
+        let held = cart.mem.buttons.get(i).copied().unwrap_or(false);
+        pressed(cart.held.get(i).copied().unwrap_or(0), held)
+    };
+    match opt_int(args, 0) {
+        None => one(Value::int(
+            (0..6).filter(|&i| down(i)).map(|i| 1 << i).sum(),
+        )),
+        Some(i) if int(args, 1) == 0 && (0..6).contains(&i) => one(down(i as usize)),
+        Some(_) => one(false),
+    }
+}
+
+fn flr(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).floor())
+}
+fn ceil(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).ceil())
+}
+fn abs(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).abs())
+}
+fn sgn(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).sgn())
+}
+fn min(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).min(num(args, 1)))
+}
+fn max(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).max(num(args, 1)))
+}
+fn mid(_: &mut LuaCart, args: &[Value]) -> Results {
+    let (a, b, c) = (num(args, 0), num(args, 1), num(args, 2));
+    one(a.min(b).max(a.max(b).min(c)))
+}
+fn sqrt(_: &mut LuaCart, args: &[Value]) -> Results {
+    let n = num(args, 0);
+    one(if n > Fix16::ZERO {
+        from_f64(libm::sqrt(to_f64(n)))
+    } else {
+        Fix16::ZERO
+    })
+}
+fn sin(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).sin())
+}
+fn cos(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(num(args, 0).cos())
+}
+fn atan2_(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(atan2(num(args, 0), num(args, 1)))
+}
+/// `rnd(n)` is from 0 up to but not including `n`, `rnd(table)` is one of its values
+fn rnd(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if let Some(Value::Table(t)) = args.first() {
+        let t = t.borrow();
+        if t.is_empty() {
+            return one(Value::Nil);
+        }
+        let i = cart.mem.rng.next_u32() as usize % t.len();
+        return one(t.get_int(i + 1));
+    }
+    let limit = opt_num(args, 0).unwrap_or(Fix16::ONE).to_bits();
+    if limit <= 0 {
+        return one(Fix16::ZERO);
+    }
+    one(Fix16::from_bits(
+        (cart.mem.rng.next_u32() % limit as u32) as i32,
+    ))
+}
+fn srand(cart: &mut LuaCart, args: &[Value]) -> Results {
+    cart.mem.rng = GameRng::new(num(args, 0).to_bits() as u32 as u64);
+    none()
+}
+fn bitwise(op: BinOp, args: &[Value]) -> Results {
+    one(arith(op, num(args, 0), num(args, 1)))
+}
+fn band(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::BAnd, args)
+}
+fn bor(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::BOr, args)
+}
+fn bxor(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::BXor, args)
+}
+fn bnot(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(Fix16::from_bits(!num(args, 0).to_bits()))
+}
+fn shl(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::Shl, args)
+}
+fn shr(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::Shr, args)
+}
+fn lshr(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::LShr, args)
+}
+fn rotl(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::RotL, args)
+}
+fn rotr(_: &mut LuaCart, args: &[Value]) -> Results {
+    bitwise(BinOp::RotR, args)
+}
+
+/// `tostr(v, [hex])`
+fn tostr(_: &mut LuaCart, args: &[Value]) -> Results {
+    match args.first() {
+        Some(Value::Num(n)) if truthy(args, 1) => one(Value::str(&format_hex(*n))),
+        _ => one(Value::Str(arg(args, 0).to_text())),
+    }
+}
+fn tonum(_: &mut LuaCart, args: &[Value]) -> Results {
+    match args.first() {
+        Some(Value::Num(n)) => one(*n),
+        Some(Value::Str(s)) => Ok(parse_num(s).map(Value::Num).into_iter().collect()),
+        _ => none(),
+    }
+}
+/// the string of the P8SCII codes given
+fn chr(_: &mut LuaCart, args: &[Value]) -> Results {
+    let bytes: Vec<u8> = (0..args.len()).map(|i| int(args, i) as u8).collect();
+    one(Value::Str(bytes.into()))
+}
+/// `ord(s, [i], [n])`: the codes of `n` characters from `i` on
+fn ord(_: &mut LuaCart, args: &[Value]) -> Results {
+    let text = arg(args, 0).to_text();
+    let start = opt_int(args, 1).unwrap_or(1);
+    let n = opt_int(args, 2).unwrap_or(1);
+    Ok((start..start.saturating_add(n))
+        .map_while(|i| {
+            let byte = usize::try_from(i - 1).ok().and_then(|i| text.get(i))?;
+            Some(Value::int(*byte as i32))
+        })
+        .collect())
+}
+/// `sub(s, i, [j])`, with negative positions counting from the end
+fn sub(_: &mut LuaCart, args: &[Value]) -> Results {
+    let text = arg(args, 0).to_text();
+    let len = text.len() as i32;
+    let start = match opt_int(args, 1).unwrap_or(1) {
+        i if i < 0 => (len + i + 1).max(1),
+        0 => 1,
+        i => i,
+    };
+    let end = match opt_int(args, 2).unwrap_or(-1) {
+        j if j < 0 => len + j + 1,
+        j => j.min(len),
+    };
+    if start > end {
+        return one(Value::str(""));
+    }
+    one(Value::Str(text[start as usize - 1..end as usize].into()))
+}
+/// `split(s, [sep], [convert])`. `sep` can be a string or a number of characters per piece,
+/// and numbers are converted unless `convert` is false
+fn split(_: &mut LuaCart, args: &[Value]) -> Results {
+    let text = arg(args, 0).to_text();
+    let convert = args.get(2).is_none_or(Value::truthy);
+    let pieces: Vec<&[u8]> = match args.get(1) {
+        Some(Value::Num(n)) => text.chunks(n.to_int().max(1) as usize).collect(),
+        Some(Value::Str(sep)) if sep.is_empty() => text.chunks(1).collect(),
+        Some(Value::Str(sep)) => split_on(&text, sep),
+        _ => split_on(&text, b","),
+    };
+    let values = pieces
+        .into_iter()
+        .map(|piece| match parse_num(piece) {
+            Some(n) if convert => Value::Num(n),
+            _ => Value::Str(piece.into()),
+        })
+        .collect();
+    one(Value::table(Table::from_values(values)))
+}
+fn split_on<'a>(text: &'a [u8], sep: &[u8]) -> Vec<&'a [u8]> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i + sep.len() <= text.len() {
+        if &text[i..i + sep.len()] == sep {
+            pieces.push(&text[start..i]);
+            i += sep.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    pieces.push(&text[start..]);
+    pieces
+}
+fn type_(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(Value::str(arg(args, 0).type_name()))
+}
+
+/// `add(t, v, [i])` puts `v` at the end, or at `i`, and returns it
+fn add(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if args.first().is_none_or(Value::is_nil) {
+        return none();
+    }
+    let t = table(cart, args, 0, "add")?;
+    let value = arg(args, 1);
+    let mut t = t.borrow_mut();
+    let pos = match opt_int(args, 2) {
+        Some(i) => i.clamp(1, t.len() as i32 + 1) as usize,
+        None => t.len() + 1,
+    };
+    t.insert(pos, value.clone());
+    one(value)
+}
+/// `del(t, v)` takes out the first `v`, moving the rest down, and returns it
+fn del(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if args.first().is_none_or(Value::is_nil) {
+        return none();
+    }
+    let t = table(cart, args, 0, "del")?;
+    let value = arg(args, 1);
+    let mut t = t.borrow_mut();
+    match t.array.iter().position(|v| v.raw_eq(&value)) {
+        Some(i) => one(t.remove(i + 1)),
+        None => none(),
+    }
+}
+/// `deli(t, [i])` takes out the value at `i`, or the last one
+fn deli(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if args.first().is_none_or(Value::is_nil) {
+        return none();
+    }
+    let t = table(cart, args, 0, "deli")?;
+    let mut t = t.borrow_mut();
+    let pos = opt_int(args, 1).unwrap_or(t.len() as i32);
+    match usize::try_from(pos) {
+        Ok(pos) => one(t.remove(pos)),
+        Err(_) => none(),
+    }
+}
+/// `count(t)` is `#t`, `count(t, v)` how many times `v` is in it
+fn count(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if args.first().is_none_or(Value::is_nil) {
+        return one(Fix16::ZERO);
+    }
+    let t = table(cart, args, 0, "count")?;
+    let t = t.borrow();
+    let n = match args.get(1) {
+        Some(value) => t.array.iter().filter(|v| v.raw_eq(value)).count(),
+        None => t.len(),
+    };
+    one(Value::int(n as i32))
+}
+fn pairs(cart: &mut LuaCart, args: &[Value]) -> Results {
+    // pico-8 lets `pairs(nil)` go through nothing
+    if args.first().is_none_or(Value::is_nil) {
+        return one(native("next", nothing));
+    }
+    let t = table(cart, args, 0, "pairs")?;
+    Ok(vec![native("next", next), Value::Table(t), Value::Nil])
+}
+fn next(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let t = table(cart, args, 0, "next")?;
+    let next = t.borrow().next(&arg(args, 1));
+    match next {
+        Some((k, v)) => Ok(vec![k, v]),
+        None => one(Value::Nil),
+    }
+}
+fn ipairs(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let t = table(cart, args, 0, "ipairs")?;
+    Ok(vec![
+        native("ipairs", ipairs_next),
+        Value::Table(t),
+        Value::int(0),
+    ])
+}
+fn ipairs_next(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let i = int(args, 1) + 1;
+    let value = cart.index(&arg(args, 0), &Value::int(i))?;
+    if value.is_nil() {
+        return one(Value::Nil);
+    }
+    Ok(vec![Value::int(i), value])
+}
+/// `unpack(t, [i], [j])`: `t[i]` to `t[j]`, all of it by default
+fn unpack(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let t = table(cart, args, 0, "unpack")?;
+    let t = t.borrow();
+    let start = opt_int(args, 1).unwrap_or(1);
+    let end = opt_int(args, 2).unwrap_or(t.len() as i32);
+    if end.saturating_sub(start) >= 0x10000 {
+        return Err(cart.error("too many results to unpack"));
+    }
+    Ok((start..=end).map(|i| t.get(&Value::int(i))).collect())
+}
+/// the arguments in a table, with how many there were in `n`
+fn pack(_: &mut LuaCart, args: &[Value]) -> Results {
+    let mut t = Table::from_values(args.to_vec());
+    t.set(Key::str("n"), Value::int(args.len() as i32));
+    one(Value::table(t))
+}
+/// `select(n, ...)`: the arguments from the `n`th on, or how many there are for `"#"`
+fn select(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let rest = &args[args.len().min(1)..];
+    if matches!(args.first(), Some(Value::Str(s)) if &**s == b"#") {
+        return one(Value::int(rest.len() as i32));
+    }
+    let n = int(args, 0);
+    let from = match n {
+        1.. => (n - 1) as usize,
+        ..=-1 if -n as usize <= rest.len() => rest.len() - (-n) as usize,
+        _ => return Err(cart.error("bad argument #1 to 'select' (index out of range)")),
+    };
+    Ok(rest.get(from..).unwrap_or_default().to_vec())
+}
+fn setmetatable(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let t = table(cart, args, 0, "setmetatable")?;
+    t.borrow_mut().meta = match args.get(1) {
+        Some(Value::Table(meta)) => Some(meta.clone()),
+        None | Some(Value::Nil) => None,
+        Some(_) => {
+            return Err(cart.error("bad argument #2 to 'setmetatable' (nil or table expected)"))
+        }
+    };
+    one(Value::Table(t))
+}
+fn getmetatable(_: &mut LuaCart, args: &[Value]) -> Results {
+    match args.first() {
+        Some(Value::Table(t)) => one(t.borrow().meta.clone().map_or(Value::Nil, Value::Table)),
+        _ => one(Value::Nil),
+    }
+}
+fn rawget(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let t = table(cart, args, 0, "rawget")?;
+    let value = t.borrow().get(&arg(args, 1));
+    one(value)
+}
+fn rawset(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let t = table(cart, args, 0, "rawset")?;
+    let Some(key) = Key::new(arg(args, 1)) else {
+        return Err(cart.error("table index is nil"));
+    };
+    t.borrow_mut().set(key, arg(args, 2));
+    one(Value::Table(t))
+}
+fn rawequal(_: &mut LuaCart, args: &[Value]) -> Results {
+    one(arg(args, 0).raw_eq(&arg(args, 1)))
+}
+fn rawlen(cart: &mut LuaCart, args: &[Value]) -> Results {
+    match args.first() {
+        Some(Value::Str(s)) => one(Value::int(s.len() as i32)),
+        _ => {
+            let t = table(cart, args, 0, "rawlen")?;
+            let len = t.borrow().len();
+            one(Value::int(len as i32))
+        }
+    }
+}
+fn assert(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if truthy(args, 0) {
+        return Ok(args.to_vec());
+    }
+    Err(cart.error(match args.get(1) {
+        Some(message) => message
+            .as_rust_str()
+            .unwrap_or_else(|| "assertion failed!".into()),
+        None => "assertion failed!".into(),
+    }))
+}
+fn stop(cart: &mut LuaCart, args: &[Value]) -> Results {
+    Err(cart.error(
+        arg(args, 0)
+            .as_rust_str()
+            .unwrap_or_else(|| "stopped".into()),
+    ))
+}
+/// goes to `Memory::logger`, which is where pico-8's terminal would be
+fn printh(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let text = alloc::string::String::from_utf8_lossy(&arg(args, 0).to_text()).into_owned();
+    (cart.mem.logger)(&text);
+    none()
+}
+
+/// `peek(addr, [n])` gives `n` bytes
+fn peek(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let addr = address(num(args, 0));
+    let n = opt_int(args, 1).unwrap_or(1).clamp(0, 8192) as usize;
+    Ok((0..n)
+        .map(|i| Value::int(cart.mem.peek(addr + i) as i32))
+        .collect())
+}
+/// `poke(addr, ...)` writes each value to the next byte
+fn poke(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let addr = address(num(args, 0));
+    for i in 1..args.len() {
+        cart.mem.poke(addr + i - 1, int(args, i) as u8);
+    }
+    none()
+}
+fn peek2(cart: &mut LuaCart, args: &[Value]) -> Results {
+    one(Value::int(cart.mem.peek2(address(num(args, 0))) as i32))
+}
+fn poke2(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let addr = address(num(args, 0));
+    for i in 1..args.len() {
+        cart.mem.poke2(addr + (i - 1) * 2, int(args, i) as i16);
+    }
+    none()
+}
+fn peek4(cart: &mut LuaCart, args: &[Value]) -> Results {
+    one(cart.mem.peek4(address(num(args, 0))))
+}
+fn poke4(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let addr = address(num(args, 0));
+    for i in 1..args.len() {
+        cart.mem.poke4(addr + (i - 1) * 4, num(args, i));
+    }
+    none()
+}
+fn memcpy(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let len = int(args, 2).clamp(0, 0x10000) as usize;
+    cart.mem
+        .memcpy(address(num(args, 0)), address(num(args, 1)), len);
+    none()
+}
+fn memset(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let len = int(args, 2).clamp(0, 0x10000) as usize;
+    cart.mem
+        .memset(address(num(args, 0)), int(args, 1) as u8, len);
+    none()
+}
+/// `reload(dest, src, len)` copies from the cart as it was loaded, all of the sprites, map and
+/// flags by default
+fn reload(cart: &mut LuaCart, args: &[Value]) -> Results {
+    let rom = Memory::from_cart(&cart.rom);
+    let (dest, src) = (address(num(args, 0)), address(num(args, 1)));
+    let len = opt_int(args, 2).unwrap_or(0x4300).clamp(0, 0x10000) as usize;
+    for i in 0..len {
+        cart.mem.poke(dest + i, rom.peek(src + i));
+    }
+    none()
+}
+/// the 64 numbers a cart can save. They're only kept until it's closed
+fn dget(cart: &mut LuaCart, args: &[Value]) -> Results {
+    match usize::try_from(int(args, 0)) {
+        Ok(i) if i < 64 => one(cart.mem.peek4(CART_DATA + i * 4)),
+        _ => one(Fix16::ZERO),
+    }
+}
+fn dset(cart: &mut LuaCart, args: &[Value]) -> Results {
+    if let Ok(i @ 0..=63) = usize::try_from(int(args, 0)) {
+        cart.mem.poke4(CART_DATA + i * 4, num(args, 1));
+    }
+    none()
+}
+/// seconds since the cart started, counted in ticks
+fn time(cart: &mut LuaCart, _: &[Value]) -> Results {
+    let fps = cart.fps() as i32;
+    let ticks = cart.ticks as i32;
+    let seconds =
+        Fix16::from_int(ticks / fps) + Fix16::from_int(ticks % fps) / Fix16::from_int(fps);
+    one(seconds)
+}
+/// Only `stat(7)`, the frame rate. Everything else is 0
+fn stat(cart: &mut LuaCart, args: &[Value]) -> Results {
+    match int(args, 0) {
+        7 => one(Value::int(cart.fps() as i32)),
+        _ => one(Fix16::ZERO),
+    }
+}
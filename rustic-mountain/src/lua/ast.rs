@@ -0,0 +1,143 @@
+//! The parsed program. Names are already resolved: locals are slots in their function's frame,
+//! upvalues are indices into the closure's captures, and globals are indices into
+//! `Globals::values`
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+
+use crate::fixed::Fix16;
+
+/// statements, each with the line it starts on
+pub type Block = Vec<(u32, Stmt)>;
+
+#[derive(Debug)]
+pub enum Stmt {
+    /// a function call on its own
+    Call(Expr),
+    /// `local a, b = ...`, as the slots being declared
+    Local(Vec<usize>, Vec<Expr>),
+    /// `local function f`, which can see itself
+    LocalFunction(usize, Rc<FuncProto>),
+    Assign(Vec<Expr>, Vec<Expr>),
+    /// `a += b` and the rest, which only evaluate `a`'s table and key once
+    OpAssign(BinOp, Expr, Expr),
+    If(Vec<(Expr, Block)>, Option<Block>),
+    While(Expr, Block),
+    Repeat(Block, Expr),
+    NumFor {
+        var: usize,
+        start: Expr,
+        limit: Expr,
+        step: Option<Expr>,
+        body: Block,
+    },
+    GenericFor {
+        vars: Vec<usize>,
+        exprs: Vec<Expr>,
+        body: Block,
+    },
+    Do(Block),
+    Return(Vec<Expr>),
+    Break,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Nil,
+    Bool(bool),
+    Num(Fix16),
+    Str(Rc<[u8]>),
+    /// `...`
+    Vararg,
+    Local(usize),
+    Upval(usize),
+    Global(usize),
+    Index(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Expr>),
+    /// `obj:name(args)`
+    Method(Box<Expr>, Rc<[u8]>, Vec<Expr>),
+    Function(Rc<FuncProto>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Table(Vec<Field>),
+    /// brackets around a call or `...`, which cut it down to one value
+    Paren(Box<Expr>),
+}
+
+#[derive(Debug)]
+pub enum Field {
+    /// `{a, b}`
+    Positional(Expr),
+    /// `{[k] = v}` and `{k = v}`
+    Keyed(Expr, Expr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// `\`, pico-8's integer division
+    IDiv,
+    Mod,
+    Pow,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BAnd,
+    BOr,
+    /// `^^`
+    BXor,
+    Shl,
+    /// `>>`, which keeps the sign
+    Shr,
+    /// `>>>`, which doesn't
+    LShr,
+    /// `<<>`
+    RotL,
+    /// `>><`
+    RotR,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+    Neg,
+    Len,
+    BNot,
+    /// `@addr`, `peek`
+    Peek,
+    /// `%addr`, `peek2`
+    Peek2,
+    /// `$addr`, `peek4`
+    Peek4,
+}
+
+/// A function as it was written. Running it makes a `Closure`
+#[derive(Debug)]
+pub struct FuncProto {
+    /// the first slots are the parameters
+    pub params: usize,
+    pub vararg: bool,
+    /// whether each slot is captured by a function inside this one, which keeps those in a cell
+    pub captured: Vec<bool>,
+    /// the name of each slot, for errors
+    pub names: Vec<Rc<str>>,
+    /// where each of the closure's captures comes from when it's made
+    pub upvals: Vec<Upval>,
+    pub upval_names: Vec<Rc<str>>,
+    pub body: Block,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Upval {
+    /// a slot of the function the closure is made in
+    Local(usize),
+    /// one of that function's own captures
+    Outer(usize),
+}
@@ -0,0 +1,898 @@
+//! Runs the `ast` by walking it. Every call gets a `Frame` with a slot per local; locals that a
+//! closure captures live in a shared cell instead, made fresh each time the local is declared
+//! so every loop iteration gets its own
+use alloc::{collections::BTreeMap, format, rc::Rc, string::String, vec, vec::Vec};
+use core::{cell::RefCell, f64::consts::PI};
+
+use super::{
+    ast::{BinOp, Block, Expr, Field, FuncProto, Stmt, UnOp, Upval},
+    value::{from_f64, to_f64, Key, Table, Value},
+    LuaCart, LuaError,
+};
+use crate::fixed::Fix16;
+
+/// How deep calls can go before it's a stack overflow. Each one takes a few rust frames, and
+/// UEFI doesn't give much stack
+const MAX_DEPTH: usize = 100;
+
+/// how many `__index` or `__newindex` tables are followed before giving up
+const MAX_META_CHAIN: usize = 100;
+
+pub struct Closure {
+    pub proto: Rc<FuncProto>,
+    pub upvals: Vec<Rc<RefCell<Value>>>,
+}
+
+/// Every global the code mentions, by the index the parser gave it
+#[derive(Default)]
+pub struct Globals {
+    indices: BTreeMap<Rc<str>, usize>,
+    pub values: Vec<Value>,
+}
+
+impl Globals {
+    /// the index of the global `name`, which starts out `nil`
+    pub fn intern(&mut self, name: &str) -> usize {
+        if let Some(&i) = self.indices.get(name) {
+            return i;
+        }
+        self.values.push(Value::Nil);
+        self.indices.insert(name.into(), self.values.len() - 1);
+        self.values.len() - 1
+    }
+    pub fn get(&self, name: &str) -> Value {
+        match self.indices.get(name) {
+            Some(&i) => self.values[i].clone(),
+            None => Value::Nil,
+        }
+    }
+    pub fn set(&mut self, name: &str, value: Value) {
+        let i = self.intern(name);
+        self.values[i] = value;
+    }
+    fn name(&self, index: usize) -> &str {
+        self.indices
+            .iter()
+            .find(|(_, &i)| i == index)
+            .map_or("?", |(name, _)| name)
+    }
+}
+
+/// The metamethods, in the order of `META_NAMES`
+#[derive(Clone, Copy)]
+pub enum Meta {
+    Index,
+    NewIndex,
+    Call,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    IDiv,
+    BAnd,
+    BOr,
+    BXor,
+    Shl,
+    Shr,
+    Concat,
+    Unm,
+    BNot,
+    Len,
+    Eq,
+    Lt,
+    Le,
+}
+
+pub const META_NAMES: [&str; 22] = [
+    "__index",
+    "__newindex",
+    "__call",
+    "__add",
+    "__sub",
+    "__mul",
+    "__div",
+    "__mod",
+    "__pow",
+    "__idiv",
+    "__band",
+    "__bor",
+    "__bxor",
+    "__shl",
+    "__shr",
+    "__concat",
+    "__unm",
+    "__bnot",
+    "__len",
+    "__eq",
+    "__lt",
+    "__le",
+];
+
+#[derive(Clone)]
+enum Slot {
+    Value(Value),
+    Cell(Rc<RefCell<Value>>),
+}
+
+/// the locals of a call that's running
+struct Frame<'a> {
+    proto: &'a FuncProto,
+    slots: Vec<Slot>,
+    upvals: &'a [Rc<RefCell<Value>>],
+    varargs: Vec<Value>,
+}
+
+impl Frame<'_> {
+    fn declare(&mut self, slot: usize, value: Value) {
+        self.slots[slot] = if self.proto.captured[slot] {
+            Slot::Cell(Rc::new(RefCell::new(value)))
+        } else {
+            Slot::Value(value)
+        };
+    }
+    fn get(&self, slot: usize) -> Value {
+        match &self.slots[slot] {
+            Slot::Value(v) => v.clone(),
+            Slot::Cell(cell) => cell.borrow().clone(),
+        }
+    }
+    fn set(&mut self, slot: usize, value: Value) {
+        match &mut self.slots[slot] {
+            Slot::Value(v) => *v = value,
+            Slot::Cell(cell) => *cell.borrow_mut() = value,
+        }
+    }
+    /// the cell of a captured slot, for a closure
+    fn cell(&mut self, slot: usize) -> Rc<RefCell<Value>> {
+        match &self.slots[slot] {
+            Slot::Cell(cell) => cell.clone(),
+            Slot::Value(v) => {
+                let cell = Rc::new(RefCell::new(v.clone()));
+                self.slots[slot] = Slot::Cell(cell.clone());
+                cell
+            }
+        }
+    }
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Return(Vec<Value>),
+}
+
+/// somewhere a value can be assigned to
+enum Place {
+    Local(usize),
+    Upval(usize),
+    Global(usize),
+    Index(Value, Value),
+}
+
+pub fn first(values: Vec<Value>) -> Value {
+    values.into_iter().next().unwrap_or_default()
+}
+
+impl LuaCart {
+    pub fn error(&self, message: impl Into<String>) -> LuaError {
+        LuaError {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    /// Calls a function, a built in or a table with `__call`, returning all its results
+    pub fn call(&mut self, func: &Value, args: Vec<Value>) -> Result<Vec<Value>, LuaError> {
+        match func {
+            Value::Function(closure) => {
+                if self.depth >= MAX_DEPTH {
+                    return Err(self.error("stack overflow"));
+                }
+                let closure = closure.clone();
+                let proto = &*closure.proto;
+                let mut frame = Frame {
+                    proto,
+                    slots: vec![Slot::Value(Value::Nil); proto.captured.len()],
+                    upvals: &closure.upvals,
+                    varargs: Vec::new(),
+                };
+                let mut args = args.into_iter();
+                for slot in 0..proto.params {
+                    frame.declare(slot, args.next().unwrap_or_default());
+                }
+                if proto.vararg {
+                    frame.varargs = args.collect();
+                }
+                let line = self.line;
+                self.depth += 1;
+                let flow = self.exec(&mut frame, &proto.body);
+                self.depth -= 1;
+                // an error keeps the line it happened on
+                let flow = flow?;
+                self.line = line;
+                Ok(match flow {
+                    Flow::Return(values) => values,
+                    _ => Vec::new(),
+                })
+            }
+            Value::Native(native) => (native.f)(self, &args),
+            _ => match self.meta(func, Meta::Call) {
+                Some(handler) => {
+                    let mut all = vec![func.clone()];
+                    all.extend(args);
+                    self.call(&handler, all)
+                }
+                None => Err(self.error(format!("attempt to call a {} value", func.type_name()))),
+            },
+        }
+    }
+
+    /// Runs the top level of a parsed chunk
+    pub(super) fn run(&mut self, main: Rc<FuncProto>) -> Result<(), LuaError> {
+        let main = Value::Function(Rc::new(Closure {
+            proto: main,
+            upvals: Vec::new(),
+        }));
+        self.call(&main, Vec::new()).map(|_| ())
+    }
+
+    /// the handler for `event` in `value`'s metatable
+    pub fn meta(&self, value: &Value, event: Meta) -> Option<Value> {
+        let Value::Table(table) = value else {
+            return None;
+        };
+        let meta = table.borrow().meta.clone()?;
+        let handler = meta.borrow().get_key(&self.meta_keys[event as usize]);
+        (!handler.is_nil()).then_some(handler)
+    }
+
+    /// `obj[key]`, going through `__index`
+    pub fn index(&mut self, obj: &Value, key: &Value) -> Result<Value, LuaError> {
+        let mut obj = obj.clone();
+        for _ in 0..MAX_META_CHAIN {
+            let Value::Table(table) = &obj else {
+                return Err(self.error(format!("attempt to index a {} value", obj.type_name())));
+            };
+            let (value, meta) = {
+                let table = table.borrow();
+                (table.get(key), table.meta.clone())
+            };
+            let Some(meta) = meta.filter(|_| value.is_nil()) else {
+                return Ok(value);
+            };
+            let handler = meta.borrow().get_key(&self.meta_keys[Meta::Index as usize]);
+            match handler {
+                Value::Nil => return Ok(Value::Nil),
+                Value::Function(_) | Value::Native(_) => {
+                    return Ok(first(self.call(&handler, vec![obj, key.clone()])?));
+                }
+                _ => obj = handler,
+            }
+        }
+        Err(self.error("'__index' chain too long, it probably loops"))
+    }
+
+    /// `obj[key] = value`, going through `__newindex` if `key` isn't there yet
+    pub fn set_index(&mut self, obj: &Value, key: Value, value: Value) -> Result<(), LuaError> {
+        let mut obj = obj.clone();
+        for _ in 0..MAX_META_CHAIN {
+            let Value::Table(table) = &obj else {
+                return Err(self.error(format!("attempt to index a {} value", obj.type_name())));
+            };
+            let Some(k) = Key::new(key.clone()) else {
+                return Err(self.error("table index is nil"));
+            };
+            let handler = {
+                let table = table.borrow();
+                match &table.meta {
+                    Some(meta) if table.get_key(&k).is_nil() => meta
+                        .borrow()
+                        .get_key(&self.meta_keys[Meta::NewIndex as usize]),
+                    _ => Value::Nil,
+                }
+            };
+            match handler {
+                Value::Nil => {
+                    table.borrow_mut().set(k, value);
+                    return Ok(());
+                }
+                Value::Function(_) | Value::Native(_) => {
+                    self.call(&handler, vec![obj.clone(), key, value])?;
+                    return Ok(());
+                }
+                _ => obj = handler,
+            }
+        }
+        Err(self.error("'__newindex' chain too long, it probably loops"))
+    }
+
+    /// `a op b` for everything but `and` and `or`
+    pub fn binary(&mut self, op: BinOp, a: &Value, b: &Value) -> Result<Value, LuaError> {
+        let event = match op {
+            BinOp::Eq => return Ok(Value::Bool(self.equals(a, b)?)),
+            BinOp::Ne => return Ok(Value::Bool(!self.equals(a, b)?)),
+            BinOp::Lt => return self.less(a, b, false).map(Value::Bool),
+            BinOp::Le => return self.less(a, b, true).map(Value::Bool),
+            BinOp::Gt => return self.less(b, a, false).map(Value::Bool),
+            BinOp::Ge => return self.less(b, a, true).map(Value::Bool),
+            BinOp::Concat => return self.concat(a, b),
+            BinOp::Add => Some(Meta::Add),
+            BinOp::Sub => Some(Meta::Sub),
+            BinOp::Mul => Some(Meta::Mul),
+            BinOp::Div => Some(Meta::Div),
+            BinOp::Mod => Some(Meta::Mod),
+            BinOp::Pow => Some(Meta::Pow),
+            BinOp::IDiv => Some(Meta::IDiv),
+            BinOp::BAnd => Some(Meta::BAnd),
+            BinOp::BOr => Some(Meta::BOr),
+            BinOp::BXor => Some(Meta::BXor),
+            BinOp::Shl => Some(Meta::Shl),
+            BinOp::Shr => Some(Meta::Shr),
+            BinOp::LShr | BinOp::RotL | BinOp::RotR => None,
+        };
+        if let (Some(x), Some(y)) = (a.to_num(), b.to_num()) {
+            return Ok(Value::Num(arith(op, x, y)));
+        }
+        if let Some(handler) = event.and_then(|e| self.meta(a, e).or_else(|| self.meta(b, e))) {
+            return Ok(first(self.call(&handler, vec![a.clone(), b.clone()])?));
+        }
+        let bad = if a.to_num().is_none() { a } else { b };
+        Err(self.error(format!(
+            "attempt to {} a {} value",
+            verb(op),
+            bad.type_name()
+        )))
+    }
+
+    fn unary(&mut self, op: UnOp, v: &Value) -> Result<Value, LuaError> {
+        let (event, verb) = match op {
+            UnOp::Neg => (Meta::Unm, "perform arithmetic on"),
+            UnOp::BNot => (Meta::BNot, "perform bitwise operation on"),
+            UnOp::Len => (Meta::Len, "get length of"),
+            UnOp::Peek | UnOp::Peek2 | UnOp::Peek4 => (Meta::Unm, "peek at"),
+        };
+        let result = match (op, v) {
+            (UnOp::Len, Value::Str(s)) => Some(Value::int(s.len() as i32)),
+            (UnOp::Len, Value::Table(t)) if self.meta(v, event).is_none() => {
+                Some(Value::int(t.borrow().len() as i32))
+            }
+            (UnOp::Len, _) => None,
+            _ => v.to_num().map(|n| match op {
+                UnOp::Neg => Value::Num(-n),
+                UnOp::BNot => Value::Num(Fix16::from_bits(!n.to_bits())),
+                UnOp::Peek => Value::int(self.mem.peek(address(n)) as i32),
+                UnOp::Peek2 => Value::int(self.mem.peek2(address(n)) as i32),
+                _ => Value::Num(self.mem.peek4(address(n))),
+            }),
+        };
+        if let Some(result) = result {
+            return Ok(result);
+        }
+        match self.meta(v, event) {
+            Some(handler) => Ok(first(self.call(&handler, vec![v.clone(), v.clone()])?)),
+            None => Err(self.error(format!("attempt to {} a {} value", verb, v.type_name()))),
+        }
+    }
+
+    /// `==`, which only asks `__eq` about two different tables
+    pub fn equals(&mut self, a: &Value, b: &Value) -> Result<bool, LuaError> {
+        if a.raw_eq(b) {
+            return Ok(true);
+        }
+        if let (Value::Table(_), Value::Table(_)) = (a, b) {
+            if let Some(handler) = self.meta(a, Meta::Eq).or_else(|| self.meta(b, Meta::Eq)) {
+                return Ok(first(self.call(&handler, vec![a.clone(), b.clone()])?).truthy());
+            }
+        }
+        Ok(false)
+    }
+
+    /// `a < b`, or `a <= b` if `or_equal`
+    fn less(&mut self, a: &Value, b: &Value, or_equal: bool) -> Result<bool, LuaError> {
+        match (a, b) {
+            (Value::Num(x), Value::Num(y)) => Ok(if or_equal { x <= y } else { x < y }),
+            (Value::Str(x), Value::Str(y)) => Ok(if or_equal { x <= y } else { x < y }),
+            _ => {
+                let event = if or_equal { Meta::Le } else { Meta::Lt };
+                if let Some(handler) = self.meta(a, event).or_else(|| self.meta(b, event)) {
+                    return Ok(first(self.call(&handler, vec![a.clone(), b.clone()])?).truthy());
+                }
+                Err(self.error(if a.type_name() == b.type_name() {
+                    format!("attempt to compare two {} values", a.type_name())
+                } else {
+                    format!(
+                        "attempt to compare {} with {}",
+                        a.type_name(),
+                        b.type_name()
+                    )
+                }))
+            }
+        }
+    }
+
+    fn concat(&mut self, a: &Value, b: &Value) -> Result<Value, LuaError> {
+        let text = |v: &Value| matches!(v, Value::Str(_) | Value::Num(_));
+        if text(a) && text(b) {
+            let mut bytes = a.to_text().to_vec();
+            bytes.extend_from_slice(&b.to_text());
+            return Ok(Value::Str(bytes.into()));
+        }
+        if let Some(handler) = self
+            .meta(a, Meta::Concat)
+            .or_else(|| self.meta(b, Meta::Concat))
+        {
+            return Ok(first(self.call(&handler, vec![a.clone(), b.clone()])?));
+        }
+        let bad = if text(a) { b } else { a };
+        Err(self.error(format!(
+            "attempt to concatenate a {} value",
+            bad.type_name()
+        )))
+    }
+
+    fn exec(&mut self, f: &mut Frame, block: &Block) -> Result<Flow, LuaError> {
+        for (line, stmt) in block {
+            self.line = *line;
+            match stmt {
+                Stmt::Call(call) => {
+                    self.eval_call(f, call)?;
+                }
+                Stmt::Local(slots, exprs) => {
+                    if let ([slot], [expr]) = (&slots[..], &exprs[..]) {
+                        let value = self.eval(f, expr)?;
+                        f.declare(*slot, value);
+                    } else {
+                        let mut values = self.eval_list(f, exprs)?.into_iter();
+                        for &slot in slots {
+                            f.declare(slot, values.next().unwrap_or_default());
+                        }
+                    }
+                }
+                Stmt::LocalFunction(slot, proto) => {
+                    f.declare(*slot, Value::Nil);
+                    let closure = self.closure(f, proto);
+                    f.set(*slot, closure);
+                }
+                Stmt::Assign(targets, exprs) => {
+                    if let ([target], [expr]) = (&targets[..], &exprs[..]) {
+                        let place = self.place(f, target)?;
+                        let value = self.eval(f, expr)?;
+                        self.assign(f, place, value)?;
+                    } else {
+                        let mut places = Vec::with_capacity(targets.len());
+                        for target in targets {
+                            places.push(self.place(f, target)?);
+                        }
+                        let mut values = self.eval_list(f, exprs)?.into_iter();
+                        for place in places {
+                            self.assign(f, place, values.next().unwrap_or_default())?;
+                        }
+                    }
+                }
+                Stmt::OpAssign(op, target, expr) => {
+                    let place = self.place(f, target)?;
+                    let current = match &place {
+                        Place::Local(slot) => f.get(*slot),
+                        Place::Upval(i) => f.upvals[*i].borrow().clone(),
+                        Place::Global(i) => self.globals.values[*i].clone(),
+                        Place::Index(obj, key) => self.index(obj, key)?,
+                    };
+                    let rhs = self.eval(f, expr)?;
+                    let value = self
+                        .binary(*op, &current, &rhs)
+                        .map_err(|e| self.name_operand(f, e, *op, target, &current))?;
+                    self.assign(f, place, value)?;
+                }
+                Stmt::If(clauses, otherwise) => {
+                    let mut body = otherwise.as_ref();
+                    for (cond, block) in clauses {
+                        if self.eval(f, cond)?.truthy() {
+                            body = Some(block);
+                            break;
+                        }
+                    }
+                    if let Some(body) = body {
+                        match self.exec(f, body)? {
+                            Flow::Normal => {}
+                            flow => return Ok(flow),
+                        }
+                    }
+                }
+                Stmt::While(cond, body) => {
+                    while self.eval(f, cond)?.truthy() {
+                        match self.exec(f, body)? {
+                            Flow::Normal => {}
+                            Flow::Break => break,
+                            flow => return Ok(flow),
+                        }
+                    }
+                }
+                Stmt::Repeat(body, cond) => loop {
+                    match self.exec(f, body)? {
+                        Flow::Normal => {}
+                        Flow::Break => break,
+                        flow => return Ok(flow),
+                    }
+                    if self.eval(f, cond)?.truthy() {
+                        break;
+                    }
+                },
+                Stmt::NumFor {
+                    var,
+                    start,
+                    limit,
+                    step,
+                    body,
+                } => {
+                    let start = self.for_num(f, start, "initial value")?;
+                    let limit = self.for_num(f, limit, "limit")?;
+                    let step = match step {
+                        Some(step) => self.for_num(f, step, "step")?,
+                        None => Fix16::ONE,
+                    };
+                    if step == Fix16::ZERO {
+                        return Err(self.error("'for' step is zero"));
+                    }
+                    let up = step > Fix16::ZERO;
+                    let mut i = start;
+                    while if up { i <= limit } else { i >= limit } {
+                        f.declare(*var, Value::Num(i));
+                        match self.exec(f, body)? {
+                            Flow::Normal => {}
+                            Flow::Break => break,
+                            flow => return Ok(flow),
+                        }
+                        let next = i + step;
+                        // stop instead of wrapping around past the largest number
+                        if (next < i) == up {
+                            break;
+                        }
+                        i = next;
+                    }
+                }
+                Stmt::GenericFor { vars, exprs, body } => {
+                    let mut values = self.eval_list(f, exprs)?.into_iter();
+                    let iter = values.next().unwrap_or_default();
+                    let state = values.next().unwrap_or_default();
+                    let mut control = values.next().unwrap_or_default();
+                    loop {
+                        let results = self.call(&iter, vec![state.clone(), control.clone()])?;
+                        control = results.first().cloned().unwrap_or_default();
+                        if control.is_nil() {
+                            break;
+                        }
+                        let mut results = results.into_iter();
+                        for &var in vars {
+                            f.declare(var, results.next().unwrap_or_default());
+                        }
+                        match self.exec(f, body)? {
+                            Flow::Normal => {}
+                            Flow::Break => break,
+                            flow => return Ok(flow),
+                        }
+                    }
+                }
+                Stmt::Do(body) => match self.exec(f, body)? {
+                    Flow::Normal => {}
+                    flow => return Ok(flow),
+                },
+                Stmt::Return(exprs) => return Ok(Flow::Return(self.eval_list(f, exprs)?)),
+                Stmt::Break => return Ok(Flow::Break),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn for_num(&mut self, f: &mut Frame, expr: &Expr, what: &str) -> Result<Fix16, LuaError> {
+        self.eval(f, expr)?
+            .to_num()
+            .ok_or_else(|| self.error(format!("'for' {} must be a number", what)))
+    }
+
+    fn place(&mut self, f: &mut Frame, target: &Expr) -> Result<Place, LuaError> {
+        Ok(match target {
+            Expr::Local(slot) => Place::Local(*slot),
+            Expr::Upval(i) => Place::Upval(*i),
+            Expr::Global(i) => Place::Global(*i),
+            Expr::Index(obj, key) => {
+                let table = self.eval(f, obj)?;
+                if !matches!(table, Value::Table(_)) {
+                    return Err(self.error(format!(
+                        "attempt to index {}",
+                        self.describe(f, obj, &table)
+                    )));
+                }
+                Place::Index(table, self.eval(f, key)?)
+            }
+            _ => unreachable!("the parser only lets names and indexes be assigned to"),
+        })
+    }
+
+    fn assign(&mut self, f: &mut Frame, place: Place, value: Value) -> Result<(), LuaError> {
+        match place {
+            Place::Local(slot) => f.set(slot, value),
+            Place::Upval(i) => *f.upvals[i].borrow_mut() = value,
+            Place::Global(i) => self.globals.values[i] = value,
+            Place::Index(table, key) => self.set_index(&table, key, value)?,
+        }
+        Ok(())
+    }
+
+    fn closure(&self, f: &mut Frame, proto: &Rc<FuncProto>) -> Value {
+        let upvals = proto
+            .upvals
+            .iter()
+            .map(|upval| match *upval {
+                Upval::Local(slot) => f.cell(slot),
+                Upval::Outer(i) => f.upvals[i].clone(),
+            })
+            .collect();
+        Value::Function(Rc::new(Closure {
+            proto: proto.clone(),
+            upvals,
+        }))
+    }
+
+    fn eval(&mut self, f: &mut Frame, expr: &Expr) -> Result<Value, LuaError> {
+        Ok(match expr {
+            Expr::Nil => Value::Nil,
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Num(n) => Value::Num(*n),
+            Expr::Str(s) => Value::Str(s.clone()),
+            Expr::Vararg => f.varargs.first().cloned().unwrap_or_default(),
+            Expr::Local(slot) => f.get(*slot),
+            Expr::Upval(i) => f.upvals[*i].borrow().clone(),
+            Expr::Global(i) => self.globals.values[*i].clone(),
+            Expr::Index(obj, key) => {
+                let table = self.eval(f, obj)?;
+                if !matches!(table, Value::Table(_)) {
+                    return Err(self.error(format!(
+                        "attempt to index {}",
+                        self.describe(f, obj, &table)
+                    )));
+                }
+                let key = self.eval(f, key)?;
+                self.index(&table, &key)?
+            }
+            Expr::Call(..) | Expr::Method(..) => first(self.eval_call(f, expr)?),
+            Expr::Function(proto) => self.closure(f, proto),
+            Expr::And(a, b) => {
+                let a = self.eval(f, a)?;
+                if a.truthy() {
+                    self.eval(f, b)?
+                } else {
+                    a
+                }
+            }
+            Expr::Or(a, b) => {
+                let a = self.eval(f, a)?;
+                if a.truthy() {
+                    a
+                } else {
+                    self.eval(f, b)?
+                }
+            }
+            Expr::Not(a) => Value::Bool(!self.eval(f, a)?.truthy()),
+            Expr::Unary(op, a) => {
+                let value = self.eval(f, a)?;
+                self.unary(*op, &value).map_err(|mut e| {
+                    if let Some(name) = self.name(f, a) {
+                        e.message = format!("{} ({})", e.message, name);
+                    }
+                    e
+                })?
+            }
+            Expr::Binary(op, a, b) => {
+                let x = self.eval(f, a)?;
+                let y = self.eval(f, b)?;
+                match self.binary(*op, &x, &y) {
+                    Ok(value) => value,
+                    Err(e) if blame(*op, &x) => return Err(self.name_operand(f, e, *op, a, &x)),
+                    Err(e) => return Err(self.name_operand(f, e, *op, b, &y)),
+                }
+            }
+            Expr::Table(fields) => self.table(f, fields)?,
+            Expr::Paren(inner) => self.eval(f, inner)?,
+        })
+    }
+
+    /// every value of a call or `...`, or the one value of anything else
+    fn eval_multi(&mut self, f: &mut Frame, expr: &Expr) -> Result<Vec<Value>, LuaError> {
+        match expr {
+            Expr::Call(..) | Expr::Method(..) => self.eval_call(f, expr),
+            Expr::Vararg => Ok(f.varargs.clone()),
+            _ => Ok(vec![self.eval(f, expr)?]),
+        }
+    }
+
+    /// the values of a list of expressions, where the last one can give any number of them
+    fn eval_list(&mut self, f: &mut Frame, exprs: &[Expr]) -> Result<Vec<Value>, LuaError> {
+        let mut values = Vec::with_capacity(exprs.len());
+        if let Some((last, rest)) = exprs.split_last() {
+            for expr in rest {
+                values.push(self.eval(f, expr)?);
+            }
+            values.extend(self.eval_multi(f, last)?);
+        }
+        Ok(values)
+    }
+
+    fn eval_call(&mut self, f: &mut Frame, expr: &Expr) -> Result<Vec<Value>, LuaError> {
+        let (func, args, callee) = match expr {
+            Expr::Call(callee, args) => {
+                let func = self.eval(f, callee)?;
+                (func, self.eval_list(f, args)?, &**callee)
+            }
+            Expr::Method(obj, name, args) => {
+                let table = self.eval(f, obj)?;
+                if !matches!(table, Value::Table(_)) {
+                    return Err(self.error(format!(
+                        "attempt to index {}",
+                        self.describe(f, obj, &table)
+                    )));
+                }
+                let func = self.index(&table, &Value::Str(name.clone()))?;
+                let mut all = vec![table];
+                all.extend(self.eval_list(f, args)?);
+                (func, all, expr)
+            }
+            _ => unreachable!("only calls are evaluated as calls"),
+        };
+        if matches!(
+            func,
+            Value::Nil | Value::Bool(_) | Value::Num(_) | Value::Str(_)
+        ) {
+            return Err(self.error(format!(
+                "attempt to call {}",
+                self.describe(f, callee, &func)
+            )));
+        }
+        self.call(&func, args)
+    }
+
+    fn table(&mut self, f: &mut Frame, fields: &[Field]) -> Result<Value, LuaError> {
+        let mut table = Table::default();
+        let mut n = 0;
+        for (i, field) in fields.iter().enumerate() {
+            match field {
+                // a call or `...` at the end fills in all its values
+                Field::Positional(expr) if i == fields.len() - 1 => {
+                    for value in self.eval_multi(f, expr)? {
+                        n += 1;
+                        table.set(Key::int(n), value);
+                    }
+                }
+                Field::Positional(expr) => {
+                    n += 1;
+                    let value = self.eval(f, expr)?;
+                    table.set(Key::int(n), value);
+                }
+                Field::Keyed(key, value) => {
+                    let key = self.eval(f, key)?;
+                    let value = self.eval(f, value)?;
+                    let Some(key) = Key::new(key) else {
+                        return Err(self.error("table index is nil"));
+                    };
+                    table.set(key, value);
+                }
+            }
+        }
+        Ok(Value::table(table))
+    }
+
+    /// how an error names the value of `expr`, like `global 'x'`
+    fn name(&self, f: &Frame, expr: &Expr) -> Option<String> {
+        let lossy = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+        Some(match expr {
+            Expr::Global(i) => format!("global '{}'", self.globals.name(*i)),
+            Expr::Local(slot) => format!("local '{}'", f.proto.names[*slot]),
+            Expr::Upval(i) => format!("upvalue '{}'", f.proto.upval_names[*i]),
+            Expr::Index(_, key) => match &**key {
+                Expr::Str(s) => format!("field '{}'", lossy(s)),
+                _ => return None,
+            },
+            Expr::Method(_, name, _) => format!("method '{}'", lossy(name)),
+            _ => return None,
+        })
+    }
+    fn describe(&self, f: &Frame, expr: &Expr, value: &Value) -> String {
+        match self.name(f, expr) {
+            Some(name) => format!("a {} value ({})", value.type_name(), name),
+            None => format!("a {} value", value.type_name()),
+        }
+    }
+    /// adds the name of the operand an operator choked on to its error
+    fn name_operand(
+        &self,
+        f: &Frame,
+        mut error: LuaError,
+        op: BinOp,
+        expr: &Expr,
+        value: &Value,
+    ) -> LuaError {
+        if blame(op, value) {
+            if let Some(name) = self.name(f, expr) {
+                error.message = format!("{} ({})", error.message, name);
+            }
+        }
+        error
+    }
+}
+
+/// whether `value` is one `op` can't work with on its own
+fn blame(op: BinOp, value: &Value) -> bool {
+    match op {
+        BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => false,
+        BinOp::Concat => !matches!(value, Value::Str(_) | Value::Num(_)),
+        _ => value.to_num().is_none(),
+    }
+}
+
+fn verb(op: BinOp) -> &'static str {
+    match op {
+        BinOp::BAnd
+        | BinOp::BOr
+        | BinOp::BXor
+        | BinOp::Shl
+        | BinOp::Shr
+        | BinOp::LShr
+        | BinOp::RotL
+        | BinOp::RotR => "perform bitwise operation on",
+        _ => "perform arithmetic on",
+    }
+}
+
+/// pico-8's addresses are 16 bits, anything past what `Memory` has reads as 0
+pub fn address(n: Fix16) -> usize {
+    (n.to_int() & 0xffff) as usize
+}
+
+/// The arithmetic and bitwise operators on numbers, the way pico-8 does them
+pub fn arith(op: BinOp, x: Fix16, y: Fix16) -> Fix16 {
+    let (a, b) = (x.to_bits(), y.to_bits());
+    let n = y.to_int();
+    let bits = match op {
+        BinOp::Add => return x + y,
+        BinOp::Sub => return x - y,
+        BinOp::Mul => return x * y,
+        BinOp::Div => return x / y,
+        BinOp::IDiv => return (x / y).floor(),
+        BinOp::Mod => return x % y,
+        BinOp::Pow => return from_f64(libm::pow(to_f64(x), to_f64(y))),
+        BinOp::BAnd => a & b,
+        BinOp::BOr => a | b,
+        BinOp::BXor => a ^ b,
+        BinOp::Shl => shift_left(a, n),
+        BinOp::Shr => shift_left(a, n.saturating_neg()),
+        BinOp::LShr => match n {
+            32.. => 0,
+            0.. => ((a as u32) >> n) as i32,
+            _ => shift_left(a, n.saturating_neg()),
+        },
+        BinOp::RotL => (a as u32).rotate_left(n as u32 & 31) as i32,
+        BinOp::RotR => (a as u32).rotate_right(n as u32 & 31) as i32,
+        _ => unreachable!("not an arithmetic operator"),
+    };
+    Fix16::from_bits(bits)
+}
+
+/// `<<` by `n`, or `>>` (keeping the sign) by `-n` when it's negative
+fn shift_left(bits: i32, n: i32) -> i32 {
+    match n {
+        32.. => 0,
+        0.. => bits << n,
+        -31.. => bits >> -n,
+        _ => bits >> 31,
+    }
+}
+
+/// pico-8's `atan2`: in turns, and with y pointing down like `sin`
+pub fn atan2(dx: Fix16, dy: Fix16) -> Fix16 {
+    if dx == Fix16::ZERO && dy == Fix16::ZERO {
+        return Fix16::from_bits(0x4000);
+    }
+    let turns = -libm::atan2(to_f64(dy), to_f64(dx)) / (2.0 * PI);
+    from_f64(turns - libm::floor(turns)) % Fix16::ONE
+}
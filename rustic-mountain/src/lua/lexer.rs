@@ -0,0 +1,265 @@
+//! Splits pico-8 Lua into tokens. On top of Lua 5.2 there are pico-8's operators (`!=`, `\`,
+//! `^^`, the shifts, the compound assignments and the `@` `%` `$` peeks), `//` comments, and
+//! P8SCII escapes in strings
+use alloc::{format, rc::Rc, string::String, vec::Vec};
+
+use super::{value::parse_num, LuaError};
+use crate::{fixed::Fix16, p8scii};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Name(Rc<str>),
+    Keyword(&'static str),
+    Op(&'static str),
+    Num(Fix16),
+    Str(Rc<[u8]>),
+    Eof,
+}
+
+const KEYWORDS: [&str; 22] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if", "in",
+    "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// longest first, so `>>>=` isn't read as `>>` and `>=`
+const OPS: [&str; 57] = [
+    ">>>=", "<<>=", ">><=", "...", "..=", "^^=", "<<=", ">>=", ">>>", "<<>", ">><", "==", "~=",
+    "!=", "<=", ">=", "<<", ">>", "..", "::", "+=", "-=", "*=", "/=", "\\=", "%=", "^=", "|=",
+    "&=", "^^", "+", "-", "*", "/", "\\", "%", "^", "#", "&", "~", "|", "<", ">", "=", "(", ")",
+    "{", "}", "[", "]", ";", ":", ",", ".", "?", "@", "$",
+];
+
+/// Every token of `code` with the line it starts on, ending with `Token::Eof`
+pub fn tokenize(code: &str) -> Result<Vec<(Token, u32)>, LuaError> {
+    let mut lexer = Lexer {
+        chars: code.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut tokens = Vec::new();
+    loop {
+        lexer.skip_space()?;
+        let line = lexer.line;
+        let Some(c) = lexer.peek(0) else {
+            tokens.push((Token::Eof, line));
+            return Ok(tokens);
+        };
+        let token = if c.is_ascii_alphabetic() || c == '_' {
+            let start = lexer.pos;
+            while lexer
+                .peek(0)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            {
+                lexer.pos += 1;
+            }
+            let name: String = lexer.chars[start..lexer.pos].iter().collect();
+            match KEYWORDS.iter().find(|k| **k == name) {
+                Some(keyword) => Token::Keyword(keyword),
+                None => Token::Name(name.into()),
+            }
+        } else if c.is_ascii_digit()
+            || (c == '.' && lexer.peek(1).is_some_and(|c| c.is_ascii_digit()))
+        {
+            lexer.number()?
+        } else if c == '"' || c == '\'' {
+            lexer.pos += 1;
+            lexer.string(c)?
+        } else if c == '[' && matches!(lexer.peek(1), Some('[' | '=')) {
+            match lexer.long_bracket()? {
+                Some(text) => Token::Str(p8scii::encode(&text).into()),
+                None => lexer.op()?,
+            }
+        } else {
+            lexer.op()?
+        };
+        tokens.push((token, line));
+    }
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: u32,
+}
+
+impl Lexer {
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.chars.get(self.pos + ahead).copied()
+    }
+    fn error(&self, message: String) -> LuaError {
+        LuaError {
+            line: self.line,
+            message,
+        }
+    }
+    fn skip_space(&mut self) -> Result<(), LuaError> {
+        while let Some(c) = self.peek(0) {
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    self.pos += 1;
+                }
+                c if c.is_whitespace() => self.pos += 1,
+                '-' if self.peek(1) == Some('-') => {
+                    self.pos += 2;
+                    if self.peek(0) != Some('[') || self.long_bracket()?.is_none() {
+                        self.skip_line();
+                    }
+                }
+                '/' if self.peek(1) == Some('/') => self.skip_line(),
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+    fn skip_line(&mut self) {
+        while self.peek(0).is_some_and(|c| c != '\n') {
+            self.pos += 1;
+        }
+    }
+    /// `[[...]]`, `[==[...]==]` and so on, starting at the first `[`. `None` if it's only a `[`
+    fn long_bracket(&mut self) -> Result<Option<String>, LuaError> {
+        let mut level = 0;
+        while self.peek(1 + level) == Some('=') {
+            level += 1;
+        }
+        if self.peek(1 + level) != Some('[') {
+            return Ok(None);
+        }
+        self.pos += 2 + level;
+        // a newline straight after the opening bracket isn't part of the string
+        if self.peek(0) == Some('\n') {
+            self.pos += 1;
+            self.line += 1;
+        }
+        let start = self.pos;
+        loop {
+            match self.peek(0) {
+                None => return Err(self.error("unfinished long string or comment".into())),
+                Some(']')
+                    if (1..=level).all(|i| self.peek(i) == Some('='))
+                        && self.peek(1 + level) == Some(']') =>
+                {
+                    let text = self.chars[start..self.pos].iter().collect();
+                    self.pos += 2 + level;
+                    return Ok(Some(text));
+                }
+                Some(c) => {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+    fn number(&mut self) -> Result<Token, LuaError> {
+        let start = self.pos;
+        let radix_prefix =
+            self.peek(0) == Some('0') && matches!(self.peek(1), Some('x' | 'X' | 'b' | 'B'));
+        if radix_prefix {
+            self.pos += 2;
+        }
+        let mut dot = false;
+        while let Some(c) = self.peek(0) {
+            let digit = if radix_prefix {
+                c.is_ascii_hexdigit()
+            } else {
+                c.is_ascii_digit()
+            };
+            // `1..x` is a concatenation, not a number with two dots
+            if c == '.' && !dot && self.peek(1) != Some('.') {
+                dot = true;
+            } else if !digit {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        parse_num(text.as_bytes())
+            .map(Token::Num)
+            .ok_or_else(|| self.error(format!("malformed number near {}", text)))
+    }
+    /// a quoted string, after the opening quote
+    fn string(&mut self, quote: char) -> Result<Token, LuaError> {
+        let mut bytes = Vec::new();
+        loop {
+            let Some(c) = self.peek(0) else {
+                return Err(self.error("unfinished string".into()));
+            };
+            self.pos += 1;
+            match c {
+                '\n' => return Err(self.error("unfinished string".into())),
+                c if c == quote => return Ok(Token::Str(bytes.into())),
+                '\\' => self.escape(&mut bytes)?,
+                c => bytes.extend(p8scii::to_p8scii(c)),
+            }
+        }
+    }
+    /// the character after a `\` in a string
+    fn escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), LuaError> {
+        let Some(c) = self.peek(0) else {
+            return Err(self.error("unfinished string".into()));
+        };
+        self.pos += 1;
+        let byte = match c {
+            'n' => b'\n',
+            't' => b'\t',
+            'r' => b'\r',
+            'a' => p8scii::AUDIO,
+            'b' => p8scii::BACKSPACE,
+            'f' => p8scii::FOREGROUND,
+            'v' => p8scii::DECORATE,
+            '*' => p8scii::REPEAT,
+            '#' => p8scii::BACKGROUND,
+            '-' => p8scii::OFFSET_X,
+            '|' => p8scii::OFFSET_Y,
+            '+' => p8scii::OFFSET,
+            '^' => p8scii::SPECIAL,
+            '\n' => {
+                self.line += 1;
+                b'\n'
+            }
+            'x' => {
+                let hex: String = (0..2).filter_map(|i| self.peek(i)).collect();
+                self.pos += 2;
+                u8::from_str_radix(&hex, 16)
+                    .map_err(|_| self.error(format!("bad escape \\x{}", hex)))?
+            }
+            'z' => {
+                while let Some(c) = self.peek(0).filter(|c| c.is_whitespace()) {
+                    if c == '\n' {
+                        self.line += 1;
+                    }
+                    self.pos += 1;
+                }
+                return Ok(());
+            }
+            c if c.is_ascii_digit() => {
+                let mut n = c.to_digit(10).unwrap();
+                for _ in 0..2 {
+                    match self.peek(0).and_then(|c| c.to_digit(10)) {
+                        Some(d) => {
+                            n = n * 10 + d;
+                            self.pos += 1;
+                        }
+                        None => break,
+                    }
+                }
+                u8::try_from(n).map_err(|_| self.error(format!("bad escape \\{}", n)))?
+            }
+            c => p8scii::to_p8scii(c).unwrap_or(b'?'),
+        };
+        bytes.push(byte);
+        Ok(())
+    }
+    fn op(&mut self) -> Result<Token, LuaError> {
+        let rest = &self.chars[self.pos..];
+        let op = OPS
+            .iter()
+            .find(|op| op.chars().enumerate().all(|(i, c)| rest.get(i) == Some(&c)))
+            .ok_or_else(|| self.error(format!("unexpected symbol {}", rest[0])))?;
+        self.pos += op.len();
+        Ok(Token::Op(op))
+    }
+}
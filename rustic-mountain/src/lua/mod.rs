@@ -0,0 +1,220 @@
+//! An interpreter for pico-8's dialect of Lua, so carts other than Celeste Classic, like the
+//! mods people have made of it, run without being ported to rust first. The native port in the
+//! rest of this crate is still how the original game runs: it's much faster, and replays,
+//! ghosts, snapshots and the TAS tools only work with it.
+//!
+//! `LuaCart` runs a cart's code against a `Memory` like the one `Celeste` draws with, so a
+//! frontend shows both the same way. Most of pico-8's API is there (see `api`). Sound is
+//! silent, and coroutines and `goto` aren't supported. Values are reference counted rather
+//! than garbage collected, so tables that refer back to themselves are never freed.
+//!
+//! Only built with the `lua` feature.
+mod api;
+mod ast;
+mod interp;
+mod lexer;
+mod parser;
+pub mod value;
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display};
+
+use crate::memory::{CartData, Memory};
+use interp::{Globals, META_NAMES};
+use value::Key;
+pub use value::Value;
+
+/// A syntax or runtime error, or a cart calling `stop` or a failed `assert`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LuaError {
+    /// counted from the first line of the code, which is the line after `__lua__` in a `.p8`
+    pub line: u32,
+    pub message: String,
+}
+
+impl Display for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A running cart. Set `mem.buttons`, call `next_tick` and `draw`, and show `mem.graphics`
+pub struct LuaCart {
+    pub mem: Memory,
+    /// the data the cart was loaded with, for `reload`
+    rom: CartData,
+    globals: Globals,
+    /// the keys of the metamethods, see `interp::Meta`
+    meta_keys: Vec<Key>,
+    /// how deep the Lua calls go right now
+    depth: usize,
+    /// the line being run, for errors
+    line: u32,
+    /// how many ticks the cart has run, for `time`
+    ticks: u32,
+    /// how many ticks each button has been held, for `btnp`
+    held: [u32; 6],
+    /// where the last `line` ended, for `line` with only an end point
+    line_end: Option<(i32, i32)>,
+}
+
+impl LuaCart {
+    /// Loads a cart with the code `code`, its `__lua__` section (see `cart::code`), on top of
+    /// `cart`. Runs the top level of the code and then `_init`, so errors in either come back
+    /// from here
+    pub fn new(cart: &CartData, code: &str) -> Result<LuaCart, LuaError> {
+        let mut lua = LuaCart {
+            mem: Memory::from_cart(cart),
+            rom: cart.clone(),
+            globals: Globals::default(),
+            meta_keys: META_NAMES.iter().map(|name| Key::str(name)).collect(),
+            depth: 0,
+            line: 0,
+            ticks: 0,
+            held: [0; 6],
+            line_end: None,
+        };
+        api::register(&mut lua)?;
+        let main = parser::parse(code, &mut lua.globals)?;
+        lua.run(main)?;
+        lua.call_global("_init")?;
+        Ok(lua)
+    }
+
+    /// Runs one tick of `_update60`, or `_update` for a 30fps cart, with `mem.buttons` held
+    pub fn next_tick(&mut self) -> Result<(), LuaError> {
+        for (held, &down) in self.held.iter_mut().zip(&self.mem.buttons) {
+            *held = if down { *held + 1 } else { 0 };
+        }
+        self.ticks += 1;
+        if self.fps() == 60 {
+            self.call_global("_update60")?;
+        } else {
+            self.call_global("_update")?;
+        }
+        Ok(())
+    }
+
+    /// Runs `_draw`
+    pub fn draw(&mut self) -> Result<(), LuaError> {
+        self.call_global("_draw").map(|_| ())
+    }
+
+    /// 60 for carts with an `_update60`, which need ticking twice as often, otherwise 30
+    pub fn fps(&self) -> u32 {
+        if self.global("_update60").is_nil() {
+            30
+        } else {
+            60
+        }
+    }
+
+    pub fn global(&self, name: &str) -> Value {
+        self.globals.get(name)
+    }
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.set(name, value);
+    }
+
+    /// Calls the global function `name` with no arguments, if the cart has one
+    pub fn call_global(&mut self, name: &str) -> Result<Vec<Value>, LuaError> {
+        let func = self.global(name);
+        if func.is_nil() {
+            return Ok(Vec::new());
+        }
+        self.call(&func, Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::{fix, Fix16};
+    use alloc::{rc::Rc, vec};
+
+    /// loads `code` on a blank cart, with a font where every glyph is a solid block
+    fn load(code: &str) -> LuaCart {
+        let cart = CartData {
+            map: Rc::new(vec![0; 128 * 32]),
+            map_width: 128,
+            sprites: Rc::new(vec![0; 128 * 128]),
+            flags: Rc::new(vec![0; 256]),
+            fontatlas: Rc::new(vec![true; 128 * 128]),
+        };
+        match LuaCart::new(&cart, code) {
+            Ok(lua) => lua,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    fn num(lua: &LuaCart, name: &str) -> Fix16 {
+        lua.global(name).to_num().unwrap()
+    }
+
+    #[test]
+    fn compound_assignment() {
+        let lua = load("a = 1 a += 2 a *= 4 a -= 1 a /= 2 a \\= 2 b = 'x' b ..= 'y'");
+        assert_eq!(num(&lua, "a"), fix(2.0));
+        assert_eq!(lua.global("b").as_rust_str().as_deref(), Some("xy"));
+    }
+
+    #[test]
+    fn not_equal() {
+        let lua = load("a = 1 != 2 b = 1 != 1 c = 'x' ~= 'x'");
+        assert!(lua.global("a").truthy());
+        assert!(!lua.global("b").truthy());
+        assert!(!lua.global("c").truthy());
+    }
+
+    #[test]
+    fn integer_division() {
+        let lua = load("a = 7 \\ 2 b = -7 \\ 2 c = 7.5 \\ 0.5");
+        assert_eq!(num(&lua, "a"), fix(3.0));
+        assert_eq!(num(&lua, "b"), fix(-4.0));
+        assert_eq!(num(&lua, "c"), fix(15.0));
+    }
+
+    #[test]
+    fn numbers_are_fixed_point() {
+        let lua = load(
+            "a = 32767 + 1 b = -32768 - 1 c = 32767 * 2 d = 0x7fff.ffff + 0x.0001 \
+             e = abs(-32768) f = 1 / 3 g = 1 / 0 h = -1 / 0",
+        );
+        assert_eq!(num(&lua, "a"), fix(-32768.0));
+        assert_eq!(num(&lua, "b"), fix(32767.0));
+        assert_eq!(num(&lua, "c"), fix(-2.0));
+        assert_eq!(num(&lua, "d"), Fix16::MIN);
+        assert_eq!(num(&lua, "e"), Fix16::MAX);
+        assert_eq!(num(&lua, "f"), Fix16::from_bits(0x5555));
+        assert_eq!(num(&lua, "g"), Fix16::MAX);
+        assert_eq!(num(&lua, "h"), -Fix16::MAX);
+    }
+
+    #[test]
+    fn print_shorthand() {
+        let mut lua = load("function _draw()\ncls()\n?\"a\",10,20,7\nend");
+        lua.draw().unwrap();
+        assert_eq!(lua.mem.pget(10, 20), 7);
+        assert_eq!(lua.mem.pget(9, 20), 0);
+        assert_eq!((lua.mem.cursor.x, lua.mem.cursor.y), (10, 26));
+    }
+
+    #[test]
+    fn runs_init_update_and_draw() {
+        let mut lua = load(
+            "function _init() x = 10 ticks = 0 end
+             function _update() ticks += 1 if (btn(1)) x += 1 end
+             function _draw() cls(1) pset(x, 5, 8) end",
+        );
+        assert_eq!(lua.fps(), 30);
+        assert_eq!(num(&lua, "x"), fix(10.0));
+        lua.mem.buttons[1] = true;
+        for _ in 0..3 {
+            lua.next_tick().unwrap();
+            lua.draw().unwrap();
+        }
+        assert_eq!(num(&lua, "ticks"), fix(3.0));
+        assert_eq!(lua.mem.pget(13, 5), 8);
+        assert_eq!(lua.mem.pget(12, 5), 1);
+    }
+}
@@ -0,0 +1,727 @@
+//! Turns tokens into the `ast`, resolving every name to a local, an upvalue or a global as it
+//! goes. Besides Lua 5.2 this handles pico-8's one line `if (cond) stmt` and `while (cond) stmt`,
+//! `?` for `print`, and the compound assignments
+use alloc::{boxed::Box, format, rc::Rc, vec, vec::Vec};
+
+use super::{
+    ast::{BinOp, Block, Expr, Field, FuncProto, Stmt, UnOp, Upval},
+    interp::Globals,
+    lexer::{tokenize, Token},
+    LuaError,
+};
+
+/// Parses a whole cart's code into the function that runs its top level
+pub fn parse(code: &str, globals: &mut Globals) -> Result<Rc<FuncProto>, LuaError> {
+    let mut parser = Parser {
+        tokens: tokenize(code)?,
+        pos: 0,
+        funcs: vec![FuncState::new()],
+        globals,
+        line_limit: None,
+    };
+    parser.funcs[0].vararg = true;
+    let body = parser.block()?;
+    if parser.peek() != &Token::Eof {
+        return Err(parser.unexpected("'<eof>' expected"));
+    }
+    let main = parser.funcs.pop().unwrap();
+    Ok(Rc::new(main.finish(0, body)))
+}
+
+/// the binding powers of unary operators, and of the binary ones on their left and right
+const UNARY_PRIORITY: u8 = 12;
+
+enum Infix {
+    Op(BinOp),
+    And,
+    Or,
+}
+
+/// a function that's being parsed
+struct FuncState {
+    /// the slots declared in each block that's open, innermost last
+    scopes: Vec<Vec<usize>>,
+    names: Vec<Rc<str>>,
+    captured: Vec<bool>,
+    upvals: Vec<Upval>,
+    upval_names: Vec<Rc<str>>,
+    vararg: bool,
+}
+
+impl FuncState {
+    fn new() -> FuncState {
+        FuncState {
+            scopes: vec![Vec::new()],
+            names: Vec::new(),
+            captured: Vec::new(),
+            upvals: Vec::new(),
+            upval_names: Vec::new(),
+            vararg: false,
+        }
+    }
+    fn finish(self, params: usize, body: Block) -> FuncProto {
+        FuncProto {
+            params,
+            vararg: self.vararg,
+            captured: self.captured,
+            names: self.names,
+            upvals: self.upvals,
+            upval_names: self.upval_names,
+            body,
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, u32)>,
+    pos: usize,
+    /// innermost last
+    funcs: Vec<FuncState>,
+    globals: &'a mut Globals,
+    /// inside a one line `if` or `while`, the line it's on. Blocks end at the end of it
+    line_limit: Option<u32>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+    fn peek_at(&self, ahead: usize) -> &Token {
+        let last = self.tokens.len() - 1;
+        &self.tokens[(self.pos + ahead).min(last)].0
+    }
+    fn line(&self) -> u32 {
+        self.tokens[self.pos].1
+    }
+    /// the line of the token before the next one
+    fn last_line(&self) -> u32 {
+        self.tokens[self.pos.saturating_sub(1)].1
+    }
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+    fn check(&self, op: &str) -> bool {
+        matches!(self.peek(), Token::Op(o) | Token::Keyword(o) if *o == op)
+    }
+    fn accept(&mut self, op: &str) -> bool {
+        let found = self.check(op);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    fn expect(&mut self, op: &str) -> Result<(), LuaError> {
+        if self.accept(op) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}' expected", op)))
+        }
+    }
+    /// the `end` of a block that started with `what` on `line`
+    fn expect_end(&mut self, what: &str, line: u32) -> Result<(), LuaError> {
+        if self.accept("end") {
+            Ok(())
+        } else if line == self.line() {
+            Err(self.unexpected("'end' expected"))
+        } else {
+            Err(self.unexpected(&format!(
+                "'end' expected (to close '{}' at line {})",
+                what, line
+            )))
+        }
+    }
+    fn name(&mut self) -> Result<Rc<str>, LuaError> {
+        match self.peek() {
+            Token::Name(name) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("name expected")),
+        }
+    }
+    fn unexpected(&self, message: &str) -> LuaError {
+        let near = match self.peek() {
+            Token::Name(name) => format!("'{}'", name),
+            Token::Keyword(k) | Token::Op(k) => format!("'{}'", k),
+            Token::Num(_) => "a number".into(),
+            Token::Str(_) => "a string".into(),
+            Token::Eof => "the end".into(),
+        };
+        LuaError {
+            line: self.line(),
+            message: format!("{} near {}", message, near),
+        }
+    }
+    fn error(&self, message: &str) -> LuaError {
+        LuaError {
+            line: self.line(),
+            message: message.into(),
+        }
+    }
+
+    fn func(&mut self) -> &mut FuncState {
+        self.funcs.last_mut().unwrap()
+    }
+    fn declare(&mut self, name: Rc<str>) -> usize {
+        let func = self.func();
+        func.names.push(name);
+        func.captured.push(false);
+        let slot = func.names.len() - 1;
+        func.scopes.last_mut().unwrap().push(slot);
+        slot
+    }
+    fn resolve(&mut self, name: &str) -> Expr {
+        let level = self.funcs.len() - 1;
+        if let Some(slot) = self.find_local(level, name) {
+            Expr::Local(slot)
+        } else if let Some(i) = self.find_upval(level, name) {
+            Expr::Upval(i)
+        } else {
+            Expr::Global(self.globals.intern(name))
+        }
+    }
+    fn find_local(&self, level: usize, name: &str) -> Option<usize> {
+        let func = &self.funcs[level];
+        func.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|&&slot| &*func.names[slot] == name)
+            .copied()
+    }
+    /// the capture of `name` in the function at `level`, adding it and the captures it goes
+    /// through in the functions between if they're not there yet
+    fn find_upval(&mut self, level: usize, name: &str) -> Option<usize> {
+        let func = &self.funcs[level];
+        if let Some(i) = func.upval_names.iter().position(|n| &**n == name) {
+            return Some(i);
+        }
+        if level == 0 {
+            return None;
+        }
+        let upval = if let Some(slot) = self.find_local(level - 1, name) {
+            self.funcs[level - 1].captured[slot] = true;
+            Upval::Local(slot)
+        } else {
+            Upval::Outer(self.find_upval(level - 1, name)?)
+        };
+        let func = &mut self.funcs[level];
+        func.upvals.push(upval);
+        func.upval_names.push(name.into());
+        Some(func.upvals.len() - 1)
+    }
+
+    fn block_ends(&self) -> bool {
+        match self.peek() {
+            Token::Eof => true,
+            Token::Keyword("end" | "else" | "elseif" | "until") => true,
+            _ => self.line_limit.is_some_and(|line| self.line() > line),
+        }
+    }
+    fn block(&mut self) -> Result<Block, LuaError> {
+        self.func().scopes.push(Vec::new());
+        let block = self.statements();
+        self.func().scopes.pop();
+        block
+    }
+    /// a block's statements, in a scope the caller opens
+    fn statements(&mut self) -> Result<Block, LuaError> {
+        let mut block = Vec::new();
+        while !self.block_ends() {
+            let line = self.line();
+            if self.accept("return") {
+                let values = if self.block_ends() || self.check(";") {
+                    Vec::new()
+                } else {
+                    self.expr_list()?
+                };
+                self.accept(";");
+                block.push((line, Stmt::Return(values)));
+                if !self.block_ends() {
+                    return Err(self.unexpected("'end' expected"));
+                }
+                break;
+            }
+            if let Some(stmt) = self.statement()? {
+                block.push((line, stmt));
+            }
+        }
+        Ok(block)
+    }
+    /// the rest of the line as a block, for pico-8's one line `if` and `while`
+    fn line_block(&mut self, line: u32) -> Result<Block, LuaError> {
+        let outer = self.line_limit.replace(line);
+        let block = self.block();
+        self.line_limit = outer;
+        block
+    }
+
+    fn statement(&mut self) -> Result<Option<Stmt>, LuaError> {
+        let line = self.line();
+        let stmt = match self.peek() {
+            Token::Op(";") => {
+                self.pos += 1;
+                return Ok(None);
+            }
+            Token::Op("?") => {
+                self.pos += 1;
+                let print = self.resolve("print");
+                Stmt::Call(Expr::Call(Box::new(print), self.expr_list()?))
+            }
+            Token::Keyword("if") => self.if_stmt()?,
+            Token::Keyword("while") => {
+                self.pos += 1;
+                let short = self.check("(");
+                let cond = self.expr()?;
+                if short && !self.check("do") {
+                    let line = self.last_line();
+                    Stmt::While(cond, self.line_block(line)?)
+                } else {
+                    self.expect("do")?;
+                    let body = self.block()?;
+                    self.expect_end("while", line)?;
+                    Stmt::While(cond, body)
+                }
+            }
+            Token::Keyword("do") => {
+                self.pos += 1;
+                let body = self.block()?;
+                self.expect_end("do", line)?;
+                Stmt::Do(body)
+            }
+            Token::Keyword("for") => self.for_stmt()?,
+            Token::Keyword("repeat") => {
+                self.pos += 1;
+                // `until` can see the body's locals
+                self.func().scopes.push(Vec::new());
+                let body = self.statements()?;
+                self.expect("until")?;
+                let cond = self.expr()?;
+                self.func().scopes.pop();
+                Stmt::Repeat(body, cond)
+            }
+            Token::Keyword("function") => {
+                self.pos += 1;
+                let mut target = {
+                    let name = self.name()?;
+                    self.resolve(&name)
+                };
+                while self.accept(".") {
+                    target = index(target, self.name()?);
+                }
+                let method = self.accept(":");
+                if method {
+                    target = index(target, self.name()?);
+                }
+                let proto = self.function_body(method)?;
+                Stmt::Assign(vec![target], vec![Expr::Function(proto)])
+            }
+            Token::Keyword("local") => {
+                self.pos += 1;
+                if self.accept("function") {
+                    let name = self.name()?;
+                    let slot = self.declare(name);
+                    Stmt::LocalFunction(slot, self.function_body(false)?)
+                } else {
+                    let mut names = vec![self.name()?];
+                    while self.accept(",") {
+                        names.push(self.name()?);
+                    }
+                    let values = if self.accept("=") {
+                        self.expr_list()?
+                    } else {
+                        Vec::new()
+                    };
+                    // declared after the values, so `local x = x` reads the outer `x`
+                    let slots = names.into_iter().map(|name| self.declare(name)).collect();
+                    Stmt::Local(slots, values)
+                }
+            }
+            Token::Keyword("break") => {
+                self.pos += 1;
+                Stmt::Break
+            }
+            Token::Keyword("goto") | Token::Op("::") => {
+                return Err(self.error("goto isn't supported"));
+            }
+            _ => self.expr_stmt()?,
+        };
+        Ok(Some(stmt))
+    }
+
+    fn if_stmt(&mut self) -> Result<Stmt, LuaError> {
+        let line = self.line();
+        self.pos += 1;
+        let short = self.check("(");
+        let cond = self.expr()?;
+        if short && !self.check("then") {
+            // pico-8's `if (cond) stmt [else stmt]`, which takes up the rest of the line
+            let line = self.last_line();
+            let body = self.line_block(line)?;
+            let otherwise = if self.check("else") && self.line() == line {
+                self.pos += 1;
+                Some(self.line_block(line)?)
+            } else {
+                None
+            };
+            return Ok(Stmt::If(vec![(cond, body)], otherwise));
+        }
+        self.expect("then")?;
+        let mut clauses = vec![(cond, self.block()?)];
+        let mut otherwise = None;
+        loop {
+            if self.accept("elseif") {
+                let cond = self.expr()?;
+                self.expect("then")?;
+                clauses.push((cond, self.block()?));
+            } else {
+                if self.accept("else") {
+                    otherwise = Some(self.block()?);
+                }
+                self.expect_end("if", line)?;
+                return Ok(Stmt::If(clauses, otherwise));
+            }
+        }
+    }
+
+    fn for_stmt(&mut self) -> Result<Stmt, LuaError> {
+        let line = self.line();
+        self.pos += 1;
+        let name = self.name()?;
+        let stmt = if self.accept("=") {
+            let start = self.expr()?;
+            self.expect(",")?;
+            let limit = self.expr()?;
+            let step = if self.accept(",") {
+                Some(self.expr()?)
+            } else {
+                None
+            };
+            self.expect("do")?;
+            self.func().scopes.push(Vec::new());
+            let var = self.declare(name);
+            let body = self.block()?;
+            self.func().scopes.pop();
+            Stmt::NumFor {
+                var,
+                start,
+                limit,
+                step,
+                body,
+            }
+        } else {
+            let mut names = vec![name];
+            while self.accept(",") {
+                names.push(self.name()?);
+            }
+            self.expect("in")?;
+            let exprs = self.expr_list()?;
+            self.expect("do")?;
+            self.func().scopes.push(Vec::new());
+            let vars = names.into_iter().map(|name| self.declare(name)).collect();
+            let body = self.block()?;
+            self.func().scopes.pop();
+            Stmt::GenericFor { vars, exprs, body }
+        };
+        self.expect_end("for", line)?;
+        Ok(stmt)
+    }
+
+    /// an assignment or a function call
+    fn expr_stmt(&mut self) -> Result<Stmt, LuaError> {
+        let target = self.suffixed_expr()?;
+        if self.check("=") || self.check(",") {
+            let mut targets = vec![target];
+            while self.accept(",") {
+                targets.push(self.suffixed_expr()?);
+            }
+            self.expect("=")?;
+            if !targets.iter().all(assignable) {
+                return Err(self.error("syntax error: can't assign to that"));
+            }
+            return Ok(Stmt::Assign(targets, self.expr_list()?));
+        }
+        let compound = match self.peek() {
+            Token::Op(op) => compound_op(op),
+            _ => None,
+        };
+        if let Some(op) = compound {
+            self.pos += 1;
+            if !assignable(&target) {
+                return Err(self.error("syntax error: can't assign to that"));
+            }
+            return Ok(Stmt::OpAssign(op, target, self.expr()?));
+        }
+        match target {
+            Expr::Call(..) | Expr::Method(..) => Ok(Stmt::Call(target)),
+            _ => Err(self.unexpected("syntax error")),
+        }
+    }
+
+    fn expr_list(&mut self) -> Result<Vec<Expr>, LuaError> {
+        let mut exprs = vec![self.expr()?];
+        while self.accept(",") {
+            exprs.push(self.expr()?);
+        }
+        Ok(exprs)
+    }
+    fn expr(&mut self) -> Result<Expr, LuaError> {
+        self.subexpr(0)
+    }
+    /// an expression whose operators all bind tighter than `limit`
+    fn subexpr(&mut self, limit: u8) -> Result<Expr, LuaError> {
+        let unary = match self.peek() {
+            Token::Keyword("not") => Some(None),
+            Token::Op("-") => Some(Some(UnOp::Neg)),
+            Token::Op("#") => Some(Some(UnOp::Len)),
+            Token::Op("~") => Some(Some(UnOp::BNot)),
+            Token::Op("@") => Some(Some(UnOp::Peek)),
+            Token::Op("%") => Some(Some(UnOp::Peek2)),
+            Token::Op("$") => Some(Some(UnOp::Peek4)),
+            _ => None,
+        };
+        let mut left = match unary {
+            Some(op) => {
+                self.pos += 1;
+                let operand = self.subexpr(UNARY_PRIORITY)?;
+                match (op, operand) {
+                    (None, operand) => Expr::Not(Box::new(operand)),
+                    (Some(UnOp::Neg), Expr::Num(n)) => Expr::Num(-n),
+                    (Some(op), operand) => Expr::Unary(op, Box::new(operand)),
+                }
+            }
+            None => self.simple_expr()?,
+        };
+        while let Some((op, left_priority, right_priority)) = self.infix_op() {
+            if left_priority <= limit {
+                break;
+            }
+            self.pos += 1;
+            let right = Box::new(self.subexpr(right_priority)?);
+            let l = Box::new(left);
+            left = match op {
+                Infix::Op(op) => Expr::Binary(op, l, right),
+                Infix::And => Expr::And(l, right),
+                Infix::Or => Expr::Or(l, right),
+            };
+        }
+        Ok(left)
+    }
+    /// the next token as a binary operator, with its priority on the left and the right
+    fn infix_op(&self) -> Option<(Infix, u8, u8)> {
+        let op = match self.peek() {
+            Token::Keyword("or") => return Some((Infix::Or, 1, 1)),
+            Token::Keyword("and") => return Some((Infix::And, 2, 2)),
+            Token::Op(op) => *op,
+            _ => return None,
+        };
+        let (op, left, right) = match op {
+            "<" => (BinOp::Lt, 3, 3),
+            ">" => (BinOp::Gt, 3, 3),
+            "<=" => (BinOp::Le, 3, 3),
+            ">=" => (BinOp::Ge, 3, 3),
+            "~=" | "!=" => (BinOp::Ne, 3, 3),
+            "==" => (BinOp::Eq, 3, 3),
+            "|" => (BinOp::BOr, 4, 4),
+            "^^" => (BinOp::BXor, 5, 5),
+            "&" => (BinOp::BAnd, 6, 6),
+            "<<" => (BinOp::Shl, 7, 7),
+            ">>" => (BinOp::Shr, 7, 7),
+            ">>>" => (BinOp::LShr, 7, 7),
+            "<<>" => (BinOp::RotL, 7, 7),
+            ">><" => (BinOp::RotR, 7, 7),
+            // right associative
+            ".." => (BinOp::Concat, 9, 8),
+            "+" => (BinOp::Add, 10, 10),
+            "-" => (BinOp::Sub, 10, 10),
+            "*" => (BinOp::Mul, 11, 11),
+            "/" => (BinOp::Div, 11, 11),
+            "\\" => (BinOp::IDiv, 11, 11),
+            "%" => (BinOp::Mod, 11, 11),
+            "^" => (BinOp::Pow, 14, 13),
+            _ => return None,
+        };
+        Some((Infix::Op(op), left, right))
+    }
+    fn simple_expr(&mut self) -> Result<Expr, LuaError> {
+        let expr = match self.peek() {
+            Token::Num(n) => Expr::Num(*n),
+            Token::Str(s) => Expr::Str(s.clone()),
+            Token::Keyword("nil") => Expr::Nil,
+            Token::Keyword("true") => Expr::Bool(true),
+            Token::Keyword("false") => Expr::Bool(false),
+            Token::Op("...") => {
+                if !self.func().vararg {
+                    return Err(self.error("cannot use '...' outside a vararg function"));
+                }
+                Expr::Vararg
+            }
+            Token::Op("{") => return self.table(),
+            Token::Keyword("function") => {
+                self.pos += 1;
+                return Ok(Expr::Function(self.function_body(false)?));
+            }
+            _ => return self.suffixed_expr(),
+        };
+        self.pos += 1;
+        Ok(expr)
+    }
+    /// a name or bracketed expression, followed by any number of indexes and calls
+    fn suffixed_expr(&mut self) -> Result<Expr, LuaError> {
+        let mut expr = match self.next() {
+            Token::Name(name) => self.resolve(&name),
+            Token::Op("(") => {
+                let inner = self.expr()?;
+                self.expect(")")?;
+                match inner {
+                    Expr::Call(..) | Expr::Method(..) | Expr::Vararg => {
+                        Expr::Paren(Box::new(inner))
+                    }
+                    _ => inner,
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.unexpected("unexpected symbol"));
+            }
+        };
+        loop {
+            expr = match self.peek() {
+                Token::Op(".") => {
+                    self.pos += 1;
+                    index(expr, self.name()?)
+                }
+                Token::Op("[") => {
+                    self.pos += 1;
+                    let key = self.expr()?;
+                    self.expect("]")?;
+                    Expr::Index(Box::new(expr), Box::new(key))
+                }
+                Token::Op(":") => {
+                    self.pos += 1;
+                    let name = self.name()?;
+                    let args = self.call_args()?;
+                    Expr::Method(Box::new(expr), name.as_bytes().into(), args)
+                }
+                Token::Op("(" | "{") | Token::Str(_) => {
+                    Expr::Call(Box::new(expr), self.call_args()?)
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+    fn call_args(&mut self) -> Result<Vec<Expr>, LuaError> {
+        match self.peek() {
+            Token::Str(s) => {
+                let arg = Expr::Str(s.clone());
+                self.pos += 1;
+                Ok(vec![arg])
+            }
+            Token::Op("{") => Ok(vec![self.table()?]),
+            Token::Op("(") => {
+                self.pos += 1;
+                if self.accept(")") {
+                    return Ok(Vec::new());
+                }
+                let args = self.expr_list()?;
+                self.expect(")")?;
+                Ok(args)
+            }
+            _ => Err(self.unexpected("function arguments expected")),
+        }
+    }
+    fn table(&mut self) -> Result<Expr, LuaError> {
+        self.expect("{")?;
+        let mut fields = Vec::new();
+        while !self.check("}") {
+            let field = if self.accept("[") {
+                let key = self.expr()?;
+                self.expect("]")?;
+                self.expect("=")?;
+                Field::Keyed(key, self.expr()?)
+            } else if let (Token::Name(name), Token::Op("=")) = (self.peek(), self.peek_at(1)) {
+                let key = Expr::Str(name.as_bytes().into());
+                self.pos += 2;
+                Field::Keyed(key, self.expr()?)
+            } else {
+                Field::Positional(self.expr()?)
+            };
+            fields.push(field);
+            if !self.accept(",") && !self.accept(";") {
+                break;
+            }
+        }
+        self.expect("}")?;
+        Ok(Expr::Table(fields))
+    }
+    /// the parameters and body of a function, after `function` and its name
+    fn function_body(&mut self, method: bool) -> Result<Rc<FuncProto>, LuaError> {
+        let line = self.line();
+        self.funcs.push(FuncState::new());
+        if method {
+            self.declare("self".into());
+        }
+        self.expect("(")?;
+        if !self.check(")") {
+            loop {
+                if self.accept("...") {
+                    self.func().vararg = true;
+                    break;
+                }
+                let name = self.name()?;
+                self.declare(name);
+                if !self.accept(",") {
+                    break;
+                }
+            }
+        }
+        self.expect(")")?;
+        let params = self.func().names.len();
+        // a function can be written inside a one line `if`, but its body can go on for longer
+        let outer = self.line_limit.take();
+        let body = self.block();
+        self.line_limit = outer;
+        let body = body?;
+        self.expect_end("function", line)?;
+        let func = self.funcs.pop().unwrap();
+        Ok(Rc::new(func.finish(params, body)))
+    }
+}
+
+fn index(expr: Expr, name: Rc<str>) -> Expr {
+    Expr::Index(Box::new(expr), Box::new(Expr::Str(name.as_bytes().into())))
+}
+
+fn assignable(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Local(_) | Expr::Upval(_) | Expr::Global(_) | Expr::Index(..)
+    )
+}
+
+fn compound_op(op: &str) -> Option<BinOp> {
+    Some(match op {
+        "+=" => BinOp::Add,
+        "-=" => BinOp::Sub,
+        "*=" => BinOp::Mul,
+        "/=" => BinOp::Div,
+        "\\=" => BinOp::IDiv,
+        "%=" => BinOp::Mod,
+        "^=" => BinOp::Pow,
+        "..=" => BinOp::Concat,
+        "|=" => BinOp::BOr,
+        "&=" => BinOp::BAnd,
+        "^^=" => BinOp::BXor,
+        "<<=" => BinOp::Shl,
+        ">>=" => BinOp::Shr,
+        ">>>=" => BinOp::LShr,
+        "<<>=" => BinOp::RotL,
+        ">><=" => BinOp::RotR,
+        _ => return None,
+    })
+}
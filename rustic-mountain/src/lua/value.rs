@@ -0,0 +1,398 @@
+//! What the interpreter computes with. Numbers are `Fix16` like they are in pico-8, and strings
+//! are P8SCII bytes rather than unicode, so `chr`, `ord` and `print` agree with pico-8 about
+//! every character
+use alloc::{collections::BTreeMap, format, rc::Rc, string::String, vec::Vec};
+use core::{
+    cell::RefCell,
+    cmp::Ordering,
+    fmt::{self, Debug},
+    ops::Bound,
+};
+
+use super::{interp::Closure, LuaCart, LuaError};
+use crate::{fixed::Fix16, p8scii};
+
+pub type TableRef = Rc<RefCell<Table>>;
+
+/// A built in function. Gets the arguments it was called with and returns its results
+pub type NativeFn = fn(&mut LuaCart, &[Value]) -> Result<Vec<Value>, LuaError>;
+
+pub struct Native {
+    pub name: &'static str,
+    pub f: NativeFn,
+}
+
+#[derive(Clone, Default)]
+pub enum Value {
+    #[default]
+    Nil,
+    Bool(bool),
+    Num(Fix16),
+    Str(Rc<[u8]>),
+    Table(TableRef),
+    Function(Rc<Closure>),
+    Native(Rc<Native>),
+}
+
+impl Value {
+    /// A string value from rust text, with pico-8's glyphs turned into their P8SCII codes
+    pub fn str(text: &str) -> Value {
+        Value::Str(p8scii::encode(text).into())
+    }
+    pub fn int(v: i32) -> Value {
+        Value::Num(Fix16::from_int(v))
+    }
+    pub fn table(table: Table) -> Value {
+        Value::Table(Rc::new(RefCell::new(table)))
+    }
+    pub fn is_nil(&self) -> bool {
+        matches!(self, Value::Nil)
+    }
+    /// everything but `nil` and `false` is true
+    pub fn truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+    /// `type()`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Num(_) => "number",
+            Value::Str(_) => "string",
+            Value::Table(_) => "table",
+            Value::Function(_) | Value::Native(_) => "function",
+        }
+    }
+    /// `rawequal()`. tables and functions are only equal to themselves
+    pub fn raw_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Num(a), Value::Num(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Table(a), Value::Table(b)) => Rc::ptr_eq(a, b),
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            (Value::Native(a), Value::Native(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+    /// The number this is, or is written as for strings. Arithmetic converts strings like this
+    pub fn to_num(&self) -> Option<Fix16> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Str(s) => parse_num(s),
+            _ => None,
+        }
+    }
+    /// `tostr()`
+    pub fn to_text(&self) -> Rc<[u8]> {
+        match self {
+            Value::Str(s) => s.clone(),
+            Value::Num(n) => format_num(*n).into_bytes().into(),
+            Value::Bool(true) => (*b"true").into(),
+            Value::Bool(false) => (*b"false").into(),
+            Value::Nil => (*b"[nil]").into(),
+            Value::Table(_) => (*b"[table]").into(),
+            Value::Function(_) | Value::Native(_) => (*b"[function]").into(),
+        }
+    }
+    /// Only for strings, with each P8SCII code as the char of the same number, which is what
+    /// `Memory::print` expects
+    pub fn as_rust_str(&self) -> Option<String> {
+        match self {
+            Value::Str(s) => Some(s.iter().map(|&b| b as char).collect()),
+            _ => None,
+        }
+    }
+}
+
+impl Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => write!(f, "{:?}", String::from_utf8_lossy(s)),
+            _ => f.write_str(&String::from_utf8_lossy(&self.to_text())),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+impl From<Fix16> for Value {
+    fn from(v: Fix16) -> Self {
+        Value::Num(v)
+    }
+}
+
+/// A value that can index a table, which is any but `nil`. Ordered so tables can keep their
+/// keys in a `BTreeMap`: by type, then by value for numbers and strings and by address for
+/// everything else
+#[derive(Clone, Debug)]
+pub struct Key(Value);
+
+impl Key {
+    pub fn new(value: Value) -> Option<Key> {
+        (!value.is_nil()).then_some(Key(value))
+    }
+    pub fn str(text: &str) -> Key {
+        Key(Value::str(text))
+    }
+    pub fn int(i: i32) -> Key {
+        Key(Value::int(i))
+    }
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+    /// the position in the array part this key would be at, if it's a whole number from 1 up
+    fn index(&self) -> Option<usize> {
+        match self.0 {
+            Value::Num(n) => array_index(n),
+            _ => None,
+        }
+    }
+    fn rank(&self) -> (u8, usize) {
+        match &self.0 {
+            Value::Nil => (0, 0),
+            Value::Bool(b) => (1, *b as usize),
+            Value::Num(_) => (2, 0),
+            Value::Str(_) => (3, 0),
+            Value::Table(t) => (4, Rc::as_ptr(t) as *const u8 as usize),
+            Value::Function(c) => (5, Rc::as_ptr(c) as *const u8 as usize),
+            Value::Native(n) => (6, Rc::as_ptr(n) as *const u8 as usize),
+        }
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Key) -> Ordering {
+        match (&self.0, &other.0) {
+            (Value::Num(a), Value::Num(b)) => a.cmp(b),
+            (Value::Str(a), Value::Str(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Key {}
+
+fn array_index(n: Fix16) -> Option<usize> {
+    (n.to_bits() & 0xffff == 0 && n.to_int() >= 1).then(|| n.to_int() as usize)
+}
+
+/// A Lua table. `t[1]` to `t[#t]` live in `array`, anything else in `hash`
+#[derive(Default)]
+pub struct Table {
+    pub array: Vec<Value>,
+    pub hash: BTreeMap<Key, Value>,
+    pub meta: Option<TableRef>,
+}
+
+impl Table {
+    /// A table with `values` at 1, 2, 3 and so on
+    pub fn from_values(values: Vec<Value>) -> Table {
+        let mut table = Table::default();
+        for (i, v) in values.into_iter().enumerate() {
+            table.set(Key::int(i as i32 + 1), v);
+        }
+        table
+    }
+    /// `rawget()`
+    pub fn get(&self, key: &Value) -> Value {
+        if let Value::Num(n) = key {
+            if let Some(i) = array_index(*n) {
+                if i <= self.array.len() {
+                    return self.array[i - 1].clone();
+                }
+            }
+        }
+        match Key::new(key.clone()) {
+            Some(key) => self.get_key(&key),
+            None => Value::Nil,
+        }
+    }
+    pub fn get_key(&self, key: &Key) -> Value {
+        match key.index() {
+            Some(i) if i <= self.array.len() => self.array[i - 1].clone(),
+            _ => self.hash.get(key).cloned().unwrap_or_default(),
+        }
+    }
+    pub fn get_int(&self, i: usize) -> Value {
+        if i >= 1 && i <= self.array.len() {
+            self.array[i - 1].clone()
+        } else {
+            self.get(&Value::int(i as i32))
+        }
+    }
+    /// `rawset()`. setting a key to `nil` removes it
+    pub fn set(&mut self, key: Key, value: Value) {
+        if let Some(i) = key.index() {
+            if i <= self.array.len() {
+                self.array[i - 1] = value;
+                // keep `#` pointing at the last value
+                while matches!(self.array.last(), Some(Value::Nil)) {
+                    self.array.pop();
+                }
+                return;
+            }
+            if i == self.array.len() + 1 && !value.is_nil() {
+                self.hash.remove(&key);
+                self.array.push(value);
+                self.pull_from_hash();
+                return;
+            }
+        }
+        if value.is_nil() {
+            self.hash.remove(&key);
+        } else {
+            self.hash.insert(key, value);
+        }
+    }
+    /// moves the numbers after the end of `array` out of `hash`, so tables filled in from the
+    /// back still end up as arrays
+    fn pull_from_hash(&mut self) {
+        while let Some(v) = self.hash.remove(&Key::int(self.array.len() as i32 + 1)) {
+            self.array.push(v);
+        }
+    }
+    /// `#`
+    pub fn len(&self) -> usize {
+        self.array.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.array.is_empty()
+    }
+    /// Puts `value` at `pos`, moving the ones from there on up, like `add(t, v, i)`
+    pub fn insert(&mut self, pos: usize, value: Value) {
+        if pos >= 1 && pos <= self.array.len() && !value.is_nil() {
+            self.array.insert(pos - 1, value);
+            self.pull_from_hash();
+        } else if let Some(key) = Key::new(Value::int(pos as i32)) {
+            self.set(key, value);
+        }
+    }
+    /// Takes out the value at `pos`, moving the ones after it down, like `deli(t, i)`
+    pub fn remove(&mut self, pos: usize) -> Value {
+        if pos >= 1 && pos <= self.array.len() {
+            let value = self.array.remove(pos - 1);
+            while matches!(self.array.last(), Some(Value::Nil)) {
+                self.array.pop();
+            }
+            value
+        } else {
+            Value::Nil
+        }
+    }
+    /// `next()`: the key and value after `key`, the array part first and then the rest in key
+    /// order. Keys can be set to `nil` while going through a table but not added
+    pub fn next(&self, key: &Value) -> Option<(Value, Value)> {
+        let start = match key {
+            Value::Nil => Some(0),
+            Value::Num(n) => array_index(*n).filter(|&i| i <= self.array.len()),
+            _ => None,
+        };
+        let mut rest = match start {
+            Some(start) => {
+                for (i, v) in self.array.iter().enumerate().skip(start) {
+                    if !v.is_nil() {
+                        return Some((Value::int(i as i32 + 1), v.clone()));
+                    }
+                }
+                self.hash.range::<Key, _>(..)
+            }
+            None => self
+                .hash
+                .range((Bound::Excluded(Key(key.clone())), Bound::Unbounded)),
+        };
+        rest.next().map(|(k, v)| (k.0.clone(), v.clone()))
+    }
+}
+
+/// pico-8's `tostr` of a number: rounded to 4 decimal places, without trailing zeros
+pub fn format_num(n: Fix16) -> String {
+    let bits = n.to_bits() as i64;
+    let abs = bits.abs();
+    let mut int = abs >> 16;
+    let mut frac = ((abs & 0xffff) * 10000 + 0x8000) >> 16;
+    if frac == 10000 {
+        int += 1;
+        frac = 0;
+    }
+    let sign = if bits < 0 && (int != 0 || frac != 0) {
+        "-"
+    } else {
+        ""
+    };
+    if frac == 0 {
+        format!("{}{}", sign, int)
+    } else {
+        let frac = format!("{:04}", frac);
+        format!("{}{}.{}", sign, int, frac.trim_end_matches('0'))
+    }
+}
+
+/// `tostr(n, true)`: all 32 bits in hex, like `0x0001.8000`
+pub fn format_hex(n: Fix16) -> String {
+    let bits = n.to_bits() as u32;
+    format!("0x{:04x}.{:04x}", bits >> 16, bits & 0xffff)
+}
+
+/// Reads a number the way pico-8 does for both literals and `tonum`: decimal, `0x` hex or `0b`
+/// binary, each with an optional fraction. Numbers too big for 16 bits wrap around
+pub fn parse_num(text: &[u8]) -> Option<Fix16> {
+    let text = text.trim_ascii();
+    let (negative, text) = match text.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        _ => (false, text),
+    };
+    let (radix, digits) = match text {
+        [b'0', b'x' | b'X', rest @ ..] => (16, rest),
+        [b'0', b'b' | b'B', rest @ ..] => (2, rest),
+        _ => (10, text),
+    };
+    let (int_digits, frac_digits) = match digits.iter().position(|&c| c == b'.') {
+        Some(dot) => (&digits[..dot], &digits[dot + 1..]),
+        None => (digits, &[][..]),
+    };
+    if int_digits.is_empty() && frac_digits.is_empty() {
+        return None;
+    }
+    let mut int: u32 = 0;
+    for &c in int_digits {
+        int = int
+            .wrapping_mul(radix)
+            .wrapping_add((c as char).to_digit(radix)?);
+    }
+    let mut frac = 0.0;
+    let mut scale = 1.0;
+    for &c in frac_digits {
+        scale /= radix as f64;
+        frac += (c as char).to_digit(radix)? as f64 * scale;
+    }
+    let bits = (int << 16).wrapping_add(libm::round(frac * 65536.0) as u32) as i32;
+    Some(Fix16::from_bits(if negative {
+        bits.wrapping_neg()
+    } else {
+        bits
+    }))
+}
+
+/// the nearest `Fix16` to `v`, saturating rather than wrapping
+pub fn from_f64(v: f64) -> Fix16 {
+    let bits = libm::round(v * 65536.0);
+    Fix16::from_bits(bits.clamp(i32::MIN as f64, i32::MAX as f64) as i32)
+}
+
+pub fn to_f64(n: Fix16) -> f64 {
+    n.to_bits() as f64 / 65536.0
+}
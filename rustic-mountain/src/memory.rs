@@ -1,19 +1,78 @@
-use crate::structures::{FlipState, Vector};
-use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
-use rand::rngs::OsRng;
+use crate::{
+    fixed::Fix16,
+    p8scii::{self, FontMetrics, Glyph, GlyphSource, TextOp},
+    rng::GameRng,
+    structures::{FlipState, Vector},
+};
+use alloc::{rc::Rc, string::String, vec, vec::Vec};
 
+/// A cart's map, sprites, flags and font, decoded once so any number of `Memory`s can share it.
+/// Each part is only copied when something writes to it, e.g. `mset` copies the map of the
+/// `Memory` it's called on
+#[derive(Clone)]
+pub struct CartData {
+    /// tiles in rows of `map_width`. A standard 128x32 map goes on for another 32 rows in the
+    /// shared half of the sprite sheet like pico-8's does, any other size is all here
+    pub map: Rc<Vec<u8>>,
+    pub map_width: usize,
+    /// one color per pixel
+    pub sprites: Rc<Vec<u8>>,
+    pub flags: Rc<Vec<u8>>,
+    /// the built in font, a pixel per bool in rows 128 wide, with each glyph in an 8x8 cell.
+    /// only goes up to `p8scii::DEFAULT_GLYPHS`
+    pub fontatlas: Rc<Vec<bool>>,
+}
+
+impl CartData {
+    /// Decodes the strings `Celeste::new` takes
+    pub fn new(map: &str, sprites: &str, flags: &str, fontatlas: &str) -> CartData {
+        CartData {
+            map: Rc::new(hex::decode(map).unwrap()),
+            map_width: 128,
+            sprites: Rc::new(
+                sprites
+                    .chars()
+                    .map(|c| c.to_digit(16).unwrap() as u8)
+                    .collect(),
+            ),
+            flags: Rc::new(hex::decode(flags).unwrap()),
+            fontatlas: Rc::new(fontatlas.chars().map(|c| c == '0').collect()),
+        }
+    }
+}
+
+/// Cloning shares the cart data, see `CartData`, and copies everything else
+#[derive(Clone)]
 pub struct Memory {
-    pub logger: Box<dyn Fn(&str)>,
+    pub logger: Rc<dyn Fn(&str)>,
     pub graphics: Vec<u8>,
-    pub fontatlas: Vec<bool>,
-    pub map: Vec<u8>,
-    pub sprites: Vec<u8>,
-    pub flags: Vec<u8>,
+    pub fontatlas: Rc<Vec<bool>>,
+    pub map: Rc<Vec<u8>>,
+    /// see `CartData::map_width`
+    pub map_width: usize,
+    pub sprites: Rc<Vec<u8>>,
+    pub flags: Rc<Vec<u8>>,
     pub buttons: Vec<bool>,
 
     pub pallete: Vec<ColorState>,
+    /// the secondary palette, applied when the screen is displayed rather than when drawing.
+    /// values 128-143 select pico-8's extended colors
+    pub display_pallete: Vec<u8>,
     pub camera: Vector,
-    pub rng: OsRng,
+    pub clip: ClipRect,
+    pub fill_pattern: FillPattern,
+    /// the current pen color, set by `color()` and by every draw call given a color
+    pub pen: u8,
+    /// where `print_at_cursor` continues from. every print moves it to the line below
+    pub cursor: Cursor,
+    /// pico-8's print attribute byte, see `p8scii::ATTR_ENABLE` and friends
+    pub print_attrs: u8,
+    /// Backing store for the parts of pico-8's address space that have no field of their own
+    /// (sfx, music, user data, ...). Laid out exactly like pico-8's ram, and only allocated once
+    /// something pokes into one of those regions
+    pub ram: Option<Vec<u8>>,
+    /// seeded from entropy, see `Celeste::seed` to pick the seed
+    pub rng: GameRng,
 }
 
 #[derive(Debug, Clone)]
@@ -22,8 +81,35 @@ pub struct ColorState {
     pub transparent: bool,
 }
 
+/// The drawable region of the screen, in screen space. `x1` and `y1` are exclusive
+#[derive(Debug, Clone)]
+pub struct ClipRect {
+    pub x0: i32,
+    pub y0: i32,
+    pub x1: i32,
+    pub y1: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cursor {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A 4x4 pattern applied to shapes. Set bits draw the high nibble of the color, or nothing if
+/// `transparent` is set. bit 15 is the top left pixel
+#[derive(Debug, Clone)]
+pub struct FillPattern {
+    pub pattern: u16,
+    pub transparent: bool,
+}
+
 impl Memory {
     pub fn new(map: String, sprites: String, flags: String, fontatlas: String) -> Memory {
+        Memory::from_cart(&CartData::new(&map, &sprites, &flags, &fontatlas))
+    }
+    /// A `Memory` that shares `cart`'s data
+    pub fn from_cart(cart: &CartData) -> Memory {
         let mut graphics = vec![];
         for i in 0..128 * 128 {
             graphics.push((i % 15) as u8);
@@ -37,19 +123,45 @@ impl Memory {
         }
         pal[0].transparent = true;
         Memory {
-            logger: Box::new(|s| {}),
+            logger: Rc::new(|_| {}),
             buttons: vec![false; 6],
             graphics,
-            fontatlas: fontatlas.chars().map(|c| c == '0').collect(),
-            map: hex::decode(map).unwrap(),
-            sprites: sprites
-                .chars()
-                .map(|c| u8::from_str_radix(&format!("{}", c), 16).unwrap())
-                .collect(),
-            flags: hex::decode(flags).unwrap(),
+            fontatlas: cart.fontatlas.clone(),
+            map: cart.map.clone(),
+            map_width: cart.map_width,
+            sprites: cart.sprites.clone(),
+            flags: cart.flags.clone(),
             pallete: pal,
-            rng: OsRng::default(),
-            camera: Vector { x: 0.0, y: 0.0 },
+            display_pallete: (0..16).collect(),
+            rng: GameRng::from_entropy(),
+            camera: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            clip: ClipRect {
+                x0: 0,
+                y0: 0,
+                x1: 128,
+                y1: 128,
+            },
+            fill_pattern: FillPattern {
+                pattern: 0,
+                transparent: false,
+            },
+            pen: 6,
+            cursor: Cursor { x: 0, y: 0 },
+            print_attrs: 0,
+            ram: None,
+        }
+    }
+    /// The map, sprites, flags and font as they are now, edits included. Shared, not copied
+    pub fn cart(&self) -> CartData {
+        CartData {
+            map: self.map.clone(),
+            map_width: self.map_width,
+            sprites: self.sprites.clone(),
+            flags: self.flags.clone(),
+            fontatlas: self.fontatlas.clone(),
         }
     }
     pub fn spr(&mut self, sprite: u8, x: i32, y: i32, flip: Option<FlipState>) {
@@ -68,12 +180,14 @@ impl Memory {
                     + (((sprite as usize / 16) * 8 * 128) + ci + (cj * 128))];
 
                 if !self.pallete[color as usize].transparent {
-                    self.pset(color, x + i as i32, y + j as i32);
+                    let (sx, sy) = self.to_screen(x + i as i32, y + j as i32);
+                    self.put(sx, sy, color);
                 }
             }
         }
     }
-    pub fn map(&mut self, celx: u8, cely: u8, sx: i32, sy: i32, celw: u8, celh: u8, mask: u8) {
+    #[allow(clippy::too_many_arguments, reason = "same arguments as pico-8's map")]
+    pub fn map(&mut self, celx: i32, cely: i32, sx: i32, sy: i32, celw: i32, celh: i32, mask: u8) {
         for ioffset in 0..celw {
             for joffset in 0..celh {
                 let sprnum = self.mget(celx + ioffset, cely + joffset);
@@ -82,101 +196,193 @@ impl Memory {
                     // if sprnum == 11 || sprnum == 12 {
                     //     dbg!((celx + ioffset, cely + joffset));
                     // }
-                    self.spr(
-                        sprnum,
-                        (sx + ioffset as i32) * 8,
-                        (sy + joffset as i32) * 8,
-                        None,
-                    );
+                    self.spr(sprnum, (sx + ioffset) * 8, (sy + joffset) * 8, None);
                 }
             }
         }
     }
-    pub fn circfill(&mut self, xc: u8, yc: u8, r: i8, c: u8) {
-        let mut x: i8 = 0;
-        let mut y: i8 = r as i8;
-        let mut d: i8 = 3 - 2 * r;
-        self.draw_circ(xc as i32, yc as i32, x as i32, y as i32, c);
-        while y >= x as i8 {
-            x += 1;
-            if d > 0 {
-                y -= 1;
-                d = d + 4 * (x - y) + 10;
-            } else {
-                d = d + 4 * x + 6;
+    /// Draws a stretched region of the sprite sheet. Pass the source size as `dw`/`dh` for an
+    /// unstretched copy
+    #[allow(clippy::too_many_arguments, reason = "same arguments as pico-8's sspr")]
+    pub fn sspr(
+        &mut self,
+        sx: i32,
+        sy: i32,
+        sw: i32,
+        sh: i32,
+        dx: i32,
+        dy: i32,
+        dw: i32,
+        dh: i32,
+        flip: Option<FlipState>,
+    ) {
+        let flip = flip.unwrap_or(FlipState { x: false, y: false });
+        if dw <= 0 || dh <= 0 {
+            return;
+        }
+        let (dx, dy) = self.to_screen(dx, dy);
+        for j in 0..dh {
+            let srcj = if flip.y { dh - 1 - j } else { j };
+            let srcy = sy + srcj * sh / dh;
+            for i in 0..dw {
+                let srci = if flip.x { dw - 1 - i } else { i };
+                let color = self.sget(sx + srci * sw / dw, srcy);
+                if !self.pallete[color as usize].transparent {
+                    self.put(dx + i, dy + j, color);
+                }
             }
-            self.draw_circ(xc as i32, yc as i32, x as i32, y as i32, c);
         }
     }
-    pub fn draw_circ(&mut self, xc: i32, yc: i32, x: i32, y: i32, c: u8) {
-        self.rectfill(
-            (xc - x).into(),
-            (yc + y).into(),
-            (xc - x).into(),
-            (yc - y).into(),
-            c,
-        );
-        self.rectfill(
-            (xc + y).into(),
-            (yc + x).into(),
-            (xc - y).into(),
-            (yc + x).into(),
-            c,
-        );
-        self.rectfill(
-            (xc + x).into(),
-            (yc - y).into(),
-            (xc + x).into(),
-            (yc + y).into(),
-            c,
-        );
-        self.rectfill(
-            (xc - y).into(),
-            (yc - x).into(),
-            (xc + y).into(),
-            (yc - x).into(),
-            c,
-        );
-        self.rectfill(
-            (xc + x).into(),
-            (yc - y).into(),
-            (xc + x).into(),
-            (yc + y).into(),
-            c,
-        );
-        self.rectfill(
-            (xc - y).into(),
-            (yc - x).into(),
-            (xc + y).into(),
-            (yc - x).into(),
-            c,
-        );
+    pub fn sget(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x >= 128 || y >= 128 {
+            return 0;
+        }
+        self.sprites[x as usize + y as usize * 128]
     }
-    pub fn rrectfill(&mut self, x: i32, y: i32, x1: i32, y1: i32, col: u8) {
-        let mut i = x;
-        loop {
-            let mut j = y;
-            loop {
-                self.pset(col, i, j);
-                j += (y1 - y).signum();
-                if j == y1 {
-                    break;
-                }
+    pub fn sset(&mut self, x: i32, y: i32, col: u8) {
+        if x < 0 || y < 0 || x >= 128 || y >= 128 {
+            return;
+        }
+        Rc::make_mut(&mut self.sprites)[x as usize + y as usize * 128] = col & 0xf;
+    }
+    /// Clears the screen, resets the clip rectangle and puts the cursor back at the top left
+    pub fn cls(&mut self, col: u8) {
+        self.clip_reset();
+        self.cursor(0, 0);
+        self.graphics.fill(col & 0xf);
+    }
+    pub fn pget(&self, x: i32, y: i32) -> u8 {
+        let (x, y) = self.to_screen(x, y);
+        if x < 0 || y < 0 || x >= 128 || y >= 128 {
+            return 0;
+        }
+        self.graphics[x as usize + y as usize * 128]
+    }
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, col: u8) {
+        self.pen = col;
+        let (x0, y0) = self.to_screen(x0, y0);
+        let (x1, y1) = self.to_screen(x1, y1);
+        let dx = x1 - x0;
+        let dy = y1 - y0;
+        // step one pixel at a time along the longer axis, rounding the other one
+        if dx.abs() >= dy.abs() {
+            let step = dx.signum();
+            for i in 0..=dx.abs() {
+                let y = y0 + rdiv(i * dy, dx.abs());
+                self.put_shape(x0 + i * step, y, col);
             }
-            i += (x1 - x).signum();
-            if i == x1 {
-                break;
+        } else {
+            let step = dy.signum();
+            for i in 0..=dy.abs() {
+                let x = x0 + rdiv(i * dx, dy.abs());
+                self.put_shape(x, y0 + i * step, col);
             }
         }
     }
-    pub fn camera(&mut self, x: f32, y: f32) {
-        self.camera = Vector { x, y };
+    pub fn rect(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, col: u8) {
+        self.pen = col;
+        let (x0, y0, x1, y1) = self.screen_rect(x0, y0, x1, y1);
+        for x in x0..=x1 {
+            self.put_shape(x, y0, col);
+            self.put_shape(x, y1, col);
+        }
+        for y in y0 + 1..y1 {
+            self.put_shape(x0, y, col);
+            self.put_shape(x1, y, col);
+        }
+    }
+    pub fn rectfill(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, col: u8) {
+        self.pen = col;
+        let (x0, y0, x1, y1) = self.screen_rect(x0, y0, x1, y1);
+        // clamp first so huge rects don't loop over offscreen pixels
+        for y in y0.max(self.clip.y0)..=y1.min(self.clip.y1 - 1) {
+            self.hspan(x0, x1, y, col);
+        }
+    }
+    pub fn circ(&mut self, xc: i32, yc: i32, r: i32, col: u8) {
+        self.pen = col;
+        let (xc, yc) = self.to_screen(xc, yc);
+        self.midpoint_circle(r, |mem, x, y| {
+            for (px, py) in [
+                (x, y),
+                (y, x),
+                (-y, x),
+                (-x, y),
+                (-x, -y),
+                (-y, -x),
+                (y, -x),
+                (x, -y),
+            ] {
+                mem.put_shape(xc + px, yc + py, col);
+            }
+        });
+    }
+    pub fn circfill(&mut self, xc: i32, yc: i32, r: i32, col: u8) {
+        self.pen = col;
+        let (xc, yc) = self.to_screen(xc, yc);
+        self.midpoint_circle(r, |mem, x, y| {
+            mem.hspan(xc - x, xc + x, yc + y, col);
+            mem.hspan(xc - y, xc + y, yc + x, col);
+            mem.hspan(xc - x, xc + x, yc - y, col);
+            mem.hspan(xc - y, xc + y, yc - x, col);
+        });
+    }
+    /// Draws the outline of the ellipse inscribed in the given rectangle
+    pub fn oval(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, col: u8) {
+        self.pen = col;
+        let (x0, y0, x1, y1) = self.screen_rect(x0, y0, x1, y1);
+        // a pixel is on the outline if it's inside the oval and one of its neighbours isn't
+        let spans: Vec<(i32, i32)> = (y0..=y1).map(|y| oval_span(x0, y0, x1, y1, y)).collect();
+        let inside = |x: i32, y: i32| {
+            y >= y0 && y <= y1 && {
+                let (l, r) = spans[(y - y0) as usize];
+                x >= l && x <= r
+            }
+        };
+        for y in y0..=y1 {
+            let (l, r) = spans[(y - y0) as usize];
+            for x in l..=r {
+                if !inside(x - 1, y) || !inside(x + 1, y) || !inside(x, y - 1) || !inside(x, y + 1)
+                {
+                    self.put_shape(x, y, col);
+                }
+            }
+        }
     }
-    pub fn rectfill(&mut self, x: i32, y: i32, x2: i32, y2: i32, c: u8) {
-        if x < 128 && x2 > 0 && y < 128 && y2 > 0 {
-            self.rrectfill(0.max(x), 0.max(y), x2.min(127), y2.min(127), c);
+    /// Fills the ellipse inscribed in the given rectangle
+    pub fn ovalfill(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, col: u8) {
+        self.pen = col;
+        let (x0, y0, x1, y1) = self.screen_rect(x0, y0, x1, y1);
+        for y in y0..=y1 {
+            let (l, r) = oval_span(x0, y0, x1, y1, y);
+            self.hspan(l, r, y, col);
         }
     }
+    pub fn camera(&mut self, x: Fix16, y: Fix16) {
+        self.camera = Vector { x, y };
+    }
+    /// Restricts drawing to the given rectangle, in screen space
+    pub fn clip(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.clip = ClipRect {
+            x0: x.clamp(0, 128),
+            y0: y.clamp(0, 128),
+            x1: (x + w).clamp(0, 128),
+            y1: (y + h).clamp(0, 128),
+        };
+    }
+    pub fn clip_reset(&mut self) {
+        self.clip(0, 0, 128, 128);
+    }
+    pub fn fillp(&mut self, pattern: u16, transparent: bool) {
+        self.fill_pattern = FillPattern {
+            pattern,
+            transparent,
+        };
+    }
+    /// Sets the pen color, returning the previous one
+    pub fn color(&mut self, col: u8) -> u8 {
+        core::mem::replace(&mut self.pen, col)
+    }
 
     pub fn pal(&mut self, index: usize, color: u8) {
         self.pallete[index].color = color;
@@ -184,69 +390,815 @@ impl Memory {
     pub fn palt(&mut self, index: usize, transparent: bool) {
         self.pallete[index].transparent = transparent;
     }
+    /// Remaps a color in the display palette, pico-8's `pal(index, color, 1)`
+    pub fn pal_display(&mut self, index: usize, color: u8) {
+        self.display_pallete[index] = color & 0x8f;
+    }
     pub fn pal_reset(&mut self) {
         for i in 0..self.pallete.len() {
             self.pallete[i].color = i as u8;
             self.pallete[i].transparent = false;
+            self.display_pallete[i] = i as u8;
         }
         self.pallete[0].transparent = true;
     }
-    pub fn print(&mut self, text: &str, x: i32, y: i32, col: u8) {
-        for (c, chr) in text.char_indices() {
-            let char_index = chr as usize;
-            for i in 0..3 {
-                for j in 0..5 {
-                    if self.fontatlas[(char_index / 16 * (128 * 8))
-                        + (char_index % 16 * 8)
-                        + i as usize
-                        + (j as usize * 128)]
-                    {
-                        self.pset(col, x + i + (c as i32 * 4), y + j);
+    /// Prints P8SCII text (see `p8scii`), so `\n`, control codes and the wide glyphs all work.
+    /// Returns the x coordinate just past the rightmost character
+    pub fn print(&mut self, text: &str, x: i32, y: i32, col: u8) -> i32 {
+        self.pen = col;
+        let codes = p8scii::encode(text);
+        let end = p8scii::layout(
+            &codes,
+            x,
+            y,
+            col,
+            self.print_attrs,
+            self.custom_font(),
+            |op| match op {
+                TextOp::Glyph(glyph) => self.draw_glyph(glyph),
+                TextOp::Clear(col) => self.cls(col),
+            },
+        );
+        self.cursor = Cursor {
+            x: end.home_x,
+            y: end.bottom,
+        };
+        end.right
+    }
+    /// `print` without a position, continuing from the cursor
+    pub fn print_at_cursor(&mut self, text: &str, col: u8) -> i32 {
+        self.print(text, self.cursor.x, self.cursor.y, col)
+    }
+    pub fn cursor(&mut self, x: i32, y: i32) {
+        self.cursor = Cursor { x, y };
+    }
+    /// The width and height `print` would take up for `text`, without drawing anything
+    pub fn measure(&self, text: &str) -> (i32, i32) {
+        let codes = p8scii::encode(text);
+        let end = p8scii::layout(
+            &codes,
+            0,
+            0,
+            self.pen,
+            self.print_attrs,
+            self.custom_font(),
+            |_| {},
+        );
+        (end.right, end.bottom)
+    }
+    pub fn pset(&mut self, col: u8, x: i32, y: i32) {
+        self.pen = col;
+        let (x, y) = self.to_screen(x, y);
+        self.put_shape(x, y, col);
+    }
+
+    /// the header of the custom font at `FONT`
+    fn custom_font(&self) -> FontMetrics {
+        FontMetrics {
+            width: self.peek(FONT) as i32,
+            wide_width: self.peek(FONT + 1) as i32,
+            height: self.peek(FONT + 2) as i32,
+            x_offset: self.peek(FONT + 3) as i8 as i32,
+            y_offset: self.peek(FONT + 4) as i8 as i32,
+        }
+    }
+    fn glyph_pixel(&self, source: GlyphSource, x: i32, y: i32) -> bool {
+        match source {
+            GlyphSource::Default(c) => {
+                let c = c as usize;
+                let i = (c / 16 * (128 * 8)) + (c % 16 * 8) + x as usize + (y as usize * 128);
+                self.fontatlas.get(i).copied().unwrap_or(false)
+            }
+            GlyphSource::Custom(c) => (self.peek(FONT + c as usize * 8 + y as usize) >> x) & 1 != 0,
+            GlyphSource::Bitmap(rows) => (rows[y as usize] >> x) & 1 != 0,
+        }
+    }
+    fn draw_glyph(&mut self, g: Glyph) {
+        if let Some(bg) = g.bg {
+            for y in g.y - 1..g.y + g.h - 1 {
+                for x in g.x - 1..g.x + g.w - 1 {
+                    let (sx, sy) = self.to_screen(x, y);
+                    self.put(sx, sy, bg);
+                }
+            }
+        }
+        let (scale_x, scale_y) = (1 + g.wide as i32, 1 + g.tall as i32);
+        // inverted glyphs fill the unlit part of the cell, so they can't draw past it
+        let (w, h) = if g.invert {
+            (g.w / scale_x, g.h / scale_y)
+        } else {
+            (8, 8)
+        };
+        for gy in 0..h.min(8) {
+            for gx in 0..w.min(8) {
+                if self.glyph_pixel(g.source, gx, gy) == g.invert {
+                    continue;
+                }
+                for dy in 0..if g.stripey || g.dotty { 1 } else { scale_y } {
+                    for dx in 0..if g.dotty { 1 } else { scale_x } {
+                        let (sx, sy) =
+                            self.to_screen(g.x + gx * scale_x + dx, g.y + gy * scale_y + dy);
+                        self.put(sx, sy, g.fg);
                     }
                 }
             }
         }
     }
-    pub fn pset(&mut self, col: u8, mut x: i32, mut y: i32) {
-        let c = &self.pallete[col as usize];
-        x += self.camera.x as i32;
-        y += self.camera.y as i32;
-        if x < 0 || y < 0 || x >= 128 || y >= 128 {
+    /// applies the camera offset
+    fn to_screen(&self, x: i32, y: i32) -> (i32, i32) {
+        (x - self.camera.x.to_int(), y - self.camera.y.to_int())
+    }
+    /// sorts the corners of a rectangle and moves it to screen space
+    fn screen_rect(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> (i32, i32, i32, i32) {
+        let (left, top) = self.to_screen(x0.min(x1), y0.min(y1));
+        let (right, bottom) = self.to_screen(x0.max(x1), y0.max(y1));
+        (left, top, right, bottom)
+    }
+    /// writes a single pixel in screen space through the draw palette, if it's inside the clip
+    /// rectangle
+    fn put(&mut self, x: i32, y: i32, col: u8) {
+        if x < self.clip.x0 || y < self.clip.y0 || x >= self.clip.x1 || y >= self.clip.y1 {
+            return;
+        }
+        self.graphics[x as usize + y as usize * 128] = self.pallete[col as usize & 0xf].color;
+    }
+    /// like `put`, but for shapes, which are affected by the fill pattern
+    fn put_shape(&mut self, x: i32, y: i32, col: u8) {
+        let bit = 15 - ((y & 3) * 4 + (x & 3));
+        if (self.fill_pattern.pattern >> bit) & 1 == 0 {
+            self.put(x, y, col & 0xf);
+        } else if !self.fill_pattern.transparent {
+            self.put(x, y, col >> 4);
+        }
+    }
+    fn hspan(&mut self, x0: i32, x1: i32, y: i32, col: u8) {
+        for x in x0.max(self.clip.x0)..=x1.min(self.clip.x1 - 1) {
+            self.put_shape(x, y, col);
+        }
+    }
+    /// walks one octant of a circle, calling `plot` with each offset. the other octants are up
+    /// to the caller
+    fn midpoint_circle(&mut self, r: i32, mut plot: impl FnMut(&mut Memory, i32, i32)) {
+        if r < 0 {
             return;
         }
-        self.graphics[x as usize + y as usize * 128] = c.color;
+        let mut x = r;
+        let mut y = 0;
+        let mut err = 1 - r;
+        while y <= x {
+            plot(self, x, y);
+            y += 1;
+            if err < 0 {
+                err += 2 * y + 1;
+            } else {
+                x -= 1;
+                err += 2 * (y - x) + 1;
+            }
+        }
     }
 
-    pub fn mget(&self, x: u8, y: u8) -> u8 {
-        if y == 64 {
-            return 0; //???
+    /// The map's width and height in tiles, see `CartData::map`
+    pub fn map_size(&self) -> (i32, i32) {
+        let w = self.map_width.max(1);
+        let h = self.map.len() / w;
+        if w == 128 && h == 32 {
+            (128, 64)
+        } else {
+            (w as i32, h as i32)
+        }
+    }
+    /// The tile at `x`, `y`, 0 outside the map
+    pub fn mget(&self, x: i32, y: i32) -> u8 {
+        match self.map_index(x, y) {
+            Some(i) if i < self.map.len() => self.map[i],
+            Some(i) => self.peek(GFX_SHARED + i - self.map.len()),
+            None => 0,
         }
-        let ind = x as usize + y as usize * 128;
-        if ind < 4096 {
-            self.map[ind]
+    }
+    /// Sets the tile at `x`, `y`. Does nothing outside the map
+    pub fn mset(&mut self, x: i32, y: i32, tile: u8) {
+        match self.map_index(x, y) {
+            Some(i) if i < self.map.len() => Rc::make_mut(&mut self.map)[i] = tile,
+            Some(i) => self.poke(GFX_SHARED + i - self.map.len(), tile),
+            None => {}
+        }
+    }
+    /// where a map cell is in `map`, or past its end for the rows of a standard map that are in
+    /// the sprite sheet
+    fn map_index(&self, x: i32, y: i32) -> Option<usize> {
+        let (w, h) = self.map_size();
+        ((0..w).contains(&x) && (0..h).contains(&y)).then(|| (x + y * w) as usize)
+    }
+    /// Whether flag `idx` (0-7) of sprite `sprnum` is set
+    pub fn fget(&self, sprnum: u8, idx: u8) -> bool {
+        self.flags[sprnum as usize] & (1 << idx) != 0
+    }
+    pub fn fget_all(&self, sprnum: u8) -> u8 {
+        self.flags[sprnum as usize]
+    }
+    /// Sets or clears flag `idx` (0-7) of sprite `sprnum`
+    pub fn fset(&mut self, sprnum: u8, idx: u8, val: bool) {
+        let flags = &mut Rc::make_mut(&mut self.flags)[sprnum as usize];
+        if val {
+            *flags |= 1 << idx;
         } else {
-            // after the 4096th octet, we start reading from the shared memory section at the bottom of sprites
-            // meaning we convert from 2 16s to 1 256
-            let start = (ind - 4096) * 2 + 4096 * 2;
+            *flags &= !(1 << idx);
+        }
+    }
 
-            self.sprites[start + 1] * 16 + self.sprites[start] // need to swap the nibbles
+    /// Reads a byte of pico-8's address space. Regions that `Memory` keeps unpacked (sprites,
+    /// map, flags, draw state, screen) are converted on the fly, so this always agrees with what
+    /// the drawing functions see
+    pub fn peek(&self, addr: usize) -> u8 {
+        match addr {
+            GFX..=0x1fff => {
+                let i = (addr - GFX) * 2;
+                self.sprites[i] | self.sprites[i + 1] << 4
+            }
+            // the top 32 rows of the map as pico-8 lays them out, 128 tiles to a row
+            MAP..=0x2fff => self.mget(((addr - MAP) % 128) as i32, ((addr - MAP) / 128) as i32),
+            FLAGS..=0x30ff => self.flags[addr - FLAGS],
+            DRAW_PAL..=0x5f0f => {
+                let c = &self.pallete[addr - DRAW_PAL];
+                c.color | if c.transparent { 0x10 } else { 0 }
+            }
+            DISPLAY_PAL..=0x5f1f => self.display_pallete[addr - DISPLAY_PAL],
+            CLIP => self.clip.x0 as u8,
+            0x5f21 => self.clip.y0 as u8,
+            0x5f22 => self.clip.x1 as u8,
+            0x5f23 => self.clip.y1 as u8,
+            PEN => self.pen,
+            CURSOR => self.cursor.x as u8,
+            0x5f27 => self.cursor.y as u8,
+            CAMERA..=0x5f2b => {
+                let v = if addr < 0x5f2a {
+                    self.camera.x
+                } else {
+                    self.camera.y
+                };
+                (v.to_int() as i16).to_le_bytes()[(addr - CAMERA) % 2]
+            }
+            FILL_PATTERN..=0x5f32 => self.fill_pattern.pattern.to_le_bytes()[addr - FILL_PATTERN],
+            0x5f33 => self.fill_pattern.transparent as u8,
+            BUTTONS => self
+                .buttons
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, b)| acc | (*b as u8) << i),
+            PRINT_ATTRS => self.print_attrs,
+            SCREEN..=0x7fff => {
+                let i = (addr - SCREEN) * 2;
+                self.graphics[i] | self.graphics[i + 1] << 4
+            }
+            _ => match &self.ram {
+                Some(ram) if addr < RAM_SIZE => ram[addr],
+                _ => 0,
+            },
         }
     }
-    pub fn mset(&mut self, x: u8, y: u8, tile: u8) {
-        let ind = x as usize + y as usize * 128;
-        if ind < 4096 {
-            self.map[ind] = tile;
+    /// Writes a byte of pico-8's address space. See `peek`
+    pub fn poke(&mut self, addr: usize, val: u8) {
+        match addr {
+            GFX..=0x1fff => {
+                let i = (addr - GFX) * 2;
+                let sprites = Rc::make_mut(&mut self.sprites);
+                sprites[i] = val & 0xf;
+                sprites[i + 1] = val >> 4;
+            }
+            MAP..=0x2fff => self.mset(
+                ((addr - MAP) % 128) as i32,
+                ((addr - MAP) / 128) as i32,
+                val,
+            ),
+            FLAGS..=0x30ff => Rc::make_mut(&mut self.flags)[addr - FLAGS] = val,
+            DRAW_PAL..=0x5f0f => {
+                self.pallete[addr - DRAW_PAL] = ColorState {
+                    color: val & 0xf,
+                    transparent: val & 0x10 != 0,
+                }
+            }
+            DISPLAY_PAL..=0x5f1f => self.display_pallete[addr - DISPLAY_PAL] = val & 0x8f,
+            CLIP => self.clip.x0 = val.min(128) as i32,
+            0x5f21 => self.clip.y0 = val.min(128) as i32,
+            0x5f22 => self.clip.x1 = val.min(128) as i32,
+            0x5f23 => self.clip.y1 = val.min(128) as i32,
+            PEN => self.pen = val,
+            CURSOR => self.cursor.x = val as i32,
+            0x5f27 => self.cursor.y = val as i32,
+            CAMERA..=0x5f2b => {
+                let v = if addr < 0x5f2a {
+                    &mut self.camera.x
+                } else {
+                    &mut self.camera.y
+                };
+                let mut bytes = (v.to_int() as i16).to_le_bytes();
+                bytes[(addr - CAMERA) % 2] = val;
+                *v = Fix16::from_int(i16::from_le_bytes(bytes) as i32);
+            }
+            FILL_PATTERN..=0x5f32 => {
+                let mut bytes = self.fill_pattern.pattern.to_le_bytes();
+                bytes[addr - FILL_PATTERN] = val;
+                self.fill_pattern.pattern = u16::from_le_bytes(bytes);
+            }
+            0x5f33 => self.fill_pattern.transparent = val & 1 != 0,
+            BUTTONS => {
+                for (i, b) in self.buttons.iter_mut().enumerate() {
+                    *b = val & (1 << i) != 0;
+                }
+            }
+            PRINT_ATTRS => self.print_attrs = val,
+            SCREEN..=0x7fff => {
+                let i = (addr - SCREEN) * 2;
+                self.graphics[i] = val & 0xf;
+                self.graphics[i + 1] = val >> 4;
+            }
+            _ if addr < RAM_SIZE => {
+                self.ram.get_or_insert_with(|| vec![0; RAM_SIZE])[addr] = val;
+            }
+            _ => (),
+        }
+    }
+    /// Reads a little endian 16 bit integer
+    pub fn peek2(&self, addr: usize) -> i16 {
+        i16::from_le_bytes([self.peek(addr), self.peek(addr + 1)])
+    }
+    pub fn poke2(&mut self, addr: usize, val: i16) {
+        for (i, b) in val.to_le_bytes().into_iter().enumerate() {
+            self.poke(addr + i, b);
+        }
+    }
+    /// Reads 4 bytes as a fixed point number, the same format pico-8 stores numbers in
+    pub fn peek4(&self, addr: usize) -> Fix16 {
+        Fix16::from_bits(i32::from_le_bytes([
+            self.peek(addr),
+            self.peek(addr + 1),
+            self.peek(addr + 2),
+            self.peek(addr + 3),
+        ]))
+    }
+    pub fn poke4(&mut self, addr: usize, val: Fix16) {
+        for (i, b) in val.to_bits().to_le_bytes().into_iter().enumerate() {
+            self.poke(addr + i, b);
+        }
+    }
+    /// Copies `len` bytes from `src` to `dest`. Overlapping ranges are fine
+    pub fn memcpy(&mut self, dest: usize, src: usize, len: usize) {
+        if dest <= src {
+            for i in 0..len {
+                self.poke(dest + i, self.peek(src + i));
+            }
         } else {
-            let start = (ind - 4096) * 2 + 4096 * 2;
+            for i in (0..len).rev() {
+                self.poke(dest + i, self.peek(src + i));
+            }
+        }
+    }
+    pub fn memset(&mut self, dest: usize, val: u8, len: usize) {
+        for i in 0..len {
+            self.poke(dest + i, val);
+        }
+    }
+}
+
+pub const RAM_SIZE: usize = 0x8000;
+pub const GFX: usize = 0x0000;
+/// the bottom half of the sprite sheet, shared with the bottom half of the map
+pub const GFX_SHARED: usize = 0x1000;
+pub const MAP: usize = 0x2000;
+pub const FLAGS: usize = 0x3000;
+pub const MUSIC: usize = 0x3100;
+pub const SFX: usize = 0x3200;
+pub const USER_DATA: usize = 0x4300;
+/// the custom font: an 8 byte header (widths, height, draw offset), then 8 bytes per character
+pub const FONT: usize = 0x5600;
+pub const CART_DATA: usize = 0x5e00;
+pub const DRAW_PAL: usize = 0x5f00;
+pub const DISPLAY_PAL: usize = 0x5f10;
+pub const CLIP: usize = 0x5f20;
+pub const PEN: usize = 0x5f25;
+pub const CURSOR: usize = 0x5f26;
+pub const CAMERA: usize = 0x5f28;
+pub const FILL_PATTERN: usize = 0x5f31;
+pub const BUTTONS: usize = 0x5f4c;
+pub const PRINT_ATTRS: usize = 0x5f58;
+pub const SCREEN: usize = 0x6000;
+
+/// divides, rounding halves away from zero
+fn rdiv(n: i32, d: i32) -> i32 {
+    if d == 0 {
+        0
+    } else if n >= 0 {
+        (n + d / 2) / d
+    } else {
+        (n - d / 2) / d
+    }
+}
+
+/// the leftmost and rightmost pixels of row `y` of the oval inscribed in a rectangle
+fn oval_span(x0: i32, y0: i32, x1: i32, y1: i32, y: i32) -> (i32, i32) {
+    // work with pixel edges, so a 1 pixel wide oval has a radius of 0.5
+    let cx = (x0 + x1 + 1) as f32 / 2.0;
+    let cy = (y0 + y1 + 1) as f32 / 2.0;
+    let rx = (x1 - x0 + 1) as f32 / 2.0;
+    let ry = (y1 - y0 + 1) as f32 / 2.0;
+    let t = (y as f32 + 0.5 - cy) / ry;
+    let half = rx * libm::sqrtf((1.0 - t * t).max(0.0));
+    (
+        libm::ceilf(cx - half - 0.5) as i32,
+        libm::floorf(cx + half - 0.5) as i32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixed::fix;
+    use alloc::string::String;
+
+    /// a blank cart on a black screen
+    fn mem() -> Memory {
+        let mut mem = Memory::from_cart(&CartData {
+            map: Rc::new(vec![0; 128 * 32]),
+            map_width: 128,
+            sprites: Rc::new(vec![0; 128 * 128]),
+            flags: Rc::new(vec![0; 256]),
+            fontatlas: Rc::new(Vec::new()),
+        });
+        mem.cls(0);
+        mem
+    }
 
-            self.sprites[start] = tile % 16;
-            self.sprites[start + 1] = tile / 16;
+    /// the screen from (0, 0) to (w, h) as hex digits, one row per line, with `.` for black
+    fn screen(mem: &Memory, w: usize, h: usize) -> String {
+        let mut out = String::new();
+        for y in 0..h {
+            for x in 0..w {
+                out.push(match mem.graphics[x + y * 128] {
+                    0 => '.',
+                    c => char::from_digit(c as u32, 16).unwrap(),
+                });
+            }
+            out.push('\n');
         }
+        out
     }
-    pub fn fget(&self, sprnum: u8, idx: u8) -> bool {
-        (self.flags[sprnum as usize] & 2 ^ idx) != 0
+
+    #[test]
+    fn pset_and_pget() {
+        let mut mem = mem();
+        mem.pset(7, 1, 2);
+        mem.pset(8, -1, 0);
+        mem.pset(8, 128, 0);
+        assert_eq!(screen(&mem, 3, 3), "...\n...\n.7.\n");
+        assert_eq!(mem.pget(1, 2), 7);
+        assert_eq!(mem.pget(-1, 2), 0);
+        assert_eq!(mem.pen, 8);
     }
-    pub fn fget_all(&self, sprnum: u8) -> u8 {
-        self.flags[sprnum as usize]
+
+    #[test]
+    fn line() {
+        let mut mem = mem();
+        mem.line(0, 0, 3, 1, 9);
+        assert_eq!(screen(&mem, 4, 2), "99..\n..99\n");
+
+        // the same pixels from either end
+        let mut mem = self::mem();
+        mem.line(3, 1, 0, 0, 9);
+        assert_eq!(screen(&mem, 4, 2), "99..\n..99\n");
+
+        let mut mem = self::mem();
+        mem.line(1, 0, 1, 2, 4);
+        mem.line(0, 3, 2, 3, 5);
+        mem.line(0, 0, 0, 0, 6);
+        assert_eq!(screen(&mem, 3, 4), "64.\n.4.\n.4.\n555\n");
+
+        let mut mem = self::mem();
+        mem.line(0, 4, 4, 0, 1);
+        assert_eq!(screen(&mem, 5, 5), "....1\n...1.\n..1..\n.1...\n1....\n");
+    }
+
+    #[test]
+    fn rect() {
+        let mut mem = mem();
+        mem.rect(3, 3, 0, 0, 8);
+        assert_eq!(screen(&mem, 5, 5), "8888.\n8..8.\n8..8.\n8888.\n.....\n");
+
+        let mut mem = self::mem();
+        mem.rect(1, 1, 1, 2, 8);
+        assert_eq!(screen(&mem, 3, 4), "...\n.8.\n.8.\n...\n");
+
+        let mut mem = self::mem();
+        mem.rectfill(1, 1, 3, 2, 7);
+        assert_eq!(screen(&mem, 5, 4), ".....\n.777.\n.777.\n.....\n");
+    }
+
+    #[test]
+    fn circ() {
+        let mut mem = mem();
+        mem.circ(2, 2, 2, 1);
+        assert_eq!(screen(&mem, 5, 5), ".111.\n1...1\n1...1\n1...1\n.111.\n");
+
+        let mut mem = self::mem();
+        mem.circfill(2, 2, 2, 1);
+        assert_eq!(screen(&mem, 5, 5), ".111.\n11111\n11111\n11111\n.111.\n");
+
+        let mut mem = self::mem();
+        mem.circfill(1, 1, 1, 3);
+        mem.circ(4, 0, 0, 2);
+        mem.circ(4, 2, -1, 2);
+        assert_eq!(screen(&mem, 5, 3), ".3..2\n333..\n.3...\n");
+    }
+
+    #[test]
+    fn oval() {
+        let mut mem = mem();
+        mem.oval(0, 0, 9, 5, 2);
+        assert_eq!(
+            screen(&mem, 10, 6),
+            "..222222..\n.2......2.\n2........2\n2........2\n.2......2.\n..222222..\n"
+        );
+
+        let mut mem = self::mem();
+        mem.ovalfill(7, 3, 0, 0, 2);
+        assert_eq!(
+            screen(&mem, 8, 4),
+            ".222222.\n22222222\n22222222\n.222222.\n"
+        );
+    }
+
+    #[test]
+    fn camera_and_clip() {
+        let mut mem = mem();
+        mem.camera(fix(-1.0), fix(1.0));
+        mem.pset(7, 0, 1);
+        assert_eq!(mem.pget(0, 1), 7);
+        assert_eq!(screen(&mem, 2, 2), ".7\n..\n");
+
+        let mut mem = self::mem();
+        mem.clip(1, 1, 2, 2);
+        mem.camera(fix(-1.0), Fix16::ZERO);
+        mem.rectfill(-5, -5, 50, 50, 3);
+        assert_eq!(screen(&mem, 4, 4), "....\n.33.\n.33.\n....\n");
+
+        // clip is in screen space, and clamped to the screen
+        mem.clip(-10, 126, 20, 20);
+        assert_eq!(
+            (mem.clip.x0, mem.clip.y0, mem.clip.x1, mem.clip.y1),
+            (0, 126, 10, 128)
+        );
+    }
+
+    #[test]
+    fn cls_resets_clip_and_cursor() {
+        let mut mem = mem();
+        mem.clip(1, 1, 1, 1);
+        mem.cursor(20, 30);
+        mem.cls(5);
+        assert_eq!(screen(&mem, 2, 2), "55\n55\n");
+        assert_eq!((mem.cursor.x, mem.cursor.y), (0, 0));
+        assert_eq!(
+            (mem.clip.x0, mem.clip.y0, mem.clip.x1, mem.clip.y1),
+            (0, 0, 128, 128)
+        );
+    }
+
+    #[test]
+    fn sspr() {
+        let mut mem = mem();
+        mem.sset(0, 0, 5);
+        mem.sset(1, 0, 6);
+        mem.sset(0, 1, 7);
+        // 0 is transparent
+        mem.sset(1, 1, 0);
+
+        mem.sspr(0, 0, 2, 2, 0, 0, 2, 2, None);
+        assert_eq!(screen(&mem, 2, 2), "56\n7.\n");
+
+        let mut mem2 = self::mem();
+        mem2.sprites = mem.sprites.clone();
+        mem2.sspr(0, 0, 2, 2, 0, 0, 4, 4, None);
+        assert_eq!(screen(&mem2, 4, 4), "5566\n5566\n77..\n77..\n");
+
+        // shrinking skips source pixels
+        let mut mem2 = self::mem();
+        mem2.sprites = mem.sprites.clone();
+        mem2.sspr(0, 0, 2, 2, 0, 0, 1, 1, None);
+        assert_eq!(screen(&mem2, 2, 2), "5.\n..\n");
+
+        let mut mem2 = self::mem();
+        mem2.sprites = mem.sprites.clone();
+        let flip = |x, y| Some(FlipState { x, y });
+        mem2.sspr(0, 0, 2, 2, 0, 0, 2, 2, flip(true, false));
+        mem2.sspr(0, 0, 2, 2, 3, 0, 2, 2, flip(false, true));
+        mem2.sspr(0, 0, 2, 2, 6, 0, 4, 2, flip(true, true));
+        assert_eq!(screen(&mem2, 10, 2), "65.7....77\n.7.56.6655\n");
+
+        let mut mem2 = self::mem();
+        mem2.sprites = mem.sprites.clone();
+        mem2.camera(fix(1.0), Fix16::ZERO);
+        mem2.clip(0, 0, 2, 1);
+        mem2.sspr(0, 0, 2, 2, 1, 0, 4, 4, None);
+        assert_eq!(screen(&mem2, 4, 4), "55..\n....\n....\n....\n");
+    }
+
+    #[test]
+    fn fillp() {
+        let mut mem = mem();
+        mem.fillp(0b1010_0101_1010_0101, false);
+        mem.rectfill(0, 0, 3, 1, 0x21);
+        assert_eq!(screen(&mem, 4, 2), "2121\n1212\n");
+
+        // the pattern stays put on screen when the camera moves
+        let mut mem = self::mem();
+        mem.fillp(0b1010_0101_1010_0101, true);
+        mem.camera(fix(1.0), Fix16::ZERO);
+        mem.rectfill(1, 0, 4, 1, 0x21);
+        assert_eq!(screen(&mem, 4, 2), ".1.1\n1.1.\n");
+
+        // only shapes use it
+        let mut mem = self::mem();
+        mem.fillp(0xffff, true);
+        mem.sset(0, 0, 4);
+        mem.sspr(0, 0, 1, 1, 0, 0, 1, 1, None);
+        mem.line(1, 0, 3, 0, 4);
+        assert_eq!(screen(&mem, 4, 1), "4...\n");
+    }
+
+    #[test]
+    fn pal_display() {
+        let mut mem = mem();
+        mem.pal_display(3, 0x8b);
+        mem.pal_display(4, 0xff);
+        mem.pset(3, 0, 0);
+        // the screen keeps the color drawn, only the display palette changes
+        assert_eq!(mem.pget(0, 0), 3);
+        assert_eq!(mem.display_pallete[3], 0x8b);
+        assert_eq!(mem.display_pallete[4], 0x8f);
+        assert_eq!(mem.peek(DISPLAY_PAL + 3), 0x8b);
+
+        mem.pal(3, 9);
+        mem.pset(3, 1, 0);
+        assert_eq!(screen(&mem, 2, 1), "39\n");
+
+        mem.pal_reset();
+        assert_eq!(mem.display_pallete[3], 3);
+        assert_eq!(mem.pallete[3].color, 3);
+    }
+
+    #[test]
+    fn map_size_comes_from_the_cart() {
+        // a standard map goes on into the shared half of the sprite sheet
+        let mut mem = mem();
+        assert_eq!(mem.map_size(), (128, 64));
+        mem.mset(3, 1, 5);
+        mem.mset(2, 32, 0x21);
+        assert_eq!(mem.mget(3, 1), 5);
+        assert_eq!(mem.peek(MAP + 3 + 128), 5);
+        assert_eq!(mem.mget(2, 32), 0x21);
+        assert_eq!(mem.peek(GFX_SHARED + 2), 0x21);
+        assert_eq!(mem.sget(4, 64), 1);
+        mem.mset(-1, 0, 9);
+        mem.mset(0, 64, 9);
+        assert_eq!(mem.mget(-1, 0), 0);
+        assert_eq!(mem.mget(0, 64), 0);
+
+        // any other size is all in the map
+        let mut cart = mem.cart();
+        cart.map = Rc::new(vec![0; 300 * 100]);
+        cart.map_width = 300;
+        let mut mem = Memory::from_cart(&cart);
+        assert_eq!(mem.map_size(), (300, 100));
+        mem.mset(299, 99, 7);
+        mem.mset(2, 32, 8);
+        assert_eq!(mem.mget(299, 99), 7);
+        assert_eq!(mem.map[299 + 99 * 300], 7);
+        assert_eq!(mem.mget(2, 32), 8);
+        assert_eq!(mem.mget(300, 0), 0);
+        assert_eq!(mem.sget(4, 64), 1);
+    }
+
+    #[test]
+    fn map_addresses_follow_the_map_width() {
+        let mut cart = mem().cart();
+        cart.map = Rc::new(vec![0; 200 * 40]);
+        cart.map_width = 200;
+        let mut mem = Memory::from_cart(&cart);
+        mem.mset(5, 2, 9);
+        mem.mset(130, 0, 4);
+        assert_eq!(mem.peek(MAP + 5 + 2 * 128), 9);
+        mem.poke(MAP + 127 + 31 * 128, 3);
+        assert_eq!(mem.mget(127, 31), 3);
+        assert_eq!(mem.map[127 + 31 * 200], 3);
+        // only the top left 128x32 tiles have addresses
+        assert!((MAP..0x3000).all(|addr| mem.peek(addr) != 4));
+    }
+
+    #[test]
+    fn draw_state_pokes() {
+        let mut mem = mem();
+        mem.poke(DRAW_PAL + 3, 0x19);
+        assert_eq!(mem.pallete[3].color, 9);
+        assert!(mem.pallete[3].transparent);
+        mem.pal(4, 5);
+        assert_eq!(mem.peek(DRAW_PAL + 4), 5);
+
+        mem.clip(4, 5, 10, 20);
+        assert_eq!([0, 1, 2, 3].map(|i| mem.peek(CLIP + i)), [4, 5, 14, 25]);
+        mem.poke(CLIP + 2, 200);
+        assert_eq!(mem.clip.x1, 128);
+
+        mem.poke(PEN, 12);
+        assert_eq!(mem.pen, 12);
+        mem.cursor(7, 9);
+        assert_eq!((mem.peek(CURSOR), mem.peek(CURSOR + 1)), (7, 9));
+
+        mem.poke2(CAMERA, -3);
+        mem.poke2(CAMERA + 2, 300);
+        assert_eq!((mem.camera.x, mem.camera.y), (fix(-3.0), fix(300.0)));
+        assert_eq!(mem.peek2(CAMERA), -3);
+
+        mem.fillp(0xa5f0, true);
+        assert_eq!(mem.peek2(FILL_PATTERN) as u16, 0xa5f0);
+        assert_eq!(mem.peek(FILL_PATTERN + 2), 1);
+        mem.poke(FILL_PATTERN + 2, 0);
+        assert!(!mem.fill_pattern.transparent);
+
+        mem.poke(BUTTONS, 0b100101);
+        assert_eq!(mem.buttons, [true, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn screen_sprites_and_map_stay_coherent() {
+        let mut mem = mem();
+        // two pixels per byte, the left one in the low nibble
+        mem.poke(SCREEN + 1, 0x7c);
+        assert_eq!((mem.pget(2, 0), mem.pget(3, 0)), (12, 7));
+        mem.pset(5, 0, 1);
+        assert_eq!(mem.peek(SCREEN + 64), 5);
+
+        mem.poke(GFX + 64, 0x21);
+        assert_eq!((mem.sget(0, 1), mem.sget(1, 1)), (1, 2));
+        mem.spr(0, 0, 8, None);
+        assert_eq!(screen(&mem, 2, 10).lines().nth(9), Some("12"));
+
+        mem.poke(MAP + 3, 42);
+        assert_eq!(mem.mget(3, 0), 42);
+        mem.mset(0, 33, 0x65);
+        assert_eq!(mem.peek(GFX_SHARED + 128), 0x65);
+        assert_eq!((mem.sget(0, 66), mem.sget(1, 66)), (5, 6));
+    }
+
+    #[test]
+    fn wide_reads_and_copies() {
+        let mut mem = mem();
+        mem.poke4(USER_DATA, fix(-1.5));
+        assert_eq!(mem.peek4(USER_DATA), fix(-1.5));
+        assert_eq!(mem.peek2(USER_DATA + 2), -2);
+        assert_eq!(mem.peek2(USER_DATA), -0x8000);
+        assert_eq!(mem.peek(USER_DATA + 1), 0x80);
+
+        mem.memset(USER_DATA, 7, 3);
+        assert_eq!(
+            [0, 1, 2, 3].map(|i| mem.peek(USER_DATA + i)),
+            [7, 7, 7, 0xff]
+        );
+        // overlapping both ways
+        for i in 0..4 {
+            mem.poke(SFX + i, i as u8 + 1);
+        }
+        mem.memcpy(SFX + 1, SFX, 3);
+        assert_eq!([0, 1, 2, 3].map(|i| mem.peek(SFX + i)), [1, 1, 2, 3]);
+        mem.memcpy(SFX, SFX + 1, 3);
+        assert_eq!([0, 1, 2, 3].map(|i| mem.peek(SFX + i)), [1, 2, 3, 3]);
+        // across regions: the screen into the sprite sheet
+        mem.cls(0);
+        mem.pset(9, 1, 0);
+        mem.memcpy(GFX, SCREEN, 64);
+        assert_eq!(mem.sget(1, 0), 9);
+        // out of range reads are 0 and writes are dropped
+        mem.poke(RAM_SIZE, 1);
+        assert_eq!(mem.peek(RAM_SIZE), 0);
+    }
+
+    #[test]
+    fn fget_reads_one_bit_of_fget_all() {
+        let mut mem = mem();
+        for (sprite, flags) in [(0, 0), (1, 0b1), (2, 0b10), (3, 0b1010_0101), (4, 0xff)] {
+            Rc::make_mut(&mut mem.flags)[sprite as usize] = flags;
+            for idx in 0..8 {
+                assert_eq!(
+                    mem.fget(sprite, idx),
+                    mem.fget_all(sprite) >> idx & 1 == 1,
+                    "sprite {} flag {}",
+                    sprite,
+                    idx
+                );
+            }
+        }
+        mem.fset(5, 6, true);
+        assert!(mem.fget(5, 6));
+        assert_eq!(mem.fget_all(5), 0b100_0000);
+        mem.fset(5, 6, false);
+        assert!(!mem.fget(5, 6));
     }
 }
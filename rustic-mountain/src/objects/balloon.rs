@@ -3,37 +3,49 @@ use alloc::rc::Rc;
 
 use rand::Rng;
 
-use crate::{structures::*, utils::*, Celeste};
+use crate::{fixed::*, objects::player::Player, structures::*, utils::*, Celeste};
 
+#[derive(Clone)]
 pub struct Balloon {
-    offset: f32,
+    offset: Fix16,
     timer: f32,
-    start: f32,
+    start: Fix16,
 }
+crate::snapshot::snapshot_fields!(Balloon {
+    offset,
+    timer,
+    start
+});
 impl Balloon {
-    pub fn init(celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 22,
             hitbox: Rectangle {
-                x: -1.0,
-                y: -1.0,
-                w: 10.0,
-                h: 10.0,
+                x: fix(-1.0),
+                y: fix(-1.0),
+                w: fix(10.0),
+                h: fix(10.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
             solids: false,
             obj_type: ObjectType::Balloon(Rc::new(RefCell::new(Self {
-                offset: celeste.mem.rng.gen_range(0.0..1.0),
+                offset: fix(celeste.mem.rng.gen_range(0.0..1.0)),
                 timer: 0.0,
                 start: y,
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Balloon",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -43,33 +55,24 @@ impl Balloon {
         };
         let mut this = tref.borrow_mut();
         if obj.spr == 22 {
-            this.offset += 0.01;
-            obj.pos.y = this.start + sin(this.offset) * 2.0;
-            let hit = obj.check(celeste, "Player", 0.0, 0.0);
-            match hit {
-                Some(i) => {
-                    let jref = celeste.objects[i].clone();
-                    let mut playerobj = jref.borrow_mut();
-                    let pref = match &mut playerobj.obj_type {
-                        ObjectType::Player(p) => p.clone(),
-                        _ => unreachable!(),
-                    };
-                    let mut player = pref.borrow_mut();
-                    if player.djump < celeste.max_djump {
-                        //psfx 6
-                        obj.init_smoke(celeste, 0.0, 0.0);
-                        player.djump = celeste.max_djump;
-                        obj.spr = 0;
-                        this.timer = 60.0;
-                    }
+            this.offset += fix(0.01);
+            obj.pos.y = this.start + sin(this.offset) * fix(2.0);
+            let hit = obj.check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO);
+            if let Some(pref) = hit.and_then(|id| celeste.get::<Player>(id)) {
+                let mut player = pref.borrow_mut();
+                if player.djump < celeste.dash_count() {
+                    celeste.psfx(6);
+                    obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
+                    player.djump = celeste.dash_count();
+                    obj.spr = 0;
+                    this.timer = 60.0;
                 }
-                None => (),
             }
         } else if this.timer > 0.0 {
             this.timer -= 1.0;
         } else {
-            // psfx 7
-            obj.init_smoke(celeste, 0.0, 0.0);
+            celeste.psfx(7);
+            obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
             obj.spr = 22;
         }
     }
@@ -81,9 +84,9 @@ impl Balloon {
         let this = tref.borrow_mut();
         if obj.spr == 22 {
             celeste.mem.spr(
-                (13.0 + (this.offset * 8.0) % 3.0) as u8,
-                obj.pos.x as i32,
-                (obj.pos.y + 6.0) as i32,
+                (fix(13.0) + (this.offset * fix(8.0)) % fix(3.0)).to_int() as u8,
+                obj.pos.x.to_int(),
+                (obj.pos.y + fix(6.0)).to_int(),
                 None,
             );
             obj.draw_sprite(celeste);
@@ -3,34 +3,48 @@ use alloc::{rc::Rc, vec::Vec, vec};
 
 use rand::Rng;
 
-use crate::{structures::*, Celeste};
+use crate::{events::GameEvent, fixed::*, structures::*, Celeste};
 
 use super::orb::Orb;
 
+#[derive(Clone)]
 pub struct BigChest {
     state: u8,
     timer: f32,
     particles: Vec<ChestParticle>,
 }
+crate::snapshot::snapshot_fields!(BigChest {
+    state,
+    timer,
+    particles
+});
 
+#[derive(Clone)]
 struct ChestParticle {
     x: f32,
     y: f32,
     h: f32,
     spd: f32,
 }
+crate::snapshot::snapshot_fields!(ChestParticle { x, y, h, spd });
 impl BigChest {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 1,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 16.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(16.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
@@ -42,41 +56,37 @@ impl BigChest {
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "BigChest",
+            id: ObjectId::default(),
         }
     }
-    pub fn update(_obj: &mut Object, _celeste: &mut Celeste) {}
-    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+    pub fn update(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
             ObjectType::BigChest(p) => p.clone(),
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
         if this.state == 0 {
-            let hit = obj.check(celeste, "Player", 0.0, 8.0);
-            match hit {
-                Some(i) => {
-                    let jref = celeste.objects[i].clone();
-                    let mut playerobj = jref.borrow_mut();
-                    if playerobj.is_solid(0.0, 1.0, celeste) {
-                        // music -1 500 7
-                        // sfx 37
-                        celeste.pause_player = true;
-                        playerobj.spd = Vector { x: 0.0, y: 0.0 };
-                        this.state = 1;
-                        obj.init_smoke(celeste, 0.0, 0.0);
-                        obj.init_smoke(celeste, 8.0, 0.0);
-                        this.timer = 60.0;
-                    }
+            let hit = obj.check(celeste, ObjectKind::Player, Fix16::ZERO, fix(8.0));
+            if let Some(jref) = hit.and_then(|id| celeste.get_object(id)) {
+                let mut playerobj = jref.borrow_mut();
+                if playerobj.is_solid(Fix16::ZERO, Fix16::ONE, celeste) {
+                    // music -1 500 7
+                    celeste.sfx(37);
+                    celeste.emit(GameEvent::ChestOpened {
+                        level: celeste.level,
+                        big: true,
+                    });
+                    celeste.pause_player = true;
+                    playerobj.spd = Vector {
+                        x: Fix16::ZERO,
+                        y: Fix16::ZERO,
+                    };
+                    this.state = 1;
+                    obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
+                    obj.init_smoke(celeste, fix(8.0), Fix16::ZERO);
+                    this.timer = 60.0;
                 }
-                _ => (),
             }
-            celeste
-                .mem
-                .spr(96, obj.pos.x as i32, obj.pos.y as i32, None);
-            celeste
-                .mem
-                .spr(97, obj.pos.x as i32 + 8, obj.pos.y as i32, None);
         } else if this.state == 1 {
             this.timer -= 1.0;
             celeste.shake = 5;
@@ -89,35 +99,146 @@ impl BigChest {
                     spd: celeste.mem.rng.gen_range(8.0..16.0),
                 });
             }
-            if this.timer < 0.0 {
-                this.state = 2;
-                this.particles.clear();
-                celeste.flash_bg = false;
-                celeste.new_bg = true;
-                let obj = Rc::new(RefCell::new(Orb::init(
-                    celeste,
-                    obj.pos.x + 4.0,
-                    obj.pos.y + 4.0,
-                )));
-                celeste.objects.push(obj);
-                celeste.pause_player = false;
-            }
             for particle in &mut this.particles {
                 particle.y += particle.spd;
-                celeste.mem.rectfill(
-                    (obj.pos.x + particle.x) as i32,
-                    (obj.pos.y + 8.0 - particle.y) as i32,
-                    (obj.pos.x + particle.x) as i32,
-                    (obj.pos.y + 8.0).min(obj.pos.y + 8.0 - particle.y + particle.h) as i32,
-                    7,
-                )
             }
         }
+    }
+    /// Lets the orb out once the chest is done opening. This runs after every object has
+    /// updated, since the cart does it in the chest's draw: the orb doesn't move until the next
+    /// tick, and neither does the player
+    pub fn late_update(obj: &mut Object, celeste: &mut Celeste) {
+        let tref = match &mut obj.obj_type {
+            ObjectType::BigChest(p) => p.clone(),
+            _ => unreachable!(),
+        };
+        let mut this = tref.borrow_mut();
+        if this.state == 1 && this.timer < 0.0 {
+            this.state = 2;
+            this.particles.clear();
+            celeste.flash_bg = false;
+            celeste.new_bg = true;
+            let orb = Orb::init(celeste, obj.pos.x + fix(4.0), obj.pos.y + fix(4.0));
+            celeste.objects.spawn(orb);
+            celeste.pause_player = false;
+        }
+    }
+    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+        let tref = match &mut obj.obj_type {
+            ObjectType::BigChest(p) => p.clone(),
+            _ => unreachable!(),
+        };
+        let this = tref.borrow();
+        if this.state == 0 {
+            celeste
+                .mem
+                .spr(96, obj.pos.x.to_int(), obj.pos.y.to_int(), None);
+            celeste
+                .mem
+                .spr(97, obj.pos.x.to_int() + 8, obj.pos.y.to_int(), None);
+        }
+        for particle in &this.particles {
+            celeste.mem.rectfill(
+                (obj.pos.x.to_f32() + particle.x) as i32,
+                (obj.pos.y.to_f32() + 8.0 - particle.y) as i32,
+                (obj.pos.x.to_f32() + particle.x) as i32,
+                (obj.pos.y.to_f32() + 8.0).min(obj.pos.y.to_f32() + 8.0 - particle.y + particle.h)
+                    as i32,
+                7,
+            )
+        }
         celeste
             .mem
-            .spr(112, obj.pos.x as i32, obj.pos.y as i32 + 8, None);
+            .spr(112, obj.pos.x.to_int(), obj.pos.y.to_int() + 8, None);
         celeste
             .mem
-            .spr(113, obj.pos.x as i32 + 8, obj.pos.y as i32 + 8, None);
+            .spr(113, obj.pos.x.to_int() + 8, obj.pos.y.to_int() + 8, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{practice::Practice, testcart};
+
+    /// the room with the big chest in it, with the player put down next to the chest
+    fn chest_room() -> Celeste {
+        let mut celeste = testcart::celeste();
+        celeste.seed(5);
+        let level = (0..=celeste.layout.summit())
+            .find(|&level| {
+                celeste.begin_practice(Practice {
+                    level,
+                    ..Practice::default()
+                });
+                celeste
+                    .objects
+                    .of_kind(ObjectKind::BigChest)
+                    .next()
+                    .is_some()
+            })
+            .unwrap();
+        assert_eq!(celeste.level, level);
+        while celeste.objects.of_kind(ObjectKind::Player).next().is_none() {
+            celeste.next_tick();
+        }
+        let chest = celeste
+            .objects
+            .of_kind(ObjectKind::BigChest)
+            .next()
+            .unwrap()
+            .1;
+        let pos = chest.borrow().pos.clone();
+        let player = celeste
+            .objects
+            .of_kind(ObjectKind::Player)
+            .next()
+            .unwrap()
+            .1;
+        player.borrow_mut().pos = Vector {
+            x: pos.x + fix(4.0),
+            y: pos.y,
+        };
+        celeste
+    }
+
+    #[test]
+    fn drawing_doesnt_change_the_game() {
+        let mut ticked = chest_room();
+        let mut drawn = chest_room();
+        let mut orb_spawned = None;
+        for tick in 0..200 {
+            // jump for the orb once it's out
+            let buttons = vec![
+                false,
+                false,
+                false,
+                false,
+                (130..140).contains(&tick),
+                false,
+            ];
+            ticked.mem.buttons = buttons.clone();
+            drawn.mem.buttons = buttons;
+            ticked.next_tick();
+            drawn.next_tick();
+            drawn.draw();
+            assert!(
+                ticked.save_state().data == drawn.save_state().data,
+                "tick {}",
+                tick
+            );
+
+            if let Some((_, orb)) = ticked.objects.of_kind(ObjectKind::Orb).next() {
+                let orb = orb.borrow();
+                // the orb sits where it spawned until the tick after
+                if orb_spawned.is_none() {
+                    assert_eq!(orb.spd.y, fix(-3.5));
+                    orb_spawned = Some(orb.pos.y);
+                } else if orb_spawned == Some(orb.pos.y) {
+                    panic!("the orb didn't move on tick {}", tick);
+                }
+            }
+        }
+        assert!(orb_spawned.is_some());
     }
 }
@@ -3,35 +3,43 @@ use alloc::rc::Rc;
 
 use rand::Rng;
 
-use crate::{structures::*, Celeste};
+use crate::{events::GameEvent, fixed::*, structures::*, Celeste};
 
+#[derive(Clone)]
 pub struct Chest {
-    start: f32,
+    start: Fix16,
     timer: i32,
 }
+crate::snapshot::snapshot_fields!(Chest { start, timer });
 impl Chest {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
-            pos: Vector { x: x - 4.0, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            pos: Vector { x: x - fix(4.0), y },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 20,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
             solids: false,
             obj_type: ObjectType::Chest(Rc::new(RefCell::new(Self {
-                start: x - 4.0,
+                start: x - fix(4.0),
                 timer: 20,
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Chest",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -42,9 +50,13 @@ impl Chest {
             };
             let mut this = tref.borrow_mut();
             this.timer -= 1;
-            obj.pos.x = this.start - 1.0 + celeste.mem.rng.gen_range(0.0..3.0);
+            obj.pos.x = this.start - Fix16::ONE + fix(celeste.mem.rng.gen_range(0.0..3.0));
             if this.timer <= 0 {
-                obj.init_fruit(celeste, 0.0, -4.0);
+                celeste.emit(GameEvent::ChestOpened {
+                    level: celeste.level,
+                    big: false,
+                });
+                obj.init_fruit(celeste, Fix16::ZERO, fix(-4.0));
             }
         }
     }
@@ -0,0 +1,332 @@
+//! Objects defined outside this crate. Implement `GameObject` for your type, then register it
+//! for the map tiles it should spawn from with `Celeste::register_object`:
+//!
+//! ```
+//! use rustic_mountain_core::{
+//!     fixed::Fix16,
+//!     objects::custom::{GameObject, ObjectFlags},
+//!     structures::Object,
+//!     Celeste,
+//! };
+//!
+//! #[derive(Clone)]
+//! struct MovingBlock { dir: Fix16 }
+//! impl GameObject for MovingBlock {
+//!     fn init(_celeste: &mut Celeste, _x: Fix16, _y: Fix16, _tile: u8) -> Self {
+//!         MovingBlock { dir: Fix16::ONE }
+//!     }
+//!     fn flags(&self) -> ObjectFlags {
+//!         ObjectFlags { solid: true, ..Default::default() }
+//!     }
+//!     fn update(&mut self, obj: &mut Object, _celeste: &mut Celeste) {
+//!         obj.spd.x = self.dir;
+//!     }
+//! }
+//! # let mut celeste = Celeste::new(
+//! #     "00".repeat(128 * 32),
+//! #     "0".repeat(128 * 128),
+//! #     "00".repeat(256),
+//! #     String::new(),
+//! # );
+//! celeste.register_object::<MovingBlock>(65);
+//! ```
+use core::{
+    any::{Any, TypeId},
+    cell::RefCell,
+};
+use alloc::rc::Rc;
+
+use crate::{fixed::*, structures::*, Celeste};
+
+/// An object type that lives outside the built in `ObjectType`s. Every hook gets the `Object`
+/// holding the shared state (position, speed, hitbox, sprite) alongside `self`. Needs `Clone`,
+/// so cloning a `Celeste` can give each copy its own state
+pub trait GameObject: AsAny + CloneState + 'static {
+    /// Creates the object for `tile`, found in the map at (x, y)
+    fn init(celeste: &mut Celeste, x: Fix16, y: Fix16, tile: u8) -> Self
+    where
+        Self: Sized;
+    /// relative to the object's position. only read once, when the object spawns
+    fn hitbox(&self) -> Rectangle {
+        Rectangle {
+            x: Fix16::ZERO,
+            y: Fix16::ZERO,
+            w: fix(8.0),
+            h: fix(8.0),
+        }
+    }
+    /// only read once, when the object spawns
+    fn flags(&self) -> ObjectFlags {
+        ObjectFlags::default()
+    }
+    /// runs every tick, after the object has moved by its speed. Move or check other objects
+    /// through `Object::with_object`, so they see this one where it is now
+    fn update(&mut self, _obj: &mut Object, _celeste: &mut Celeste) {}
+    /// runs with `obj` still borrowed from its slot, so don't borrow it again through
+    /// `celeste.objects`
+    fn draw(&mut self, obj: &mut Object, celeste: &mut Celeste) {
+        obj.draw_sprite(celeste);
+    }
+    /// runs after `update` on every tick the player overlaps the object
+    fn collide(&mut self, _obj: &mut Object, _player: ObjectId, _celeste: &mut Celeste) {}
+}
+
+/// Lets `Objects::get_custom` get the concrete type back out of a `dyn GameObject`. implemented
+/// for everything, so there's no need to implement it yourself
+pub trait AsAny {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+impl<T: Any> AsAny for T {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Copies a `dyn GameObject`. implemented for everything `Clone`, so there's no need to implement
+/// it yourself
+pub trait CloneState {
+    fn clone_state(&self) -> Rc<RefCell<dyn GameObject>>;
+}
+impl<T: GameObject + Clone> CloneState for T {
+    fn clone_state(&self) -> Rc<RefCell<dyn GameObject>> {
+        Rc::new(RefCell::new(self.clone()))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectFlags {
+    /// whether `check` (and so `collide`) can find it
+    pub collidable: bool,
+    /// whether it stops against solid tiles and objects when moving, like the player does
+    pub solids: bool,
+    /// whether the player, and anything else with `solids`, is stopped by it, collidable or not
+    pub solid: bool,
+}
+
+impl Default for ObjectFlags {
+    fn default() -> Self {
+        ObjectFlags {
+            collidable: true,
+            solids: false,
+            solid: false,
+        }
+    }
+}
+
+/// The `ObjectType` of a `GameObject`. Cloning shares the state, see `deep_clone`
+#[derive(Clone)]
+pub struct CustomObject {
+    pub type_id: TypeId,
+    pub flags: ObjectFlags,
+    pub state: Rc<RefCell<dyn GameObject>>,
+}
+
+impl CustomObject {
+    /// A copy with its own copy of the state
+    pub fn deep_clone(&self) -> CustomObject {
+        CustomObject {
+            type_id: self.type_id,
+            flags: self.flags,
+            state: self.state.borrow().clone_state(),
+        }
+    }
+}
+
+/// The constructor `Celeste::register_object` registers for `T`
+pub fn init<T: GameObject>(celeste: &mut Celeste, x: Fix16, y: Fix16, tile: u8) -> Option<Object> {
+    let state = T::init(celeste, x, y, tile);
+    let flags = state.flags();
+    Some(Object {
+        pos: Vector { x, y },
+        spd: Vector {
+            x: Fix16::ZERO,
+            y: Fix16::ZERO,
+        },
+        rem: Vector {
+            x: Fix16::ZERO,
+            y: Fix16::ZERO,
+        },
+        spr: tile,
+        hitbox: state.hitbox(),
+        flip: FlipState { x: false, y: false },
+        collidable: flags.collidable,
+        solids: flags.solids,
+        obj_type: ObjectType::Custom(CustomObject {
+            type_id: TypeId::of::<T>(),
+            flags,
+            state: Rc::new(RefCell::new(state)),
+        }),
+        draw: ObjFunc(draw),
+        update: ObjFunc(update),
+        id: ObjectId::default(),
+    })
+}
+pub(crate) fn update(obj: &mut Object, celeste: &mut Celeste) {
+    let tref = match &mut obj.obj_type {
+        ObjectType::Custom(p) => p.state.clone(),
+        _ => unreachable!(),
+    };
+    let mut this = tref.borrow_mut();
+    this.update(obj, celeste);
+    if let Some(player) = obj.check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO) {
+        this.collide(obj, player, celeste);
+    }
+}
+pub(crate) fn draw(obj: &mut Object, celeste: &mut Celeste) {
+    let tref = match &mut obj.obj_type {
+        ObjectType::Custom(p) => p.state.clone(),
+        _ => unreachable!(),
+    };
+    let mut this = tref.borrow_mut();
+    this.draw(obj, celeste);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory::CartData, objects::player::Player, rooms::Room};
+    use alloc::{vec, vec::Vec};
+
+    #[derive(Clone)]
+    struct Counter {
+        ticks: u32,
+    }
+    impl GameObject for Counter {
+        fn init(_celeste: &mut Celeste, _x: Fix16, _y: Fix16, _tile: u8) -> Self {
+            Counter { ticks: 0 }
+        }
+        fn update(&mut self, _obj: &mut Object, _celeste: &mut Celeste) {
+            self.ticks += 1;
+        }
+    }
+
+    #[test]
+    fn clones_get_their_own_state() {
+        let mut celeste = Celeste::from_cart(&CartData {
+            map: Rc::new(vec![0; 128 * 32]),
+            map_width: 128,
+            sprites: Rc::new(vec![0; 128 * 128]),
+            flags: Rc::new(vec![0; 256]),
+            fontatlas: Rc::new(vec![]),
+        });
+        let obj = init::<Counter>(&mut celeste, Fix16::ZERO, Fix16::ZERO, 1).unwrap();
+        let id = celeste.objects.spawn(obj);
+        celeste.objects.flush(0);
+
+        let mut fork = celeste.clone();
+        fork.next_tick();
+        fork.get_custom::<Counter>(id).unwrap().ticks += 10;
+        fork.get_object(id).unwrap().borrow_mut().pos.x = fix(8.0);
+
+        assert_eq!(fork.get_custom::<Counter>(id).unwrap().ticks, 11);
+        assert_eq!(celeste.get_custom::<Counter>(id).unwrap().ticks, 0);
+        assert_eq!(celeste.get_object(id).unwrap().borrow().pos.x, Fix16::ZERO);
+    }
+
+    /// solid, but not collidable
+    #[derive(Clone)]
+    struct Block {
+        bumps: u32,
+    }
+    impl GameObject for Block {
+        fn init(_celeste: &mut Celeste, _x: Fix16, _y: Fix16, _tile: u8) -> Self {
+            Block { bumps: 0 }
+        }
+        fn flags(&self) -> ObjectFlags {
+            ObjectFlags {
+                collidable: false,
+                solid: true,
+                ..Default::default()
+            }
+        }
+        fn collide(&mut self, _obj: &mut Object, _player: ObjectId, _celeste: &mut Celeste) {
+            self.bumps += 1;
+        }
+    }
+
+    /// the second room has blocks (tile 65) at (8, 24) and (16, 24) and a counter (tile 66) at
+    /// (32, 24)
+    fn blocks() -> Celeste {
+        let mut map = vec![0; 128 * 32];
+        map[17 + 3 * 128] = 65;
+        map[18 + 3 * 128] = 65;
+        map[20 + 3 * 128] = 66;
+        let mut celeste = Celeste::from_cart(&CartData {
+            map: Rc::new(map),
+            map_width: 128,
+            sprites: Rc::new(vec![0; 128 * 128]),
+            flags: Rc::new(vec![0; 256]),
+            fontatlas: Rc::new(vec![]),
+        });
+        celeste.register_object::<Block>(65);
+        celeste.register_object::<Counter>(66);
+        celeste.load_room(Room::screen(1, 0));
+        celeste
+    }
+
+    fn spawn_player(celeste: &mut Celeste, x: f32, y: f32) -> ObjectId {
+        let player = Player::init(celeste, fix(x), fix(y));
+        let id = celeste.objects.spawn(player);
+        celeste.objects.flush(0);
+        id
+    }
+
+    #[test]
+    fn registered_tiles_spawn_with_the_room() {
+        let celeste = blocks();
+        let blocks: Vec<_> = celeste
+            .objects
+            .of_kind(ObjectKind::of::<Block>())
+            .map(|(_, obj)| {
+                let obj = obj.borrow();
+                (obj.pos.x, obj.pos.y, obj.spr, obj.collidable)
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            [
+                (fix(8.0), fix(24.0), 65, false),
+                (fix(16.0), fix(24.0), 65, false)
+            ]
+        );
+        assert_eq!(
+            celeste.objects.of_kind(ObjectKind::of::<Counter>()).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn collide_runs_while_the_player_overlaps() {
+        let mut celeste = blocks();
+        let (block, _) = celeste
+            .objects
+            .of_kind(ObjectKind::of::<Block>())
+            .next()
+            .unwrap();
+        let player = spawn_player(&mut celeste, 8.0, 24.0);
+        celeste.next_tick();
+        assert_eq!(celeste.get_custom::<Block>(block).unwrap().bumps, 1);
+
+        celeste.get_object(player).unwrap().borrow_mut().pos.x = fix(64.0);
+        celeste.next_tick();
+        assert_eq!(celeste.get_custom::<Block>(block).unwrap().bumps, 1);
+    }
+
+    #[test]
+    fn solid_objects_block_movers() {
+        let mut celeste = blocks();
+        let player = spawn_player(&mut celeste, 8.0, 16.0);
+        let player = celeste.get_object(player).unwrap();
+        assert!(player
+            .borrow()
+            .is_solid(Fix16::ZERO, Fix16::ONE, &mut celeste));
+        assert!(!player
+            .borrow()
+            .is_solid(Fix16::ZERO, Fix16::ZERO, &mut celeste));
+        // the counter isn't solid
+        player.borrow_mut().pos.x = fix(32.0);
+        assert!(!player
+            .borrow()
+            .is_solid(Fix16::ZERO, Fix16::ONE, &mut celeste));
+    }
+}
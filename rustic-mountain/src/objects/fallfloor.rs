@@ -1,31 +1,41 @@
 use core::cell::RefCell;
 use alloc::rc::Rc;
 
-use crate::{structures::*, Celeste};
+use crate::{fixed::*, structures::*, Celeste};
 
+use super::spring::Spring;
+
+#[derive(Clone)]
 pub struct FallFloor {
     state: u8,
     delay: u8,
 }
+crate::snapshot::snapshot_fields!(FallFloor { state, delay });
 impl FallFloor {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 23,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
             obj_type: ObjectType::FallFloor(Rc::new(RefCell::new(Self { state: 0, delay: 0 }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "FallFloor",
+            id: ObjectId::default(),
             solids: false,
         }
     }
@@ -38,7 +48,12 @@ impl FallFloor {
         if this.state == 0 {
             for i in 0..3 {
                 if obj
-                    .check(celeste, "Player", (i - 1) as f32, -(i % 2) as f32)
+                    .check(
+                        celeste,
+                        ObjectKind::Player,
+                        Fix16::from_int(i - 1),
+                        Fix16::from_int(-(i % 2)),
+                    )
                     .is_some()
                 {
                     this.break_floor(obj, celeste);
@@ -46,18 +61,23 @@ impl FallFloor {
             }
         } else if this.state == 1 {
             this.delay -= 1;
-            if this.delay <= 0 {
+            if this.delay == 0 {
                 this.state = 2;
                 this.delay = 60;
                 obj.collidable = false;
             }
         } else if this.state == 2 {
-            this.delay -= 1;
-            if this.delay <= 0 && obj.check(celeste, "Player", 0.0, 0.0).is_none() {
-                // psfx 7
+            // stays at 0 until the player is out of the way, like the cart's goes negative
+            this.delay = this.delay.saturating_sub(1);
+            if this.delay == 0
+                && obj
+                    .check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO)
+                    .is_none()
+            {
+                celeste.psfx(7);
                 this.state = 0;
                 obj.collidable = true;
-                obj.init_smoke(celeste, 0.0, 0.0);
+                obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
             }
         }
     }
@@ -76,8 +96,8 @@ impl FallFloor {
             } else {
                 0
             },
-            obj.pos.x as i32,
-            obj.pos.y as i32,
+            obj.pos.x.to_int(),
+            obj.pos.y.to_int(),
             None,
         );
         // this.state==1 and
@@ -86,22 +106,13 @@ impl FallFloor {
     }
     pub fn break_floor(&mut self, obj: &mut Object, celeste: &mut Celeste) {
         if self.state == 0 {
-            //psfx 15
+            celeste.psfx(15);
             self.state = 1;
             self.delay = 15;
-            obj.init_smoke(celeste, 0.0, 0.0);
-            let springdex = obj.check(celeste, "Spring", 0.0, -1.0);
-            match springdex {
-                Some(i) => {
-                    let jref = celeste.objects[i].clone();
-                    let mut springobj = jref.borrow_mut();
-                    let pref = match &mut springobj.obj_type {
-                        ObjectType::Spring(p) => p.clone(),
-                        _ => unreachable!(),
-                    };
-                    pref.borrow_mut().hide_in = 15;
-                }
-                None => (),
+            obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
+            let springdex = obj.check(celeste, ObjectKind::Spring, Fix16::ZERO, fix(-1.0));
+            if let Some(spring) = springdex.and_then(|id| celeste.get::<Spring>(id)) {
+                spring.borrow_mut().hide_in = 15;
             }
         }
     }
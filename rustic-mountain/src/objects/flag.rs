@@ -1,24 +1,32 @@
 use core::cell::RefCell;
 use alloc::{format, rc::Rc};
 
-use crate::{draw_time, structures::*, Celeste};
+use crate::{draw_time, events::GameEvent, fixed::*, structures::*, Celeste};
 
+#[derive(Clone)]
 pub struct Flag {
     score: u8,
     show: bool,
 }
+crate::snapshot::snapshot_fields!(Flag { score, show });
 impl Flag {
-    pub fn init(celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
-            pos: Vector { x: x + 5.0, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            pos: Vector { x: x + fix(5.0), y },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 1,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
@@ -34,32 +42,135 @@ impl Flag {
             }))), // score =
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Flag",
+            id: ObjectId::default(),
         }
     }
-    pub fn update(_obj: &mut Object, _celeste: &mut Celeste) {}
-    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+    pub fn update(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
             ObjectType::Flag(p) => p.clone(),
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
 
-        obj.spr = 118 + ((celeste.frames / 5) % 3) as u8;
+        obj.spr = 118 + (celeste.frames / 5) % 3;
+        if !this.show
+            && obj
+                .check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO)
+                .is_some()
+        {
+            celeste.sfx(55);
+            celeste.sfx_timer = 30;
+            celeste.emit(GameEvent::SummitReached);
+            this.show = true;
+        }
+    }
+    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+        let tref = match &mut obj.obj_type {
+            ObjectType::Flag(p) => p.clone(),
+            _ => unreachable!(),
+        };
+        let this = tref.borrow();
+
         obj.draw_sprite(celeste);
 
         if this.show {
-            celeste.mem.rectfill(32, 2, 96, 31, 0);
-            celeste.mem.spr(26, 55, 6, None);
-            celeste.mem.print(&format!("x{}", this.score), 64, 9, 7);
-            draw_time(celeste, 49, 16);
+            celeste.draw_fixed(|celeste| {
+                celeste.mem.rectfill(32, 2, 96, 31, 0);
+                celeste.mem.spr(26, 55, 6, None);
+                celeste.mem.print(&format!("x{}", this.score), 64, 9, 7);
+                draw_time(celeste, 49, 16);
+                celeste
+                    .mem
+                    .print(&format!("deaths:{}", celeste.deaths), 48, 24, 7);
+                Self::draw_room_deaths(celeste);
+            });
+        }
+    }
+    /// deaths in each room below the score, 100m-1000m on the first row, and so on. Only the
+    /// first 30 rooms fit
+    fn draw_room_deaths(celeste: &mut Celeste) {
+        celeste.mem.rectfill(3, 34, 124, 69, 0);
+        let title = if celeste.splits.assisted {
+            "deaths per room (assisted)"
+        } else {
+            "deaths per room"
+        };
+        let (w, _) = celeste.mem.measure(title);
+        celeste.mem.print(title, 64 - w / 2, 36, 7);
+        for (i, room) in celeste
+            .room_stats
+            .iter()
+            .take(celeste.layout.summit().min(30) as usize)
+            .enumerate()
+        {
+            let x = 5 + (i as i32 % 10) * 12;
+            let y = 44 + (i as i32 / 10) * 8;
+            let col = match room.deaths {
+                0 => 5,
+                1..=9 => 6,
+                _ => 8,
+            };
             celeste
                 .mem
-                .print(&format!("deaths:{}", celeste.deaths), 48, 24, 7);
-        } else if obj.check(celeste, "Player", 0.0, 0.0).is_some() {
-            // sfx 55
-            // sfx timer = 30
-            this.show = true;
+                .print(&format!("{}", room.deaths.min(999)), x, y, col);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{practice::Practice, testcart};
+    use alloc::vec;
+
+    /// the summit, with the player put down next to the flag
+    fn summit() -> Celeste {
+        let mut celeste = testcart::celeste();
+        celeste.seed(9);
+        celeste.begin_practice(Practice {
+            level: celeste.layout.summit(),
+            ..Practice::default()
+        });
+        while celeste.objects.of_kind(ObjectKind::Player).next().is_none() {
+            celeste.next_tick();
+        }
+        let flag = celeste.objects.of_kind(ObjectKind::Flag).next().unwrap().1;
+        let pos = flag.borrow().pos.clone();
+        let player = celeste
+            .objects
+            .of_kind(ObjectKind::Player)
+            .next()
+            .unwrap()
+            .1;
+        player.borrow_mut().pos = Vector {
+            x: pos.x - fix(12.0),
+            y: pos.y,
+        };
+        celeste
+    }
+
+    #[test]
+    fn drawing_doesnt_change_the_game() {
+        let mut ticked = summit();
+        let mut drawn = summit();
+        let mut reached = false;
+        for tick in 0..120 {
+            // walk right into the flag
+            let buttons = vec![false, tick < 40, false, false, false, false];
+            ticked.mem.buttons = buttons.clone();
+            drawn.mem.buttons = buttons;
+            ticked.next_tick();
+            drawn.next_tick();
+            drawn.draw();
+            assert!(
+                ticked.save_state().data == drawn.save_state().data,
+                "tick {}",
+                tick
+            );
+            reached |= ticked
+                .drain_events()
+                .any(|event| event == GameEvent::SummitReached);
         }
+        assert!(reached);
     }
 }
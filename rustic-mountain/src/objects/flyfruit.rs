@@ -1,34 +1,51 @@
 use core::cell::RefCell;
 use alloc::rc::Rc;
 
-use crate::{structures::*, utils::*, Celeste};
+use crate::{fixed::*, structures::*, utils::*, Celeste};
 
 use super::fruit::check_fruit;
 
+#[derive(Clone)]
 pub struct FlyFruit {
-    off: f32,
-    start: f32,
+    off: Fix16,
+    start: Fix16,
+    sfx_delay: u8,
 }
+crate::snapshot::snapshot_fields!(FlyFruit {
+    off,
+    start,
+    sfx_delay
+});
 impl FlyFruit {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 26,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: false,
             solids: false,
-            obj_type: ObjectType::FlyFruit(Rc::new(RefCell::new(Self { start: y, off: 0.5 }))),
+            obj_type: ObjectType::FlyFruit(Rc::new(RefCell::new(Self {
+                start: y,
+                off: fix(0.5),
+                sfx_delay: 8,
+            }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "FlyFruit",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -38,14 +55,20 @@ impl FlyFruit {
         };
         let mut this = tref.borrow_mut();
         if celeste.has_dashed {
-            // sfx shit
-            obj.spd.y = appr(obj.spd.y, -3.5, 0.25);
-            if obj.spd.y < -16.0 {
+            if this.sfx_delay > 0 {
+                this.sfx_delay -= 1;
+                if this.sfx_delay == 0 {
+                    celeste.sfx_timer = 20;
+                    celeste.sfx(14);
+                }
+            }
+            obj.spd.y = appr(obj.spd.y, fix(-3.5), fix(0.25));
+            if obj.spd.y < fix(-16.0) {
                 obj.destroy_self(celeste);
             }
         } else {
-            this.off += 0.05;
-            obj.spd.y = sin(this.off) * 0.5;
+            this.off += fix(0.05);
+            obj.spd.y = sin(this.off) * fix(0.5);
         }
 
         check_fruit(obj, celeste);
@@ -59,15 +82,15 @@ impl FlyFruit {
         obj.draw_sprite(celeste);
         for i in [-6, 6] {
             celeste.mem.spr(
-                if celeste.has_dashed || sin(this.off) >= 0.0 {
+                if celeste.has_dashed || sin(this.off) >= Fix16::ZERO {
                     45
                 } else if obj.pos.y > this.start {
                     47
                 } else {
                     46
                 },
-                obj.pos.x as i32 + i,
-                obj.pos.y as i32 - 2,
+                obj.pos.x.to_int() + i,
+                obj.pos.y.to_int() - 2,
                 Some(FlipState {
                     x: i == -6,
                     y: false,
@@ -1,34 +1,45 @@
 use core::cell::RefCell;
 use alloc::rc::Rc;
 
-use crate::{structures::*, utils::*, Celeste};
+use crate::{events::GameEvent, fixed::*, structures::*, utils::*, Celeste};
 
-use super::lifeup::LifeUp;
+use super::{lifeup::LifeUp, player::Player};
 
+#[derive(Clone)]
 pub struct Fruit {
-    off: f32,
-    start: f32,
+    off: Fix16,
+    start: Fix16,
 }
+crate::snapshot::snapshot_fields!(Fruit { off, start });
 impl Fruit {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 26,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
             solids: false,
-            obj_type: ObjectType::Fruit(Rc::new(RefCell::new(Self { start: y, off: 0.0 }))),
+            obj_type: ObjectType::Fruit(Rc::new(RefCell::new(Self {
+                start: y,
+                off: Fix16::ZERO,
+            }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Fruit",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -37,8 +48,8 @@ impl Fruit {
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
-        this.off += 0.025;
-        obj.pos.y = this.start + sin(this.off) * 2.5;
+        this.off += fix(0.025);
+        obj.pos.y = this.start + sin(this.off) * fix(2.5);
 
         check_fruit(obj, celeste);
     }
@@ -48,29 +59,22 @@ impl Fruit {
 }
 
 pub fn check_fruit(obj: &mut Object, celeste: &mut Celeste) {
-    match obj.check(celeste, "Player", 0.0, 0.0) {
-        Some(i) => {
-            let jref = celeste.objects[i].clone();
-            let mut playerobj = jref.borrow_mut();
-            let pref = match &mut playerobj.obj_type {
-                ObjectType::Player(p) => p.clone(),
-                _ => unreachable!(),
-            };
-            let mut player = pref.borrow_mut();
-            player.djump = celeste.max_djump;
-            // sfx_timer = 20
-            //sfx 13
-            while celeste.got_fruit.len() <= celeste.level as usize {
-                celeste.got_fruit.push(false);
-            }
-            celeste.got_fruit[celeste.level as usize] = true;
-
-            let lifeup = Rc::new(RefCell::new(LifeUp::init(celeste, obj.pos.x, obj.pos.y)));
-            celeste.objects.push(lifeup);
-            drop(player);
-            drop(playerobj); //manual drop called so that player doesn't get deleted by the retain call
-            obj.destroy_self(celeste);
+    if let Some(id) = obj.check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO) {
+        if let Some(player) = celeste.get::<Player>(id) {
+            player.borrow_mut().djump = celeste.dash_count();
         }
-        None => (),
+        celeste.sfx_timer = 20;
+        celeste.sfx(13);
+        celeste.emit(GameEvent::BerryCollected {
+            level: celeste.level,
+        });
+        while celeste.got_fruit.len() <= celeste.level as usize {
+            celeste.got_fruit.push(false);
+        }
+        celeste.got_fruit[celeste.level as usize] = true;
+
+        let lifeup = LifeUp::init(celeste, obj.pos.x, obj.pos.y);
+        celeste.objects.spawn(lifeup);
+        obj.destroy_self(celeste);
     }
 }
@@ -1,21 +1,29 @@
 use core::cell::RefCell;
 use alloc::rc::Rc;
 
-use crate::{structures::*, utils::*, Celeste};
+use crate::{events::GameEvent, fixed::*, structures::*, utils::*, Celeste};
 
+#[derive(Clone)]
 pub struct Key {}
+crate::snapshot::snapshot_fields!(Key {});
 impl Key {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 22,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
@@ -23,16 +31,24 @@ impl Key {
             obj_type: ObjectType::Key(Rc::new(RefCell::new(Self {}))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Key",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
-        obj.spr = (9.5 + sin(celeste.frames as f32 / 30.0)).floor() as u8;
+        obj.spr =
+            (fix(9.5) + sin(Fix16::from_int(celeste.frames as i32) / fix(30.0))).to_int() as u8;
         if celeste.frames == 18 {
             obj.flip.x = !obj.flip.x;
         }
-        if obj.check(celeste, "Player", 0.0, 0.0).is_some() {
-            // sfx shit
+        if obj
+            .check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO)
+            .is_some()
+        {
+            celeste.sfx(23);
+            celeste.sfx_timer = 10;
+            celeste.emit(GameEvent::KeyCollected {
+                level: celeste.level,
+            });
             celeste.has_key = true;
             obj.destroy_self(celeste);
         }
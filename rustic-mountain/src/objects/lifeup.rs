@@ -1,24 +1,32 @@
 use core::cell::RefCell;
 use alloc::rc::Rc;
 
-use crate::{structures::*, Celeste};
+use crate::{fixed::*, structures::*, Celeste};
 
+#[derive(Clone)]
 pub struct LifeUp {
     duration: f32,
     flash: f32,
 }
+crate::snapshot::snapshot_fields!(LifeUp { duration, flash });
 impl LifeUp {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: -0.25 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: fix(-0.25),
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 1,
             hitbox: Rectangle {
-                x: -1.0,
-                y: -1.0,
-                w: 10.0,
-                h: 10.0,
+                x: fix(-1.0),
+                y: fix(-1.0),
+                w: fix(10.0),
+                h: fix(10.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
@@ -29,7 +37,7 @@ impl LifeUp {
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "LifeUp",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -38,6 +46,7 @@ impl LifeUp {
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
+        this.flash += 0.5;
         this.duration -= 1.0;
         if this.duration <= 0.0 {
             obj.destroy_self(celeste);
@@ -48,13 +57,12 @@ impl LifeUp {
             ObjectType::LifeUp(p) => p.clone(),
             _ => unreachable!(),
         };
-        let mut this = tref.borrow_mut();
-        this.flash += 0.5;
+        let this = tref.borrow();
         celeste.mem.print(
-            "1000".into(),
-            obj.pos.x as i32 - 4,
-            obj.pos.y as i32 - 4,
+            "1000",
+            obj.pos.x.to_int() - 4,
+            obj.pos.y.to_int() - 4,
             7 + (this.flash % 2.0) as u8,
-        )
+        );
     }
 }
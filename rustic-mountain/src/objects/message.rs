@@ -1,24 +1,34 @@
 use core::cell::RefCell;
 use alloc::{boxed::Box, rc::Rc, string::ToString};
 
-use crate::{structures::*, Celeste};
+use crate::{fixed::*, structures::*, Celeste};
 
+#[derive(Clone)]
 pub struct Message {
     index: f32,
     last: f32,
 }
+crate::snapshot::snapshot_fields!(Message { index, last });
+
+const TEXT: &str = "-- celeste mountain --#this memorial to those# perished on the climb";
 impl Message {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 1,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 16.0,
-                h: 16.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(16.0),
+                h: fix(16.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
@@ -29,32 +39,45 @@ impl Message {
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Message",
+            id: ObjectId::default(),
         }
     }
-    pub fn update(_obj: &mut Object, _celeste: &mut Celeste) {}
-    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
-        let text: Box<[char]> =
-            "-- celeste mountain --#this memorial to those# perished on the climb"
-                .chars()
-                .collect();
+    pub fn update(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
             ObjectType::Message(p) => p.clone(),
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
 
-        if obj.check(celeste, "Player", 4.0, 0.0).is_some() {
-            if this.index < text.len() as f32 {
+        if obj
+            .check(celeste, ObjectKind::Player, fix(4.0), Fix16::ZERO)
+            .is_some()
+        {
+            if this.index < TEXT.len() as f32 {
                 this.index += 0.5;
                 if this.index >= this.last + 1.0 {
                     this.last += 1.0;
-                    // sfx 35
+                    celeste.sfx(35);
                 }
             }
+        } else {
+            this.index = 0.0;
+            this.last = 0.0;
+        }
+    }
+    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+        let text: Box<[char]> = TEXT.chars().collect();
+        let tref = match &mut obj.obj_type {
+            ObjectType::Message(p) => p.clone(),
+            _ => unreachable!(),
+        };
+        let this = tref.borrow();
+
+        // `index` goes back to 0 as soon as the player walks away
+        celeste.draw_fixed(|celeste| {
             let mut _x = 8;
             let mut _y = 96;
-            for i in 1..this.index as i32 {
+            for i in 0..this.index as i32 {
                 if text[i as usize] != '#' {
                     celeste.mem.rectfill(_x - 2, _y - 2, _x + 7, _y + 6, 7);
                     celeste.mem.print(&text[i as usize].to_string(), _x, _y, 0);
@@ -64,9 +87,6 @@ impl Message {
                     _y += 7;
                 }
             }
-        } else {
-            this.index = 0.0;
-            this.last = 0.0;
-        }
+        });
     }
 }
@@ -1,6 +1,7 @@
 pub mod balloon;
 pub mod bigchest;
 pub mod chest;
+pub mod custom;
 pub mod fakewall;
 pub mod fallfloor;
 pub mod flag;
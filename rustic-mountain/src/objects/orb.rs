@@ -2,24 +2,37 @@ use core::cell::RefCell;
 use alloc::rc::Rc;
 
 use crate::{
+    events::GameEvent,
+    fixed::*,
+    objects::player::Player,
     structures::*,
     utils::{appr, cos, sin},
     Celeste,
 };
 
+#[derive(Clone)]
 pub struct Orb {}
+crate::snapshot::snapshot_fields!(Orb {});
 impl Orb {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: -4.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            // the cart starts at -4, but spawns the orb in `_draw` where it slows down once
+            // before it first moves
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: fix(-3.5),
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 102,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: false,
@@ -27,47 +40,35 @@ impl Orb {
             obj_type: ObjectType::Orb(Rc::new(RefCell::new(Self {}))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Orb",
+            id: ObjectId::default(),
         }
     }
-    pub fn update(obj: &mut Object, _celeste: &mut Celeste) {}
-    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
-        obj.spd.y = appr(obj.spd.y, 0.0, 0.5);
-        if obj.spd.y == 0.0 {
-            let hit = obj.check(celeste, "Player", 0.0, 0.0);
-            match hit {
-                Some(i) => {
-                    let jref = celeste.objects[i].clone();
-                    let mut playerobj = jref.borrow_mut();
-                    let pref = match &mut playerobj.obj_type {
-                        ObjectType::Player(p) => p.clone(),
-                        _ => unreachable!(),
-                    };
-
-                    let mut player = pref.borrow_mut();
-                    // music timer 45
-                    // sfx 51
-                    celeste.freeze = 10;
-                    celeste.shake = 10;
-                    player.djump = 2;
-                    drop(player);
-                    drop(playerobj);
-                    drop(pref);
-                    drop(jref);
-                    // ^ take out of scope early
-                    obj.destroy_self(celeste);
-                    celeste.max_djump = 2;
+    pub fn update(obj: &mut Object, celeste: &mut Celeste) {
+        obj.spd.y = appr(obj.spd.y, Fix16::ZERO, fix(0.5));
+        if obj.spd.y == Fix16::ZERO {
+            let hit = obj.check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO);
+            if let Some(id) = hit {
+                // music timer 45
+                celeste.sfx(51);
+                celeste.emit(GameEvent::OrbCollected);
+                celeste.freeze = 10;
+                celeste.shake = 10;
+                if let Some(player) = celeste.get::<Player>(id) {
+                    player.borrow_mut().djump = 2;
                 }
-                _ => (),
+                obj.destroy_self(celeste);
+                celeste.max_djump = 2;
             }
         }
-
+    }
+    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
         obj.draw_sprite(celeste);
+        let frames = Fix16::from_int(celeste.frames as i32);
         for x in 0..8 {
-            let i = x as f32 * 0.125;
+            let i = Fix16::from_int(x) * fix(0.125);
             celeste.mem.circfill(
-                (obj.pos.x + 4.0 + cos(celeste.frames as f32 / 30.0 + i) * 8.0) as u8,
-                (obj.pos.y + 4.0 + sin(celeste.frames as f32 / 30.0 + i) * 8.0) as u8,
+                (obj.pos.x + fix(4.0) + cos(frames / fix(30.0) + i) * fix(8.0)).to_int(),
+                (obj.pos.y + fix(4.0) + sin(frames / fix(30.0) + i) * fix(8.0)).to_int(),
                 1,
                 7,
             )
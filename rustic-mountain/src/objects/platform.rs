@@ -1,35 +1,43 @@
 use core::cell::RefCell;
 use alloc::rc::Rc;
 
-use crate::{structures::*, Celeste};
+use crate::{fixed::*, structures::*, Celeste};
 
+#[derive(Clone)]
 pub struct Platform {
-    last: f32,
-    dir: f32,
+    last: Fix16,
+    dir: Fix16,
 }
+crate::snapshot::snapshot_fields!(Platform { last, dir });
 impl Platform {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32, spr: u8) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16, spr: u8) -> Object {
         Object {
-            pos: Vector { x: x - 4.0, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            pos: Vector { x: x - fix(4.0), y },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 16.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(16.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             collidable: true,
             solids: false,
             obj_type: ObjectType::Platform(Rc::new(RefCell::new(Self {
-                last: -4.0,
-                dir: if spr == 11 { -1.0 } else { 1.0 },
+                last: fix(-4.0),
+                dir: if spr == 11 { fix(-1.0) } else { Fix16::ONE },
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Platform",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -38,22 +46,23 @@ impl Platform {
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
-        obj.spd.x = this.dir * 0.65;
-        if obj.pos.x < -16.0 {
-            obj.pos.x = 128.0;
-        } else if obj.pos.x > 128.0 {
-            obj.pos.x = -16.0;
+        obj.spd.x = this.dir * fix(0.65);
+        let width = Fix16::from_int(celeste.room.width());
+        if obj.pos.x < fix(-16.0) {
+            obj.pos.x = width;
+        } else if obj.pos.x > width {
+            obj.pos.x = fix(-16.0);
         }
 
-        if obj.check(celeste, "Player", 0.0, 0.0).is_none() {
-            match obj.check(celeste, "Player", 0.0, -1.0) {
-                Some(pind) => {
-                    let playerref = celeste.objects[pind].clone();
-                    let mut playerobj = playerref.borrow_mut();
-                    playerobj.do_move(celeste, obj.pos.x - this.last, 0.0, 1.0);
-                    // drop(&playerref);
-                }
-                None => (),
+        if obj
+            .check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO)
+            .is_none()
+        {
+            if let Some(id) = obj.check(celeste, ObjectKind::Player, Fix16::ZERO, fix(-1.0)) {
+                let dx = obj.pos.x - this.last;
+                obj.with_object(celeste, id, |player, celeste| {
+                    player.do_move(celeste, dx, Fix16::ZERO, Fix16::ONE)
+                });
             }
         }
         this.last = obj.pos.x;
@@ -67,8 +76,8 @@ impl Platform {
         for i in 0..2 {
             celeste.mem.spr(
                 11 + i,
-                obj.pos.x as i32 + (i * 8) as i32,
-                obj.pos.y as i32 - 1,
+                obj.pos.x.to_int() + (i * 8) as i32,
+                obj.pos.y.to_int() - 1,
                 None,
             )
         }
@@ -4,8 +4,13 @@ use alloc::vec::Vec;
 use alloc::vec;
 
 use crate::utils::mid;
+use crate::events::GameEvent;
 use crate::DeadParticle;
-use crate::{structures::*, utils::*, Celeste};
+use crate::{fixed::*, structures::*, utils::*, Celeste};
+
+/// the cart's `0.70710678118`, as close as an f32 gets. the diagonal dash values are
+/// multiplied out at runtime like in the cart, so they round the same way
+const DIAGONAL: Fix16 = fix(0.707_106_77);
 
 #[derive(Clone)]
 
@@ -15,59 +20,94 @@ pub struct Player {
     pub djump: u8,
     pub dash_time: i32,
     pub dash_effect_time: u8,
-    pub dash_target_effect: f32,
-    pub dash_target_x: f32,
-    pub dash_target_y: f32,
-    pub dash_accel_x: f32,
-    pub dash_accel_y: f32,
-    pub spr_off: f32,
+    pub dash_target_effect: Fix16,
+    pub dash_target_x: Fix16,
+    pub dash_target_y: Fix16,
+    pub dash_accel_x: Fix16,
+    pub dash_accel_y: Fix16,
+    pub spr_off: Fix16,
     pub was_on_ground: bool,
     pub hair: Vec<Vector>,
     pub p_jump: bool,
     pub p_dash: bool,
 }
+crate::snapshot::snapshot_fields!(Player {
+    grace,
+    jbuffer,
+    djump,
+    dash_time,
+    dash_effect_time,
+    dash_target_effect,
+    dash_target_x,
+    dash_target_y,
+    dash_accel_x,
+    dash_accel_y,
+    spr_off,
+    was_on_ground,
+    hair,
+    p_jump,
+    p_dash
+});
 impl Player {
-    pub fn init(celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             flip: FlipState { x: false, y: false },
             spr: 1,
             hitbox: Rectangle {
-                x: 1.0,
-                y: 3.0,
-                w: 6.0,
-                h: 5.0,
+                x: Fix16::ONE,
+                y: fix(3.0),
+                w: fix(6.0),
+                h: fix(5.0),
             },
             collidable: true,
             solids: true,
             obj_type: ObjectType::Player(Rc::new(RefCell::new(Self {
                 grace: 0,
                 jbuffer: 0,
-                dash_accel_x: 0f32,
+                dash_accel_x: Fix16::ZERO,
                 dash_time: 0,
-                dash_accel_y: 0f32,
+                dash_accel_y: Fix16::ZERO,
                 dash_effect_time: 0,
-                dash_target_effect: 0f32,
-                dash_target_x: 0f32,
-                dash_target_y: 0f32,
-                spr_off: 0.0,
+                dash_target_effect: Fix16::ZERO,
+                dash_target_x: Fix16::ZERO,
+                dash_target_y: Fix16::ZERO,
+                spr_off: Fix16::ZERO,
                 p_jump: false,
                 p_dash: false,
                 hair: vec![Vector { x, y }; 4],
-                djump: celeste.max_djump,
+                djump: celeste.dash_count(),
                 was_on_ground: false,
             }))),
-            name: "Player",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
-        if celeste.pause_player {
-            return;
+        if !celeste.pause_player {
+            Self::control(obj, celeste);
         }
+    }
+    /// runs after every object has updated, where the cart draws the player
+    pub fn late_update(obj: &mut Object, celeste: &mut Celeste) {
+        let tref = match &mut obj.obj_type {
+            ObjectType::Player(p) => p.clone(),
+            _ => unreachable!(),
+        };
+        let mut this = tref.borrow_mut();
+        update_player(obj, celeste, &mut this.hair);
+    }
+    /// everything the player does on their own, which stops while `Celeste::pause_player` is set
+    fn control(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
             ObjectType::Player(p) => p.clone(),
             _ => unreachable!(),
@@ -81,23 +121,31 @@ impl Player {
             0
         };
 
-        if celeste.spikes_at(
+        let room = celeste.room;
+        let fell_out = !room.exits.bottom && obj.pos.y > Fix16::from_int(room.height());
+        if celeste.assist.invincible {
+            if fell_out {
+                // bounce back into the room instead of dying
+                obj.spd.y = fix(-5.0);
+                this.djump = celeste.dash_count();
+            }
+        } else if celeste.spikes_at(
             obj.left(),
             obj.top(),
             obj.right(),
             obj.bottom(),
             obj.spd.x,
             obj.spd.y,
-        ) || obj.pos.y > 128.0
+        ) || fell_out
         {
             // spike kill
             this.kill(obj, celeste);
         }
 
-        let on_ground = obj.is_solid(0.0, 1.0, celeste);
+        let on_ground = obj.is_solid(Fix16::ZERO, Fix16::ONE, celeste);
 
         if on_ground && !this.was_on_ground {
-            obj.init_smoke(celeste, 0.0, 4.0);
+            obj.init_smoke(celeste, Fix16::ZERO, fix(4.0));
         }
 
         let jump = celeste.mem.buttons[4] && !this.p_jump;
@@ -113,96 +161,108 @@ impl Player {
         // (celeste.mem.logger)(&format!("g: {}", self.grace));
         if on_ground {
             this.grace = 6;
-            if this.djump < celeste.max_djump {
-                // sfx 54
-                this.djump = celeste.max_djump;
+            if this.djump < celeste.dash_count() {
+                celeste.psfx(54);
+                this.djump = celeste.dash_count();
             }
         } else if this.grace > 0 {
             this.grace -= 1;
         }
+        if celeste.assist.infinite_dashes {
+            this.djump = celeste.dash_count();
+        }
         if this.dash_effect_time > 0 {
             this.dash_effect_time -= 1;
         }
         // self.dash_effect_time -= 1;
         if this.dash_time > 0 {
-            obj.init_smoke(celeste, 0.0, 0.0);
+            obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
             this.dash_time -= 1;
             obj.spd = Vector {
                 x: appr(obj.spd.x, this.dash_target_x, this.dash_accel_x),
                 y: appr(obj.spd.y, this.dash_target_y, this.dash_accel_y), // do something here idk
             }
         } else {
-            let maxrun = 1.0;
-            let decel = 0.15;
+            let maxrun = Fix16::ONE;
+            let decel = fix(0.15);
             // replace with on ice
             let accel = if false {
-                0.05
+                fix(0.05)
             } else {
                 if on_ground {
-                    0.6
+                    fix(0.6)
                 } else {
-                    0.4
+                    fix(0.4)
                 }
             };
 
             obj.spd.x = if obj.spd.x.abs() <= maxrun {
-                appr(obj.spd.x, h_input as f32 * maxrun, accel)
+                appr(obj.spd.x, Fix16::from_int(h_input) * maxrun, accel)
             } else {
                 appr(obj.spd.x, sign(obj.spd.x) * maxrun, decel)
             };
-            if obj.spd.x.abs() != 0f32 {
-                obj.flip.x = obj.spd.x < 0f32;
+            if obj.spd.x.abs() != Fix16::ZERO {
+                obj.flip.x = obj.spd.x < Fix16::ZERO;
             }
 
-            let mut maxfall = 2.0;
+            let mut maxfall = fix(2.0);
 
-            if h_input != 0 && obj.is_solid((h_input * 2) as f32, 0f32, celeste) {
-                maxfall = 0.4;
+            if h_input != 0 && obj.is_solid(Fix16::from_int(h_input * 2), Fix16::ZERO, celeste) {
+                maxfall = fix(0.4);
             }
             if !on_ground {
                 obj.spd.y = appr(
                     obj.spd.y,
                     maxfall,
-                    if obj.spd.y.abs() > 0.15 { 0.21 } else { 0.105 },
+                    if obj.spd.y.abs() > fix(0.15) {
+                        fix(0.21)
+                    } else {
+                        fix(0.105)
+                    },
                 )
             }
 
             if this.jbuffer > 0 {
                 if this.grace > 0 {
+                    celeste.psfx(1);
                     this.jbuffer = 0;
                     this.grace = 0;
-                    obj.spd.y = -2f32;
-                    obj.init_smoke(celeste, 0.0, 4.0)
+                    obj.spd.y = fix(-2.0);
+                    obj.init_smoke(celeste, Fix16::ZERO, fix(4.0))
                 } else {
-                    let wall_dir = if obj.is_solid(-3f32, 0f32, celeste) {
-                        -1f32
-                    } else if obj.is_solid(3f32, 0f32, celeste) {
-                        1f32
+                    let wall_dir = if obj.is_solid(fix(-3.0), Fix16::ZERO, celeste) {
+                        fix(-1.0)
+                    } else if obj.is_solid(fix(3.0), Fix16::ZERO, celeste) {
+                        Fix16::ONE
                     } else {
-                        0f32
+                        Fix16::ZERO
                     };
-                    if wall_dir != 0f32 {
-                        // psfx 2
+                    if wall_dir != Fix16::ZERO {
+                        celeste.psfx(2);
                         this.jbuffer = 0;
                         obj.spd = Vector {
-                            x: wall_dir * (-1f32 - maxrun),
-                            y: -2f32,
+                            x: wall_dir * (fix(-1.0) - maxrun),
+                            y: fix(-2.0),
                         };
-                        if !obj.is_ice(wall_dir * 3.0, 0.0, celeste) {
-                            obj.init_smoke(celeste, wall_dir * 6.0, 0.0);
+                        if !obj.is_ice(wall_dir * fix(3.0), Fix16::ZERO, celeste) {
+                            obj.init_smoke(celeste, wall_dir * fix(6.0), Fix16::ZERO);
                         }
                     }
                 }
             }
 
-            let d_full = 5.0;
-            let d_half = 3.5355339059;
+            let d_full = fix(5.0);
+            let d_half = d_full * DIAGONAL;
             if this.djump > 0 && dash {
-                obj.init_smoke(celeste, 0.0, 0.0);
+                obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
                 this.djump -= 1;
                 this.dash_time = 4;
                 celeste.has_dashed = true;
                 this.dash_effect_time = 10;
+                celeste.psfx(3);
+                celeste.emit(GameEvent::DashUsed {
+                    level: celeste.level,
+                });
 
                 let v_input = if celeste.mem.buttons[2] {
                     -1
@@ -214,38 +274,52 @@ impl Player {
 
                 obj.spd = Vector {
                     x: if h_input != 0 {
-                        h_input as f32 * (if v_input != 0 { d_half } else { d_full })
+                        Fix16::from_int(h_input) * (if v_input != 0 { d_half } else { d_full })
                     } else {
                         if v_input != 0 {
-                            0f32
+                            Fix16::ZERO
                         } else {
                             if obj.flip.x {
-                                -1f32
+                                fix(-1.0)
                             } else {
-                                1f32
+                                Fix16::ONE
                             }
                         }
                     },
                     y: if v_input != 0 {
-                        v_input as f32 * if h_input != 0 { d_half } else { d_full }
+                        Fix16::from_int(v_input) * if h_input != 0 { d_half } else { d_full }
                     } else {
-                        0f32
+                        Fix16::ZERO
                     },
                 };
 
                 celeste.freeze = 2;
                 celeste.shake = 6;
 
-                this.dash_target_x = 2.0 * sign(obj.spd.x);
-                this.dash_target_y = (if obj.spd.y >= 0.0 { 2.0 } else { 1.5 }) * sign(obj.spd.y);
-                this.dash_accel_x = if obj.spd.y == 0.0 { 1.5 } else { 1.06066017177 };
-                this.dash_accel_y = if obj.spd.x == 0.0 { 1.5 } else { 1.06066017177 };
+                this.dash_target_x = fix(2.0) * sign(obj.spd.x);
+                this.dash_target_y = (if obj.spd.y >= Fix16::ZERO {
+                    fix(2.0)
+                } else {
+                    fix(1.5)
+                }) * sign(obj.spd.y);
+                this.dash_accel_x = if obj.spd.y == Fix16::ZERO {
+                    fix(1.5)
+                } else {
+                    fix(1.5) * DIAGONAL
+                };
+                this.dash_accel_y = if obj.spd.x == Fix16::ZERO {
+                    fix(1.5)
+                } else {
+                    fix(1.5) * DIAGONAL
+                };
+            } else if dash && this.djump == 0 {
+                celeste.psfx(9);
             }
         }
 
-        this.spr_off += 0.25;
+        this.spr_off += fix(0.25);
         obj.spr = if !on_ground {
-            if obj.is_solid(h_input as f32 * 2.0, 0.0, celeste) {
+            if obj.is_solid(Fix16::from_int(h_input * 2), Fix16::ZERO, celeste) {
                 5
             } else {
                 3
@@ -256,14 +330,18 @@ impl Player {
             } else if celeste.mem.buttons[2] {
                 7
             } else {
-                if obj.spd.x != 0.0 && h_input != 0 {
-                    (1.0 + this.spr_off % 4.0) as u8
+                if obj.spd.x != Fix16::ZERO && h_input != 0 {
+                    (Fix16::ONE + this.spr_off % fix(4.0)).to_int() as u8
                 } else {
                     1
                 }
             }
         };
-        if obj.pos.y < -4.0 {
+        if (room.exits.top && obj.pos.y < fix(-4.0))
+            || (room.exits.bottom && obj.pos.y > Fix16::from_int(room.height() - 4))
+            || (room.exits.left && obj.pos.x < fix(-4.0))
+            || (room.exits.right && obj.pos.x > Fix16::from_int(room.width() - 4))
+        {
             celeste.next_room();
         }
         this.was_on_ground = on_ground;
@@ -273,39 +351,66 @@ impl Player {
             ObjectType::Player(p) => p.clone(),
             _ => unreachable!(),
         };
-        let mut this = tref.borrow_mut();
-        let djump = this.djump.clone();
-
-        draw_player(obj, celeste, &mut this.hair, djump);
+        let this = tref.borrow();
+        draw_player(obj, celeste, &this.hair, this.djump);
     }
     pub fn kill(&mut self, obj: &mut Object, celeste: &mut Celeste) {
+        celeste.sfx_timer = 12;
+        celeste.sfx(0);
+        celeste.deaths += 1;
+        celeste.emit(GameEvent::PlayerDied {
+            level: celeste.level,
+            x: obj.pos.x.to_int(),
+            y: obj.pos.y.to_int(),
+        });
         obj.destroy_self(celeste);
         celeste.dead_particles.clear();
-        let mut i: f32 = 0.0;
+        let mut i = Fix16::ZERO;
         loop {
             celeste.dead_particles.push(DeadParticle {
-                x: obj.pos.x + 4.0,
-                y: obj.pos.y + 4.0,
+                x: obj.pos.x.to_f32() + 4.0,
+                y: obj.pos.y.to_f32() + 4.0,
                 t: 2.0,
-                dx: sin(i) * 3.0f32,
-                dy: cos(i) * 3.0f32,
+                dx: (sin(i) * fix(3.0)).to_f32(),
+                dy: (cos(i) * fix(3.0)).to_f32(),
             });
 
-            if i >= 0.875 {
+            if i >= fix(0.875) {
                 break;
             }
-            i += 0.125;
+            i += fix(0.125);
         }
         celeste.delay_restart = 15;
     }
 }
-pub fn draw_player(obj: &mut Object, celeste: &mut Celeste, hair: &mut Vec<Vector>, djump: u8) {
-    let clamped = mid(obj.pos.x, -1.0, 121.0);
+/// The part of the cart's `draw_player` that changes things: keeps the player inside the sides
+/// of the room that aren't exits, and pulls the hair along behind them
+pub fn update_player(obj: &mut Object, celeste: &Celeste, hair: &mut [Vector]) {
+    let exits = celeste.room.exits;
+    let left = if exits.left { fix(-8.0) } else { fix(-1.0) };
+    let right = Fix16::from_int(celeste.room.width() - if exits.right { 0 } else { 7 });
+    let clamped = mid(obj.pos.x, left, right);
     if obj.pos.x != clamped {
         obj.pos.x = clamped;
-        obj.spd.x = 0.0;
+        obj.spd.x = Fix16::ZERO;
     }
 
+    let mut last = Vector {
+        x: obj.pos.x + if obj.flip.x { fix(6.0) } else { fix(2.0) },
+        y: obj.pos.y
+            + if celeste.mem.buttons[3] {
+                fix(4.0)
+            } else {
+                fix(3.0)
+            },
+    };
+    for h in hair.iter_mut() {
+        h.x += (last.x - h.x) / fix(1.5);
+        h.y += (last.y + fix(0.5) - h.y) / fix(1.5);
+        last = h.clone();
+    }
+}
+pub fn draw_player(obj: &Object, celeste: &mut Celeste, hair: &[Vector], djump: u8) {
     let haircol = if djump == 1 {
         8
     } else if djump == 0 {
@@ -317,18 +422,13 @@ pub fn draw_player(obj: &mut Object, celeste: &mut Celeste, hair: &mut Vec<Vecto
     };
     celeste.mem.pal(8, haircol);
 
-    let mut first = Vector {
-        x: obj.pos.x + if obj.flip.x { 6.0 } else { 2.0 },
-        y: obj.pos.y + if celeste.mem.buttons[3] { 4.0 } else { 3.0 },
-    };
-    let mut last = &mut first;
-    for (i, h) in hair.iter_mut().enumerate() {
-        h.x += (last.x - h.x) / 1.5;
-        h.y += (last.y + 0.5 - h.y) / 1.5;
-        celeste
-            .mem
-            .circfill(h.x as u8, h.y as u8, (3 - i).min(2).max(1) as i8, haircol);
-        last = h;
+    for (i, h) in hair.iter().enumerate() {
+        celeste.mem.circfill(
+            h.x.to_int(),
+            h.y.to_int(),
+            (3 - i as i32).clamp(1, 2),
+            haircol,
+        );
     }
     obj.draw_sprite(celeste);
     celeste.mem.pal(8, 8);
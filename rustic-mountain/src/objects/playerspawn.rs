@@ -1,30 +1,48 @@
 use core::cell::RefCell;
 use alloc::{rc::Rc, vec, vec::Vec};
 
-use crate::{structures::*, Celeste};
+use crate::{fixed::*, structures::*, Celeste};
 
-use super::player::{draw_player, Player};
+use super::player::{draw_player, update_player, Player};
 
+#[derive(Clone)]
 pub struct PlayerSpawn {
-    target: f32,
+    /// where the player lands
+    pub target: Fix16,
     state: u8,
     delay: i8,
     djump: u8,
 
     hair: Vec<Vector>,
 }
+crate::snapshot::snapshot_fields!(PlayerSpawn {
+    target,
+    state,
+    delay,
+    djump,
+    hair
+});
 impl PlayerSpawn {
-    pub fn init(celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
+        celeste.sfx(4);
+        // flies in from the bottom of the screen, wherever it ends up in a big room
+        let bottom = celeste.scroll_for(x, y).y + fix(128.0);
         Object {
-            pos: Vector { x, y: 128.0 },
-            spd: Vector { x: 0.0, y: -4.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            pos: Vector { x, y: bottom },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: fix(-4.0),
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 3,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 0.0,
-                h: 0.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: Fix16::ZERO,
+                h: Fix16::ZERO,
             },
             flip: FlipState { x: false, y: false },
             collidable: false,
@@ -32,15 +50,14 @@ impl PlayerSpawn {
             obj_type: ObjectType::PlayerSpawn(Rc::new(RefCell::new(Self {
                 delay: 0,
                 state: 0,
-                djump: celeste.max_djump,
+                djump: celeste.dash_count(),
                 target: y,
-                hair: vec![Vector { x, y: 128.0 }; 4],
+                hair: vec![Vector { x, y: bottom }; 4],
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "PlayerSpawn",
+            id: ObjectId::default(),
         }
-        //sfx 4
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
@@ -49,23 +66,26 @@ impl PlayerSpawn {
         };
 
         let mut this = tref.borrow_mut();
-        if this.state == 0 && obj.pos.y < this.target + 16.0 {
+        if this.state == 0 && obj.pos.y < this.target + fix(16.0) {
             this.state = 1;
             this.delay = 3;
         } else if this.state == 1 {
-            obj.spd.y += 0.5;
-            if obj.spd.y > 0.0 {
+            obj.spd.y += fix(0.5);
+            if obj.spd.y > Fix16::ZERO {
                 if this.delay > 0 {
-                    obj.spd.y = 0.0;
+                    obj.spd.y = Fix16::ZERO;
                     this.delay -= 1;
                 } else if obj.pos.y > this.target {
                     obj.pos.y = this.target;
-                    obj.spd = Vector { x: 0.0, y: 0.0 };
+                    obj.spd = Vector {
+                        x: Fix16::ZERO,
+                        y: Fix16::ZERO,
+                    };
                     this.state = 2;
                     this.delay = 5;
                     celeste.shake = 5;
                     // init smoke
-                    // sfx 5
+                    celeste.sfx(5);
                 }
             }
         } else if this.state == 2 {
@@ -73,18 +93,26 @@ impl PlayerSpawn {
             obj.spr = 6;
             if this.delay < 0 {
                 let player = Player::init(celeste, obj.pos.x, this.target);
-                celeste.objects.push(Rc::new(RefCell::new(player)));
+                celeste.objects.spawn(player);
                 obj.destroy_self(celeste);
             }
         }
     }
-    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+    /// runs after every object has updated, where the cart draws the spawning player
+    pub fn late_update(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
             ObjectType::PlayerSpawn(p) => p.clone(),
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
-        let djump = this.djump.clone();
-        draw_player(obj, celeste, &mut this.hair, djump)
+        update_player(obj, celeste, &mut this.hair);
+    }
+    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+        let tref = match &mut obj.obj_type {
+            ObjectType::PlayerSpawn(p) => p.clone(),
+            _ => unreachable!(),
+        };
+        let this = tref.borrow();
+        draw_player(obj, celeste, &this.hair, this.djump)
     }
 }
@@ -1,23 +1,31 @@
 use core::cell::RefCell;
-use alloc::{format, rc::Rc};
+use alloc::rc::Rc;
 
-use crate::{draw_time, structures::*, Celeste};
+use crate::{draw_time, fixed::*, stats::room_name, structures::*, Celeste};
 
+#[derive(Clone)]
 pub struct RoomTitle {
     delay: i32,
 }
+crate::snapshot::snapshot_fields!(RoomTitle { delay });
 impl RoomTitle {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 0,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 0.0,
-                h: 0.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: Fix16::ZERO,
+                h: Fix16::ZERO,
             },
             flip: FlipState { x: false, y: false },
             collidable: false,
@@ -25,11 +33,10 @@ impl RoomTitle {
             obj_type: ObjectType::RoomTitle(Rc::new(RefCell::new(Self { delay: 5 }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Orb",
+            id: ObjectId::default(),
         }
     }
-    pub fn update(_obj: &mut Object, _celeste: &mut Celeste) {}
-    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+    pub fn update(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
             ObjectType::RoomTitle(p) => p.clone(),
             _ => unreachable!(),
@@ -38,22 +45,23 @@ impl RoomTitle {
         this.delay -= 1;
         if this.delay < -30 {
             obj.destroy_self(celeste);
-        } else if this.delay < 0 {
-            celeste.mem.rectfill(24, 58, 104, 70, 0);
-            if celeste.level == 11 {
-                celeste.mem.print("old site", 48, 62, 7);
-            } else if celeste.level == 30 {
-                celeste.mem.print("summit", 52, 62, 7);
-            } else {
-                celeste.mem.print(
-                    &format!("{}00 m", celeste.level + 1),
-                    if celeste.level < 10 { 54 } else { 52 },
-                    62,
-                    7,
-                );
-            }
         }
+    }
+    pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
+        let tref = match &mut obj.obj_type {
+            ObjectType::RoomTitle(p) => p.clone(),
+            _ => unreachable!(),
+        };
+        let this = tref.borrow();
+        celeste.draw_fixed(|celeste| {
+            if this.delay < 0 {
+                celeste.mem.rectfill(24, 58, 104, 70, 0);
+                let title = room_name(&celeste.layout, celeste.level);
+                let (w, _) = celeste.mem.measure(&title);
+                celeste.mem.print(&title, 64 - w / 2, 62, 7);
+            }
 
-        draw_time(celeste, 4, 4);
+            draw_time(celeste, 4, 4);
+        });
     }
 }
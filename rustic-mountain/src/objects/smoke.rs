@@ -3,29 +3,34 @@ use alloc::rc::Rc;
 
 use rand::Rng;
 
-use crate::{structures::*, Celeste};
+use crate::{fixed::*, structures::*, Celeste};
 
+#[derive(Clone)]
 pub struct Smoke {
-    spr: f32,
+    spr: Fix16,
 }
+crate::snapshot::snapshot_fields!(Smoke { spr });
 impl Smoke {
-    pub fn init(celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector {
-                x: x + celeste.mem.rng.gen_range(-1.0..1.0),
-                y: y + celeste.mem.rng.gen_range(-1.0..1.0),
+                x: x + fix(celeste.mem.rng.gen_range(-1.0..1.0)),
+                y: y + fix(celeste.mem.rng.gen_range(-1.0..1.0)),
             },
             spd: Vector {
-                x: celeste.mem.rng.gen_range(0.3..0.5),
-                y: -0.1,
+                x: fix(celeste.mem.rng.gen_range(0.3..0.5)),
+                y: fix(-0.1),
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
             },
-            rem: Vector { x: 0.0, y: 0.0 },
             spr: 29,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 0.0,
-                h: 0.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: Fix16::ZERO,
+                h: Fix16::ZERO,
             },
             flip: FlipState {
                 x: celeste.mem.rng.gen(),
@@ -33,10 +38,10 @@ impl Smoke {
             },
             collidable: false,
             solids: false,
-            obj_type: ObjectType::Smoke(Rc::new(RefCell::new(Self { spr: 29.0 }))),
+            obj_type: ObjectType::Smoke(Rc::new(RefCell::new(Self { spr: fix(29.0) }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Smoke",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -45,11 +50,11 @@ impl Smoke {
             _ => unreachable!(),
         };
         let mut this = tref.borrow_mut();
-        this.spr += 0.2;
-        if this.spr >= 32.0 {
+        this.spr += fix(0.2);
+        if this.spr >= fix(32.0) {
             obj.destroy_self(celeste);
         }
-        obj.spr = this.spr as u8;
+        obj.spr = this.spr.to_int() as u8;
     }
     pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
         obj.draw_sprite(celeste);
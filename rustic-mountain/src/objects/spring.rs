@@ -1,25 +1,39 @@
 use core::cell::RefCell;
 use alloc::rc::Rc;
 
-use crate::{structures::*, Celeste};
+use crate::{fixed::*, structures::*, Celeste};
 
+use super::{fallfloor::FallFloor, player::Player};
+
+#[derive(Clone)]
 pub struct Spring {
     pub hide_in: u8,
     hide_for: u8,
     delay: u8,
 }
+crate::snapshot::snapshot_fields!(Spring {
+    hide_in,
+    hide_for,
+    delay
+});
 impl Spring {
-    pub fn init(_celeste: &mut Celeste, x: f32, y: f32) -> Object {
+    pub fn init(_celeste: &mut Celeste, x: Fix16, y: Fix16) -> Object {
         Object {
             pos: Vector { x, y },
-            spd: Vector { x: 0.0, y: 0.0 },
-            rem: Vector { x: 0.0, y: 0.0 },
+            spd: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
+            rem: Vector {
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+            },
             spr: 18,
             hitbox: Rectangle {
-                x: 0.0,
-                y: 0.0,
-                w: 8.0,
-                h: 8.0,
+                x: Fix16::ZERO,
+                y: Fix16::ZERO,
+                w: fix(8.0),
+                h: fix(8.0),
             },
             flip: FlipState { x: false, y: false },
             solids: true,
@@ -31,7 +45,7 @@ impl Spring {
             }))),
             draw: ObjFunc(Self::draw),
             update: ObjFunc(Self::update),
-            name: "Spring",
+            id: ObjectId::default(),
         }
     }
     pub fn update(obj: &mut Object, celeste: &mut Celeste) {
@@ -39,69 +53,65 @@ impl Spring {
             ObjectType::Spring(p) => p.clone(),
             _ => unreachable!(),
         };
-        let mut this = tref.borrow_mut();
-
-        if this.hide_for > 0 {
-            this.hide_for -= 1;
-            if this.hide_for <= 0 {
-                obj.spr = 18;
-                this.delay = 0;
-            }
-        } else if obj.spr == 18 {
-            let hit = obj.check(celeste, "Player", 0.0, 0.0);
-            // dbg!(&hit);
-            match hit {
-                Some(i) => {
-                    // panic!();'
-                    let jref = celeste.objects[i].clone();
-                    let mut playerobj = jref.borrow_mut();
-                    let pref = match &mut playerobj.obj_type {
-                        ObjectType::Player(p) => p.clone(),
-                        _ => unreachable!(),
-                    };
-
-                    let mut player = pref.borrow_mut();
-                    if playerobj.spd.y >= 0.0 {
-                        obj.spr = 19;
-                        playerobj.pos.y = obj.pos.y - 4.0;
-                        playerobj.spd.x *= 0.2;
-                        playerobj.spd.y = -3.0;
-                        player.djump = celeste.max_djump;
-                        this.delay = 10;
-                        obj.init_smoke(celeste, 0.0, 0.0);
-
-                        let floordex = obj.check(celeste, "FallFloor", 0.0, 1.0);
-                        if let Some(i) = floordex {
-                            let oref = celeste.objects[i].clone();
-                            let mut floorobj = oref.borrow_mut();
-                            let fref = match &mut floorobj.obj_type {
-                                ObjectType::FallFloor(p) => p.clone(),
-                                _ => unreachable!(),
-                            };
-                            let mut floor = fref.borrow_mut();
-                            this.hide_in = 15; // TODO: innacuracy: break_floor doesn't hide it
-                                               // because then they would both have a mut ref, impossible in safe rust
-                            floor.break_floor(&mut floorobj, celeste);
-                        }
-                        // psfx 8
-                    }
+        let bounced = {
+            let mut this = tref.borrow_mut();
+            if this.hide_for > 0 {
+                this.hide_for -= 1;
+                if this.hide_for == 0 {
+                    obj.spr = 18;
+                    this.delay = 0;
+                }
+                false
+            } else if obj.spr == 18 {
+                this.bounce(obj, celeste)
+            } else if this.delay > 0 {
+                this.delay -= 1;
+                if this.delay == 0 {
+                    obj.spr = 18;
                 }
-                None => (),
+                false
+            } else {
+                false
             }
-        } else if this.delay > 0 {
-            this.delay -= 1;
-            if this.delay <= 0 {
-                obj.spr = 18;
+        };
+        if bounced {
+            // break_floor hides the spring on top, so this one can't be borrowed meanwhile
+            let floordex = obj.check(celeste, ObjectKind::FallFloor, Fix16::ZERO, Fix16::ONE);
+            if let Some((oref, fref)) = floordex
+                .and_then(|id| Some((celeste.get_object(id)?, celeste.get::<FallFloor>(id)?)))
+            {
+                fref.borrow_mut()
+                    .break_floor(&mut oref.borrow_mut(), celeste);
             }
+            celeste.psfx(8);
         }
+        let mut this = tref.borrow_mut();
         if this.hide_in > 0 {
             this.hide_in -= 1;
-            if this.hide_in <= 0 {
+            if this.hide_in == 0 {
                 this.hide_for = 60;
                 obj.spr = 0;
             }
         }
     }
+    /// Launches a player standing on the spring, if there is one
+    fn bounce(&mut self, obj: &mut Object, celeste: &mut Celeste) -> bool {
+        let hit = obj.check(celeste, ObjectKind::Player, Fix16::ZERO, Fix16::ZERO);
+        match hit.and_then(|id| Some((celeste.get_object(id)?, celeste.get::<Player>(id)?))) {
+            Some((jref, pref)) if jref.borrow().spd.y >= Fix16::ZERO => {
+                let mut playerobj = jref.borrow_mut();
+                obj.spr = 19;
+                playerobj.pos.y = obj.pos.y - fix(4.0);
+                playerobj.spd.x *= fix(0.2);
+                playerobj.spd.y = fix(-3.0);
+                pref.borrow_mut().djump = celeste.dash_count();
+                self.delay = 10;
+                obj.init_smoke(celeste, Fix16::ZERO, Fix16::ZERO);
+                true
+            }
+            _ => false,
+        }
+    }
     pub fn draw(obj: &mut Object, celeste: &mut Celeste) {
         let tref = match &mut obj.obj_type {
             ObjectType::Spring(p) => p.clone(),
@@ -0,0 +1,461 @@
+//! P8SCII, pico-8's character set. Codes 0-15 are control codes, 16-127 are ascii-ish glyphs
+//! and 128-255 are the wide glyphs (button icons, symbols, kana).
+//!
+//! Rust strings are unicode, so `encode` maps the symbols pico-8 itself shows for the wide glyphs
+//! back to their codes, and anything below 256 is passed straight through. Control codes are
+//! written with escapes, e.g. `"\x0c8red\x0c7 white"` is pico-8's `"\f8red\f7 white"`.
+//!
+//! The built in font only has glyphs up to 153 (`▥`), not the kana after it, so those are drawn
+//! as `?` with the default font. A custom font can use every code from 16 up.
+//!
+//! Laying text out is kept separate from drawing it, so `Memory::measure` can reuse it without
+//! touching the screen.
+use alloc::vec::Vec;
+
+pub const TERMINATE: u8 = 0;
+/// `\*`: repeat the next character n times
+pub const REPEAT: u8 = 1;
+/// `\#`: set the background color
+pub const BACKGROUND: u8 = 2;
+/// `\-`: move the cursor horizontally by n-16 pixels
+pub const OFFSET_X: u8 = 3;
+/// `\|`: move the cursor vertically by n-16 pixels
+pub const OFFSET_Y: u8 = 4;
+/// `\+`: move the cursor by (n-16, m-16) pixels
+pub const OFFSET: u8 = 5;
+/// `\^`: special commands, see `Layout::special`
+pub const SPECIAL: u8 = 6;
+/// `\a`: play a sound. there's no audio, so the command is skipped
+pub const AUDIO: u8 = 7;
+pub const BACKSPACE: u8 = 8;
+pub const TAB: u8 = 9;
+pub const NEWLINE: u8 = 10;
+/// `\v`: draw the following character over the previous one, offset by (n%4-2, n/4-8)
+pub const DECORATE: u8 = 11;
+/// `\f`: set the foreground color
+pub const FOREGROUND: u8 = 12;
+pub const CARRIAGE_RETURN: u8 = 13;
+/// `\014`: switch to the custom font at 0x5600
+pub const CUSTOM_FONT: u8 = 14;
+/// `\015`: switch back to the default font
+pub const DEFAULT_FONT: u8 = 15;
+
+/// the symbols pico-8 uses for glyphs 128 and up, in order
+const GLYPHS: [char; 26] = [
+    '█', '▒', '🐱', '⬇', '░', '✽', '●', '♥', '☉', '웃', '⌂', '⬅', '😐', '♪', '🅾', '◆', '…', '➡',
+    '★', '⧗', '⬆', 'ˇ', '∧', '❎', '▤', '▥',
+];
+
+/// Converts a string to p8scii codes. Characters pico-8 can't show become `?`
+pub fn encode(text: &str) -> Vec<u8> {
+    text.chars().filter_map(to_p8scii).collect()
+}
+
+pub fn to_p8scii(c: char) -> Option<u8> {
+    if let Some(i) = GLYPHS.iter().position(|g| *g == c) {
+        Some(128 + i as u8)
+    } else if (c as u32) < 256 {
+        Some(c as u8)
+    } else if c == '\u{fe0f}' {
+        // the emoji variation selector pico-8 puts after its button glyphs
+        None
+    } else {
+        Some(b'?')
+    }
+}
+
+/// the built in font (`CartData::fontatlas`) has glyphs for the codes below this
+pub const DEFAULT_GLYPHS: u8 = 128 + GLYPHS.len() as u8;
+
+/// The size of a font's characters, in pixels
+#[derive(Debug, Clone, Copy)]
+pub struct FontMetrics {
+    /// horizontal advance of characters below 128
+    pub width: i32,
+    /// horizontal advance of characters 128 and up
+    pub wide_width: i32,
+    /// vertical advance of a line
+    pub height: i32,
+    pub x_offset: i32,
+    pub y_offset: i32,
+}
+
+/// the built in 3x5 font
+pub const DEFAULT_METRICS: FontMetrics = FontMetrics {
+    width: 4,
+    wide_width: 8,
+    height: 6,
+    x_offset: 0,
+    y_offset: 0,
+};
+
+/// Where a glyph's pixels come from
+#[derive(Debug, Clone, Copy)]
+pub enum GlyphSource {
+    /// the font atlas
+    Default(u8),
+    /// the custom font at 0x5600
+    Custom(u8),
+    /// a one-off glyph from `\^:` or `\^.`, one byte per row, lowest bit on the left
+    Bitmap([u8; 8]),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub source: GlyphSource,
+    pub x: i32,
+    pub y: i32,
+    /// size of the character cell, after scaling
+    pub w: i32,
+    pub h: i32,
+    pub fg: u8,
+    pub bg: Option<u8>,
+    pub wide: bool,
+    pub tall: bool,
+    /// when scaled, only draw the top left pixel of each block (`\^=` for rows, `\^p` for both)
+    pub stripey: bool,
+    pub dotty: bool,
+    pub invert: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum TextOp {
+    Glyph(Glyph),
+    /// `\^c`: clear the screen
+    Clear(u8),
+}
+
+/// Where laid out text ended up
+#[derive(Debug, Clone, Copy)]
+pub struct TextEnd {
+    /// the rightmost x any character advanced to
+    pub right: i32,
+    /// the y just below the last line
+    pub bottom: i32,
+    /// where a following `\n` would start the next line
+    pub home_x: i32,
+}
+
+/// bits of the print attribute byte at 0x5f58, which sets defaults for every print when bit 0 is
+/// set
+pub const ATTR_ENABLE: u8 = 1;
+pub const ATTR_WIDE: u8 = 1 << 2;
+pub const ATTR_TALL: u8 = 1 << 3;
+pub const ATTR_SOLID_BG: u8 = 1 << 4;
+pub const ATTR_INVERT: u8 = 1 << 5;
+pub const ATTR_DOTTY: u8 = 1 << 6;
+pub const ATTR_CUSTOM_FONT: u8 = 1 << 7;
+
+struct Layout<'a> {
+    codes: &'a [u8],
+    i: usize,
+    custom_metrics: FontMetrics,
+
+    x: i32,
+    y: i32,
+    home_x: i32,
+    home_y: i32,
+    last_x: i32,
+    line_h: i32,
+    right: i32,
+    bottom: i32,
+
+    fg: u8,
+    bg: Option<u8>,
+    wide: bool,
+    tall: bool,
+    stripey: bool,
+    dotty: bool,
+    invert: bool,
+    custom: bool,
+    char_w: Option<i32>,
+    char_h: Option<i32>,
+    tab_w: i32,
+}
+
+/// Lays out p8scii `codes` starting at (x, y), calling `emit` for everything that needs drawing.
+/// `attrs` is the print attribute byte, and `custom_metrics` the header of the custom font
+pub fn layout(
+    codes: &[u8],
+    x: i32,
+    y: i32,
+    col: u8,
+    attrs: u8,
+    custom_metrics: FontMetrics,
+    mut emit: impl FnMut(TextOp),
+) -> TextEnd {
+    let attrs = if attrs & ATTR_ENABLE != 0 { attrs } else { 0 };
+    let mut l = Layout {
+        codes,
+        i: 0,
+        custom_metrics,
+        x,
+        y,
+        home_x: x,
+        home_y: y,
+        last_x: x,
+        line_h: 0,
+        right: x,
+        bottom: y,
+        fg: col,
+        bg: if attrs & ATTR_SOLID_BG != 0 {
+            Some(0)
+        } else {
+            None
+        },
+        wide: attrs & ATTR_WIDE != 0,
+        tall: attrs & ATTR_TALL != 0,
+        stripey: false,
+        dotty: attrs & ATTR_DOTTY != 0,
+        invert: attrs & ATTR_INVERT != 0,
+        custom: attrs & ATTR_CUSTOM_FONT != 0,
+        char_w: None,
+        char_h: None,
+        tab_w: 16,
+    };
+    while let Some(c) = l.next() {
+        match c {
+            TERMINATE => break,
+            REPEAT => {
+                let n = l.param();
+                if let Some(c) = l.next() {
+                    for _ in 0..n {
+                        l.glyph(c, &mut emit);
+                    }
+                }
+            }
+            BACKGROUND => l.bg = Some(l.param() as u8),
+            OFFSET_X => l.x += l.param() - 16,
+            OFFSET_Y => l.y += l.param() - 16,
+            OFFSET => {
+                l.x += l.param() - 16;
+                l.y += l.param() - 16;
+            }
+            SPECIAL => l.special(&mut emit),
+            AUDIO => {
+                while let Some(c) = l.next() {
+                    if c == b' ' {
+                        break;
+                    }
+                }
+            }
+            BACKSPACE => l.x -= l.metrics().width * l.scale_x(),
+            TAB => l.x = l.home_x + ((l.x - l.home_x) / l.tab_w + 1) * l.tab_w,
+            NEWLINE => l.newline(),
+            DECORATE => {
+                let n = l.param();
+                if let Some(c) = l.next() {
+                    let (x, y) = (l.x, l.y);
+                    l.x = l.last_x + n % 4 - 2;
+                    l.y += n / 4 - 8;
+                    l.glyph(c, &mut emit);
+                    l.x = x;
+                    l.y = y;
+                }
+            }
+            FOREGROUND => l.fg = l.param() as u8,
+            CARRIAGE_RETURN => l.x = l.home_x,
+            CUSTOM_FONT => l.custom = true,
+            DEFAULT_FONT => l.custom = false,
+            c => l.glyph(c, &mut emit),
+        }
+    }
+    if l.line_h > 0 {
+        l.bottom = l.bottom.max(l.y + l.line_h);
+    }
+    TextEnd {
+        right: l.right,
+        bottom: l.bottom,
+        home_x: l.home_x,
+    }
+}
+
+impl Layout<'_> {
+    fn next(&mut self) -> Option<u8> {
+        let c = self.codes.get(self.i).copied();
+        self.i += 1;
+        c
+    }
+    /// reads a one character parameter: 0-9, then a-z for 10-35
+    fn param(&mut self) -> i32 {
+        match self.next() {
+            Some(c @ b'0'..=b'9') => (c - b'0') as i32,
+            Some(c @ b'a'..=b'z') => (c - b'a') as i32 + 10,
+            Some(c @ b'A'..=b'Z') => (c - b'A') as i32 + 10,
+            _ => 0,
+        }
+    }
+    fn metrics(&self) -> FontMetrics {
+        if self.custom {
+            self.custom_metrics
+        } else {
+            DEFAULT_METRICS
+        }
+    }
+    fn scale_x(&self) -> i32 {
+        if self.wide {
+            2
+        } else {
+            1
+        }
+    }
+    fn scale_y(&self) -> i32 {
+        if self.tall {
+            2
+        } else {
+            1
+        }
+    }
+    fn char_h(&self) -> i32 {
+        self.char_h.unwrap_or(self.metrics().height) * self.scale_y()
+    }
+    fn newline(&mut self) {
+        let h = if self.line_h > 0 {
+            self.line_h
+        } else {
+            self.char_h()
+        };
+        self.y += h;
+        self.bottom = self.bottom.max(self.y);
+        self.x = self.home_x;
+        self.line_h = 0;
+    }
+    fn glyph(&mut self, c: u8, emit: &mut impl FnMut(TextOp)) {
+        let source = if self.custom {
+            GlyphSource::Custom(c)
+        } else if c < DEFAULT_GLYPHS {
+            GlyphSource::Default(c)
+        } else {
+            // keeps the width of the glyph it stands in for
+            GlyphSource::Default(b'?')
+        };
+        let metrics = self.metrics();
+        let w = if c >= 128 {
+            metrics.wide_width
+        } else {
+            metrics.width
+        };
+        self.put(source, self.char_w.unwrap_or(w), emit);
+    }
+    fn put(&mut self, source: GlyphSource, w: i32, emit: &mut impl FnMut(TextOp)) {
+        let metrics = self.metrics();
+        let w = w * self.scale_x();
+        let h = self.char_h();
+        emit(TextOp::Glyph(Glyph {
+            source,
+            x: self.x + metrics.x_offset,
+            y: self.y + metrics.y_offset,
+            w,
+            h,
+            fg: self.fg,
+            bg: self.bg,
+            wide: self.wide,
+            tall: self.tall,
+            stripey: self.stripey,
+            dotty: self.dotty,
+            invert: self.invert,
+        }));
+        self.last_x = self.x;
+        self.x += w;
+        self.right = self.right.max(self.x);
+        self.line_h = self.line_h.max(h);
+    }
+    /// `\^` commands. a `-` before a mode turns it off instead
+    fn special(&mut self, emit: &mut impl FnMut(TextOp)) {
+        let (cmd, on) = match self.next() {
+            Some(b'-') => (self.next(), false),
+            cmd => (cmd, true),
+        };
+        match cmd {
+            Some(b'w') => self.wide = on,
+            Some(b't') => self.tall = on,
+            Some(b'=') => {
+                self.stripey = on;
+                self.tall = on;
+            }
+            Some(b'p') => {
+                self.wide = on;
+                self.tall = on;
+                self.dotty = on;
+            }
+            Some(b'i') => self.invert = on,
+            Some(b'c') => {
+                emit(TextOp::Clear(self.param() as u8));
+                self.x = 0;
+                self.y = 0;
+                self.home_x = 0;
+                self.home_y = 0;
+            }
+            Some(b'g') => {
+                self.x = self.home_x;
+                self.y = self.home_y;
+            }
+            Some(b'h') => {
+                self.home_x = self.x;
+                self.home_y = self.y;
+            }
+            Some(b'j') => {
+                self.x = self.param() * 4;
+                self.y = self.param() * 4;
+            }
+            Some(b's') => self.tab_w = self.param().max(1) * 4,
+            Some(b'x') => self.char_w = Some(self.param()),
+            Some(b'y') => self.char_h = Some(self.param()),
+            Some(b':') => {
+                let mut rows = [0; 8];
+                for row in rows.iter_mut() {
+                    *row = (self.hex() << 4) | self.hex();
+                }
+                self.put(GlyphSource::Bitmap(rows), DEFAULT_METRICS.wide_width, emit);
+            }
+            Some(b'.') => {
+                let mut rows = [0; 8];
+                for row in rows.iter_mut() {
+                    *row = self.next().unwrap_or(0);
+                }
+                self.put(GlyphSource::Bitmap(rows), DEFAULT_METRICS.wide_width, emit);
+            }
+            // delays and the rest only make sense for pico-8's own print loop
+            Some(b'd') | Some(b'r') => {
+                self.param();
+            }
+            _ => (),
+        }
+    }
+    fn hex(&mut self) -> u8 {
+        match self.next() {
+            Some(c @ b'0'..=b'9') => c - b'0',
+            Some(c @ b'a'..=b'f') => c - b'a' + 10,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the source and width of every glyph `codes` lays out as
+    fn glyphs(codes: &[u8]) -> Vec<(u8, i32)> {
+        let mut out = Vec::new();
+        layout(codes, 0, 0, 7, 0, DEFAULT_METRICS, |op| {
+            if let TextOp::Glyph(g) = op {
+                let code = match g.source {
+                    GlyphSource::Default(c) | GlyphSource::Custom(c) => c,
+                    GlyphSource::Bitmap(_) => 0,
+                };
+                out.push((code, g.w));
+            }
+        });
+        out
+    }
+
+    #[test]
+    fn glyphs_missing_from_the_font_draw_as_question_marks() {
+        let codes = encode("a▥\u{9a}\u{ff}");
+        assert_eq!(codes, [b'a', 153, 154, 255]);
+        assert_eq!(glyphs(&codes), [(b'a', 4), (153, 8), (b'?', 8), (b'?', 8)]);
+        // the custom font has them all
+        assert_eq!(glyphs(&[CUSTOM_FONT, 154]), [(154, 8)]);
+    }
+}
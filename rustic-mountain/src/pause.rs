@@ -0,0 +1,100 @@
+//! A pico-8 style pause menu, drawn over the frozen game. Open it with `Celeste::pause`; while
+//! it's open `next_tick` only moves the selection around
+use crate::{fixed::Fix16, memory::Memory};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseItem {
+    Continue,
+    /// reloads the room with a fresh IL timer, without counting a death
+    RestartRoom,
+    /// left to the frontend, see `GameEvent::PauseMenu`
+    Settings,
+    /// left to the frontend, see `GameEvent::PauseMenu`
+    QuitToMenu,
+}
+
+impl PauseItem {
+    pub const ALL: [PauseItem; 4] = [
+        PauseItem::Continue,
+        PauseItem::RestartRoom,
+        PauseItem::Settings,
+        PauseItem::QuitToMenu,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PauseItem::Continue => "continue",
+            PauseItem::RestartRoom => "restart room",
+            PauseItem::Settings => "settings",
+            PauseItem::QuitToMenu => "quit to menu",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PauseMenu {
+    /// index into `PauseItem::ALL`
+    pub selected: usize,
+    /// the buttons on the last tick, so holding one down only moves the selection once
+    held: [bool; 6],
+}
+
+impl PauseMenu {
+    /// `buttons` are the ones down right now, they need to be let go of before they do anything
+    pub fn new(buttons: &[bool]) -> PauseMenu {
+        let mut menu = PauseMenu {
+            selected: 0,
+            held: [false; 6],
+        };
+        menu.press(buttons);
+        menu
+    }
+
+    /// Moves the selection with up and down. Returns the selected item once z or x is pressed
+    pub fn update(&mut self, buttons: &[bool]) -> Option<PauseItem> {
+        let pressed = self.press(buttons);
+        if pressed[2] {
+            self.selected = (self.selected + PauseItem::ALL.len() - 1) % PauseItem::ALL.len();
+        }
+        if pressed[3] {
+            self.selected = (self.selected + 1) % PauseItem::ALL.len();
+        }
+        if pressed[4] || pressed[5] {
+            Some(PauseItem::ALL[self.selected])
+        } else {
+            None
+        }
+    }
+
+    /// which buttons went down since the last call
+    fn press(&mut self, buttons: &[bool]) -> [bool; 6] {
+        let mut pressed = [false; 6];
+        for (i, &down) in buttons.iter().take(6).enumerate() {
+            pressed[i] = down && !self.held[i];
+            self.held[i] = down;
+        }
+        pressed
+    }
+
+    pub fn draw(&self, mem: &mut Memory) {
+        let camera = mem.camera.clone();
+        mem.camera(Fix16::ZERO, Fix16::ZERO);
+        for i in 0..16 {
+            mem.pal(i, i as u8);
+        }
+
+        let h = PauseItem::ALL.len() as i32 * 8 + 8;
+        let (x0, y0) = (32, 64 - h / 2);
+        mem.rectfill(x0, y0, 127 - x0, y0 + h, 0);
+        mem.rect(x0 + 1, y0 + 1, 126 - x0, y0 + h - 1, 7);
+        for (i, item) in PauseItem::ALL.iter().enumerate() {
+            let y = y0 + 6 + i as i32 * 8;
+            if i == self.selected {
+                mem.print(">", x0 + 6, y, 7);
+            }
+            mem.print(item.label(), x0 + 12, y, 7);
+        }
+
+        mem.camera = camera;
+    }
+}
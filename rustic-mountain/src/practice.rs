@@ -0,0 +1,109 @@
+use alloc::format;
+
+use crate::{speedrun::format_frames, Celeste};
+
+/// How `Celeste::begin_practice` sets up the room it starts in. `retry` puts the room back in
+/// this state too
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Practice {
+    /// 0-29 for 100m to 3000m (11 is old site), 30 for the summit
+    pub level: u8,
+    /// 2 is what you'd have after the orb
+    pub max_djump: u8,
+    /// start with the room's key already collected
+    pub has_key: bool,
+    /// treat the room's berry as already collected, so it doesn't spawn
+    pub berry: bool,
+}
+
+impl Default for Practice {
+    fn default() -> Self {
+        Practice {
+            level: 0,
+            max_djump: 1,
+            has_key: false,
+            berry: false,
+        }
+    }
+}
+
+/// The IL timer in the top right corner, with the last completed room's time under it
+pub fn draw_il_time(celeste: &mut Celeste) {
+    let time = format_frames(celeste.il_frames);
+    let last = celeste
+        .last_il
+        .map(|t| format!("last {}", format_frames(t)))
+        .unwrap_or_default();
+    let time_w = celeste.mem.measure(&time).0;
+    let last_w = celeste.mem.measure(&last).0;
+    celeste
+        .mem
+        .rectfill(123 - time_w.max(last_w), 4, 125, 18, 0);
+    celeste.mem.print(&time, 125 - time_w, 6, 7);
+    celeste.mem.print(&last, 125 - last_w, 12, 6);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixed::Fix16, structures::ObjectKind, testcart};
+    use alloc::{vec, vec::Vec};
+
+    /// what the room looks like, without the object ids, which a reload changes
+    #[derive(Debug, PartialEq)]
+    struct RoomState {
+        objects: Vec<(ObjectKind, Fix16, Fix16, u8)>,
+        max_djump: u8,
+        has_key: bool,
+        berry: bool,
+        il_frames: u64,
+    }
+    impl RoomState {
+        fn of(celeste: &Celeste) -> RoomState {
+            RoomState {
+                objects: celeste
+                    .objects
+                    .iter()
+                    .map(|(_, obj)| {
+                        let obj = obj.borrow();
+                        (obj.kind(), obj.pos.x, obj.pos.y, obj.spr)
+                    })
+                    .collect(),
+                max_djump: celeste.max_djump,
+                has_key: celeste.has_key,
+                berry: celeste.got_fruit[celeste.level as usize],
+                il_frames: celeste.il_frames,
+            }
+        }
+    }
+
+    #[test]
+    fn retry_goes_back_to_the_start_of_the_practice() {
+        let mut celeste = testcart::celeste();
+        let practice = Practice {
+            level: 3,
+            max_djump: 2,
+            has_key: true,
+            berry: false,
+        };
+        celeste.begin_practice(practice);
+        let start = RoomState::of(&celeste);
+
+        // run right, jump and dash around
+        for tick in 0..90 {
+            celeste.mem.buttons =
+                vec![false, true, tick % 20 < 3, false, tick % 30 < 5, tick == 40];
+            celeste.next_tick();
+        }
+        celeste.max_djump = 1;
+        celeste.has_key = false;
+        celeste.got_fruit[3] = true;
+        assert_ne!(RoomState::of(&celeste), start);
+
+        let deaths = celeste.deaths;
+        celeste.retry();
+        assert_eq!(RoomState::of(&celeste), start);
+        assert_eq!(celeste.deaths, deaths);
+        assert_eq!(celeste.practice, Some(practice));
+    }
+}
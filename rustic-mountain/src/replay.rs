@@ -0,0 +1,157 @@
+//! Inputs recorded tick by tick, that play a game back exactly. A replay starts from a fresh
+//! `Celeste` on the title screen that's been given the replay's seed, see `Replay::start`
+use alloc::{format, string::String, vec::Vec};
+
+use crate::Celeste;
+
+/// The buttons held on each tick, as pico-8's `btn()` bitmask: bit 0 is left, then right, up,
+/// down, z and x
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Replay {
+    pub seed: u64,
+    pub inputs: Vec<u8>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Replay {
+        Replay {
+            seed,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Seeds `celeste`, which should be fresh from `Celeste::new`, ready to play the replay
+    pub fn start(&self, celeste: &mut Celeste) {
+        celeste.seed(self.seed);
+    }
+
+    /// Adds the buttons that are held for the next tick
+    pub fn record(&mut self, buttons: &[bool]) {
+        self.inputs.push(to_mask(buttons));
+    }
+
+    /// Holds the buttons of tick `tick` on `celeste`. Returns `false`, leaving the buttons alone,
+    /// once the replay has run out
+    pub fn apply(&self, tick: usize, celeste: &mut Celeste) -> bool {
+        let Some(&mask) = self.inputs.get(tick) else {
+            return false;
+        };
+        for (i, button) in celeste.mem.buttons.iter_mut().enumerate() {
+            *button = mask & (1 << i) != 0;
+        }
+        true
+    }
+
+    /// A `seed` line, then the bitmask of every tick separated by commas. The second line is the
+    /// input file format used by the Celeste Classic TAS tools
+    pub fn to_text(&self) -> String {
+        let inputs: Vec<String> = self.inputs.iter().map(|i| format!("{}", i)).collect();
+        format!("seed {}\n{}\n", self.seed, inputs.join(","))
+    }
+    /// Parses what `to_text` wrote. A missing `seed` line means seed 0
+    pub fn from_text(text: &str) -> Option<Replay> {
+        let mut replay = Replay::default();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(seed) = line.strip_prefix("seed ") {
+                replay.seed = seed.parse().ok()?;
+                continue;
+            }
+            for input in line.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+                replay.inputs.push(input.parse().ok()?);
+            }
+        }
+        Some(replay)
+    }
+}
+
+/// `buttons` as a `btn()` bitmask
+pub fn to_mask(buttons: &[bool]) -> u8 {
+    buttons
+        .iter()
+        .take(8)
+        .enumerate()
+        .fold(0, |mask, (i, &down)| mask | ((down as u8) << i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testcart;
+    use alloc::vec;
+
+    /// what someone might press: start the game, then run right, jumping and dashing now and then
+    fn buttons(tick: u32) -> Vec<bool> {
+        if tick < 80 {
+            return vec![false, false, false, false, tick == 10, false];
+        }
+        vec![
+            tick % 90 > 70,
+            tick % 90 < 60,
+            tick % 33 < 2,
+            false,
+            tick % 21 < 5,
+            tick.is_multiple_of(47),
+        ]
+    }
+
+    #[test]
+    fn recordings_play_back_from_text() {
+        let mut celeste = testcart::celeste();
+        let mut replay = Replay::new(1234);
+        replay.start(&mut celeste);
+        let mut states = Vec::new();
+        for tick in 0..400 {
+            celeste.mem.buttons = buttons(tick);
+            replay.record(&celeste.mem.buttons);
+            celeste.next_tick();
+            states.push(celeste.save_state().data);
+        }
+        assert!(!celeste.is_title());
+
+        let text = replay.to_text();
+        assert!(text.starts_with("seed 1234\n0,0,0,0,0,0,0,0,0,0,16,0,"));
+        let loaded = Replay::from_text(&text).unwrap();
+        assert_eq!(loaded, replay);
+
+        let mut celeste = testcart::celeste();
+        loaded.start(&mut celeste);
+        let mut tick = 0;
+        while loaded.apply(tick, &mut celeste) {
+            celeste.next_tick();
+            assert!(celeste.save_state().data == states[tick], "tick {}", tick);
+            tick += 1;
+        }
+        assert_eq!(tick, 400);
+    }
+
+    #[test]
+    fn from_text_parses_tas_input_files() {
+        assert_eq!(
+            Replay::from_text("1, 2,\n 16 ,0\n"),
+            Some(Replay {
+                seed: 0,
+                inputs: vec![1, 2, 16, 0],
+            })
+        );
+        assert_eq!(Replay::from_text("seed 7\n"), Some(Replay::new(7)));
+        for text in ["seed x\n", "1,2,x\n", "1,256\n", "-1\n"] {
+            assert_eq!(Replay::from_text(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn masks_follow_btn() {
+        assert_eq!(to_mask(&[true, false, false, false, true, false]), 0b10001);
+        let mut celeste = testcart::celeste();
+        let replay = Replay {
+            seed: 0,
+            inputs: vec![0b100010],
+        };
+        assert!(replay.apply(0, &mut celeste));
+        assert_eq!(
+            celeste.mem.buttons,
+            [false, true, false, false, false, true]
+        );
+        assert!(!replay.apply(1, &mut celeste));
+    }
+}
@@ -0,0 +1,232 @@
+//! Emulator style rewind. Push the game into a `Rewind` after every tick, and `step_back` to go
+//! back through them one tick at a time.
+//!
+//! Only the newest state is kept whole. Every older one is stored as the bytes that differ from
+//! the state after it, which is about a third of a state, so ten seconds of them take a few
+//! hundred kilobytes
+use alloc::{collections::VecDeque, vec::Vec};
+
+use crate::{objects::custom::CustomObject, snapshot::SaveState, Celeste};
+
+/// A state stored as what changed going to the next newer one
+struct Delta {
+    /// length of the state's data
+    len: usize,
+    /// alternating runs of unchanged and changed bytes, see `encode`
+    runs: Vec<u8>,
+    customs: Vec<CustomObject>,
+}
+
+pub struct Rewind {
+    /// how many states are kept, counting the newest
+    capacity: usize,
+    newest: Option<SaveState>,
+    /// oldest first
+    deltas: VecDeque<Delta>,
+}
+
+impl Rewind {
+    /// Keeps the last `capacity` ticks, 30 for every second
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind {
+            capacity: capacity.max(1),
+            newest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Saves the state the game is in now. Call it once per tick, after the tick
+    pub fn push(&mut self, celeste: &Celeste) {
+        let state = celeste.save_state();
+        if let Some(older) = self.newest.replace(state) {
+            let newer = &self.newest.as_ref().unwrap().data;
+            self.deltas.push_back(Delta {
+                len: older.data.len(),
+                runs: encode(&older.data, newer),
+                customs: older.customs,
+            });
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+    }
+
+    /// Loads the state from the tick before the newest one, and forgets the newest. Returns
+    /// `false` once there's nothing older left
+    pub fn step_back(&mut self, celeste: &mut Celeste) -> bool {
+        let (Some(newest), Some(delta)) = (&self.newest, self.deltas.pop_back()) else {
+            return false;
+        };
+        let state = SaveState {
+            data: decode(&delta, &newest.data),
+            customs: delta.customs,
+        };
+        if !celeste.load_state(&state) {
+            // shouldn't happen, but if it does nothing older will load either
+            self.clear();
+            return false;
+        }
+        self.newest = Some(state);
+        true
+    }
+
+    /// Forgets every state, e.g. when a new game is started
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+    }
+
+    /// How many ticks `step_back` can go back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Bytes used by the stored states
+    pub fn memory_usage(&self) -> usize {
+        self.newest.as_ref().map_or(0, |s| s.data.len())
+            + self.deltas.iter().map(|d| d.runs.len()).sum::<usize>()
+    }
+}
+
+/// `older` xored with `newer` (padded with zeroes), as pairs of run lengths: the number of zero
+/// bytes, then the number of bytes that follow them, then those bytes
+fn encode(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| older[i] ^ newer.get(i).copied().unwrap_or(0);
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < older.len() {
+        let start = i;
+        while i < older.len() && xor(i) == 0 {
+            i += 1;
+        }
+        let zeros = i - start;
+        let start = i;
+        while i < older.len() && xor(i) != 0 {
+            i += 1;
+        }
+        push_len(&mut runs, zeros);
+        push_len(&mut runs, i - start);
+        runs.extend((start..i).map(xor));
+    }
+    runs
+}
+
+fn decode(delta: &Delta, newer: &[u8]) -> Vec<u8> {
+    let mut older: Vec<u8> = (0..delta.len)
+        .map(|i| newer.get(i).copied().unwrap_or(0))
+        .collect();
+    let mut runs = delta.runs.iter().copied();
+    let mut i = 0;
+    while let Some(zeros) = read_len(&mut runs) {
+        i += zeros;
+        let changed = read_len(&mut runs).unwrap_or(0);
+        for (byte, x) in older[i..i + changed].iter_mut().zip(&mut runs) {
+            *byte ^= x;
+        }
+        i += changed;
+    }
+    older
+}
+
+/// LEB128, so short runs take a single byte
+fn push_len(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+}
+
+fn read_len(bytes: &mut impl Iterator<Item = u8>) -> Option<usize> {
+    let mut len = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes.next()?;
+        len |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(len);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testcart;
+    use alloc::vec;
+
+    fn round_trip(older: &[u8], newer: &[u8]) {
+        let delta = Delta {
+            len: older.len(),
+            runs: encode(older, newer),
+            customs: Vec::new(),
+        };
+        assert_eq!(decode(&delta, newer), older);
+    }
+
+    #[test]
+    fn deltas_decode_to_the_older_state() {
+        let base: Vec<u8> = (0..1000).map(|i| (i * 7 % 251) as u8).collect();
+        let mut changed = base.clone();
+        changed[0] ^= 1;
+        // longer than a one byte length
+        changed[500..800].fill(0xaa);
+        changed[999] = 0;
+
+        round_trip(&base, &base);
+        round_trip(&base, &changed);
+        round_trip(&changed, &base);
+        // states grow and shrink as objects come and go
+        round_trip(&base[..600], &changed);
+        round_trip(&base, &changed[..300]);
+        round_trip(&[], &base);
+        round_trip(&base, &[]);
+
+        // nothing changed is just the one run of 1000 unchanged bytes
+        assert_eq!(encode(&base, &base), [0xe8, 0x07, 0]);
+    }
+
+    #[test]
+    fn lengths_round_trip() {
+        for len in [0, 1, 127, 128, 300, 1 << 20] {
+            let mut bytes = Vec::new();
+            push_len(&mut bytes, len);
+            assert_eq!(
+                bytes.len(),
+                1 + (len >= 128) as usize + (len >= 1 << 14) as usize
+            );
+            assert_eq!(read_len(&mut bytes.into_iter()), Some(len));
+        }
+        assert_eq!(read_len(&mut [0x80].into_iter()), None);
+    }
+
+    #[test]
+    fn step_back_goes_through_each_earlier_tick() {
+        let mut celeste = testcart::celeste();
+        celeste.seed(9);
+        celeste.begin_game();
+        let mut rewind = Rewind::new(20);
+        let mut states = Vec::new();
+        for tick in 0..30 {
+            celeste.mem.buttons = vec![false, true, false, false, tick % 12 < 4, tick == 15];
+            celeste.next_tick();
+            rewind.push(&celeste);
+            states.push(celeste.save_state().data);
+        }
+        assert_eq!(rewind.len(), 19);
+        for back in 1..=19 {
+            assert!(rewind.step_back(&mut celeste));
+            assert!(
+                celeste.save_state().data == states[29 - back],
+                "{} back",
+                back
+            );
+        }
+        assert!(!rewind.step_back(&mut celeste));
+        assert!(rewind.is_empty());
+    }
+}
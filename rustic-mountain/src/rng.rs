@@ -0,0 +1,44 @@
+//! The random number generator behind `Memory::rng`. Unlike `OsRng` it can be seeded and saved
+//! in a snapshot, so the same seed and inputs always play out the same way
+use rand::{rngs::OsRng, Error, RngCore};
+
+/// splitmix64, small and fast. Not for anything that needs to be unpredictable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRng {
+    pub state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        GameRng { state: seed }
+    }
+    /// Seeded from the platform's entropy source, for when nobody cares which seed is used
+    pub fn from_entropy() -> GameRng {
+        GameRng::new(OsRng.next_u64())
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+crate::snapshot::snapshot_fields!(GameRng { state });
@@ -0,0 +1,109 @@
+//! Where each room is in the map and how it's left. The cart hard codes an 8x4 grid of one screen
+//! rooms, entered in reading order and left through the top, which is `Layout::classic`. Mods
+//! can give `Celeste::layout` rooms of any size up to the whole map, in any order, left through
+//! any side. Rooms bigger than the screen scroll to follow the player, see `Celeste::scroll`
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+/// A rectangle of map tiles that's played as one room
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Room {
+    /// the top left tile, see `Memory::map_size` for how big the map is
+    pub x: i32,
+    pub y: i32,
+    /// size in tiles, at least 16 so the room fills the screen
+    pub w: i32,
+    pub h: i32,
+    pub exits: Exits,
+}
+crate::snapshot::snapshot_fields!(Room { x, y, w, h, exits });
+
+/// The sides of a room the player goes to the next room through. The other sides are walls,
+/// except the bottom, which is a pit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Exits {
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+}
+crate::snapshot::snapshot_fields!(Exits {
+    top,
+    bottom,
+    left,
+    right
+});
+
+impl Room {
+    /// A one screen room at `x`, `y` in rooms rather than tiles, left through the top
+    pub const fn screen(x: i32, y: i32) -> Room {
+        Room {
+            x: x * 16,
+            y: y * 16,
+            w: 16,
+            h: 16,
+            exits: Exits {
+                top: true,
+                bottom: false,
+                left: false,
+                right: false,
+            },
+        }
+    }
+    /// width in pixels
+    pub fn width(&self) -> i32 {
+        self.w * 8
+    }
+    /// height in pixels
+    pub fn height(&self) -> i32 {
+        self.h * 8
+    }
+}
+
+/// Every room of the game by `Celeste::level`, and the title screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    /// entered in this order, from 100m. The last one is the summit, where the timer stops. At
+    /// most 254, so `title_level` fits in a `u8`
+    pub rooms: Vec<Room>,
+    /// drawn behind the title screen
+    pub title: Room,
+    /// what the title card calls a room instead of its height, by level. The summit is called
+    /// the summit unless it's named here
+    pub names: BTreeMap<u8, String>,
+}
+
+impl Layout {
+    /// The cart's rooms: 100m at the top left of the map, the summit at the bottom, the title
+    /// screen in the bottom right corner
+    pub fn classic() -> Layout {
+        Layout {
+            rooms: (0..31)
+                .map(|level| Room::screen(level % 8, level / 8))
+                .collect(),
+            title: Room::screen(7, 3),
+            names: BTreeMap::from([(11, "old site".into())]),
+        }
+    }
+    /// The room of `level`, or the title screen's past the last one. The cart goes on from the
+    /// summit to the room after it in the grid, which is the title screen's too
+    pub fn room(&self, level: u8) -> Room {
+        self.rooms
+            .get(level as usize)
+            .copied()
+            .unwrap_or(self.title)
+    }
+    /// the level the timer stops at
+    pub fn summit(&self) -> u8 {
+        self.rooms.len().saturating_sub(1) as u8
+    }
+    /// The `level` of the title screen. One past the room after the summit, like the cart's 32
+    pub fn title_level(&self) -> u8 {
+        (self.rooms.len() + 1).min(u8::MAX as usize) as u8
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::classic()
+    }
+}
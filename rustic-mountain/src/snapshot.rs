@@ -0,0 +1,326 @@
+//! Copies of the game state that can be loaded back later, see `Celeste::save_state`.
+//!
+//! Everything that changes while playing is in a snapshot: the objects, timers, stats, the
+//! random number generator and the cosmetic particles. Settings (`assist`, `practice`, `debug`),
+//! the personal best, ghosts and queued events aren't. A `GameObject`'s own state isn't
+//! serialized, the snapshot keeps a copy of it instead, and every load gets a fresh copy of that
+use alloc::vec::Vec;
+
+use crate::{fixed::Fix16, objects::custom::CustomObject, Celeste};
+
+/// A saved copy of the game, from `Celeste::save_state`
+#[derive(Clone)]
+pub struct SaveState {
+    /// the serialized state. stable for a given build of the crate, but nothing more
+    pub data: Vec<u8>,
+    pub(crate) customs: Vec<CustomObject>,
+}
+
+/// Where `Snapshot::save` writes to
+#[derive(Default)]
+pub struct Writer {
+    pub data: Vec<u8>,
+    pub(crate) customs: Vec<CustomObject>,
+}
+
+/// Where `Snapshot::load` reads from
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pub(crate) customs: &'a [CustomObject],
+}
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.data.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(n);
+        self.data = rest;
+        Some(taken)
+    }
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+}
+
+/// Something that can be written into a snapshot and read back. `load` returns `None` if the
+/// data doesn't make sense
+pub trait Snapshot: Sized {
+    fn save(&self, w: &mut Writer);
+    fn load(r: &mut Reader) -> Option<Self>;
+}
+
+macro_rules! snapshot_numbers {
+    ($($t:ty),*) => {
+        $(
+            impl Snapshot for $t {
+                fn save(&self, w: &mut Writer) {
+                    w.data.extend_from_slice(&self.to_le_bytes());
+                }
+                fn load(r: &mut Reader) -> Option<Self> {
+                    Some(<$t>::from_le_bytes(r.array()?))
+                }
+            }
+        )*
+    };
+}
+snapshot_numbers!(u8, i8, u16, u32, i32, u64, f32);
+
+impl Snapshot for bool {
+    fn save(&self, w: &mut Writer) {
+        (*self as u8).save(w);
+    }
+    fn load(r: &mut Reader) -> Option<Self> {
+        match u8::load(r)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Snapshot for Fix16 {
+    fn save(&self, w: &mut Writer) {
+        self.to_bits().save(w);
+    }
+    fn load(r: &mut Reader) -> Option<Self> {
+        Some(Fix16::from_bits(i32::load(r)?))
+    }
+}
+
+impl<T: Snapshot> Snapshot for Option<T> {
+    fn save(&self, w: &mut Writer) {
+        self.is_some().save(w);
+        if let Some(v) = self {
+            v.save(w);
+        }
+    }
+    fn load(r: &mut Reader) -> Option<Self> {
+        Some(if bool::load(r)? {
+            Some(T::load(r)?)
+        } else {
+            None
+        })
+    }
+}
+
+impl<T: Snapshot> Snapshot for Vec<T> {
+    fn save(&self, w: &mut Writer) {
+        (self.len() as u32).save(w);
+        for v in self {
+            v.save(w);
+        }
+    }
+    fn load(r: &mut Reader) -> Option<Self> {
+        let len = u32::load(r)? as usize;
+        // every element takes at least a byte, so this stops a bad length from allocating
+        // more than the data could hold
+        let mut v = Vec::with_capacity(len.min(r.data.len()));
+        for _ in 0..len {
+            v.push(T::load(r)?);
+        }
+        Some(v)
+    }
+}
+
+/// Implements `Snapshot` for a struct by saving each of the listed fields in order. The list has
+/// to name every field
+macro_rules! snapshot_fields {
+    ($ty:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::snapshot::Snapshot for $ty {
+            fn save(&self, _w: &mut $crate::snapshot::Writer) {
+                $($crate::snapshot::Snapshot::save(&self.$field, _w);)*
+            }
+            fn load(_r: &mut $crate::snapshot::Reader) -> Option<Self> {
+                Some($ty {
+                    $($field: $crate::snapshot::Snapshot::load(_r)?,)*
+                })
+            }
+        }
+    };
+}
+pub(crate) use snapshot_fields;
+
+/// The fields of `Celeste` that go in a snapshot, saved and loaded in this order
+macro_rules! celeste_fields {
+    ($($field:ident),* $(,)?) => {
+        impl Celeste {
+            fn save_fields(&self, w: &mut Writer) {
+                $(self.$field.save(w);)*
+            }
+            /// all or nothing, nothing is changed unless every field loads
+            fn load_fields(&mut self, r: &mut Reader) -> Option<()> {
+                $(let $field = Snapshot::load(r)?;)*
+                $(self.$field = $field;)*
+                Some(())
+            }
+        }
+    };
+}
+celeste_fields!(
+    objects,
+    got_fruit,
+    max_djump,
+    deaths,
+    frames,
+    room,
+    scroll,
+    level,
+    has_dashed,
+    has_key,
+    freeze,
+    particles,
+    dead_particles,
+    delay_restart,
+    shake,
+    seconds,
+    minutes,
+    clouds,
+    start_game_flash,
+    music_timer,
+    start_game,
+    flash_bg,
+    new_bg,
+    pause_player,
+    sfx_timer,
+    run_frames,
+    room_stats,
+    splits,
+    il_frames,
+    last_il,
+    speed_clock,
+    skipped_buttons,
+);
+
+impl Celeste {
+    /// Copies the current state of the game. Call it between ticks
+    pub fn save_state(&self) -> SaveState {
+        let mut w = Writer::default();
+        self.mem.camera.save(&mut w);
+        self.mem.rng.save(&mut w);
+        self.save_fields(&mut w);
+        SaveState {
+            data: w.data,
+            customs: w.customs,
+        }
+    }
+    /// Puts the game back to how it was when `state` was saved. Returns `false`, leaving the
+    /// game as it was, if `state` doesn't load
+    pub fn load_state(&mut self, state: &SaveState) -> bool {
+        let mut r = Reader {
+            data: &state.data,
+            customs: &state.customs,
+        };
+        // memory is read first but only set once the rest has loaded
+        let (Some(camera), Some(rng)) = (Snapshot::load(&mut r), Snapshot::load(&mut r)) else {
+            return false;
+        };
+        if self.load_fields(&mut r).is_none() {
+            return false;
+        }
+        self.mem.camera = camera;
+        self.mem.rng = rng;
+        self.pause_menu = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixed::fix,
+        objects::custom::{self, GameObject},
+        structures::{Object, ObjectKind},
+        testcart,
+    };
+    use alloc::vec;
+
+    #[derive(Clone)]
+    struct Counter {
+        ticks: u32,
+    }
+    impl GameObject for Counter {
+        fn init(_celeste: &mut Celeste, _x: Fix16, _y: Fix16, _tile: u8) -> Self {
+            Counter { ticks: 0 }
+        }
+        fn update(&mut self, _obj: &mut Object, _celeste: &mut Celeste) {
+            self.ticks += 1;
+        }
+    }
+
+    /// 100m with a `Counter` in it
+    fn playing() -> Celeste {
+        let mut celeste = testcart::celeste();
+        celeste.seed(5);
+        celeste.begin_game();
+        let counter = custom::init::<Counter>(&mut celeste, fix(8.0), fix(8.0), 0).unwrap();
+        celeste.objects.spawn(counter);
+        celeste.objects.flush(0);
+        celeste
+    }
+
+    fn play(celeste: &mut Celeste, ticks: u32) {
+        for tick in 0..ticks {
+            celeste.mem.buttons = vec![false, tick % 40 < 30, false, false, tick % 25 < 3, false];
+            celeste.next_tick();
+        }
+    }
+
+    fn counted(celeste: &Celeste) -> u32 {
+        let (id, _) = celeste
+            .objects
+            .of_kind(ObjectKind::of::<Counter>())
+            .next()
+            .unwrap();
+        celeste.get_custom::<Counter>(id).unwrap().ticks
+    }
+
+    #[test]
+    fn loading_goes_back_to_the_saved_tick() {
+        let mut celeste = playing();
+        play(&mut celeste, 20);
+        let saved = celeste.save_state();
+        play(&mut celeste, 45);
+        let after = celeste.save_state();
+        assert!(after.data != saved.data);
+        assert_eq!(counted(&celeste), 65);
+
+        assert!(celeste.load_state(&saved));
+        assert!(celeste.save_state().data == saved.data);
+        assert_eq!(counted(&celeste), 20);
+
+        // playing on doesn't touch the state it was loaded from, and goes the same way again
+        play(&mut celeste, 45);
+        assert!(celeste.save_state().data == after.data);
+        assert!(celeste.load_state(&saved));
+        assert_eq!(counted(&celeste), 20);
+        play(&mut celeste, 45);
+        assert!(celeste.save_state().data == after.data);
+        assert_eq!(counted(&celeste), 65);
+    }
+
+    #[test]
+    fn broken_states_leave_the_game_alone() {
+        let mut celeste = playing();
+        play(&mut celeste, 10);
+        let before = celeste.save_state();
+        let mut other = playing();
+        play(&mut other, 30);
+        let mut state = other.save_state();
+
+        let len = state.data.len();
+        for len in [0, 4, 8, len / 2, len - 1] {
+            let truncated = SaveState {
+                data: state.data[..len].to_vec(),
+                customs: state.customs.clone(),
+            };
+            assert!(!celeste.load_state(&truncated), "{} bytes", len);
+            assert!(celeste.save_state().data == before.data);
+        }
+        state.customs.clear();
+        assert!(!celeste.load_state(&state));
+        assert!(celeste.save_state().data == before.data);
+        assert_eq!(counted(&celeste), 10);
+    }
+}
@@ -0,0 +1,183 @@
+use alloc::{format, string::String, vec::Vec};
+
+/// The run timer at the moment each room was left, in frames since the run started. Recorded by
+/// `Celeste::next_room`, so the last split is also the final time of a finished run. A full run
+/// leaves every room before the summit, so it has `Layout::summit` splits
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Splits {
+    pub times: Vec<u64>,
+    /// assist mode was used at some point during the run
+    pub assisted: bool,
+}
+crate::snapshot::snapshot_fields!(Splits { times, assisted });
+
+impl Splits {
+    /// Whether the run has all `count` splits of a full run
+    pub fn is_complete(&self, count: usize) -> bool {
+        count > 0 && self.times.len() >= count
+    }
+    /// The final time, if the run made it to the summit
+    pub fn total(&self, count: usize) -> Option<u64> {
+        if self.is_complete(count) {
+            self.times.get(count - 1).copied()
+        } else {
+            None
+        }
+    }
+    /// Time spent in room `i` alone
+    pub fn segment(&self, i: usize) -> Option<u64> {
+        let end = *self.times.get(i)?;
+        Some(end - if i == 0 { 0 } else { self.times[i - 1] })
+    }
+    /// How far ahead (negative) or behind (positive) `other` this run was when leaving room `i`
+    pub fn delta(&self, other: &Splits, i: usize) -> Option<i64> {
+        Some(*self.times.get(i)? as i64 - *other.times.get(i)? as i64)
+    }
+    /// Whether this is an unassisted finished run that's faster than `other`, or `other` never
+    /// finished
+    pub fn beats(&self, other: &Splits, count: usize) -> bool {
+        if self.assisted {
+            return false;
+        }
+        match (self.total(count), other.total(count)) {
+            (Some(a), Some(b)) => a < b,
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+    /// One split per line, as a frame count, after an `assisted` line if it was. This is what
+    /// frontends should save
+    pub fn to_text(&self) -> String {
+        let mut text: String = self.times.iter().map(|t| format!("{}\n", t)).collect();
+        if self.assisted {
+            text.insert_str(0, "assisted\n");
+        }
+        text
+    }
+    /// Parses what `to_text` wrote. Returns `None` if a line isn't a frame count or the times
+    /// go backwards
+    pub fn from_text(text: &str) -> Option<Splits> {
+        let mut times = Vec::new();
+        let mut assisted = false;
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if line == "assisted" {
+                assisted = true;
+                continue;
+            }
+            let t: u64 = line.parse().ok()?;
+            if times.last().is_some_and(|&last| t < last) {
+                return None;
+            }
+            times.push(t);
+        }
+        Some(Splits { times, assisted })
+    }
+}
+
+/// `frames` as minutes:seconds.hundredths. Every frame gets a distinct value
+pub fn format_frames(frames: u64) -> String {
+    format!(
+        "{}:{:02}.{:02}",
+        frames / 1800,
+        frames / 30 % 60,
+        frames % 30 * 100 / 30
+    )
+}
+
+/// A split delta, with the sign always shown
+pub fn format_delta(delta: i64) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    let frames = delta.unsigned_abs();
+    if frames < 1800 {
+        format!("{}{}.{:02}", sign, frames / 30, frames % 30 * 100 / 30)
+    } else {
+        format!("{}{}", sign, format_frames(frames))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn splits(times: &[u64]) -> Splits {
+        Splits {
+            times: times.to_vec(),
+            assisted: false,
+        }
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let run = splits(&[90, 200, 200, 451]);
+        assert_eq!(run.to_text(), "90\n200\n200\n451\n");
+        assert_eq!(Splits::from_text(&run.to_text()), Some(run.clone()));
+
+        let assisted = Splits {
+            assisted: true,
+            ..run
+        };
+        assert!(assisted.to_text().starts_with("assisted\n"));
+        assert_eq!(Splits::from_text(&assisted.to_text()), Some(assisted));
+        assert_eq!(Splits::from_text(""), Some(Splits::default()));
+    }
+
+    #[test]
+    fn from_text_tolerates_blank_lines_and_spaces() {
+        assert_eq!(
+            Splits::from_text("\r\n 90 \r\n\n200\r\n"),
+            Some(splits(&[90, 200]))
+        );
+    }
+
+    #[test]
+    fn from_text_rejects_malformed_lines() {
+        for text in ["90\nabc\n", "-3\n", "1.5\n", "90 200\n", "assisted 90\n"] {
+            assert_eq!(Splits::from_text(text), None, "{:?}", text);
+        }
+        // times only go forwards
+        assert_eq!(Splits::from_text("200\n90\n"), None);
+    }
+
+    #[test]
+    fn beats_needs_a_faster_unassisted_full_run() {
+        let pb = splits(&[100, 200, 300]);
+        assert!(splits(&[90, 180, 299]).beats(&pb, 3));
+        assert!(!splits(&[90, 180, 300]).beats(&pb, 3));
+        assert!(!splits(&[90, 180, 301]).beats(&pb, 3));
+        // extra splits past the summit don't count
+        assert!(splits(&[90, 180, 299, 1000]).beats(&pb, 3));
+
+        // unfinished runs never beat anything, and lose to anything finished
+        assert!(!splits(&[90, 180]).beats(&pb, 3));
+        assert!(splits(&[1000, 2000, 3000]).beats(&splits(&[100]), 3));
+        assert!(splits(&[1000, 2000, 3000]).beats(&Splits::default(), 3));
+        assert!(!splits(&[90]).beats(&Splits::default(), 3));
+
+        let assisted = Splits {
+            times: vec![1, 2, 3],
+            assisted: true,
+        };
+        assert!(!assisted.beats(&pb, 3));
+        assert!(!splits(&[1]).beats(&Splits::default(), 0));
+    }
+
+    #[test]
+    fn segments_and_deltas() {
+        let run = splits(&[90, 200, 451]);
+        let pb = splits(&[100, 190]);
+        assert_eq!(run.total(3), Some(451));
+        assert_eq!(run.total(4), None);
+        assert_eq!(
+            [0, 1, 2, 3].map(|i| run.segment(i)),
+            [Some(90), Some(110), Some(251), None]
+        );
+        assert_eq!(
+            [0, 1, 2].map(|i| run.delta(&pb, i)),
+            [Some(-10), Some(10), None]
+        );
+        assert_eq!(format_frames(1800 + 30 * 5 + 15), "1:05.50");
+        assert_eq!(format_delta(-10), "-0.33");
+        assert_eq!(format_delta(1800 + 31), "+1:01.03");
+    }
+}
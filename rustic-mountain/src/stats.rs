@@ -0,0 +1,166 @@
+use alloc::{format, string::String};
+
+use crate::{events::GameEvent, rooms::Layout};
+
+/// What happened in one room over the course of a run. Times are in frames (30 a second) and
+/// only count while the in-game timer is running
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoomStats {
+    pub deaths: u32,
+    /// time spent in the room, including the time spent dead
+    pub frames: u32,
+    pub dashes: u32,
+    pub berry: bool,
+    /// `Celeste::run_frames` when the room was first entered, `None` if it hasn't been yet
+    pub first_entered: Option<u64>,
+    /// assist mode was on for at least one tick spent here
+    pub assisted: bool,
+}
+crate::snapshot::snapshot_fields!(RoomStats {
+    deaths,
+    frames,
+    dashes,
+    berry,
+    first_entered,
+    assisted,
+});
+
+impl RoomStats {
+    /// Updates the stats of the room `event` happened in
+    pub fn record(stats: &mut [RoomStats], event: GameEvent, run_frames: u64) {
+        let level = match event {
+            GameEvent::PlayerDied { level, .. }
+            | GameEvent::DashUsed { level }
+            | GameEvent::BerryCollected { level }
+            | GameEvent::RoomEntered { level } => level,
+            _ => return,
+        };
+        let Some(room) = stats.get_mut(level as usize) else {
+            return;
+        };
+        match event {
+            GameEvent::PlayerDied { .. } => room.deaths += 1,
+            GameEvent::DashUsed { .. } => room.dashes += 1,
+            GameEvent::BerryCollected { .. } => room.berry = true,
+            GameEvent::RoomEntered { .. } => {
+                room.first_entered.get_or_insert(run_frames);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The name the room title card shows for `level` of `layout`
+pub fn room_name(layout: &Layout, level: u8) -> String {
+    match layout.names.get(&level) {
+        Some(name) => name.clone(),
+        None if level == layout.summit() => "summit".into(),
+        None => format!("{}00 m", level as u32 + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory::CartData, rooms::Room, Celeste};
+    use alloc::{rc::Rc, vec};
+
+    #[test]
+    fn names_come_from_the_layout() {
+        let mut layout = Layout::classic();
+        assert_eq!(room_name(&layout, 0), "100 m");
+        assert_eq!(room_name(&layout, 10), "1100 m");
+        assert_eq!(room_name(&layout, 11), "old site");
+        assert_eq!(room_name(&layout, 30), "summit");
+
+        layout.rooms.truncate(3);
+        layout.names = [(0, "base".into()), (2, "top".into())].into();
+        assert_eq!(room_name(&layout, 0), "base");
+        assert_eq!(room_name(&layout, 1), "200 m");
+        assert_eq!(room_name(&layout, 2), "top");
+        assert_eq!(room_name(&layout, 11), "1200 m");
+        layout.rooms.push(Room::screen(3, 0));
+        assert_eq!(room_name(&layout, 3), "summit");
+    }
+
+    #[test]
+    fn events_add_up_per_room() {
+        let mut stats = vec![RoomStats::default(); 2];
+        let events = [
+            GameEvent::RoomEntered { level: 0 },
+            GameEvent::DashUsed { level: 0 },
+            GameEvent::PlayerDied {
+                level: 0,
+                x: 4,
+                y: 128,
+            },
+            GameEvent::RoomEntered { level: 0 },
+            GameEvent::DashUsed { level: 0 },
+            GameEvent::RoomEntered { level: 1 },
+            GameEvent::BerryCollected { level: 1 },
+            GameEvent::KeyCollected { level: 1 },
+            GameEvent::SummitReached,
+            // past the last room
+            GameEvent::PlayerDied {
+                level: 5,
+                x: 0,
+                y: 0,
+            },
+        ];
+        for (frame, event) in events.into_iter().enumerate() {
+            RoomStats::record(&mut stats, event, frame as u64 * 10);
+        }
+        assert_eq!(
+            stats[0],
+            RoomStats {
+                deaths: 1,
+                dashes: 2,
+                first_entered: Some(0),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            stats[1],
+            RoomStats {
+                berry: true,
+                first_entered: Some(50),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn frames_count_while_the_timer_runs() {
+        let mut celeste = Celeste::from_cart(&CartData {
+            map: Rc::new(vec![0; 128 * 32]),
+            map_width: 128,
+            sprites: Rc::new(vec![0; 128 * 128]),
+            flags: Rc::new(vec![0; 256]),
+            fontatlas: Rc::new(vec![]),
+        });
+        celeste.next_tick();
+        assert!(celeste.room_stats.iter().all(|room| room.frames == 0));
+
+        celeste.begin_game();
+        for _ in 0..45 {
+            celeste.next_tick();
+        }
+        celeste.assist.invincible = true;
+        celeste.next_tick();
+        assert_eq!(celeste.room_stats[0].frames, 46);
+        assert_eq!(celeste.room_stats[0].first_entered, Some(0));
+        assert!(celeste.room_stats[0].assisted);
+        assert!(celeste.room_stats[1..]
+            .iter()
+            .all(|room| *room == RoomStats::default()));
+
+        celeste.begin_game();
+        assert_eq!(
+            celeste.room_stats[0],
+            RoomStats {
+                first_entered: Some(0),
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -1,37 +1,61 @@
-use core::cell::RefCell;
-use alloc::rc::Rc;
+use core::{
+    any::TypeId,
+    cell::{RefCell, RefMut},
+};
+use alloc::{rc::Rc, vec::Vec};
 
 // #[macro_use]
 
 use crate::{
     objects::{
-        balloon::Balloon, bigchest::BigChest, chest::Chest, fakewall::FakeWall,
-        fallfloor::FallFloor, flag::Flag, flyfruit::FlyFruit, fruit::Fruit, key::Key,
-        lifeup::LifeUp, message::Message, orb::Orb, platform::Platform, player::Player,
-        playerspawn::PlayerSpawn, roomtitle::RoomTitle, smoke::Smoke, spring::Spring,
+        balloon::Balloon,
+        bigchest::BigChest,
+        chest::Chest,
+        custom::{self, AsAny, CustomObject, GameObject},
+        fakewall::FakeWall,
+        fallfloor::FallFloor,
+        flag::Flag,
+        flyfruit::FlyFruit,
+        fruit::Fruit,
+        key::Key,
+        lifeup::LifeUp,
+        message::Message,
+        orb::Orb,
+        platform::Platform,
+        player::Player,
+        playerspawn::PlayerSpawn,
+        roomtitle::RoomTitle,
+        smoke::Smoke,
+        spring::Spring,
     },
+    fixed::{fix, Fix16},
+    snapshot::{snapshot_fields, Reader, Snapshot, Writer},
     utils::*,
     Celeste,
 };
 
 #[derive(PartialEq, Clone)]
 pub struct Vector {
-    pub x: f32,
-    pub y: f32,
+    pub x: Fix16,
+    pub y: Fix16,
 }
+snapshot_fields!(Vector { x, y });
 
+#[derive(Clone)]
 pub struct Rectangle {
-    pub x: f32,
-    pub y: f32,
-    pub w: f32,
-    pub h: f32,
+    pub x: Fix16,
+    pub y: Fix16,
+    pub w: Fix16,
+    pub h: Fix16,
 }
+snapshot_fields!(Rectangle { x, y, w, h });
 
 #[derive(Clone)]
 pub struct FlipState {
     pub x: bool,
     pub y: bool,
 }
+snapshot_fields!(FlipState { x, y });
 
 pub struct Object {
     pub pos: Vector,
@@ -48,9 +72,11 @@ pub struct Object {
     pub draw: ObjFunc,
     pub update: ObjFunc,
 
-    pub name: &'static str,
+    /// assigned by `Objects::spawn`
+    pub id: ObjectId,
 }
 
+#[derive(Clone, Copy)]
 pub struct ObjFunc(pub fn(&mut Object, &mut Celeste));
 impl Default for ObjFunc {
     fn default() -> Self {
@@ -59,134 +85,153 @@ impl Default for ObjFunc {
 }
 pub fn noop(_: &mut Object, _: &mut Celeste) {}
 impl Object {
+    pub fn kind(&self) -> ObjectKind {
+        self.obj_type.kind()
+    }
     pub fn draw(&mut self, celeste: &mut Celeste) {
         (self.draw).0(self, celeste);
     }
     pub fn update(&mut self, celeste: &mut Celeste) {
         (self.update).0(self, celeste);
     }
-    pub fn left(&self) -> f32 {
+    pub fn left(&self) -> Fix16 {
         self.pos.x + self.hitbox.x
     }
-    pub fn right(&self) -> f32 {
-        self.left() + self.hitbox.w - 1f32
+    pub fn right(&self) -> Fix16 {
+        self.left() + self.hitbox.w - Fix16::ONE
     }
-    pub fn top(&self) -> f32 {
+    pub fn top(&self) -> Fix16 {
         self.pos.y + self.hitbox.y
     }
-    pub fn bottom(&self) -> f32 {
-        self.top() + self.hitbox.h - 1f32
+    pub fn bottom(&self) -> Fix16 {
+        self.top() + self.hitbox.h - Fix16::ONE
     }
 
-    pub fn init_smoke(&self, celeste: &mut Celeste, x: f32, y: f32) {
+    pub fn init_smoke(&self, celeste: &mut Celeste, x: Fix16, y: Fix16) {
         let smoke = Smoke::init(celeste, self.pos.x + x, self.pos.y + y);
-        celeste.objects.push(Rc::new(RefCell::new(smoke)));
+        celeste.objects.spawn(smoke);
     }
 
     pub fn draw_sprite(&self, celeste: &mut Celeste) {
         celeste.mem.spr(
             self.spr,
-            self.pos.x as i32,
-            self.pos.y as i32,
+            self.pos.x.to_int(),
+            self.pos.y.to_int(),
             Some(self.flip.clone()),
         )
     }
 
-    pub fn do_move(&mut self, celeste: &mut Celeste, ox: f32, oy: f32, start: f32) {
+    pub fn do_move(&mut self, celeste: &mut Celeste, ox: Fix16, oy: Fix16, start: Fix16) {
         self.rem.x += ox;
-        let amt = (self.rem.x + 0.5).floor();
+        let amt = (self.rem.x + fix(0.5)).floor();
         self.rem.x -= amt;
         if self.solids {
             let step = sign(amt);
             let mut i = start;
             loop {
-                if !self.is_solid(step, 0f32, celeste) {
+                if !self.is_solid(step, Fix16::ZERO, celeste) {
                     self.pos.x += step;
                 } else {
-                    self.spd.x = 0f32;
-                    self.rem.x = 0f32;
+                    self.spd.x = Fix16::ZERO;
+                    self.rem.x = Fix16::ZERO;
                     break;
                 }
                 if i >= amt.abs() {
                     break;
                 }
-                i += 1f32;
+                i += Fix16::ONE;
             }
         } else {
             self.pos.x += amt;
         }
 
         self.rem.y += oy;
-        let amt = (self.rem.y + 0.5).floor();
+        let amt = (self.rem.y + fix(0.5)).floor();
         self.rem.y -= amt;
         if self.solids {
             let step = sign(amt);
-            let mut i = 0f32; //start
+            let mut i = Fix16::ZERO; //start
             loop {
-                if !self.is_solid(0f32, step, celeste) {
+                if !self.is_solid(Fix16::ZERO, step, celeste) {
                     self.pos.y += step;
                 } else {
-                    self.spd.y = 0f32;
-                    self.rem.y = 0f32;
+                    self.spd.y = Fix16::ZERO;
+                    self.rem.y = Fix16::ZERO;
                     break;
                 }
                 if i >= amt.abs() {
                     break;
                 }
-                i += 1f32;
+                i += Fix16::ONE;
             }
         } else {
             self.pos.y += amt;
         }
     }
+    /// Finds a collidable object of `kind` overlapping this one once it's moved by (x, y)
     pub fn check(
-        &mut self,
-        celeste: &mut Celeste,
-        name: &'static str,
-        x: f32,
-        y: f32,
-    ) -> Option<usize> {
-        let obj = self;
-        for i in 0..celeste.objects.len() {
-            match celeste.objects[i].try_borrow() {
-                Ok(other) => {
-                    if other.name == name && other.collidable {
-                        if other.right() >= obj.left() + x
-                            && other.bottom() >= obj.top() + y
-                            && other.left() <= obj.right() + x
-                            && other.top() <= obj.bottom() + y
-                        {
-                            return Some(i);
-                        }
-                    }
-                }
-                Err(_) => (), //println!("couldn't borrow {} from {}", &name, &obj.name),
-            };
-        }
-        None
+        &self,
+        celeste: &Celeste,
+        kind: ObjectKind,
+        x: Fix16,
+        y: Fix16,
+    ) -> Option<ObjectId> {
+        celeste
+            .objects
+            .of_kind(kind)
+            .find(|(id, other)| *id != self.id && self.hits(&other.borrow(), x, y))
+            .map(|(id, _)| id)
+    }
+    /// whether any custom object flagged `solid` overlaps this one once it's moved by (x, y)
+    pub fn check_custom_solid(&self, celeste: &Celeste, x: Fix16, y: Fix16) -> bool {
+        celeste.objects.customs().any(|(id, custom, other)| {
+            id != self.id && custom.flags.solid && self.overlaps(&other.borrow(), x, y)
+        })
     }
-    pub fn is_ice(&self, x: f32, y: f32, celeste: &mut Celeste) -> bool {
+    fn hits(&self, other: &Object, x: Fix16, y: Fix16) -> bool {
+        other.collidable && self.overlaps(other, x, y)
+    }
+    fn overlaps(&self, other: &Object, x: Fix16, y: Fix16) -> bool {
+        other.right() >= self.left() + x
+            && other.bottom() >= self.top() + y
+            && other.left() <= self.right() + x
+            && other.top() <= self.bottom() + y
+    }
+    pub fn is_ice(&self, x: Fix16, y: Fix16, celeste: &mut Celeste) -> bool {
         self.is_flag(x, y, 4, celeste)
     }
-    pub fn is_solid(&mut self, x: f32, y: f32, celeste: &mut Celeste) -> bool {
-        return (y > 0f32
-            && self.check(celeste, "Platform", x, 0f32).is_none()
-            && self.check(celeste, "Platform", x, y).is_some())
+    pub fn is_solid(&self, x: Fix16, y: Fix16, celeste: &mut Celeste) -> bool {
+        (y > Fix16::ZERO
+            && self
+                .check(celeste, ObjectKind::Platform, x, Fix16::ZERO)
+                .is_none()
+            && self.check(celeste, ObjectKind::Platform, x, y).is_some())
             || self.is_flag(x, y, 1, celeste)
-            || self.check(celeste, "FallFloor", x, y).is_some()
-            || self.check(celeste, "FakeWall", x, y).is_some();
+            || self.check(celeste, ObjectKind::FallFloor, x, y).is_some()
+            || self.check(celeste, ObjectKind::FakeWall, x, y).is_some()
+            || self.check_custom_solid(celeste, x, y)
     }
-    pub fn is_flag(&self, x: f32, y: f32, flag: u8, celeste: &mut Celeste) -> bool {
-        for i in max(0f32, (self.left() + x) / 8f32) as i32
-            ..(min(15f32, (self.right() + x) / 8f32)) as i32 + 1
+    pub fn is_flag(&self, x: Fix16, y: Fix16, flag: u8, celeste: &mut Celeste) -> bool {
+        let eight = Fix16::from_int(8);
+        for i in max(Fix16::ZERO, (self.left() + x) / eight).to_int()
+            ..min(
+                Fix16::from_int(celeste.room.w - 1),
+                (self.right() + x) / eight,
+            )
+            .to_int()
+                + 1
         {
-            for j in max(0f32, (self.top() + y) / 8f32) as i32
-                ..min(15f32, (self.bottom() + y) / 8f32) as i32 + 1
+            for j in max(Fix16::ZERO, (self.top() + y) / eight).to_int()
+                ..min(
+                    Fix16::from_int(celeste.room.h - 1),
+                    (self.bottom() + y) / eight,
+                )
+                .to_int()
+                    + 1
             {
-                let fg = celeste.mem.fget_all(celeste.mem.mget(
-                    (celeste.room.x as u8 * 16) + i as u8,
-                    (celeste.room.y as u8 * 16) + j as u8,
-                ));
+                let fg = celeste
+                    .mem
+                    .fget_all(celeste.mem.mget(celeste.room.x + i, celeste.room.y + j));
                 if (flag & fg) == flag {
                     return true;
                 }
@@ -196,44 +241,572 @@ impl Object {
     }
 
     /// and then they turned themself into a strawberry. funniest shit i've ever seen
-    pub fn init_fruit(&mut self, celeste: &mut Celeste, ox: f32, oy: f32) {
-        // sfx shit
+    pub fn init_fruit(&self, celeste: &mut Celeste, ox: Fix16, oy: Fix16) {
+        celeste.sfx_timer = 20;
+        celeste.sfx(16);
         let fruit = Fruit::init(celeste, self.pos.x + ox, self.pos.y + oy);
-        celeste.objects.push(Rc::new(RefCell::new(fruit)));
+        celeste.objects.spawn(fruit);
         self.destroy_self(celeste);
     }
 
-    /// WARNING: Only use this function if it is being called by the object itself in an update loop
-    pub fn destroy_self(&mut self, celeste: &mut Celeste) {
-        celeste.objects.retain(|o| o.try_borrow().is_ok());
-        // this particular bit of jank will delete any object that is currently being used in memory
+    pub fn destroy_self(&self, celeste: &mut Celeste) {
+        celeste.objects.despawn(self.id);
+    }
+
+    /// Runs `f` (an update) on the object in `cell` without keeping `cell` borrowed. A copy
+    /// stands in for the object while it runs, so collision checks that other objects make
+    /// meanwhile still find it; see `with_object` for keeping the copy up to date. Draws don't
+    /// move anything, so they run on the object in place instead
+    pub(crate) fn run(
+        cell: &RefCell<Object>,
+        celeste: &mut Celeste,
+        f: impl FnOnce(&mut Object, &mut Celeste),
+    ) {
+        let stand_in = {
+            let obj = cell.borrow();
+            obj.copy_with(obj.obj_type.clone())
+        };
+        let mut obj = cell.replace(stand_in);
+        f(&mut obj, celeste);
+        *cell.borrow_mut() = obj;
+    }
+
+    /// Runs `f` on the object `id` from this one's update or draw, with this object put back in
+    /// its slot meanwhile, so anything `f` moves or checks runs into it where it is now rather
+    /// than where it was when it started running. `None` if `id` is gone
+    pub fn with_object<R>(
+        &mut self,
+        celeste: &mut Celeste,
+        id: ObjectId,
+        f: impl FnOnce(&mut Object, &mut Celeste) -> R,
+    ) -> Option<R> {
+        let other = celeste.objects.get_object(id)?;
+        // a drawing object is already in its slot, and borrowed
+        let this = celeste
+            .objects
+            .get_object(self.id)
+            .filter(|this| this.try_borrow_mut().is_ok());
+        if let Some(this) = &this {
+            core::mem::swap(self, &mut this.borrow_mut());
+        }
+        let res = f(&mut other.borrow_mut(), celeste);
+        if let Some(this) = &this {
+            core::mem::swap(self, &mut this.borrow_mut());
+        }
+        Some(res)
+    }
+
+    /// a copy of everything but the type specific state, which is `obj_type` instead
+    fn copy_with(&self, obj_type: ObjectType) -> Object {
+        Object {
+            pos: self.pos.clone(),
+            spd: self.spd.clone(),
+            rem: self.rem.clone(),
+            spr: self.spr,
+            hitbox: self.hitbox.clone(),
+            flip: self.flip.clone(),
+            collidable: self.collidable,
+            solids: self.solids,
+            obj_type,
+            draw: self.draw,
+            update: self.update,
+            id: self.id,
+        }
     }
+}
 
-    pub fn destroy_other(&mut self, celeste: &mut Celeste) {
-        celeste.objects.retain(|objref| match objref.try_borrow() {
-            Ok(obj) => obj.name == self.name && obj.pos == self.pos,
-            Err(_) => true,
+/// `draw` and `update` aren't saved, they're put back from `obj_type` when loading
+impl Snapshot for Object {
+    fn save(&self, w: &mut Writer) {
+        self.pos.save(w);
+        self.spd.save(w);
+        self.rem.save(w);
+        self.spr.save(w);
+        self.hitbox.save(w);
+        self.flip.save(w);
+        self.collidable.save(w);
+        self.solids.save(w);
+        self.obj_type.save(w);
+        self.id.save(w);
+    }
+    fn load(r: &mut Reader) -> Option<Self> {
+        let pos = Vector::load(r)?;
+        let spd = Vector::load(r)?;
+        let rem = Vector::load(r)?;
+        let spr = u8::load(r)?;
+        let hitbox = Rectangle::load(r)?;
+        let flip = FlipState::load(r)?;
+        let collidable = bool::load(r)?;
+        let solids = bool::load(r)?;
+        let obj_type = ObjectType::load(r)?;
+        let id = ObjectId::load(r)?;
+        let (draw, update) = obj_type.funcs();
+        Some(Object {
+            pos,
+            spd,
+            rem,
+            spr,
+            hitbox,
+            flip,
+            collidable,
+            solids,
+            obj_type,
+            draw,
+            update,
+            id,
+        })
+    }
+}
+
+/// A handle to an object in `Objects`. Handles are never reused, so one that outlives its
+/// object just stops resolving
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ObjectId {
+    index: u32,
+    /// 0 is never handed out, so `ObjectId::default()` never resolves
+    generation: u32,
+}
+snapshot_fields!(ObjectId { index, generation });
+
+struct Slot {
+    generation: u32,
+    entry: Option<(Rc<RefCell<Object>>, ObjectType)>,
+}
+
+impl Clone for Slot {
+    /// copies the object and its state, so changing one slot never changes the other
+    fn clone(&self) -> Slot {
+        let entry = self.entry.as_ref().map(|(obj, _)| {
+            let obj = obj.borrow();
+            let copy = obj.copy_with(obj.obj_type.deep_clone());
+            let state = copy.obj_type.clone();
+            (Rc::new(RefCell::new(copy)), state)
         });
+        Slot {
+            generation: self.generation,
+            entry,
+        }
     }
 }
 
-pub enum ObjectType {
-    Player(Rc<RefCell<Player>>),
-    PlayerSpawn(Rc<RefCell<PlayerSpawn>>),
-    Balloon(Rc<RefCell<Balloon>>),
-    Spring(Rc<RefCell<Spring>>),
-    FallFloor(Rc<RefCell<FallFloor>>),
-    Platform(Rc<RefCell<Platform>>),
-    Smoke(Rc<RefCell<Smoke>>),
-    BigChest(Rc<RefCell<BigChest>>),
-    Flag(Rc<RefCell<Flag>>),
-    Fruit(Rc<RefCell<Fruit>>),
-    FlyFruit(Rc<RefCell<FlyFruit>>),
-    LifeUp(Rc<RefCell<LifeUp>>),
-    FakeWall(Rc<RefCell<FakeWall>>),
-    Key(Rc<RefCell<Key>>),
-    Chest(Rc<RefCell<Chest>>),
-    Message(Rc<RefCell<Message>>),
-    RoomTitle(Rc<RefCell<RoomTitle>>),
-    Orb(Rc<RefCell<Orb>>),
+impl Snapshot for Slot {
+    fn save(&self, w: &mut Writer) {
+        self.generation.save(w);
+        self.entry.is_some().save(w);
+        if let Some((obj, _)) = &self.entry {
+            obj.borrow().save(w);
+        }
+    }
+    fn load(r: &mut Reader) -> Option<Self> {
+        let generation = u32::load(r)?;
+        let entry = if bool::load(r)? {
+            let obj = Object::load(r)?;
+            let state = obj.obj_type.clone();
+            Some((Rc::new(RefCell::new(obj)), state))
+        } else {
+            None
+        };
+        Some(Slot { generation, entry })
+    }
+}
+
+/// Every object in the current room, in the order they update and draw.
+///
+/// Spawning and despawning don't touch that order straight away; they're queued and applied by
+/// `flush`, which `Celeste` calls after each object's update and draw. Despawned objects stop
+/// resolving immediately, and spawned ones resolve immediately but only join the order on the
+/// next flush, so nothing shifts under an object while it runs.
+///
+/// Cloning copies every object rather than sharing them, `GameObject`s included. Don't clone while an object is running, it'd copy the stand in `Object::run` leaves
+#[derive(Clone)]
+pub struct Objects {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    order: Vec<ObjectId>,
+    spawned: Vec<ObjectId>,
+    despawned: Vec<ObjectId>,
+}
+
+impl Objects {
+    pub fn new() -> Objects {
+        Objects {
+            slots: Vec::new(),
+            free: Vec::new(),
+            order: Vec::new(),
+            spawned: Vec::new(),
+            despawned: Vec::new(),
+        }
+    }
+    pub fn spawn(&mut self, mut obj: Object) -> ObjectId {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry: None,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.generation += 1;
+        let id = ObjectId {
+            index,
+            generation: slot.generation,
+        };
+        obj.id = id;
+        let state = obj.obj_type.clone();
+        slot.entry = Some((Rc::new(RefCell::new(obj)), state));
+        self.spawned.push(id);
+        id
+    }
+    pub fn despawn(&mut self, id: ObjectId) {
+        if self.slot(id).is_some() {
+            self.slots[id.index as usize].entry = None;
+            self.free.push(id.index);
+            self.despawned.push(id);
+        }
+    }
+    /// Removes every object right away, queued spawns included
+    pub fn clear(&mut self) {
+        for id in self.order.drain(..).chain(self.spawned.drain(..)) {
+            let slot = &mut self.slots[id.index as usize];
+            if slot.generation == id.generation && slot.entry.take().is_some() {
+                self.free.push(id.index);
+            }
+        }
+        self.despawned.clear();
+    }
+    /// Applies queued spawns and despawns. `next` is the position in the order of the next object
+    /// to run, which is returned adjusted for any despawns before it
+    pub fn flush(&mut self, next: usize) -> usize {
+        let mut removed = 0;
+        if !self.despawned.is_empty() {
+            let mut i = 0;
+            let despawned = core::mem::take(&mut self.despawned);
+            self.order.retain(|id| {
+                let keep = !despawned.contains(id);
+                if !keep && i < next {
+                    removed += 1;
+                }
+                i += 1;
+                keep
+            });
+        }
+        for id in self.spawned.drain(..) {
+            if self.slots[id.index as usize].generation == id.generation
+                && self.slots[id.index as usize].entry.is_some()
+            {
+                self.order.push(id);
+            }
+        }
+        next - removed
+    }
+
+    /// the id at `position` in the update order
+    pub fn at(&self, position: usize) -> Option<ObjectId> {
+        self.order.get(position).copied()
+    }
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+    pub fn contains(&self, id: ObjectId) -> bool {
+        self.slot(id).is_some()
+    }
+    pub fn get_object(&self, id: ObjectId) -> Option<Rc<RefCell<Object>>> {
+        self.slot(id).map(|(obj, _)| obj.clone())
+    }
+    /// The state specific to `T`, if `id` is still around and is a `T`
+    pub fn get<T: ObjectState>(&self, id: ObjectId) -> Option<Rc<RefCell<T>>> {
+        self.slot(id).and_then(|(_, state)| T::from_type(state))
+    }
+    /// The state of a `GameObject`, if `id` is still around and is a `T`. Panics if the object
+    /// is already borrowed, e.g. because it's the one running
+    pub fn get_custom<T: GameObject>(&self, id: ObjectId) -> Option<RefMut<'_, T>> {
+        match self.slot(id) {
+            Some((_, ObjectType::Custom(custom))) if custom.type_id == TypeId::of::<T>() => {
+                Some(RefMut::map(custom.state.borrow_mut(), |state| {
+                    <dyn GameObject as AsAny>::as_any_mut(state)
+                        .downcast_mut::<T>()
+                        .unwrap()
+                }))
+            }
+            _ => None,
+        }
+    }
+    pub fn kind(&self, id: ObjectId) -> Option<ObjectKind> {
+        self.slot(id).map(|(_, state)| state.kind())
+    }
+    /// Every object in update order
+    pub fn iter(&self) -> impl Iterator<Item = (ObjectId, &Rc<RefCell<Object>>)> + '_ {
+        self.order
+            .iter()
+            .filter_map(|id| self.slot(*id).map(|(obj, _)| (*id, obj)))
+    }
+    pub fn of_kind(
+        &self,
+        kind: ObjectKind,
+    ) -> impl Iterator<Item = (ObjectId, &Rc<RefCell<Object>>)> + '_ {
+        self.order
+            .iter()
+            .filter_map(move |id| match self.slot(*id) {
+                Some((obj, state)) if state.kind() == kind => Some((*id, obj)),
+                _ => None,
+            })
+    }
+
+    /// every `GameObject` in update order
+    pub fn customs(
+        &self,
+    ) -> impl Iterator<Item = (ObjectId, &CustomObject, &Rc<RefCell<Object>>)> + '_ {
+        self.order.iter().filter_map(|id| match self.slot(*id) {
+            Some((obj, ObjectType::Custom(custom))) => Some((*id, custom, obj)),
+            _ => None,
+        })
+    }
+
+    fn slot(&self, id: ObjectId) -> Option<&(Rc<RefCell<Object>>, ObjectType)> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.entry.as_ref())
+    }
+}
+
+snapshot_fields!(Objects {
+    slots,
+    free,
+    order,
+    spawned,
+    despawned,
+});
+
+impl Default for Objects {
+    fn default() -> Self {
+        Objects::new()
+    }
+}
+
+impl ObjectKind {
+    /// the kind of a `GameObject`
+    pub fn of<T: GameObject>() -> ObjectKind {
+        ObjectKind::Custom(TypeId::of::<T>())
+    }
+}
+
+/// Builds the object for a map tile, or nothing. `Celeste::load_room` looks these up by tile
+pub type ObjectConstructor = fn(&mut Celeste, Fix16, Fix16, u8) -> Option<Object>;
+
+/// Object state types that can be pulled out of an `ObjectType`, for `Objects::get`
+pub trait ObjectState: Sized {
+    const KIND: ObjectKind;
+    fn from_type(obj_type: &ObjectType) -> Option<Rc<RefCell<Self>>>;
+}
+
+/// `GameObject`s aren't serialized, a copy goes alongside the data instead. see `snapshot`
+const CUSTOM_TAG: u8 = 255;
+
+macro_rules! object_types {
+    ($($kind:ident),* $(,)?) => {
+        #[derive(Clone)]
+        pub enum ObjectType {
+            $($kind(Rc<RefCell<$kind>>),)*
+            /// a `GameObject` from outside this crate
+            Custom(CustomObject),
+        }
+
+        /// What sort of object something is, without its state. used for collision queries
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+        pub enum ObjectKind {
+            $($kind,)*
+            /// a `GameObject`, by the `TypeId` of its state. see `ObjectKind::of`
+            Custom(TypeId),
+        }
+
+        impl ObjectType {
+            pub fn kind(&self) -> ObjectKind {
+                match self {
+                    $(ObjectType::$kind(_) => ObjectKind::$kind,)*
+                    ObjectType::Custom(custom) => ObjectKind::Custom(custom.type_id),
+                }
+            }
+            /// A copy of the state for a copy of the object to have
+            fn deep_clone(&self) -> ObjectType {
+                match self {
+                    $(ObjectType::$kind(state) => {
+                        ObjectType::$kind(Rc::new(RefCell::new(state.borrow().clone())))
+                    })*
+                    ObjectType::Custom(custom) => ObjectType::Custom(custom.deep_clone()),
+                }
+            }
+            /// the `draw` and `update` that objects of this type are spawned with
+            fn funcs(&self) -> (ObjFunc, ObjFunc) {
+                match self {
+                    $(ObjectType::$kind(_) => (ObjFunc($kind::draw), ObjFunc($kind::update)),)*
+                    ObjectType::Custom(_) => (ObjFunc(custom::draw), ObjFunc(custom::update)),
+                }
+            }
+        }
+
+        /// Saved as the position of its kind in this list, or `CUSTOM_TAG`
+        const SNAPSHOT_KINDS: &[ObjectKind] = &[$(ObjectKind::$kind,)*];
+
+        impl Snapshot for ObjectType {
+            fn save(&self, w: &mut Writer) {
+                let kind = self.kind();
+                let tag = SNAPSHOT_KINDS.iter().position(|k| *k == kind);
+                tag.map_or(CUSTOM_TAG, |tag| tag as u8).save(w);
+                match self {
+                    $(ObjectType::$kind(state) => state.borrow().save(w),)*
+                    ObjectType::Custom(custom) => {
+                        (w.customs.len() as u32).save(w);
+                        w.customs.push(custom.deep_clone());
+                    }
+                }
+            }
+            fn load(r: &mut Reader) -> Option<Self> {
+                let tag = u8::load(r)?;
+                if tag == CUSTOM_TAG {
+                    let index = u32::load(r)? as usize;
+                    return Some(ObjectType::Custom(r.customs.get(index)?.deep_clone()));
+                }
+                Some(match SNAPSHOT_KINDS.get(tag as usize)? {
+                    $(ObjectKind::$kind => ObjectType::$kind(Rc::new(RefCell::new($kind::load(r)?))),)*
+                    ObjectKind::Custom(_) => return None,
+                })
+            }
+        }
+
+        $(
+            impl ObjectState for $kind {
+                const KIND: ObjectKind = ObjectKind::$kind;
+                fn from_type(obj_type: &ObjectType) -> Option<Rc<RefCell<Self>>> {
+                    match obj_type {
+                        ObjectType::$kind(state) => Some(state.clone()),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+object_types! {
+    Player,
+    PlayerSpawn,
+    Balloon,
+    Spring,
+    FallFloor,
+    Platform,
+    Smoke,
+    BigChest,
+    Flag,
+    Fruit,
+    FlyFruit,
+    LifeUp,
+    FakeWall,
+    Key,
+    Chest,
+    Message,
+    RoomTitle,
+    Orb,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::CartData;
+    use alloc::vec;
+
+    fn spring() -> Object {
+        let mut celeste = Celeste::from_cart(&CartData {
+            map: Rc::new(vec![0; 128 * 32]),
+            map_width: 128,
+            sprites: Rc::new(vec![0; 128 * 128]),
+            flags: Rc::new(vec![0; 256]),
+            fontatlas: Rc::new(vec![]),
+        });
+        Spring::init(&mut celeste, Fix16::ZERO, Fix16::ZERO)
+    }
+
+    fn order(objects: &Objects) -> Vec<ObjectId> {
+        (0..objects.len()).filter_map(|i| objects.at(i)).collect()
+    }
+
+    #[test]
+    fn reused_slots_get_a_new_generation() {
+        let mut objects = Objects::new();
+        let a = objects.spawn(spring());
+        objects.despawn(a);
+        let b = objects.spawn(spring());
+        assert_eq!(b.index, a.index);
+        assert_eq!(b.generation, a.generation + 1);
+
+        assert!(!objects.contains(a));
+        assert!(objects.get_object(a).is_none());
+        assert!(objects.get::<Spring>(a).is_none());
+        assert!(objects.kind(a).is_none());
+        assert!(objects.get_object(ObjectId::default()).is_none());
+        assert!(objects.get::<Spring>(b).is_some());
+        assert!(objects.get::<Player>(b).is_none());
+
+        // a stale handle can't despawn whatever took its slot
+        objects.despawn(a);
+        assert!(objects.contains(b));
+    }
+
+    #[test]
+    fn spawns_and_despawns_wait_for_flush() {
+        let mut objects = Objects::new();
+        let a = objects.spawn(spring());
+        let b = objects.spawn(spring());
+        assert!(objects.contains(a));
+        assert!(objects.is_empty());
+        objects.flush(0);
+        assert_eq!(order(&objects), [a, b]);
+
+        let c = objects.spawn(spring());
+        objects.despawn(a);
+        assert!(!objects.contains(a));
+        assert_eq!(order(&objects), [a, b]);
+        assert_eq!(objects.iter().map(|(id, _)| id).collect::<Vec<_>>(), [b]);
+        objects.flush(0);
+        assert_eq!(order(&objects), [b, c]);
+
+        // despawned before it ever joined
+        let d = objects.spawn(spring());
+        objects.despawn(d);
+        objects.flush(0);
+        assert_eq!(order(&objects), [b, c]);
+
+        objects.spawn(spring());
+        objects.clear();
+        objects.flush(0);
+        assert!(objects.is_empty());
+        assert!(!objects.contains(b));
+    }
+
+    #[test]
+    fn flush_keeps_the_next_object_in_place() {
+        let mut objects = Objects::new();
+        let ids: Vec<_> = (0..4).map(|_| objects.spawn(spring())).collect();
+        objects.flush(0);
+
+        // the third object is running and despawns itself and the first
+        objects.despawn(ids[0]);
+        objects.despawn(ids[2]);
+        let e = objects.spawn(spring());
+        let next = objects.flush(3);
+        assert_eq!(next, 1);
+        assert_eq!(objects.at(next), Some(ids[3]));
+        assert_eq!(objects.at(next + 1), Some(e));
+
+        // despawns after the next object don't move it
+        objects.despawn(e);
+        assert_eq!(objects.flush(1), 1);
+        assert_eq!(order(&objects), [ids[1], ids[3]]);
+    }
 }
@@ -0,0 +1,20 @@
+//! The real game's cart, for tests that play it. The UEFI frontend keeps its sections
+use crate::{memory::CartData, Celeste};
+
+mod consts {
+    include!("../../src/consts.rs");
+}
+
+pub fn cart() -> CartData {
+    CartData::new(
+        consts::MAPDATA,
+        consts::SPRITES,
+        consts::FLAGS,
+        consts::FONTATLAS,
+    )
+}
+
+/// A game of it on the title screen
+pub fn celeste() -> Celeste {
+    Celeste::from_cart(&cart())
+}
@@ -1,6 +1,6 @@
-use core::f32::consts::PI;
+use libm::Libm;
 
-use libm::{fabsf, floorf, remainderf, Libm};
+use crate::fixed::Fix16;
 
 // pub fn sign()
 // use pico::Pico;
@@ -9,6 +9,8 @@ use libm::{fabsf, floorf, remainderf, Libm};
 //     return celeste.mem.mget()
 // }
 
+/// float helpers for the purely cosmetic parts of the game (clouds, particles). anything that
+/// affects gameplay goes through `Fix16` instead
 pub trait LibmExt {
     fn floor(self) -> f32;
     fn abs(self) -> f32;
@@ -36,38 +38,43 @@ impl LibmExt for f32 {
     }
 
     fn rem_euclid(self, x: f32) -> f32 {
-        Libm::<f32>::remainder(x, self)
+        let r = Libm::<f32>::fmod(self, x);
+        if r < 0.0 {
+            r + x.abs()
+        } else {
+            r
+        }
     }
 }
 
-pub fn min(v1: f32, v2: f32) -> f32 {
-    f32::min(v1, v2)
+pub fn min(v1: Fix16, v2: Fix16) -> Fix16 {
+    v1.min(v2)
 }
-pub fn sin(percentage: f32) -> f32 {
+pub fn sin(percentage: Fix16) -> Fix16 {
     // p8's trig is weird asf
-    Libm::<f32>::sin(percentage * -2.0 * PI)
+    percentage.sin()
 }
-pub fn cos(percentage: f32) -> f32 {
+pub fn cos(percentage: Fix16) -> Fix16 {
     // p8's trig is weird asf
-    Libm::<f32>::cos(percentage * -2.0 * PI)
+    percentage.cos()
 }
-pub fn sign(v: f32) -> f32 {
-    if v != 0f32 {
-        v.signum()
+pub fn sign(v: Fix16) -> Fix16 {
+    if v != Fix16::ZERO {
+        v.sgn()
     } else {
-        0f32
+        Fix16::ZERO
     }
 }
-pub fn max(v1: f32, v2: f32) -> f32 {
-    f32::max(v1, v2)
+pub fn max(v1: Fix16, v2: Fix16) -> Fix16 {
+    v1.max(v2)
 }
-pub fn appr(val: f32, target: f32, amount: f32) -> f32 {
+pub fn appr(val: Fix16, target: Fix16, amount: Fix16) -> Fix16 {
     if val > target {
         max(val - amount, target)
     } else {
         min(val + amount, target)
     }
 }
-pub fn mid(v1: f32, v2: f32, v3: f32) -> f32 {
-    return v1.max(v2).min(v3);
+pub fn mid(v1: Fix16, v2: Fix16, v3: Fix16) -> Fix16 {
+    v1.max(v2).min(v3)
 }
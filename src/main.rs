@@ -7,7 +7,12 @@ extern crate alloc;
 
 use core::fmt::Display;
 
-use alloc::{format, string::ToString};
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
 use embedded_graphics::{
     draw_target::DrawTarget,
     geometry::{OriginDimensions, Point, Size},
@@ -18,9 +23,26 @@ use embedded_graphics::{
     Drawable, Pixel,
 };
 use log::info;
-use profont::PROFONT_18_POINT;
-use rustic_mountain_core::Celeste;
+use profont::{PROFONT_12_POINT, PROFONT_18_POINT};
+use rustic_mountain_core::{
+    assist::Assist,
+    debug,
+    events::GameEvent,
+    fixed::Fix16,
+    ghost::Ghosts,
+    memory::{CartData, Memory},
+    pause::PauseItem,
+    practice::Practice,
+    replay::{to_mask, Replay},
+    rewind::Rewind,
+    rooms::Layout,
+    speedrun::{format_delta, format_frames, Splits},
+    stats::room_name,
+    Celeste,
+};
 use uefi::{
+    cstr16,
+    fs::{FileSystem, Path},
     helpers::system_table,
     prelude::*,
     proto::console::{
@@ -28,14 +50,28 @@ use uefi::{
         text::{Key, ScanCode},
     },
     table::boot::{OpenProtocolAttributes, OpenProtocolParams},
-    Char16,
+    CStr16, Char16,
 };
 use uefi_graphics2::{UefiDisplay, UefiDisplayError};
 
+#[cfg(feature = "lua")]
+use alloc::{rc::Rc, vec};
+#[cfg(feature = "lua")]
+use rustic_mountain_core::{
+    cart,
+    lua::{LuaCart, LuaError},
+};
+#[cfg(feature = "lua")]
+use uefi::CString16;
+
 #[derive(Debug)]
 enum UefilesteError {
     Uefi(uefi::Error),
     Display(UefiDisplayError),
+    Fs(uefi::fs::Error),
+    /// a Lua cart that couldn't be loaded, or stopped with an error
+    #[cfg(feature = "lua")]
+    Cart(String),
 }
 
 impl From<uefi::Error> for UefilesteError {
@@ -50,11 +86,27 @@ impl From<UefiDisplayError> for UefilesteError {
     }
 }
 
+impl From<uefi::fs::Error> for UefilesteError {
+    fn from(value: uefi::fs::Error) -> Self {
+        Self::Fs(value)
+    }
+}
+
+#[cfg(feature = "lua")]
+impl From<LuaError> for UefilesteError {
+    fn from(value: LuaError) -> Self {
+        Self::Cart(value.to_string())
+    }
+}
+
 impl Display for UefilesteError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Uefi(err) => err.fmt(f),
             Self::Display(err) => err.fmt(f),
+            Self::Fs(err) => err.fmt(f),
+            #[cfg(feature = "lua")]
+            Self::Cart(err) => f.write_str(err),
         }
     }
 }
@@ -84,47 +136,741 @@ fn draw_text(
     Ok(())
 }
 
-fn celeste_loop(
-    mut display: UefiDisplay,
+fn draw_stats(display: &mut UefiDisplay, engine: &Celeste) -> Result<(), UefilesteError> {
+    let text_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::WHITE);
+    let row = |i: i32| Point::new(4, 4 + 16 * (i + 1));
+
+    display.clear(Rgb888::BLACK)?;
+    Text::new(
+        &format!(
+            "{:<10}{:>8}{:>12}{:>8}{:>7}{:>12}{:>8}",
+            "ROOM", "DEATHS", "TIME", "DASHES", "BERRY", "ENTERED", "ASSIST"
+        ),
+        row(0),
+        text_style,
+    )
+    .draw(display)?;
+    for (i, room) in engine.room_stats.iter().enumerate() {
+        Text::new(
+            &format!(
+                "{:<10}{:>8}{:>12}{:>8}{:>7}{:>12}{:>8}",
+                room_name(&engine.layout, i as u8).to_uppercase(),
+                room.deaths,
+                format_frames(room.frames as u64),
+                room.dashes,
+                if room.berry { "YES" } else { "-" },
+                room.first_entered.map_or_else(|| "-".to_string(), format_frames),
+                if room.assisted { "YES" } else { "-" },
+            ),
+            row(i as i32 + 1),
+            text_style,
+        )
+        .draw(display)?;
+    }
+    Text::new(
+        &format!(
+            "TOTAL DEATHS: {}   TIME: {}{}   TAB - BACK TO GAME",
+            engine.deaths,
+            format_frames(engine.run_frames),
+            if engine.splits.assisted { " (ASSISTED)" } else { "" }
+        ),
+        row(engine.room_stats.len() as i32 + 2),
+        text_style,
+    )
+    .draw(display)?;
+
+    display.flush();
+    Ok(())
+}
+
+/// Personal best splits live next to the bootloader on the ESP
+const PB_PATH: &CStr16 = cstr16!("\\uefileste-pb.txt");
+
+fn load_personal_best() -> Option<Splits> {
+    let system = system_table();
+    let boot = system.boot_services();
+    let mut fs = FileSystem::new(boot.get_image_file_system(boot.image_handle()).ok()?);
+    let data = fs.read(Path::new(PB_PATH)).ok()?;
+    Splits::from_text(core::str::from_utf8(&data).ok()?)
+}
+
+fn save_personal_best(splits: &Splits) -> Result<(), UefilesteError> {
+    let system = system_table();
+    let boot = system.boot_services();
+    let mut fs = FileSystem::new(boot.get_image_file_system(boot.image_handle())?);
+    fs.write(Path::new(PB_PATH), splits.to_text())?;
+    Ok(())
+}
+
+/// The fastest run through each room, next to the personal best
+const GHOSTS_PATH: &CStr16 = cstr16!("\\uefileste-ghosts.txt");
+
+fn load_ghosts() -> Option<Ghosts> {
+    let system = system_table();
+    let boot = system.boot_services();
+    let mut fs = FileSystem::new(boot.get_image_file_system(boot.image_handle()).ok()?);
+    let data = fs.read(Path::new(GHOSTS_PATH)).ok()?;
+    Ghosts::from_text(core::str::from_utf8(&data).ok()?)
+}
+
+fn save_ghosts(ghosts: &Ghosts) -> Result<(), UefilesteError> {
+    let system = system_table();
+    let boot = system.boot_services();
+    let mut fs = FileSystem::new(boot.get_image_file_system(boot.image_handle())?);
+    fs.write(Path::new(GHOSTS_PATH), ghosts.to_text())?;
+    Ok(())
+}
+
+/// Draws the last `rows` splits, up to the room that's being played, in the margin left of
+/// the game
+fn draw_splits(
+    display: &mut UefiDisplay,
+    engine: &Celeste,
+    rows: usize,
+    width: u32,
+) -> Result<(), UefilesteError> {
+    let text_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::WHITE);
+    let ahead_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::new(0, 228, 54));
+    let behind_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::new(255, 0, 77));
+    let row = |i: i32| Point::new(4, 4 + 16 * (i + 1));
+    let count = engine.layout.summit() as usize;
+    let rows = rows.min(count);
+
+    Rectangle::new(Point::zero(), Size::new(width, 16 * (rows as u32 + 3)))
+        .draw_styled(&PrimitiveStyle::with_fill(Rgb888::BLACK), display)?;
+
+    let current = engine.splits.times.len().min(count.saturating_sub(1));
+    let first = (current + 1).saturating_sub(rows);
+    for (r, i) in (first..=current).enumerate() {
+        let pb = engine.personal_best.as_ref();
+        let time = engine.splits.times.get(i).copied().unwrap_or(engine.run_frames);
+        Text::new(
+            &format!(
+                "{:<9}{:>10}{:>10}",
+                room_name(&engine.layout, i as u8).to_uppercase(),
+                format_frames(time),
+                pb.and_then(|pb| pb.times.get(i)).map_or_else(|| "-".to_string(), |t| format_frames(*t)),
+            ),
+            row(r as i32),
+            text_style,
+        )
+        .draw(display)?;
+        if let Some(delta) = pb.and_then(|pb| engine.splits.delta(pb, i)) {
+            Text::new(
+                &format!("{:>10}", format_delta(delta)),
+                row(r as i32) + Point::new(PROFONT_12_POINT.character_size.width as i32 * 29, 0),
+                if delta < 0 { ahead_style } else { behind_style },
+            )
+            .draw(display)?;
+        }
+    }
+    Text::new(
+        &format!(
+            "{:<9}{:>10}{:>10}",
+            if engine.splits.assisted { "ASSISTED" } else { "TOTAL" },
+            format_frames(engine.run_frames),
+            engine.personal_best.as_ref().and_then(|pb| pb.total(count)).map_or_else(|| "-".to_string(), format_frames),
+        ),
+        row(rows as i32 + 1),
+        text_style,
+    )
+    .draw(display)?;
+    Ok(())
+}
+
+/// Draws the debug overlay's hitboxes over the game at full resolution, and its text panel to
+/// the right of the game
+fn draw_debug(
+    display: &mut UefiDisplay,
+    engine: &Celeste,
+    palette: &[Rgb888],
+    topleft: Point,
+    scale: i32,
+) -> Result<(), UefilesteError> {
+    for r in debug::shapes(engine) {
+        Rectangle::with_corners(
+            topleft + Point::new(r.x0 * scale, r.y0 * scale),
+            topleft + Point::new((r.x1 + 1) * scale - 1, (r.y1 + 1) * scale - 1),
+        )
+        .draw_styled(&PrimitiveStyle::with_stroke(palette[r.col as usize], 1), display)?;
+    }
+
+    let text_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::WHITE);
+    let x = topleft.x + 128 * scale + 8;
+    let lines = debug::panel(engine);
+    Rectangle::new(
+        Point::new(x, 0),
+        Size::new(display.size().width.saturating_sub(x as u32), 16 * (lines.len() as u32 + 1) + 4),
+    )
+    .draw_styled(&PrimitiveStyle::with_fill(Rgb888::BLACK), display)?;
+    for (i, line) in lines.iter().enumerate() {
+        Text::new(line, Point::new(x, 4 + 16 * (i as i32 + 1)), text_style).draw(display)?;
+    }
+    Ok(())
+}
+
+/// Width of the input display, which needs this much room right of the game to be shown
+const INPUTS_WIDTH: u32 = 120;
+
+/// Rows of input history above the controller
+const INPUT_ROWS: usize = 12;
+
+/// The buttons the input display shows, in `btn()` order, with where they go on the controller
+/// in 18 pixel cells
+const INPUT_BUTTONS: [(&str, i32, i32); 6] = [
+    ("<", 0, 1),
+    (">", 2, 1),
+    ("^", 1, 0),
+    ("v", 1, 2),
+    ("Z", 4, 1),
+    ("X", 5, 1),
+];
+
+/// Draws the buttons held on the last tick as a controller in the bottom right corner, with the
+/// ticks before it above. `history` is `btn()` bitmasks and how many ticks in a row each was
+/// held, oldest first, at most `INPUT_ROWS` of them
+fn draw_inputs(display: &mut UefiDisplay, history: &VecDeque<(u8, u32)>) -> Result<(), UefilesteError> {
+    let text_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::WHITE);
+    let held_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::BLACK);
+    let size = display.size();
+    let x = size.width as i32 - INPUTS_WIDTH as i32;
+    let top = (size.height as i32 - 16 * INPUT_ROWS as i32 - 3 * 18 - 12).max(0);
+    Rectangle::new(Point::new(x, top), Size::new(INPUTS_WIDTH, size.height - top as u32))
+        .draw_styled(&PrimitiveStyle::with_fill(Rgb888::BLACK), display)?;
+
+    // newest at the bottom, right above the controller
+    for (row, &(mask, ticks)) in history.iter().enumerate() {
+        let buttons: String = INPUT_BUTTONS
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _, _))| if mask & (1 << i) != 0 { *name } else { "." })
+            .collect();
+        Text::new(
+            &format!("{:>4} {}", ticks, buttons),
+            Point::new(x, top + 16 * (row as i32 + 1)),
+            text_style,
+        )
+        .draw(display)?;
+    }
+
+    let mask = history.back().map_or(0, |(mask, _)| *mask);
+    let controller = Point::new(x, size.height as i32 - 3 * 18 - 4);
+    for (i, &(name, cx, cy)) in INPUT_BUTTONS.iter().enumerate() {
+        let held = mask & (1 << i) != 0;
+        let cell = controller + Point::new(cx * 18, cy * 18);
+        let style = if held {
+            PrimitiveStyle::with_fill(Rgb888::WHITE)
+        } else {
+            PrimitiveStyle::with_stroke(Rgb888::new(95, 87, 79), 1)
+        };
+        Rectangle::new(cell, Size::new(16, 16)).draw_styled(&style, display)?;
+        Text::new(
+            name,
+            cell + Point::new(4, 12),
+            if held { held_style } else { text_style },
+        )
+        .draw(display)?;
+    }
+    Ok(())
+}
+
+/// The cart the editor saves to, next to the personal best. It's loaded instead of the built in
+/// one when it's there. Copy the original cart here first to be able to open the edits in pico-8
+const CART_PATH: &CStr16 = cstr16!("\\uefileste.p8");
+
+/// `cart` with the data from the edited cart on the ESP, if there is one
+fn load_edited_cart(cart: &CartData) -> Option<CartData> {
+    let system = system_table();
+    let boot = system.boot_services();
+    let mut fs = FileSystem::new(boot.get_image_file_system(boot.image_handle()).ok()?);
+    let data = fs.read(Path::new(CART_PATH)).ok()?;
+    cart.with_p8(core::str::from_utf8(&data).ok()?)
+}
+
+fn save_cart(cart: &CartData) -> Result<(), UefilesteError> {
+    let system = system_table();
+    let boot = system.boot_services();
+    let mut fs = FileSystem::new(boot.get_image_file_system(boot.image_handle())?);
+    // written into the cart that's there, so its code and sound are kept
+    let base = fs
+        .read(Path::new(CART_PATH))
+        .ok()
+        .and_then(|data| String::from_utf8(data).ok())
+        .unwrap_or_default();
+    fs.write(Path::new(CART_PATH), cart.to_p8(&base))?;
+    Ok(())
+}
+
+/// Tiles that spawn objects, on the number keys in the editor from 1 to 0
+const OBJECT_TILES: [(u8, &str); 10] = [
+    (1, "PLAYER"),
+    (18, "SPRING"),
+    (22, "BALLOON"),
+    (23, "FALL FLOOR"),
+    (8, "KEY"),
+    (20, "CHEST"),
+    (26, "BERRY"),
+    (28, "FLYING BERRY"),
+    (64, "FAKE WALL"),
+    (11, "PLATFORM"),
+];
+
+/// What each sprite flag means to the game, see `Memory::map`'s mask and `Object::is_flag`
+const FLAG_NAMES: [&str; 8] = ["SOLID", "FOREGROUND", "BACKGROUND", "", "ICE", "", "", ""];
+
+/// The editor's cursors, and the tile and color they place
+struct Editor {
+    /// editing the pixels of `tile` rather than the room's tiles
+    sprite_mode: bool,
+    /// the tile under the map cursor, counted from the room's top left
+    x: i32,
+    y: i32,
+    tile: u8,
+    /// the pixel of `tile` under the sprite cursor
+    px: i32,
+    py: i32,
+    color: u8,
+    /// what the last save did
+    message: String,
+}
+
+/// Draws the tiles of the room being edited with the map cursor over them, and right of the game
+/// the sprite sheet to pick tiles from, the selected sprite blown up with the palette next to it,
+/// and the sprite's flags
+fn draw_editor(
+    display: &mut UefiDisplay,
+    engine: &mut Celeste,
+    editor: &Editor,
+    topleft: Point,
+    scale: i32,
+) -> Result<(), UefilesteError> {
+    // the view follows the map cursor through rooms bigger than the screen
+    let view = engine.scroll_for(
+        Fix16::from_int(editor.x * 8),
+        Fix16::from_int(editor.y * 8),
+    );
+    let camera = engine.mem.camera.clone();
+    engine.mem.camera(view.x, view.y);
+    engine.mem.cls(0);
+    let room = engine.room;
+    engine.mem.map(room.x, room.y, 0, 0, room.w, room.h, 0);
+    engine.mem.camera = camera;
+    draw_game(display, &engine.mem, topleft, scale)?;
+
+    let cell = 8 * scale;
+    if !editor.sprite_mode {
+        let (view_x, view_y) = (view.x.to_int() / 8, view.y.to_int() / 8);
+        Rectangle::new(
+            topleft
+                + Point::new(
+                    (editor.x - view_x) * cell,
+                    (editor.y - view_y) * cell,
+                ),
+            Size::new(cell as u32, cell as u32),
+        )
+        .draw_styled(&PrimitiveStyle::with_stroke(Rgb888::WHITE, 2), display)?;
+    }
+
+    // the sprite sheet at twice the size, the selected tile boxed
+    let x = topleft.x + 128 * scale + 8;
+    Rectangle::new(
+        Point::new(x, 0),
+        Size::new(display.size().width.saturating_sub(x as u32), 8 + 256 + 8 + 128 + 16 * 10),
+    )
+    .draw_styled(&PrimitiveStyle::with_fill(Rgb888::BLACK), display)?;
+    for dx in 0..2 {
+        for dy in 0..2 {
+            display.draw_iter(engine.mem.sprites.iter().enumerate().map(|(i, col)| {
+                Pixel(
+                    Point::new(x + (i as i32 % 128) * 2 + dx, 8 + (i as i32 / 128) * 2 + dy),
+                    PALETTE[(col & 0xf) as usize],
+                )
+            }))?;
+        }
+    }
+    let tile = editor.tile as i32;
+    Rectangle::new(
+        Point::new(x + tile % 16 * 16 - 1, 8 + tile / 16 * 16 - 1),
+        Size::new(18, 18),
+    )
+    .draw_styled(&PrimitiveStyle::with_stroke(Rgb888::WHITE, 1), display)?;
+
+    // the selected sprite at 16 times the size, with the colors in a 4x4 grid beside it
+    let zoom = Point::new(x, 8 + 256 + 8);
+    for py in 0..8 {
+        for px in 0..8 {
+            let col = engine.mem.sget(tile % 16 * 8 + px, tile / 16 * 8 + py);
+            Rectangle::new(zoom + Point::new(px * 16, py * 16), Size::new(16, 16))
+                .draw_styled(&PrimitiveStyle::with_fill(PALETTE[col as usize]), display)?;
+        }
+    }
+    for col in 0..16 {
+        let swatch = zoom + Point::new(128 + 8 + col % 4 * 16, col / 4 * 16);
+        Rectangle::new(swatch, Size::new(16, 16))
+            .draw_styled(&PrimitiveStyle::with_fill(PALETTE[col as usize]), display)?;
+    }
+    if editor.sprite_mode {
+        Rectangle::new(
+            zoom + Point::new(editor.px * 16, editor.py * 16),
+            Size::new(16, 16),
+        )
+        .draw_styled(&PrimitiveStyle::with_stroke(Rgb888::WHITE, 2), display)?;
+    }
+    let color = editor.color as i32;
+    Rectangle::new(
+        zoom + Point::new(128 + 8 + color % 4 * 16, color / 4 * 16),
+        Size::new(16, 16),
+    )
+    .draw_styled(&PrimitiveStyle::with_stroke(Rgb888::WHITE, 2), display)?;
+
+    let text_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::WHITE);
+    let object = OBJECT_TILES.iter().find(|(t, _)| *t == editor.tile);
+    let flags = engine.mem.fget_all(editor.tile);
+    let flag_names: String = (0..8)
+        .filter(|&i| flags & (1 << i) != 0)
+        .map(|i| match FLAG_NAMES[i] {
+            "" => format!(" {}", i),
+            name => format!(" {}", name),
+        })
+        .collect();
+    let mut lines = [
+        format!("TILE {}  {}", editor.tile, object.map_or("", |&(_, name)| name)),
+        format!("FLAGS {:08b} {}", flags.reverse_bits(), flag_names),
+        editor.message.clone(),
+        String::new(),
+        "TAB - EDIT SPRITE  WASD - PICK TILE".to_string(),
+        "ARROWS - MOVE  Z - PLACE  X - ERASE".to_string(),
+        "Q - COPY TILE  1-0 - OBJECTS".to_string(),
+        "ENTER - SAVE CART  E - PLAYTEST".to_string(),
+    ];
+    if editor.sprite_mode {
+        lines[4] = "TAB - EDIT MAP  WASD - PICK SPRITE".to_string();
+        lines[5] = "ARROWS - MOVE  Z - PAINT  X - CLEAR".to_string();
+        lines[6] = "Q - COPY COLOR  [ ] - COLOR  0-7 - FLAGS".to_string();
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let y = zoom.y + 128 + 16 * (i as i32 + 1);
+        Text::new(line, Point::new(x, y), text_style).draw(display)?;
+    }
+    Ok(())
+}
+
+/// Handles a key pressed in the editor. Returns `false` when it's closed, which reloads the room
+/// so the edits can be played right away. Sprites and flags change in the game straight away
+fn edit(editor: &mut Editor, engine: &mut Celeste, key: Key) -> bool {
+    let room = engine.room;
+    let (x, y) = (room.x + editor.x, room.y + editor.y);
+    let (sx, sy) = (
+        (editor.tile % 16 * 8) as i32 + editor.px,
+        (editor.tile / 16 * 8) as i32 + editor.py,
+    );
+    let (cursor_x, cursor_y, w, h) = if editor.sprite_mode {
+        (&mut editor.px, &mut editor.py, 8, 8)
+    } else {
+        (&mut editor.x, &mut editor.y, room.w, room.h)
+    };
+    match key {
+        Key::Special(ScanCode::LEFT) => *cursor_x = (*cursor_x - 1).max(0),
+        Key::Special(ScanCode::RIGHT) => *cursor_x = (*cursor_x + 1).min(w - 1),
+        Key::Special(ScanCode::UP) => *cursor_y = (*cursor_y - 1).max(0),
+        Key::Special(ScanCode::DOWN) => *cursor_y = (*cursor_y + 1).min(h - 1),
+        Key::Special(ScanCode::ESCAPE) => {
+            engine.load_room(room);
+            return false;
+        }
+        Key::Printable(key) => match char::from(key) {
+            '\t' => editor.sprite_mode = !editor.sprite_mode,
+            'a' => editor.tile = editor.tile.wrapping_sub(1),
+            'd' => editor.tile = editor.tile.wrapping_add(1),
+            'w' => editor.tile = editor.tile.wrapping_sub(16),
+            's' => editor.tile = editor.tile.wrapping_add(16),
+            '[' => editor.color = (editor.color + 15) % 16,
+            ']' => editor.color = (editor.color + 1) % 16,
+            'z' | 'c' if editor.sprite_mode => engine.mem.sset(sx, sy, editor.color),
+            'x' if editor.sprite_mode => engine.mem.sset(sx, sy, 0),
+            'q' if editor.sprite_mode => editor.color = engine.mem.sget(sx, sy),
+            digit @ '0'..='7' if editor.sprite_mode => {
+                let flag = digit as u8 - b'0';
+                let set = engine.mem.fget_all(editor.tile) & (1 << flag) != 0;
+                engine.mem.fset(editor.tile, flag, !set);
+            }
+            'z' | 'c' => engine.mem.mset(x, y, editor.tile),
+            'x' => engine.mem.mset(x, y, 0),
+            'q' => editor.tile = engine.mem.mget(x, y),
+            digit @ '0'..='9' if !editor.sprite_mode => {
+                // 1 is the first, 0 the last, like the keyboard
+                editor.tile = OBJECT_TILES[(digit as usize - '0' as usize + 9) % 10].0;
+            }
+            '\r' => {
+                editor.message = match save_cart(&engine.mem.cart()) {
+                    Ok(()) => "SAVED TO UEFILESTE.P8".to_string(),
+                    Err(err) => {
+                        info!("couldn't save cart: {}", err);
+                        "COULDN'T SAVE THE CART".to_string()
+                    }
+                };
+            }
+            'e' => {
+                engine.load_room(room);
+                return false;
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+    true
+}
+
+/// Everything the settings menu changes
+struct Settings {
     key_duration: u8,
+    scale: u32,
+    split_setting: usize,
+    /// `None` for a full game
+    practice_level: Option<u8>,
+    practice: Practice,
+    assist: Assist,
+    ghost: bool,
+    input_display: bool,
+    /// which of the `.p8` files on the ESP to run on the Lua interpreter, `None` for the
+    /// native port of Celeste
+    cart: Option<usize>,
+}
+
+/// How many frames each tick takes at each slow motion setting
+const SLOW_MOTION: [u32; 3] = [1, 2, 4];
+
+/// How many ticks back the rewind key can go
+const REWIND_TICKS: usize = 10 * 30;
+
+/// The cart that comes with the game, which the demo was recorded on
+fn builtin_cart() -> CartData {
+    CartData::new(
+        consts::MAPDATA,
+        consts::SPRITES,
+        consts::FLAGS,
+        consts::FONTATLAS,
+    )
+}
+
+/// A game on the edited cart if there is one, otherwise the built in one
+fn load_cart() -> Celeste {
+    let builtin = builtin_cart();
+    Celeste::from_cart(&load_edited_cart(&builtin).unwrap_or(builtin))
+}
+
+fn new_game(settings: &Settings) -> Celeste {
+    let mut engine = load_cart();
+    engine.personal_best = load_personal_best();
+    engine.ghosts = load_ghosts().unwrap_or_default();
+    if let Some(level) = settings.practice_level {
+        engine.begin_practice(Practice { level, ..settings.practice });
+    }
+    engine
+}
+
+/// Shows whether the game is rewinding, frozen or in slow motion, in the bottom left corner
+fn draw_status(
+    display: &mut UefiDisplay,
+    rewinding: bool,
+    frozen: bool,
+    slow_motion: usize,
+    width: u32,
+) -> Result<(), UefilesteError> {
+    let text_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::WHITE);
+    let height = display.size().height as i32;
+    Rectangle::new(Point::new(0, height - 20), Size::new(width, 20))
+        .draw_styled(&PrimitiveStyle::with_fill(Rgb888::BLACK), display)?;
+    let status = match (frozen, SLOW_MOTION[slow_motion]) {
+        _ if rewinding => "REWIND".to_string(),
+        (true, _) => "FROZEN".to_string(),
+        (false, 1) => return Ok(()),
+        (false, n) => format!("SLOW 1/{}", n),
+    };
+    Text::new(&status, Point::new(4, height - 6), text_style).draw(display)?;
+    Ok(())
+}
+
+/// pico-8's colors, as indexed by `Memory::display_pallete`
+const PALETTE: [Rgb888; 32] = [
+    Rgb888::new(0, 0, 0),
+    Rgb888::new(29, 43, 83),
+    Rgb888::new(126, 37, 83),
+    Rgb888::new(0, 135, 81),
+    Rgb888::new(171, 82, 54),
+    Rgb888::new(95, 87, 79),
+    Rgb888::new(194, 195, 199),
+    Rgb888::new(255, 241, 232),
+    Rgb888::new(255, 0, 77),
+    Rgb888::new(255, 163, 0),
+    Rgb888::new(255, 236, 85),
+    Rgb888::new(0, 228, 54),
+    Rgb888::new(41, 173, 255),
+    Rgb888::new(131, 118, 156),
+    Rgb888::new(255, 119, 168),
+    Rgb888::new(255, 204, 170),
+    // extended colors, only reachable through the display palette
+    Rgb888::new(41, 24, 20),
+    Rgb888::new(17, 29, 53),
+    Rgb888::new(66, 33, 54),
+    Rgb888::new(18, 83, 89),
+    Rgb888::new(116, 47, 41),
+    Rgb888::new(73, 51, 59),
+    Rgb888::new(162, 136, 121),
+    Rgb888::new(243, 239, 125),
+    Rgb888::new(190, 18, 80),
+    Rgb888::new(255, 108, 36),
+    Rgb888::new(168, 231, 46),
+    Rgb888::new(0, 181, 67),
+    Rgb888::new(6, 90, 181),
+    Rgb888::new(117, 70, 101),
+    Rgb888::new(255, 110, 89),
+    Rgb888::new(255, 157, 129),
+];
+
+/// Draws the screen of `mem`, `scale` pixels per game pixel with its top left corner at `topleft`
+fn draw_game(
+    display: &mut UefiDisplay,
+    mem: &Memory,
+    topleft: Point,
     scale: i32,
 ) -> Result<(), UefilesteError> {
+    for x in 0..scale {
+        for y in 0..scale {
+            display.draw_iter(mem.graphics.iter().enumerate().map(|(i, col)| {
+                let col = mem.display_pallete[*col as usize];
+                Pixel(
+                    Point::new(
+                        topleft.x + ((i as i32 % 128) * scale) + x,
+                        topleft.y + ((i as i32 / 128) * scale) + y,
+                    ),
+                    PALETTE[(col & 0xf) as usize + if col & 0x80 != 0 { 16 } else { 0 }],
+                )
+            }))?;
+        }
+    }
+    Ok(())
+}
+
+/// Inputs through the first few rooms, played on the title screen when nobody's playing
+const DEMO: &str = include_str!("demo.txt");
+
+/// How long the title screen sits idle before the demo starts
+const DEMO_DELAY: u32 = 20 * 30;
+
+/// Plays `DEMO` on a game of its own, until it ends or a key is pressed
+fn play_demo(display: &mut UefiDisplay, topleft: Point, scale: i32) -> Result<(), UefilesteError> {
     let mut input_table = system_table();
     let input = input_table.stdin();
     let boot_table = system_table();
     let boot = boot_table.boot_services();
 
-    let palette = &[
-        Rgb888::new(0, 0, 0),
-        Rgb888::new(29, 43, 83),
-        Rgb888::new(126, 37, 83),
-        Rgb888::new(0, 135, 81),
-        Rgb888::new(171, 82, 54),
-        Rgb888::new(95, 87, 79),
-        Rgb888::new(194, 195, 199),
-        Rgb888::new(255, 241, 232),
-        Rgb888::new(255, 0, 77),
-        Rgb888::new(255, 163, 0),
-        Rgb888::new(255, 236, 85),
-        Rgb888::new(0, 228, 54),
-        Rgb888::new(41, 173, 255),
-        Rgb888::new(131, 118, 156),
-        Rgb888::new(255, 119, 168),
-        Rgb888::new(255, 204, 170),
-    ];
+    let Some(replay) = Replay::from_text(DEMO) else {
+        return Ok(());
+    };
+    let mut demo = Celeste::from_cart(&builtin_cart());
+    replay.start(&mut demo);
 
-    let mut engine = Celeste::new(
-        consts::MAPDATA.into(),
-        consts::SPRITES.into(),
-        consts::FLAGS.into(),
-        consts::FONTATLAS.into(),
-    );
+    let text_style = MonoTextStyle::new(&PROFONT_12_POINT, Rgb888::WHITE);
+    let height = display.size().height as i32;
+    let mut tick = 0;
+    while replay.apply(tick, &mut demo) {
+        demo.next_tick();
+        demo.draw();
+        demo.drain_events();
+        tick += 1;
 
+        draw_game(display, &demo.mem, topleft, scale)?;
+        Text::new("DEMO - PRESS ANY KEY", Point::new(4, height - 6), text_style).draw(display)?;
+        display.flush();
+
+        if input.read_key()?.is_some() {
+            break;
+        }
+        boot.stall(33_000);
+    }
+    display.clear(Rgb888::BLACK)?;
+    Ok(())
+}
+
+/// Holds the button `key` is bound to. There's no key up event, so the arrows are held for
+/// `key_duration` ticks after each repeat of the key, and z and x for one tick
+fn press_button(mem: &mut Memory, timing: &mut [u8; 4], key: Key, key_duration: u8) {
     let key_z = Char16::try_from('z').unwrap();
     let key_c = Char16::try_from('c').unwrap();
     let key_x = Char16::try_from('x').unwrap();
+    let button = match key {
+        Key::Special(ScanCode::LEFT) => 0,
+        Key::Special(ScanCode::RIGHT) => 1,
+        Key::Special(ScanCode::UP) => 2,
+        Key::Special(ScanCode::DOWN) => 3,
+        Key::Printable(key) if key == key_z || key == key_c => 4,
+        Key::Printable(key) if key == key_x => 5,
+        _ => return,
+    };
+    mem.buttons[button] = true;
+    if let Some(t) = timing.get_mut(button) {
+        *t = key_duration;
+    }
+}
+
+/// Lets go of z, x and the arrows that have run out of time, see `press_button`. Called once
+/// per tick, after it's run
+fn release_buttons(mem: &mut Memory, timing: &mut [u8; 4]) {
+    for (i, t) in timing.iter_mut().enumerate() {
+        if *t == 0 {
+            mem.buttons[i] = false;
+        } else {
+            *t -= 1;
+        }
+    }
+    mem.buttons[4] = false;
+    mem.buttons[5] = false;
+}
+
+/// Runs the game until the pause menu asks for the settings or to quit to the menu, which is
+/// what gets returned
+fn celeste_loop(
+    display: &mut UefiDisplay,
+    engine: &mut Celeste,
+    settings: &Settings,
+) -> Result<PauseItem, UefilesteError> {
+    let mut input_table = system_table();
+    let input = input_table.stdin();
+    let boot_table = system_table();
+    let boot = boot_table.boot_services();
+
+    let key_tab = Char16::try_from('\t').unwrap();
+    let key_r = Char16::try_from('r').unwrap();
+    let key_d = Char16::try_from('d').unwrap();
+    let key_p = Char16::try_from('p').unwrap();
+    let key_f = Char16::try_from('f').unwrap();
+    let key_s = Char16::try_from('s').unwrap();
+    let key_i = Char16::try_from('i').unwrap();
+    let key_e = Char16::try_from('e').unwrap();
+    let key_backspace = Char16::try_from('\u{8}').unwrap();
 
+    let scale = settings.scale as i32;
+    let split_rows = SPLIT_ROWS[settings.split_setting];
+
+    let mut show_stats = false;
+    let mut show_debug = false;
+    let mut show_inputs = settings.input_display;
+    // the game is stopped while the editor's open
+    let mut editor: Option<Editor> = None;
+    // the buttons held on each tick, with repeats counted rather than stored, newest last
+    let mut input_history: VecDeque<(u8, u32)> = VecDeque::new();
+    let mut frozen = false;
+    let mut advance = false;
+    let mut slow_motion = 0;
+    let mut frame: u32 = 0;
+    // frames the title screen has gone without a key press
+    let mut idle: u32 = 0;
     let mut timing = [0u8; 4];
+    // like the arrows, there's no key up event, so rewind is held for as long as it keeps
+    // repeating
+    let mut rewind_held = 0u8;
+    let mut rewind = Rewind::new(REWIND_TICKS);
 
     let display_size = display.size();
     let celeste_topleft = Point::new(
@@ -133,98 +879,317 @@ fn celeste_loop(
     );
 
     loop {
-        engine.next_tick();
-        engine.draw();
-
-        for x in 0..scale {
-            for y in 0..scale {
-                display.draw_iter(engine.mem.graphics.iter().enumerate().map(|(i, col)| {
-                    Pixel(
-                        Point::new(
-                            celeste_topleft.x + ((i as i32 % 128) * scale) + x,
-                            celeste_topleft.y + ((i as i32 / 128) * scale) + y,
-                        ),
-                        palette[*col as usize],
-                    )
-                }))?;
+        frame = frame.wrapping_add(1);
+        let rewinding =
+            rewind_held > 0 && !show_stats && editor.is_none() && engine.pause_menu.is_none();
+        if rewinding {
+            rewind_held -= 1;
+            if rewind.step_back(engine) {
+                engine.draw();
             }
         }
+        // the pause menu runs on ticks, so it has to keep ticking while frozen
+        let tick = !show_stats
+            && !rewinding
+            && editor.is_none()
+            && if engine.pause_menu.is_some() {
+                true
+            } else if frozen {
+                core::mem::take(&mut advance)
+            } else {
+                frame.is_multiple_of(SLOW_MOTION[slow_motion])
+            };
 
-        display.flush();
+        if tick {
+            if engine.pause_menu.is_none() {
+                let mask = to_mask(&engine.mem.buttons);
+                match input_history.back_mut() {
+                    Some((last, ticks)) if *last == mask => *ticks += 1,
+                    _ => {
+                        if input_history.len() == INPUT_ROWS {
+                            input_history.pop_front();
+                        }
+                        input_history.push_back((mask, 1));
+                    }
+                }
+            }
+            engine.next_tick();
+            engine.draw();
+            if engine.pause_menu.is_none() {
+                rewind.push(engine);
+            }
 
-        for (i, t) in timing.iter_mut().enumerate() {
-            if *t <= 0 {
-                engine.mem.buttons[i] = false;
-            } else {
-                *t -= 1;
+            // nothing to play sfx on yet, but the queue still has to be emptied every frame
+            let mut new_pb = false;
+            let mut new_ghost = false;
+            let mut picked = None;
+            for event in engine.drain_events() {
+                match event {
+                    GameEvent::PersonalBest => new_pb = true,
+                    GameEvent::GhostRecorded { .. } => new_ghost = true,
+                    GameEvent::PauseMenu(item) => picked = Some(item),
+                    _ => {}
+                }
+            }
+            if new_pb {
+                if let Some(pb) = &engine.personal_best {
+                    if let Err(err) = save_personal_best(pb) {
+                        info!("couldn't save personal best: {}", err);
+                    }
+                }
+            }
+            if new_ghost {
+                if let Err(err) = save_ghosts(&engine.ghosts) {
+                    info!("couldn't save ghosts: {}", err);
+                }
+            }
+            if let Some(item) = picked {
+                return Ok(item);
+            }
+        }
+
+        if tick || rewinding {
+            draw_game(display, &engine.mem, celeste_topleft, scale)?;
+
+            if show_debug {
+                draw_debug(display, engine, &PALETTE, celeste_topleft, scale)?;
             }
+
+            if split_rows > 0 && !engine.is_title() && engine.practice.is_none() {
+                draw_splits(display, engine, split_rows, celeste_topleft.x.max(0) as u32)?;
+            }
+
+            let right = display_size.width as i32 - (celeste_topleft.x + 128 * scale);
+            if show_inputs && right >= INPUTS_WIDTH as i32 {
+                draw_inputs(display, &input_history)?;
+            }
+        }
+
+        if let Some(editor) = &editor {
+            draw_editor(display, engine, editor, celeste_topleft, scale)?;
+        }
+
+        if tick {
+            // only on ticks, so slow motion and frame advance don't eat inputs
+            release_buttons(&mut engine.mem, &mut timing);
         }
 
-        engine.mem.buttons[4] = false;
-        engine.mem.buttons[5] = false;
         while let Some(key) = input.read_key()? {
+            idle = 0;
+            if let Some(open) = &mut editor {
+                if !edit(open, engine, key) {
+                    editor = None;
+                    display.clear(Rgb888::BLACK)?;
+                }
+                continue;
+            }
             match key {
-                Key::Printable(key) if key == key_z || key == key_c => engine.mem.buttons[4] = true,
-                Key::Printable(key) if key == key_x => engine.mem.buttons[5] = true,
-                Key::Special(ScanCode::LEFT) => {
-                    engine.mem.buttons[0] = true;
-                    timing[0] = key_duration;
+                Key::Printable(key) if key == key_tab => {
+                    show_stats = !show_stats;
+                    if show_stats {
+                        draw_stats(display, engine)?;
+                    } else {
+                        display.clear(Rgb888::BLACK)?;
+                    }
                 }
-                Key::Special(ScanCode::RIGHT) => {
-                    engine.mem.buttons[1] = true;
-                    timing[1] = key_duration;
+                Key::Printable(key) if key == key_r && engine.practice.is_some() => engine.retry(),
+                Key::Printable(key) if key == key_d => {
+                    show_debug = !show_debug;
+                    if !show_debug {
+                        display.clear(Rgb888::BLACK)?;
+                    }
                 }
-                Key::Special(ScanCode::UP) => {
-                    engine.mem.buttons[2] = true;
-                    timing[2] = key_duration;
+                Key::Printable(key) if key == key_i => {
+                    show_inputs = !show_inputs;
+                    if !show_inputs {
+                        display.clear(Rgb888::BLACK)?;
+                    }
                 }
-                Key::Special(ScanCode::DOWN) => {
-                    engine.mem.buttons[3] = true;
-                    timing[3] = key_duration;
+                Key::Printable(key) if key == key_e && engine.pause_menu.is_none() => {
+                    editor = Some(Editor {
+                        sprite_mode: false,
+                        // the middle of what's on the screen
+                        x: engine.scroll.x.to_int() / 8 + 8,
+                        y: engine.scroll.y.to_int() / 8 + 8,
+                        tile: 0,
+                        px: 0,
+                        py: 0,
+                        color: 7,
+                        message: String::new(),
+                    });
+                    engine.mem.buttons.fill(false);
+                    timing = [0; 4];
+                    display.clear(Rgb888::BLACK)?;
                 }
-                _ => {}
+                Key::Printable(key) if key == key_p => frozen = !frozen,
+                Key::Printable(key) if key == key_f => {
+                    frozen = true;
+                    advance = true;
+                }
+                Key::Printable(key) if key == key_s => slow_motion = (slow_motion + 1) % SLOW_MOTION.len(),
+                Key::Printable(key) if key == key_backspace => rewind_held = settings.key_duration,
+                Key::Special(ScanCode::ESCAPE) => engine.pause(),
+                key => press_button(&mut engine.mem, &mut timing, key, settings.key_duration),
+            }
+        }
+
+        if !show_stats {
+            draw_status(display, rewinding, frozen, slow_motion, celeste_topleft.x.max(0) as u32)?;
+            display.flush();
+        }
+
+        if engine.is_title() && engine.pause_menu.is_none() && !show_stats && editor.is_none() {
+            idle += 1;
+            if idle >= DEMO_DELAY {
+                play_demo(display, celeste_topleft, scale)?;
+                idle = 0;
             }
+        } else {
+            idle = 0;
         }
 
         boot.stall(33_000);
     }
 }
 
-fn real_main() -> Result<(), UefilesteError> {
-    // can't do system.stdin() because of https://github.com/rust-osdev/uefi-rs/issues/838
+/// The `.p8` files in the root of the ESP, which can be run on the Lua interpreter instead of
+/// the native port
+#[cfg(feature = "lua")]
+fn find_carts() -> Vec<String> {
     let system = system_table();
     let boot = system.boot_services();
+    let Ok(volume) = boot.get_image_file_system(boot.image_handle()) else {
+        return Vec::new();
+    };
+    let mut fs = FileSystem::new(volume);
+    let Ok(entries) = fs.read_dir(Path::new(cstr16!("\\"))) else {
+        return Vec::new();
+    };
+    let mut carts: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|info| !info.is_directory())
+        .map(|info| info.file_name().to_string())
+        .filter(|name| name.to_lowercase().ends_with(".p8"))
+        .collect();
+    carts.sort();
+    carts
+}
+
+/// Loads the cart `name` from the root of the ESP and runs its `_init`
+#[cfg(feature = "lua")]
+fn load_lua_cart(name: &str) -> Result<LuaCart, UefilesteError> {
+    let system = system_table();
+    let boot = system.boot_services();
+    let mut fs = FileSystem::new(boot.get_image_file_system(boot.image_handle())?);
+    let path = CString16::try_from(format!("\\{}", name).as_str())
+        .map_err(|_| UefilesteError::Cart(format!("can't open {}", name)))?;
+    let bytes = fs.read(Path::new(&*path))?;
+    let text = String::from_utf8_lossy(&bytes);
+    // a blank cart for the sections the file doesn't have, with the built in font
+    let blank = CartData {
+        map: Rc::new(vec![0; 128 * 32]),
+        map_width: 128,
+        sprites: Rc::new(vec![0; 128 * 128]),
+        flags: Rc::new(vec![0; 256]),
+        fontatlas: builtin_cart().fontatlas,
+    };
+    let not_a_cart = || UefilesteError::Cart(format!("{} isn't a pico-8 cart", name));
+    let data = blank.with_p8(&text).ok_or_else(not_a_cart)?;
+    let code = cart::code(&text).ok_or_else(not_a_cart)?;
+    Ok(LuaCart::new(&data, &code)?)
+}
+
+/// Shows `message` until a key is pressed
+#[cfg(feature = "lua")]
+fn show_error(display: &mut UefiDisplay, message: &str) -> Result<(), UefilesteError> {
     let mut input_table = system_table();
     let input = input_table.stdin();
+    let boot_table = system_table();
+    let boot = boot_table.boot_services();
 
-    boot.set_watchdog_timer(0, 0x10000, None)?;
+    let text_style = MonoTextStyle::new(&PROFONT_18_POINT, Rgb888::WHITE);
+    display.clear(Rgb888::BLACK)?;
+    Text::new(message, Point::new(4, 4 + (22 + 4)), text_style).draw(display)?;
+    Text::new("PRESS ANY KEY", Point::new(4, 4 + (22 + 4) * 3), text_style).draw(display)?;
+    display.flush();
+    while input.read_key()?.is_none() {
+        boot.stall(33_000);
+    }
+    display.clear(Rgb888::BLACK)?;
+    Ok(())
+}
 
-    info!("CELESTE: UEFI");
+/// Runs the cart `name` on the Lua interpreter until escape is pressed. An error in the cart
+/// is shown rather than returned, it's the cart that broke and not the frontend
+#[cfg(feature = "lua")]
+fn lua_loop(
+    display: &mut UefiDisplay,
+    name: &str,
+    settings: &Settings,
+) -> Result<(), UefilesteError> {
+    let mut input_table = system_table();
+    let input = input_table.stdin();
+    let boot_table = system_table();
+    let boot = boot_table.boot_services();
 
-    let gop_handle = boot.get_handle_for_protocol::<GraphicsOutput>()?;
-    let mut gop = unsafe {
-        boot.open_protocol::<GraphicsOutput>(
-            OpenProtocolParams {
-                handle: gop_handle,
-                agent: boot.image_handle(),
-                controller: None,
-            },
-            OpenProtocolAttributes::GetProtocol,
-        )?
+    let mut lua = match load_lua_cart(name) {
+        Ok(lua) => lua,
+        Err(err) => return show_error(display, &format!("{}: {}", name, err)),
     };
-    let mode = gop.current_mode_info();
-    let mut display = UefiDisplay::new(gop.frame_buffer(), mode);
+    let scale = settings.scale as i32;
+    let display_size = display.size();
+    let topleft = Point::new(
+        display_size.width as i32 / 2 - (64 * scale),
+        display_size.height as i32 / 2 - (64 * scale),
+    );
+    // a 60fps cart ticks twice as often, so holds the arrows for twice as many ticks
+    let ticks_per_frame = lua.fps() / 30;
+    let key_duration = settings.key_duration * ticks_per_frame as u8;
+    let mut timing = [0u8; 4];
 
-    info!("created display...");
+    loop {
+        if let Err(err) = lua.next_tick().and_then(|_| lua.draw()) {
+            return show_error(display, &format!("{}: {}", name, err));
+        }
+        draw_game(display, &lua.mem, topleft, scale)?;
+        display.flush();
+        release_buttons(&mut lua.mem, &mut timing);
 
-    let max_scale = (display.size().width / 128).min(display.size().height / 128);
+        while let Some(key) = input.read_key()? {
+            match key {
+                Key::Special(ScanCode::ESCAPE) => {
+                    display.clear(Rgb888::BLACK)?;
+                    return Ok(());
+                }
+                key => press_button(&mut lua.mem, &mut timing, key, key_duration),
+            }
+        }
 
-    let title_string = format!(
-        "CELESTE: UEFI: {:?} ({}x{} px)",
-        system.firmware_vendor().to_string(),
-        display.size().width,
-        display.size().height,
-    );
+        boot.stall(33_000 / ticks_per_frame as usize);
+    }
+}
+
+/// Entries in the settings menu, the last one starts the game
+const MENU_ITEMS: u8 = 15;
+
+/// The choices for how many splits the overlay shows, 0 hides it and `usize::MAX` shows all
+const SPLIT_ROWS: [usize; 4] = [0, 5, 10, usize::MAX];
+
+/// Shows the settings menu until the last entry is picked. `resuming` is whether that goes back
+/// to a game that's already running, the practice settings only apply to new ones. `carts` are
+/// the Lua carts there are to pick from
+fn settings_menu(
+    display: &mut UefiDisplay,
+    settings: &mut Settings,
+    carts: &[String],
+    max_scale: u32,
+    title_string: &str,
+    resuming: bool,
+) -> Result<(), UefilesteError> {
+    let mut input_table = system_table();
+    let input = input_table.stdin();
+    let boot_table = system_table();
+    let boot = boot_table.boot_services();
 
     let text_style = MonoTextStyle::new(&PROFONT_18_POINT, Rgb888::WHITE);
     let text_style_selected = MonoTextStyle::new(&PROFONT_18_POINT, Rgb888::BLACK);
@@ -234,81 +1199,123 @@ fn real_main() -> Result<(), UefilesteError> {
 
     let mut start_game = false;
     let mut selected: u8 = 0;
-    let mut key_duration = 15;
-    let mut scale = max_scale / 2;
 
     let key_enter = Char16::try_from('\r').unwrap();
 
+    let Settings {
+        key_duration,
+        scale,
+        split_setting,
+        practice_level,
+        practice,
+        assist,
+        ghost,
+        input_display,
+        cart,
+    } = settings;
+
     while !start_game {
         display.clear(Rgb888::BLACK)?;
 
-        Text::new(&title_string, Point::new(4, 4 + (22 + 4) * 1), text_style).draw(&mut display)?;
+        Text::new(title_string, Point::new(4, 4 + (22 + 4)), text_style).draw(display)?;
 
-        Text::new("LEFT/RIGHT ARROW - CHANGE SETTING", Point::new(4, 4 + (22 + 4) * 2), text_style).draw(&mut display)?;
-        Text::new("UP/DOWN ARROW - CHANGE SELECTION", Point::new(4, 4 + (22 + 4) * 3), text_style).draw(&mut display)?;
-        Text::new("ENTER - PERFORM ACTION", Point::new(4, 4 + (22 + 4) * 4), text_style).draw(&mut display)?;
+        Text::new("LEFT/RIGHT ARROW - CHANGE SETTING", Point::new(4, 4 + (22 + 4) * 2), text_style).draw(display)?;
+        Text::new("UP/DOWN ARROW - CHANGE SELECTION", Point::new(4, 4 + (22 + 4) * 3), text_style).draw(display)?;
+        Text::new("ENTER - PERFORM ACTION", Point::new(4, 4 + (22 + 4) * 4), text_style).draw(display)?;
+        Text::new("IN GAME: TAB - STATS  D - DEBUG OVERLAY  R - RETRY ROOM (PRACTICE)", Point::new(4, 4 + (22 + 4) * 20), text_style).draw(display)?;
+        Text::new("ESC - PAUSE MENU (BACK TO HERE FOR LUA CARTS)  P - FREEZE  F - FRAME ADVANCE  S - SLOW MOTION", Point::new(4, 4 + (22 + 4) * 21), text_style).draw(display)?;
+        Text::new("HOLD BACKSPACE - REWIND (UP TO 10 SECONDS)  I - INPUT DISPLAY  E - EDITOR", Point::new(4, 4 + (22 + 4) * 22), text_style).draw(display)?;
 
-        draw_text(
-            &mut display,
-            &format!("KEY DURATION (FRAMES): {}", key_duration),
-            Point::new(4, 4 + (22 + 4) * 5),
-            selected == 0,
-            text_style,
-            text_style_selected,
-            &bg_style_selected,
-        )?;
-
-        draw_text(
-            &mut display,
-            &format!("SCALE: {}", scale),
-            Point::new(4, 4 + (22 + 4) * 6),
-            selected == 1,
-            text_style,
-            text_style_selected,
-            &bg_style_selected,
-        )?;
-
-        draw_text(
-            &mut display,
-            "START GAME",
-            Point::new(4, 4 + (22 + 4) * 7),
-            selected == 2,
-            text_style,
-            text_style_selected,
-            &bg_style_selected,
-        )?;
+        let yes_no = |b: bool| if b { "YES" } else { "NO" };
+        // a Lua cart always starts over
+        let resume = resuming && cart.is_none();
+        let items = [
+            format!("KEY DURATION (FRAMES): {}", key_duration),
+            format!("SCALE: {}", scale),
+            match SPLIT_ROWS[*split_setting] {
+                0 => "SPLITS: OFF".to_string(),
+                usize::MAX => "SPLITS: ALL".to_string(),
+                rows => format!("SPLITS: LAST {}", rows),
+            },
+            match practice_level {
+                None => "LEVEL: FULL GAME".to_string(),
+                Some(level) => format!("LEVEL: {} (PRACTICE)", room_name(&Layout::classic(), *level).to_uppercase()),
+            },
+            format!("DASHES (PRACTICE): {}", practice.max_djump),
+            format!("START WITH KEY (PRACTICE): {}", yes_no(practice.has_key)),
+            format!("BERRY COLLECTED (PRACTICE): {}", yes_no(practice.berry)),
+            format!("GAME SPEED (ASSIST): {}%", assist.game_speed),
+            format!("INFINITE DASHES (ASSIST): {}", yes_no(assist.infinite_dashes)),
+            format!("INVINCIBLE (ASSIST): {}", yes_no(assist.invincible)),
+            match assist.air_dashes {
+                None => "AIR DASHES (ASSIST): DEFAULT".to_string(),
+                Some(dashes) => format!("AIR DASHES (ASSIST): {}", dashes),
+            },
+            format!("GHOST: {}", if *ghost { "ON" } else { "OFF" }),
+            format!("INPUT DISPLAY: {}", if *input_display { "ON" } else { "OFF" }),
+            match cart {
+                None => "CART: CELESTE (NATIVE)".to_string(),
+                Some(i) => format!("CART: {} (LUA)", carts[*i].to_uppercase()),
+            },
+            (if resume { "RESUME GAME" } else { "START GAME" }).to_string(),
+        ];
+        for (i, item) in items.iter().enumerate() {
+            draw_text(
+                display,
+                item,
+                Point::new(4, 4 + (22 + 4) * (5 + i as i32)),
+                selected as usize == i,
+                text_style,
+                text_style_selected,
+                &bg_style_selected,
+            )?;
+        }
 
         display.flush();
 
         while let Some(key) = input.read_key()? {
             match key {
-                Key::Special(ScanCode::LEFT) => {
-                    if selected == 0 {
-                        key_duration = (key_duration - 1).max(1);
-                    } else if selected == 1 {
-                        scale = (scale - 1).max(1);
-                    }
-                }
-                Key::Special(ScanCode::RIGHT) => {
-                    if selected == 0 {
-                        key_duration = (key_duration + 1).min(30);
-                    } else if selected == 1 {
-                        scale = (scale + 1).min(max_scale);
-                    }
-                }
+                Key::Special(ScanCode::LEFT) => match selected {
+                    0 => *key_duration = (*key_duration - 1).max(1),
+                    1 => *scale = (*scale - 1).max(1),
+                    2 => *split_setting = split_setting.saturating_sub(1),
+                    3 => *practice_level = practice_level.and_then(|level| level.checked_sub(1)),
+                    4 => practice.max_djump = 1,
+                    5 => practice.has_key = false,
+                    6 => practice.berry = false,
+                    7 => assist.game_speed = (assist.game_speed - 10).max(50),
+                    8 => assist.infinite_dashes = false,
+                    9 => assist.invincible = false,
+                    10 => assist.air_dashes = assist.air_dashes.and_then(|dashes| dashes.checked_sub(1)),
+                    11 => *ghost = false,
+                    12 => *input_display = false,
+                    13 => *cart = cart.and_then(|i| i.checked_sub(1)),
+                    _ => {}
+                },
+                Key::Special(ScanCode::RIGHT) => match selected {
+                    0 => *key_duration = (*key_duration + 1).min(30),
+                    1 => *scale = (*scale + 1).min(max_scale),
+                    2 => *split_setting = (*split_setting + 1).min(SPLIT_ROWS.len() - 1),
+                    3 => *practice_level = Some(practice_level.map_or(0, |level| (level + 1).min(Layout::classic().summit()))),
+                    4 => practice.max_djump = 2,
+                    5 => practice.has_key = true,
+                    6 => practice.berry = true,
+                    7 => assist.game_speed = (assist.game_speed + 10).min(100),
+                    8 => assist.infinite_dashes = true,
+                    9 => assist.invincible = true,
+                    10 => assist.air_dashes = Some(assist.air_dashes.map_or(0, |dashes| (dashes + 1).min(3))),
+                    11 => *ghost = true,
+                    12 => *input_display = true,
+                    13 if !carts.is_empty() => *cart = Some(cart.map_or(0, |i| (i + 1).min(carts.len() - 1))),
+                    _ => {}
+                },
                 Key::Special(ScanCode::UP) => {
-                    if selected == 0 {
-                        selected = 2;
-                    }
-                    selected -= 1;
+                    selected = (selected + MENU_ITEMS - 1) % MENU_ITEMS;
                 }
                 Key::Special(ScanCode::DOWN) => {
-                    if selected == 2 {
-                        selected = 0;
-                    }
-                    selected += 1;
+                    selected = (selected + 1) % MENU_ITEMS;
                 }
-                Key::Printable(key) if key == key_enter && selected == 2 => {
+                Key::Printable(key) if key == key_enter && selected == MENU_ITEMS - 1 => {
                     start_game = true;
                 }
                 _ => {}
@@ -319,8 +1326,77 @@ fn real_main() -> Result<(), UefilesteError> {
     }
 
     display.clear(Rgb888::BLACK)?;
+    Ok(())
+}
+
+fn real_main() -> Result<(), UefilesteError> {
+    // can't do system.stdin() because of https://github.com/rust-osdev/uefi-rs/issues/838
+    let system = system_table();
+    let boot = system.boot_services();
 
-    celeste_loop(display, key_duration, scale as i32)
+    boot.set_watchdog_timer(0, 0x10000, None)?;
+
+    info!("CELESTE: UEFI");
+
+    let gop_handle = boot.get_handle_for_protocol::<GraphicsOutput>()?;
+    let mut gop = unsafe {
+        boot.open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle: gop_handle,
+                agent: boot.image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )?
+    };
+    let mode = gop.current_mode_info();
+    let mut display = UefiDisplay::new(gop.frame_buffer(), mode);
+
+    info!("created display...");
+
+    let max_scale = (display.size().width / 128).min(display.size().height / 128);
+
+    let title_string = format!(
+        "CELESTE: UEFI: {:?} ({}x{} px)",
+        system.firmware_vendor().to_string(),
+        display.size().width,
+        display.size().height,
+    );
+
+    let mut settings = Settings {
+        key_duration: 15,
+        scale: max_scale / 2,
+        split_setting: 0,
+        practice_level: None,
+        practice: Practice::default(),
+        assist: Assist::default(),
+        ghost: true,
+        input_display: false,
+        cart: None,
+    };
+    #[cfg(feature = "lua")]
+    let carts = find_carts();
+    // without the interpreter there's nothing to pick, so `settings.cart` stays `None`
+    #[cfg(not(feature = "lua"))]
+    let carts = Vec::new();
+    let mut game: Option<Celeste> = None;
+
+    loop {
+        settings_menu(&mut display, &mut settings, &carts, max_scale, &title_string, game.is_some())?;
+
+        #[cfg(feature = "lua")]
+        if let Some(cart) = settings.cart {
+            lua_loop(&mut display, &carts[cart], &settings)?;
+            continue;
+        }
+
+        let engine = game.get_or_insert_with(|| new_game(&settings));
+        engine.assist = settings.assist;
+        engine.show_ghost = settings.ghost;
+        if celeste_loop(&mut display, engine, &settings)? == PauseItem::QuitToMenu {
+            game = None;
+        }
+    }
 }
 
 #[entry]
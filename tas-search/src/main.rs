@@ -0,0 +1,193 @@
+//! Finds the fewest ticks through a room of Celeste Classic and writes the inputs out as a
+//! replay, see `rustic_mountain_core::replay`.
+//!
+//! The search starts either from a room, set up the way practice mode would, or from wherever
+//! a replay leaves off. In the first case the replay's inputs start on the tick after the room
+//! is loaded, like the per-level files of the pico-8 TAS tools. In the second they follow on
+//! from the given replay's.
+//!
+//! It runs on the host rather than in UEFI: `cargo run -r -p tas-search -- 4 -o room4.txt`
+mod search;
+
+use std::{env, fs, process::ExitCode};
+
+use rustic_mountain_core::{memory::CartData, practice::Practice, replay::Replay, Celeste};
+use search::Options;
+
+mod consts {
+    include!("../../src/consts.rs");
+}
+
+const USAGE: &str = "\
+usage: tas-search <level> [options]
+       tas-search --replay <file> [options]
+
+searches for the fastest way out of a room and prints it as a replay
+
+  <level>          the room to start in, 0 for 100m
+  --replay <file>  start from where this replay ends instead
+  --max-djump <n>  dashes the player has, 1 unless it's after the orb
+  --key            start with the room's key
+  --berry          start with the room's berry already collected
+  --seed <n>       seed for the random number generator, 0 by default
+  --beam <n>       games kept after every tick, 2000 by default. more is slower but finds
+                   faster routes
+  --max-ticks <n>  give up after this many ticks, 600 by default
+  -o <file>        write the replay here instead of printing it
+";
+
+struct Args {
+    level: Option<u8>,
+    replay: Option<String>,
+    practice: Practice,
+    seed: u64,
+    options: Options,
+    out: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        level: None,
+        replay: None,
+        practice: Practice::default(),
+        seed: 0,
+        options: Options {
+            beam: 2000,
+            max_ticks: 600,
+        },
+        out: None,
+    };
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().ok_or(format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--replay" => args.replay = Some(value()?),
+            "--max-djump" => args.practice.max_djump = number(&arg, value()?)?,
+            "--key" => args.practice.has_key = true,
+            "--berry" => args.practice.berry = true,
+            "--seed" => args.seed = number(&arg, value()?)?,
+            "--beam" => args.options.beam = number(&arg, value()?)?,
+            "--max-ticks" => args.options.max_ticks = number(&arg, value()?)?,
+            "-o" => args.out = Some(value()?),
+            "-h" | "--help" => return Err(String::new()),
+            _ if args.level.is_none() && !arg.starts_with('-') => {
+                args.level = Some(number(&arg, arg.clone())?)
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    if args.level.is_some() == args.replay.is_some() {
+        return Err("give either a level or --replay".into());
+    }
+    Ok(args)
+}
+
+fn number<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} isn't a number: {}", arg, value))
+}
+
+/// The game to search from, and the replay the route gets added to
+fn start(args: &Args, cart: &CartData) -> Result<(Celeste, Replay), String> {
+    let mut celeste = Celeste::from_cart(cart);
+    if let Some(path) = &args.replay {
+        let text = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        let replay = Replay::from_text(&text).ok_or(format!("{} isn't a replay", path))?;
+        replay.start(&mut celeste);
+        for tick in 0..replay.inputs.len() {
+            replay.apply(tick, &mut celeste);
+            celeste.next_tick();
+        }
+        if celeste.is_title() {
+            return Err(format!("{} doesn't leave the title screen", path));
+        }
+        Ok((celeste, replay))
+    } else {
+        let level = args.level.unwrap();
+        if level as usize >= celeste.layout.rooms.len() {
+            return Err(format!("there's no room {}", level));
+        }
+        celeste.seed(args.seed);
+        celeste.begin_practice(Practice {
+            level,
+            ..args.practice
+        });
+        Ok((celeste, Replay::new(args.seed)))
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("{}\n", e);
+            }
+            eprint!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    let cart = CartData::new(
+        consts::MAPDATA,
+        consts::SPRITES,
+        consts::FLAGS,
+        consts::FONTATLAS,
+    );
+    let (mut celeste, mut replay) = match start(&args, &cart) {
+        Ok(start) => start,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    celeste.drain_events();
+    let level = celeste.level;
+    eprintln!("searching room {}", level);
+
+    let route = search::search(&celeste, &args.options, |tick, games, distance| {
+        if tick % 10 == 0 && distance != u32::MAX {
+            eprintln!(
+                "tick {}: {} games, {} tiles from the exit",
+                tick, games, distance
+            );
+        }
+    });
+    let Some(route) = route else {
+        eprintln!("no way out in {} ticks", args.options.max_ticks);
+        return ExitCode::FAILURE;
+    };
+    eprintln!(
+        "found a way out in {} ticks, after trying {} games",
+        route.inputs.len(),
+        route.explored
+    );
+
+    // play it back to make sure, the search shouldn't ever find something that doesn't work
+    let mut check = celeste.clone();
+    let tail = Replay {
+        seed: replay.seed,
+        inputs: route.inputs.clone(),
+    };
+    for tick in 0..tail.inputs.len() {
+        tail.apply(tick, &mut check);
+        check.next_tick();
+    }
+    if check.level == level {
+        eprintln!("the route doesn't leave the room when played back");
+        return ExitCode::FAILURE;
+    }
+
+    replay.inputs.extend(route.inputs);
+    let text = replay.to_text();
+    match &args.out {
+        Some(path) => {
+            if let Err(e) = fs::write(path, text) {
+                eprintln!("can't write {}: {}", path, e);
+                return ExitCode::FAILURE;
+            }
+        }
+        None => print!("{}", text),
+    }
+    ExitCode::SUCCESS
+}
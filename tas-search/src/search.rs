@@ -0,0 +1,433 @@
+//! Breadth first search over the buttons held each tick, one tick deeper per round. Every round
+//! forks each game in the frontier once for every input in `INPUTS`, drops the forks that died
+//! or ended up in a state that was already reached, and keeps the `beam` closest to the exit
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+};
+
+use rustic_mountain_core::{
+    fixed::Fix16,
+    objects::player::Player,
+    rooms::Room,
+    snapshot::{Snapshot, Writer},
+    structures::ObjectKind,
+    Celeste,
+};
+
+const LEFT: u8 = 1 << 0;
+const RIGHT: u8 = 1 << 1;
+const UP: u8 = 1 << 2;
+const DOWN: u8 = 1 << 3;
+const JUMP: u8 = 1 << 4;
+const DASH: u8 = 1 << 5;
+
+/// Every input worth trying, as `btn()` bitmasks. Holding both directions on an axis is the
+/// same as holding the first, so those are left out
+const INPUTS: [u8; 36] = {
+    let mut inputs = [0; 36];
+    let mut i = 0;
+    while i < 36 {
+        let h = [0, LEFT, RIGHT][i % 3];
+        let v = [0, UP, DOWN][i / 3 % 3];
+        let jump = if i / 9 % 2 == 1 { JUMP } else { 0 };
+        let dash = if i / 18 == 1 { DASH } else { 0 };
+        inputs[i] = h | v | jump | dash;
+        i += 1;
+    }
+    inputs
+};
+
+pub struct Options {
+    /// how many games are kept after every tick
+    pub beam: usize,
+    /// gives up after this many ticks
+    pub max_ticks: usize,
+}
+
+/// The fastest inputs found, from the game as it was given to `search`
+pub struct Route {
+    pub inputs: Vec<u8>,
+    /// how many games were stepped to find it
+    pub explored: usize,
+}
+
+/// a game in the frontier
+struct Node {
+    celeste: Celeste,
+    /// index into `Search::steps` of the last input that got here
+    step: usize,
+}
+
+struct Search {
+    /// every input taken by a node that made it into a frontier, as (previous step, input).
+    /// usize::MAX is the start
+    steps: Vec<(usize, u8)>,
+    seen: HashSet<u64>,
+    /// tiles from the exit, see `Distances`
+    distances: Distances,
+}
+
+/// Looks for the fewest ticks that get the player out of the room `start` is in. `start`
+/// should be between ticks, with the player spawned or about to spawn
+pub fn search(
+    start: &Celeste,
+    options: &Options,
+    mut progress: impl FnMut(usize, usize, u32),
+) -> Option<Route> {
+    let level = start.level;
+    let deaths = start.deaths;
+    let mut search = Search {
+        steps: Vec::new(),
+        seen: HashSet::new(),
+        distances: Distances::new(start),
+    };
+    let mut frontier = vec![Node {
+        celeste: start.clone(),
+        step: usize::MAX,
+    }];
+    let mut explored = 0;
+    for tick in 1..=options.max_ticks {
+        let mut next = Vec::new();
+        for node in &frontier {
+            for &input in &INPUTS {
+                let mut celeste = node.celeste.clone();
+                for (i, button) in celeste.mem.buttons.iter_mut().enumerate() {
+                    *button = input & (1 << i) != 0;
+                }
+                celeste.next_tick();
+                celeste.drain_events();
+                explored += 1;
+                if celeste.level != level {
+                    search.steps.push((node.step, input));
+                    return Some(Route {
+                        inputs: search.inputs(search.steps.len() - 1),
+                        explored,
+                    });
+                }
+                if celeste.deaths != deaths || !search.seen.insert(state_key(&celeste)) {
+                    continue;
+                }
+                search.steps.push((node.step, input));
+                next.push(Node {
+                    celeste,
+                    step: search.steps.len() - 1,
+                });
+            }
+        }
+        if next.is_empty() {
+            return None;
+        }
+        frontier = search.keep(next, options.beam);
+        progress(tick, frontier.len(), search.score(&frontier[0].celeste).0);
+    }
+    None
+}
+
+impl Search {
+    /// the inputs that lead to `step`, oldest first
+    fn inputs(&self, mut step: usize) -> Vec<u8> {
+        let mut inputs = VecDeque::new();
+        while step != usize::MAX {
+            let (previous, input) = self.steps[step];
+            inputs.push_front(input);
+            step = previous;
+        }
+        inputs.into()
+    }
+
+    /// The `beam` games worth going on with. Keeping only the closest to the exit would fill the
+    /// beam with games a pixel apart, stuck at the same wall, so the best game in every tile
+    /// and dash count goes in first, then the second best of each and so on
+    fn keep(&self, mut games: Vec<Node>, beam: usize) -> Vec<Node> {
+        if games.len() <= beam {
+            games.sort_by_cached_key(|node| self.score(&node.celeste));
+            return games;
+        }
+        let mut cells: HashMap<_, Vec<_>> = HashMap::new();
+        for node in games {
+            let (distance, height) = self.score(&node.celeste);
+            cells
+                .entry(cell(&node.celeste))
+                .or_default()
+                .push((distance, height, node));
+        }
+        let mut cells: Vec<_> = cells.into_values().collect();
+        for cell in &mut cells {
+            // worst first, so the best can be popped off the end
+            cell.sort_unstable_by_key(|&(distance, height, _)| Reverse((distance, height)));
+        }
+        let mut kept = Vec::with_capacity(beam);
+        while kept.len() < beam {
+            let mut round: Vec<_> = cells.iter_mut().filter_map(|cell| cell.pop()).collect();
+            round.sort_unstable_by_key(|&(distance, height, _)| (distance, height));
+            kept.extend(round.into_iter().map(|(_, _, node)| node));
+        }
+        kept.truncate(beam);
+        kept
+    }
+
+    /// lower is closer to the exit. the player's tile's distance from the exit, then how far
+    /// they have left to go inside the tile
+    fn score(&self, celeste: &Celeste) -> (u32, i32) {
+        let Some((_, obj)) = celeste.objects.of_kind(ObjectKind::Player).next() else {
+            // still spawning, which every game in the frontier is at the same time
+            return (u32::MAX, 0);
+        };
+        let obj = obj.borrow();
+        self.distances
+            .score(obj.pos.x.to_int() + 4, obj.pos.y.to_int() + 4)
+    }
+}
+
+/// The tile the player's in and how many dashes they have left. `None` while there's no player
+fn cell(celeste: &Celeste) -> Option<(i32, i32, u8)> {
+    let (id, obj) = celeste.objects.of_kind(ObjectKind::Player).next()?;
+    let obj = obj.borrow();
+    let room = &celeste.room;
+    let x = (obj.pos.x.to_int() + 4)
+        .clamp(-8, room.width())
+        .div_euclid(8);
+    let y = (obj.pos.y.to_int() + 4)
+        .clamp(-8, room.height())
+        .div_euclid(8);
+    let djump = celeste.get::<Player>(id).map_or(0, |p| p.borrow().djump);
+    Some((x, y, djump))
+}
+
+/// How many tiles each tile of the room is from a side the room exits through, going around
+/// solid tiles and spikes. Objects are ignored, so this is only a guess at how far the player
+/// has left to go
+struct Distances {
+    room: Room,
+    /// row by row, `u32::MAX` for tiles the exits can't be reached from
+    tiles: Vec<u32>,
+}
+
+impl Distances {
+    fn new(celeste: &Celeste) -> Distances {
+        let room = celeste.room;
+        let open = |x: i32, y: i32| {
+            let tile = celeste.tile_at(Fix16::from_int(x), Fix16::from_int(y));
+            !celeste.mem.fget(tile, 0) && !matches!(tile, 17 | 27 | 43 | 59)
+        };
+        let mut distances = Distances {
+            room,
+            tiles: vec![u32::MAX; (room.w * room.h) as usize],
+        };
+        let mut queue = VecDeque::new();
+        for y in 0..room.h {
+            for x in 0..room.w {
+                let edge = (room.exits.top && y == 0)
+                    || (room.exits.bottom && y == room.h - 1)
+                    || (room.exits.left && x == 0)
+                    || (room.exits.right && x == room.w - 1);
+                if edge && open(x, y) {
+                    distances.tiles[(y * room.w + x) as usize] = 0;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+        while let Some((x, y)) = queue.pop_front() {
+            let next = distances.get(x, y).unwrap() + 1;
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if distances.get(nx, ny) == Some(u32::MAX) && open(nx, ny) {
+                    distances.tiles[(ny * room.w + nx) as usize] = next;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        distances
+    }
+
+    /// the distance of the tile at `x`, `y`, `None` outside the room
+    fn get(&self, x: i32, y: i32) -> Option<u32> {
+        if (0..self.room.w).contains(&x) && (0..self.room.h).contains(&y) {
+            Some(self.tiles[(y * self.room.w + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// `Search::score` for a player whose middle is at `x`, `y` in pixels. Past a side the room
+    /// exits through is as close as it gets, and the further past the better. Inside a tile,
+    /// it's how many pixels are left to the side of it that's closer to the exit
+    fn score(&self, x: i32, y: i32) -> (u32, i32) {
+        let (w, h) = (self.room.width(), self.room.height());
+        let exits = self.room.exits;
+        let past = [
+            (exits.top, -y),
+            (exits.bottom, y - h + 1),
+            (exits.left, -x),
+            (exits.right, x - w + 1),
+        ];
+        if let Some(&(_, by)) = past.iter().find(|&&(exit, by)| exit && by > 0) {
+            return (0, -by);
+        }
+        let (x, y) = (x.clamp(0, w - 1), y.clamp(0, h - 1));
+        let (tx, ty) = (x / 8, y / 8);
+        let distance = self.get(tx, ty).unwrap();
+        if distance == u32::MAX {
+            return (distance, 0);
+        }
+        // the way out of the tile, through an exit or into a closer tile
+        let closer = |nx: i32, ny: i32| match self.get(nx, ny) {
+            Some(d) => d.checked_add(1) == Some(distance),
+            None => distance == 0,
+        };
+        let left = if closer(tx, ty - 1) && (ty > 0 || exits.top) {
+            y % 8
+        } else if closer(tx - 1, ty) && (tx > 0 || exits.left) {
+            x % 8
+        } else if closer(tx + 1, ty) && (tx < self.room.w - 1 || exits.right) {
+            7 - x % 8
+        } else {
+            7 - y % 8
+        };
+        (distance, left)
+    }
+}
+
+/// A hash of everything that changes how the game plays from here on. Timers, particles and
+/// the player's hair and animation are left out, so games that only differ in those count as
+/// the same state
+fn state_key(celeste: &Celeste) -> u64 {
+    let mut w = Writer::default();
+    celeste.has_key.save(&mut w);
+    celeste.has_dashed.save(&mut w);
+    celeste.max_djump.save(&mut w);
+    celeste.freeze.save(&mut w);
+    celeste.delay_restart.save(&mut w);
+    celeste.pause_player.save(&mut w);
+    for (id, obj) in celeste.objects.iter() {
+        let obj = obj.borrow();
+        match obj.kind() {
+            ObjectKind::Smoke | ObjectKind::LifeUp | ObjectKind::RoomTitle => {}
+            ObjectKind::Player => {
+                obj.pos.save(&mut w);
+                obj.rem.save(&mut w);
+                obj.spd.save(&mut w);
+                obj.flip.x.save(&mut w);
+                let Some(player) = celeste.get::<Player>(id) else {
+                    continue;
+                };
+                let p = player.borrow();
+                for byte in [p.grace, p.jbuffer, p.djump, p.dash_effect_time] {
+                    byte.save(&mut w);
+                }
+                p.dash_time.save(&mut w);
+                for fix in [
+                    p.dash_target_effect,
+                    p.dash_target_x,
+                    p.dash_target_y,
+                    p.dash_accel_x,
+                    p.dash_accel_y,
+                ] {
+                    fix.save(&mut w);
+                }
+                for flag in [p.was_on_ground, p.p_jump, p.p_dash] {
+                    flag.save(&mut w);
+                }
+            }
+            _ => obj.save(&mut w),
+        }
+    }
+    let mut hasher = DefaultHasher::new();
+    w.data.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    use rustic_mountain_core::{
+        memory::CartData,
+        practice::Practice,
+        replay::Replay,
+        rooms::{Exits, Layout},
+    };
+
+    /// A room 24 tiles wide that's left through the right side, with a floor and a wall halfway
+    /// along to get over, then a second room to get to
+    fn wide_room(seed: u64) -> Celeste {
+        let mut map = vec![0; 128 * 32];
+        for x in 0..40 {
+            map[15 * 128 + x] = 32;
+        }
+        map[13 * 128 + 12] = 32;
+        map[14 * 128 + 12] = 32;
+        map[14 * 128 + 2] = 1;
+        let mut flags = vec![0; 256];
+        flags[32] = 1;
+        let mut celeste = Celeste::from_cart(&CartData {
+            map: Rc::new(map),
+            map_width: 128,
+            sprites: Rc::new(vec![0; 128 * 128]),
+            flags: Rc::new(flags),
+            fontatlas: Rc::new(vec![]),
+        });
+        celeste.layout = Rc::new(Layout {
+            rooms: vec![
+                Room {
+                    x: 0,
+                    y: 0,
+                    w: 24,
+                    h: 16,
+                    exits: Exits {
+                        right: true,
+                        ..Exits::default()
+                    },
+                },
+                Room::screen(3, 0),
+            ],
+            title: Room::screen(7, 3),
+            names: Default::default(),
+        });
+        celeste.seed(seed);
+        celeste.begin_practice(Practice::default());
+        celeste.drain_events();
+        celeste
+    }
+
+    #[test]
+    fn distances_lead_to_the_exits() {
+        let celeste = wide_room(0);
+        let distances = Distances::new(&celeste);
+        assert_eq!(distances.get(23, 14), Some(0));
+        assert_eq!(distances.get(22, 14), Some(1));
+        // over the wall, rather than through it
+        assert_eq!(distances.get(12, 14), Some(u32::MAX));
+        assert_eq!(distances.get(11, 14), Some(14));
+        assert_eq!(distances.get(24, 14), None);
+        // further right is closer, and past the exit is closest
+        assert!(distances.score(100, 116) > distances.score(104, 116));
+        assert!(distances.score(191, 116) > distances.score(196, 116));
+        assert_eq!(distances.score(196, 116).0, 0);
+    }
+
+    #[test]
+    fn routes_play_back_into_the_next_room() {
+        let start = wide_room(4);
+        let options = Options {
+            beam: 40,
+            max_ticks: 300,
+        };
+        let route = search(&start, &options, |_, _, _| {}).unwrap();
+
+        let replay = Replay {
+            seed: 4,
+            inputs: route.inputs,
+        };
+        let replay = Replay::from_text(&replay.to_text()).unwrap();
+        let mut celeste = wide_room(replay.seed);
+        for tick in 0..replay.inputs.len() {
+            assert_eq!(celeste.level, 0, "left the room early, on tick {}", tick);
+            replay.apply(tick, &mut celeste);
+            celeste.next_tick();
+        }
+        assert_eq!(celeste.level, 1);
+    }
+}